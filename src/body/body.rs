@@ -10,10 +10,12 @@ use http_body::{Body as HttpBody, SizeHint};
 use super::DecodedLength;
 use crate::common::Future;
 use crate::common::{task, watch, Pin, Poll};
+#[cfg(feature = "http1")]
+use crate::ext::ChunkExtensions;
 #[cfg(all(feature = "http2", any(feature = "client", feature = "server")))]
 use crate::proto::h2::ping;
 
-type BodySender = mpsc::Sender<Result<Bytes, crate::Error>>;
+type BodySender = mpsc::Sender<Result<Chunk, crate::Error>>;
 type TrailersSender = oneshot::Sender<HeaderMap>;
 
 /// A stream of `Bytes`, used when receiving bodies.
@@ -34,8 +36,10 @@ enum Kind {
     Chan {
         content_length: DecodedLength,
         want_tx: watch::Sender,
-        data_rx: mpsc::Receiver<Result<Bytes, crate::Error>>,
+        data_rx: mpsc::Receiver<Result<Chunk, crate::Error>>,
         trailers_rx: oneshot::Receiver<HeaderMap>,
+        #[cfg(feature = "http1")]
+        chunk_extensions: Option<Box<ChunkExtensions>>,
     },
     #[cfg(all(feature = "http2", any(feature = "client", feature = "server")))]
     H2 {
@@ -67,6 +71,13 @@ pub struct Sender {
     trailers_tx: Option<TrailersSender>,
 }
 
+/// A piece of data sent through the channel of a `Recv`.
+struct Chunk {
+    data: Bytes,
+    #[cfg(feature = "http1")]
+    extensions: Option<Box<ChunkExtensions>>,
+}
+
 const WANT_PENDING: usize = 1;
 const WANT_READY: usize = 2;
 
@@ -99,6 +110,8 @@ impl Recv {
             want_tx,
             data_rx,
             trailers_rx,
+            #[cfg(feature = "http1")]
+            chunk_extensions: None,
         });
 
         (tx, rx)
@@ -122,13 +135,16 @@ impl Recv {
     pub(crate) fn h2(
         recv: h2::RecvStream,
         mut content_length: DecodedLength,
-        ping: ping::Recorder,
+        mut ping: ping::Recorder,
     ) -> Self {
         // If the stream is already EOS, then the "unknown length" is clearly
         // actually ZERO.
         if !content_length.is_exact() && recv.is_end_stream() {
             content_length = DecodedLength::ZERO;
         }
+        if recv.is_end_stream() {
+            ping.record_end_of_stream();
+        }
         let body = Recv::new(Kind::H2 {
             ping,
             content_length,
@@ -138,6 +154,22 @@ impl Recv {
         body
     }
 
+    /// Returns the chunk extensions of the last chunk returned by `poll_data`.
+    ///
+    /// Extensions are only available for HTTP/1 chunked bodies received on a
+    /// connection with the `http1_chunk_extensions` option enabled.
+    #[cfg(feature = "http1")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http1")))]
+    pub fn chunk_extensions(&self) -> Option<&ChunkExtensions> {
+        match self.kind {
+            Kind::Chan {
+                ref chunk_extensions,
+                ..
+            } => chunk_extensions.as_deref(),
+            _ => None,
+        }
+    }
+
     #[cfg(feature = "ffi")]
     pub(crate) fn as_ffi_mut(&mut self) -> &mut crate::ffi::UserBody {
         match self.kind {
@@ -160,21 +192,27 @@ impl Recv {
                 content_length: ref mut len,
                 ref mut data_rx,
                 ref mut want_tx,
+                #[cfg(feature = "http1")]
+                ref mut chunk_extensions,
                 ..
             } => {
                 want_tx.send(WANT_READY);
 
                 match ready!(Pin::new(data_rx).poll_next(cx)?) {
                     Some(chunk) => {
-                        len.sub_if(chunk.len() as u64);
-                        Poll::Ready(Some(Ok(chunk)))
+                        len.sub_if(chunk.data.len() as u64);
+                        #[cfg(feature = "http1")]
+                        {
+                            *chunk_extensions = chunk.extensions;
+                        }
+                        Poll::Ready(Some(Ok(chunk.data)))
                     }
                     None => Poll::Ready(None),
                 }
             }
             #[cfg(all(feature = "http2", any(feature = "client", feature = "server")))]
             Kind::H2 {
-                ref ping,
+                ref mut ping,
                 recv: ref mut h2,
                 content_length: ref mut len,
             } => match ready!(h2.poll_data(cx)) {
@@ -182,10 +220,18 @@ impl Recv {
                     let _ = h2.flow_control().release_capacity(bytes.len());
                     len.sub_if(bytes.len() as u64);
                     ping.record_data(bytes.len());
+                    if h2.is_end_stream() {
+                        ping.record_end_of_stream();
+                    }
                     Poll::Ready(Some(Ok(bytes)))
                 }
                 Some(Err(e)) => Poll::Ready(Some(Err(crate::Error::new_body(e)))),
-                None => Poll::Ready(None),
+                None => {
+                    if h2.is_end_stream() {
+                        ping.record_end_of_stream();
+                    }
+                    Poll::Ready(None)
+                }
             },
 
             #[cfg(feature = "ffi")]
@@ -214,11 +260,12 @@ impl HttpBody for Recv {
             #[cfg(all(feature = "http2", any(feature = "client", feature = "server")))]
             Kind::H2 {
                 recv: ref mut h2,
-                ref ping,
+                ref mut ping,
                 ..
             } => match ready!(h2.poll_trailers(cx)) {
                 Ok(t) => {
                     ping.record_non_data();
+                    ping.record_end_of_stream();
                     Poll::Ready(Ok(t))
                 }
                 Err(e) => Poll::Ready(Err(crate::Error::new_h2(e))),
@@ -314,12 +361,20 @@ impl Sender {
     pub async fn send_data(&mut self, chunk: Bytes) -> crate::Result<()> {
         self.ready().await?;
         self.data_tx
-            .try_send(Ok(chunk))
+            .try_send(Ok(Chunk::new(chunk)))
             .map_err(|_| crate::Error::new_closed())
     }
 
     /// Send trailers on trailers channel.
     pub async fn send_trailers(&mut self, trailers: HeaderMap) -> crate::Result<()> {
+        self.try_send_trailers(trailers)
+    }
+
+    /// Try to send trailers on this channel.
+    ///
+    /// Sending trailers never has to wait, so this is what `send_trailers`
+    /// does without needing an async context.
+    pub(crate) fn try_send_trailers(&mut self, trailers: HeaderMap) -> crate::Result<()> {
         let tx = match self.trailers_tx.take() {
             Some(tx) => tx,
             None => return Err(crate::Error::new_closed()),
@@ -341,8 +396,19 @@ impl Sender {
     /// `send_data()` instead.
     pub fn try_send_data(&mut self, chunk: Bytes) -> Result<(), Bytes> {
         self.data_tx
-            .try_send(Ok(chunk))
-            .map_err(|err| err.into_inner().expect("just sent Ok"))
+            .try_send(Ok(Chunk::new(chunk)))
+            .map_err(|err| err.into_inner().expect("just sent Ok").data)
+    }
+
+    #[cfg(feature = "http1")]
+    pub(crate) fn try_send_data_with_extensions(
+        &mut self,
+        chunk: Bytes,
+        extensions: ChunkExtensions,
+    ) -> Result<(), Bytes> {
+        self.data_tx
+            .try_send(Ok(Chunk::with_extensions(chunk, extensions)))
+            .map_err(|err| err.into_inner().expect("just sent Ok").data)
     }
 
     /// Aborts the body in an abnormal fashion.
@@ -354,9 +420,38 @@ impl Sender {
             .try_send(Err(crate::Error::new_body_write_aborted()));
     }
 
-    #[cfg(feature = "http1")]
+    #[cfg(any(
+        feature = "http1",
+        all(
+            feature = "http2",
+            feature = "runtime",
+            any(feature = "client", feature = "server")
+        )
+    ))]
     pub(crate) fn send_error(&mut self, err: crate::Error) {
-        let _ = self.data_tx.try_send(Err(err));
+        let _ = self
+            .data_tx
+            // clone so the send works even if buffer is full
+            .clone()
+            .try_send(Err(err));
+    }
+}
+
+impl Chunk {
+    fn new(data: Bytes) -> Chunk {
+        Chunk {
+            data,
+            #[cfg(feature = "http1")]
+            extensions: None,
+        }
+    }
+
+    #[cfg(feature = "http1")]
+    fn with_extensions(data: Bytes, extensions: ChunkExtensions) -> Chunk {
+        Chunk {
+            data,
+            extensions: Some(Box::new(extensions)),
+        }
     }
 }
 
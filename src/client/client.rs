@@ -0,0 +1,795 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::mem;
+use std::sync::Arc;
+use std::time::Duration;
+
+use http::header::{HeaderValue, HOST};
+use http::uri::{Port, Scheme};
+use http::{Method, Request, Response, Uri, Version};
+use tracing::{debug, trace, warn};
+
+use super::conn;
+#[cfg(feature = "tcp")]
+use super::connect::HttpConnector;
+use super::connect::{sealed::Internal, Alpn, Connect, Connected, Connection};
+use super::pool::{
+    self, Checkout, Connecting, Key as PoolKey, Pool, Poolable, Pooled, Reservation,
+};
+use crate::body::{HttpBody, Recv};
+use crate::common::{
+    exec::{BoxSendFuture, Exec},
+    task, Future, Pin, Poll,
+};
+use crate::rt::Executor;
+
+/// A Client to make outgoing HTTP requests.
+///
+/// `Client` is cheap to clone and cloning is the recommended way to share a `Client`. The
+/// underlying connection pool will be reused.
+pub struct Client<C, B = Recv> {
+    config: Config,
+    connector: C,
+    exec: Exec,
+    #[cfg(feature = "http1")]
+    h1_builder: conn::http1::Builder,
+    #[cfg(feature = "http2")]
+    h2_builder: conn::http2::Builder,
+    pool: Pool<PoolClient<B>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Config {
+    set_host: bool,
+    ver: Ver,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Ver {
+    Auto,
+    Http2,
+}
+
+/// A `Future` that will resolve to an HTTP Response.
+///
+/// This is returned by `Client::request` (and `Client::get`).
+#[must_use = "futures do nothing unless polled"]
+pub struct ResponseFuture {
+    inner: Pin<Box<dyn Future<Output = crate::Result<Response<Recv>>> + Send>>,
+}
+
+// ===== impl Client =====
+
+impl Client<(), Recv> {
+    /// Create a builder to configure a new `Client`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "runtime")]
+    /// # fn run () {
+    /// use std::time::Duration;
+    /// use hyper::client::Client;
+    ///
+    /// let mut builder = Client::builder();
+    /// builder
+    ///     .pool_idle_timeout(Duration::from_secs(30))
+    ///     .pool_max_idle_per_host(8);
+    /// # drop(builder);
+    /// # }
+    /// # fn main() {}
+    /// ```
+    #[inline]
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+}
+
+#[cfg(feature = "tcp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tcp")))]
+impl Client<HttpConnector, Recv> {
+    /// Create a new Client with the default [config](Builder).
+    ///
+    /// # Note
+    ///
+    /// The default connector does **not** handle TLS. Speaking to `https`
+    /// destinations will require [configuring a connector that implements
+    /// TLS](crate::client::connect#custom-connectors).
+    #[inline]
+    pub fn new() -> Client<HttpConnector, Recv> {
+        Builder::default().build_http()
+    }
+}
+
+#[cfg(feature = "tcp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tcp")))]
+impl Default for Client<HttpConnector, Recv> {
+    fn default() -> Client<HttpConnector, Recv> {
+        Client::new()
+    }
+}
+
+impl<C, B> Client<C, B>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    /// Send a `GET` request to the supplied `Uri`.
+    ///
+    /// # Note
+    ///
+    /// This requires that the `HttpBody` type have a `Default` implementation.
+    /// It *should* return an "empty" version of itself, such that
+    /// `HttpBody::is_end_stream` is `true`.
+    pub fn get(&self, uri: Uri) -> ResponseFuture
+    where
+        B: Default,
+    {
+        let body = B::default();
+        if !body.is_end_stream() {
+            warn!("default HttpBody used for get() does not return true for is_end_stream");
+        }
+
+        let mut req = Request::new(body);
+        *req.uri_mut() = uri;
+        self.request(req)
+    }
+
+    /// Send a constructed `Request` using this `Client`.
+    ///
+    /// The `Uri` of the request must be in absolute-form, since its scheme
+    /// and authority are used to pick a pooled connection, or to make a
+    /// new one with the connector.
+    pub fn request(&self, mut req: Request<B>) -> ResponseFuture {
+        let is_http_connect = req.method() == Method::CONNECT;
+        match req.version() {
+            Version::HTTP_11 => (),
+            Version::HTTP_10 => {
+                if is_http_connect {
+                    warn!("CONNECT is not allowed for HTTP/1.0");
+                    return ResponseFuture::error_version(req.version());
+                }
+            }
+            Version::HTTP_2 => (),
+            // completely unsupported HTTP version (like HTTP/0.9)!
+            other => return ResponseFuture::error_version(other),
+        };
+
+        let pool_key = match extract_domain(req.uri_mut(), is_http_connect) {
+            Ok(s) => s,
+            Err(err) => {
+                return ResponseFuture::new(std::future::ready(Err(err)));
+            }
+        };
+
+        ResponseFuture::new(self.clone().send_request(req, pool_key))
+    }
+
+    async fn send_request(
+        self,
+        mut req: Request<B>,
+        pool_key: PoolKey,
+    ) -> crate::Result<Response<Recv>> {
+        let mut pooled = self.connection_for(pool_key).await?;
+
+        if pooled.is_http1() {
+            if req.version() == Version::HTTP_2 {
+                warn!("Connection is HTTP/1, but request requires HTTP/2");
+                return Err(crate::Error::new_user_unsupported_version());
+            }
+
+            if self.config.set_host {
+                let uri = req.uri().clone();
+                req.headers_mut().entry(HOST).or_insert_with(|| {
+                    let hostname = uri.host().expect("authority implies host");
+                    if let Some(port) = get_non_default_port(&uri) {
+                        let s = format!("{}:{}", hostname, port);
+                        HeaderValue::from_str(&s)
+                    } else {
+                        HeaderValue::from_str(hostname)
+                    }
+                    .expect("uri host is valid header value")
+                });
+            }
+
+            // CONNECT always sends authority-form, so check it first...
+            if req.method() == Method::CONNECT {
+                authority_form(req.uri_mut());
+            } else if pooled.conn_info.is_proxied {
+                absolute_form(req.uri_mut());
+            } else {
+                origin_form(req.uri_mut());
+            }
+        } else if req.method() == Method::CONNECT {
+            authority_form(req.uri_mut());
+        }
+
+        let res = pooled.send_request(req).await?;
+
+        // If pooled is HTTP/2, we can toss this reference immediately.
+        //
+        // when pooled is dropped, it will try to insert back into the
+        // pool. To delay that, spawn a future that completes once the
+        // sender is ready again.
+        //
+        // This *should* only be once the related `Connection` has polled
+        // for a new request to start.
+        //
+        // It won't be ready if there is a body to stream.
+        if pooled.is_http2() || !pooled.is_pool_enabled() || pooled.is_ready() {
+            drop(pooled);
+        } else {
+            let on_idle = async move {
+                let _ = futures_util::future::poll_fn(|cx| pooled.poll_ready(cx)).await;
+            };
+
+            self.exec.execute(on_idle);
+        }
+
+        Ok(res)
+    }
+
+    async fn connection_for(&self, pool_key: PoolKey) -> crate::Result<Pooled<PoolClient<B>>> {
+        loop {
+            match self.pool.checkout(pool_key.clone(), self.is_http2()) {
+                Checkout::Idle(pooled) => {
+                    trace!("reusing idle connection for {:?}", pool_key);
+                    return Ok(pooled);
+                }
+                Checkout::Wait(waiter) => {
+                    if let Some(pooled) = waiter.await {
+                        return Ok(pooled);
+                    }
+                    // The connection being waited on failed, so check
+                    // out again and maybe connect this time.
+                    trace!(
+                        "shared connection for {:?} failed, checking out again",
+                        pool_key
+                    );
+                }
+                Checkout::Connect(connecting) => {
+                    return self.connect_to(pool_key, connecting).await;
+                }
+            }
+        }
+    }
+
+    async fn connect_to(
+        &self,
+        pool_key: PoolKey,
+        connecting: Connecting<PoolClient<B>>,
+    ) -> crate::Result<Pooled<PoolClient<B>>> {
+        let dst = domain_as_uri(pool_key);
+        let io = self
+            .connector
+            .clone()
+            .connect(Internal, dst)
+            .await
+            .map_err(crate::Error::new_connect)?;
+
+        let conn_info = io.connected();
+        let is_h2 = self.is_http2() || conn_info.alpn == Alpn::H2;
+
+        let tx = self.handshake(io, is_h2).await?;
+        Ok(self.pool.pooled(connecting, PoolClient { conn_info, tx }))
+    }
+
+    #[allow(clippy::needless_return)]
+    #[cfg_attr(not(feature = "http2"), allow(unused_variables))]
+    async fn handshake<T>(&self, io: T, is_h2: bool) -> crate::Result<PoolTx<B>>
+    where
+        T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+    {
+        #[cfg(feature = "http2")]
+        {
+            if is_h2 {
+                trace!("http2 handshake");
+                let (tx, conn) = self.h2_builder.handshake(io).await?;
+                self.exec.execute(async move {
+                    if let Err(err) = conn.await {
+                        debug!("client connection error: {}", err);
+                    }
+                });
+                return Ok(PoolTx::Http2(tx));
+            }
+        }
+
+        #[cfg(feature = "http1")]
+        {
+            trace!("http1 handshake");
+            let (tx, conn) = self.h1_builder.handshake(io).await?;
+            self.exec.execute(async move {
+                if let Err(err) = conn.await {
+                    debug!("client connection error: {}", err);
+                }
+            });
+            return Ok(PoolTx::Http1(tx));
+        }
+
+        #[cfg(not(feature = "http1"))]
+        unreachable!("http2 is always used without http1");
+    }
+
+    fn is_http2(&self) -> bool {
+        cfg!(not(feature = "http1")) || self.config.ver == Ver::Http2
+    }
+}
+
+impl<C, B> tower_service::Service<Request<B>> for Client<C, B>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    type Response = Response<Recv>;
+    type Error = crate::Error;
+    type Future = ResponseFuture;
+
+    fn poll_ready(&mut self, _: &mut task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        self.request(req)
+    }
+}
+
+impl<C, B> tower_service::Service<Request<B>> for &'_ Client<C, B>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    type Response = Response<Recv>;
+    type Error = crate::Error;
+    type Future = ResponseFuture;
+
+    fn poll_ready(&mut self, _: &mut task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        self.request(req)
+    }
+}
+
+impl<C: Clone, B> Clone for Client<C, B> {
+    fn clone(&self) -> Client<C, B> {
+        Client {
+            config: self.config,
+            exec: self.exec.clone(),
+            #[cfg(feature = "http1")]
+            h1_builder: self.h1_builder.clone(),
+            #[cfg(feature = "http2")]
+            h2_builder: self.h2_builder.clone(),
+            connector: self.connector.clone(),
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+impl<C, B> fmt::Debug for Client<C, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client").finish()
+    }
+}
+
+// ===== impl ResponseFuture =====
+
+impl ResponseFuture {
+    fn new<F>(value: F) -> Self
+    where
+        F: Future<Output = crate::Result<Response<Recv>>> + Send + 'static,
+    {
+        Self {
+            inner: Box::pin(value),
+        }
+    }
+
+    fn error_version(ver: Version) -> Self {
+        warn!("Request has unsupported version \"{:?}\"", ver);
+        ResponseFuture::new(std::future::ready(Err(
+            crate::Error::new_user_unsupported_version(),
+        )))
+    }
+}
+
+impl fmt::Debug for ResponseFuture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("Future<Response>")
+    }
+}
+
+impl Future for ResponseFuture {
+    type Output = crate::Result<Response<Recv>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        self.inner.as_mut().poll(cx)
+    }
+}
+
+// ===== impl PoolClient =====
+
+// FIXME: allow() required due to `impl Trait` leaking types to this lint
+#[allow(missing_debug_implementations)]
+struct PoolClient<B> {
+    conn_info: Connected,
+    tx: PoolTx<B>,
+}
+
+enum PoolTx<B> {
+    #[cfg(feature = "http1")]
+    Http1(conn::http1::SendRequest<B>),
+    #[cfg(feature = "http2")]
+    Http2(conn::http2::SendRequest<B>),
+}
+
+impl<B> PoolClient<B> {
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Result<()>> {
+        match self.tx {
+            #[cfg(feature = "http1")]
+            PoolTx::Http1(ref mut tx) => tx.poll_ready(cx),
+            #[cfg(feature = "http2")]
+            PoolTx::Http2(ref mut tx) => tx.poll_ready(cx),
+        }
+    }
+
+    fn is_http1(&self) -> bool {
+        !self.is_http2()
+    }
+
+    fn is_http2(&self) -> bool {
+        match self.tx {
+            #[cfg(feature = "http1")]
+            PoolTx::Http1(_) => false,
+            #[cfg(feature = "http2")]
+            PoolTx::Http2(_) => true,
+        }
+    }
+
+    fn is_ready(&self) -> bool {
+        match self.tx {
+            #[cfg(feature = "http1")]
+            PoolTx::Http1(ref tx) => tx.is_ready(),
+            #[cfg(feature = "http2")]
+            PoolTx::Http2(ref tx) => tx.is_ready(),
+        }
+    }
+}
+
+impl<B: HttpBody + 'static> PoolClient<B> {
+    async fn send_request(&mut self, req: Request<B>) -> crate::Result<Response<Recv>> {
+        match self.tx {
+            #[cfg(feature = "http1")]
+            PoolTx::Http1(ref mut tx) => tx.send_request(req).await,
+            #[cfg(feature = "http2")]
+            PoolTx::Http2(ref mut tx) => tx.send_request(req).await,
+        }
+    }
+}
+
+impl<B> Poolable for PoolClient<B>
+where
+    B: Send + 'static,
+{
+    fn is_open(&self) -> bool {
+        self.is_ready()
+    }
+
+    fn reserve(self) -> Reservation<Self> {
+        match self.tx {
+            #[cfg(feature = "http1")]
+            PoolTx::Http1(tx) => Reservation::Unique(PoolClient {
+                conn_info: self.conn_info,
+                tx: PoolTx::Http1(tx),
+            }),
+            #[cfg(feature = "http2")]
+            PoolTx::Http2(tx) => {
+                let b = PoolClient {
+                    conn_info: self.conn_info.clone(),
+                    tx: PoolTx::Http2(tx.clone()),
+                };
+                let a = PoolClient {
+                    conn_info: self.conn_info,
+                    tx: PoolTx::Http2(tx),
+                };
+                Reservation::Shared(a, b)
+            }
+        }
+    }
+
+    fn can_share(&self) -> bool {
+        self.is_http2()
+    }
+}
+
+fn origin_form(uri: &mut Uri) {
+    let path = match uri.path_and_query() {
+        Some(path) if path.as_str() != "/" => {
+            let mut parts = ::http::uri::Parts::default();
+            parts.path_and_query = Some(path.clone());
+            Uri::from_parts(parts).expect("path is valid uri")
+        }
+        _none_or_just_slash => {
+            debug_assert!(Uri::default() == "/");
+            Uri::default()
+        }
+    };
+    *uri = path
+}
+
+fn absolute_form(uri: &mut Uri) {
+    debug_assert!(uri.scheme().is_some(), "absolute_form needs a scheme");
+    debug_assert!(
+        uri.authority().is_some(),
+        "absolute_form needs an authority"
+    );
+    // If the URI is to HTTPS, and the connector claimed to be a proxy,
+    // then it *should* have tunneled, and so we don't want to send
+    // absolute-form in that case.
+    if uri.scheme() == Some(&Scheme::HTTPS) {
+        origin_form(uri);
+    }
+}
+
+fn authority_form(uri: &mut Uri) {
+    if let Some(path) = uri.path_and_query() {
+        // `https://hyper.rs` would parse with `/` path, don't
+        // annoy people about this...
+        if path != "/" {
+            warn!("HTTP/1.1 CONNECT request stripping path: {:?}", path);
+        }
+    }
+    *uri = match uri.authority() {
+        Some(auth) => {
+            let mut parts = ::http::uri::Parts::default();
+            parts.authority = Some(auth.clone());
+            Uri::from_parts(parts).expect("authority is valid")
+        }
+        None => {
+            unreachable!("authority_form with relative uri");
+        }
+    };
+}
+
+fn extract_domain(uri: &mut Uri, is_http_connect: bool) -> crate::Result<PoolKey> {
+    let uri_clone = uri.clone();
+    match (uri_clone.scheme(), uri_clone.authority()) {
+        (Some(scheme), Some(auth)) => Ok((scheme.clone(), auth.clone())),
+        (None, Some(auth)) if is_http_connect => {
+            let scheme = match auth.port_u16() {
+                Some(443) => {
+                    set_scheme(uri, Scheme::HTTPS);
+                    Scheme::HTTPS
+                }
+                _ => {
+                    set_scheme(uri, Scheme::HTTP);
+                    Scheme::HTTP
+                }
+            };
+            Ok((scheme, auth.clone()))
+        }
+        _ => {
+            debug!("Client requires absolute-form URIs, received: {:?}", uri);
+            Err(crate::Error::new_user_absolute_uri_required())
+        }
+    }
+}
+
+fn domain_as_uri((scheme, auth): PoolKey) -> Uri {
+    http::uri::Builder::new()
+        .scheme(scheme)
+        .authority(auth)
+        .path_and_query("/")
+        .build()
+        .expect("domain is valid Uri")
+}
+
+fn set_scheme(uri: &mut Uri, scheme: Scheme) {
+    debug_assert!(
+        uri.scheme().is_none(),
+        "set_scheme expects no existing scheme"
+    );
+    let old = mem::take(uri);
+    let mut parts: ::http::uri::Parts = old.into();
+    parts.scheme = Some(scheme);
+    parts.path_and_query = Some("/".parse().expect("slash is a valid path"));
+    *uri = Uri::from_parts(parts).expect("scheme is valid");
+}
+
+fn get_non_default_port(uri: &Uri) -> Option<Port<&str>> {
+    match (uri.port().map(|p| p.as_u16()), is_schema_secure(uri)) {
+        (Some(443), true) => None,
+        (Some(80), false) => None,
+        _ => uri.port(),
+    }
+}
+
+fn is_schema_secure(uri: &Uri) -> bool {
+    uri.scheme_str()
+        .map(|scheme_str| matches!(scheme_str, "wss" | "https"))
+        .unwrap_or_default()
+}
+
+// ===== impl Builder =====
+
+/// A builder to configure a new [`Client`](Client).
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "runtime")]
+/// # fn run () {
+/// use std::time::Duration;
+/// use hyper::client::Client;
+///
+/// let mut builder = Client::builder();
+/// builder
+///     .pool_idle_timeout(Duration::from_secs(30))
+///     .http2_only(true);
+/// # drop(builder);
+/// # }
+/// # fn main() {}
+/// ```
+#[derive(Clone, Debug)]
+pub struct Builder {
+    client_config: Config,
+    exec: Exec,
+    #[cfg(feature = "http1")]
+    h1_builder: conn::http1::Builder,
+    #[cfg(feature = "http2")]
+    h2_builder: conn::http2::Builder,
+    pool_config: pool::Config,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            client_config: Config {
+                set_host: true,
+                ver: Ver::Auto,
+            },
+            exec: Exec::Default,
+            #[cfg(feature = "http1")]
+            h1_builder: conn::http1::Builder::new(),
+            #[cfg(feature = "http2")]
+            h2_builder: conn::http2::Builder::new(),
+            pool_config: pool::Config {
+                idle_timeout: Some(Duration::from_secs(90)),
+                max_idle_per_host: usize::MAX,
+            },
+        }
+    }
+}
+
+impl Builder {
+    /// Set an optional timeout for idle sockets being kept-alive.
+    ///
+    /// Pass `None` to disable timeout.
+    ///
+    /// Default is 90 seconds.
+    pub fn pool_idle_timeout<D>(&mut self, val: D) -> &mut Self
+    where
+        D: Into<Option<Duration>>,
+    {
+        self.pool_config.idle_timeout = val.into();
+        self
+    }
+
+    /// Sets the maximum idle connection per host allowed in the pool.
+    ///
+    /// Setting this to `0` disables pooling, so that every request gets a
+    /// new connection.
+    ///
+    /// Default is `usize::MAX` (no limit).
+    pub fn pool_max_idle_per_host(&mut self, max_idle: usize) -> &mut Self {
+        self.pool_config.max_idle_per_host = max_idle;
+        self
+    }
+
+    /// Set whether the connection **must** use HTTP/2.
+    ///
+    /// Note that setting this to true prevents HTTP/1 from being allowed.
+    ///
+    /// A single HTTP/2 connection per host is shared by all requests to it.
+    ///
+    /// Default is false.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub fn http2_only(&mut self, val: bool) -> &mut Self {
+        self.client_config.ver = if val { Ver::Http2 } else { Ver::Auto };
+        self
+    }
+
+    /// Set whether to automatically add the `Host` header to requests.
+    ///
+    /// If true, and a request does not include a `Host` header, one will be
+    /// added automatically, derived from the authority of the `Uri`.
+    ///
+    /// Default is `true`.
+    #[inline]
+    pub fn set_host(&mut self, val: bool) -> &mut Self {
+        self.client_config.set_host = val;
+        self
+    }
+
+    /// Provide an executor to execute background `Connection` tasks.
+    pub fn executor<E>(&mut self, exec: E) -> &mut Self
+    where
+        E: Executor<BoxSendFuture> + Send + Sync + 'static,
+    {
+        self.exec = Exec::Executor(Arc::new(exec));
+        self
+    }
+
+    /// Returns the builder used for the HTTP/1 connections of the `Client`.
+    ///
+    /// Its executor is replaced by the one given to
+    /// [`executor`](Builder::executor).
+    #[cfg(feature = "http1")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http1")))]
+    pub fn http1(&mut self) -> &mut conn::http1::Builder {
+        &mut self.h1_builder
+    }
+
+    /// Returns the builder used for the HTTP/2 connections of the `Client`.
+    ///
+    /// Its executor is replaced by the one given to
+    /// [`executor`](Builder::executor).
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub fn http2(&mut self) -> &mut conn::http2::Builder {
+        &mut self.h2_builder
+    }
+
+    /// Combine the configuration of this builder with a connector to create a `Client`.
+    pub fn build<C, B>(&self, connector: C) -> Client<C, B>
+    where
+        C: Connect + Clone,
+        B: HttpBody + Send,
+        B::Data: Send,
+    {
+        #[cfg(feature = "http1")]
+        let mut h1_builder = self.h1_builder.clone();
+        #[cfg(feature = "http1")]
+        {
+            h1_builder.exec = self.exec.clone();
+        }
+        #[cfg(feature = "http2")]
+        let mut h2_builder = self.h2_builder.clone();
+        #[cfg(feature = "http2")]
+        {
+            h2_builder.exec = self.exec.clone();
+        }
+
+        Client {
+            config: self.client_config,
+            exec: self.exec.clone(),
+            #[cfg(feature = "http1")]
+            h1_builder,
+            #[cfg(feature = "http2")]
+            h2_builder,
+            connector,
+            pool: Pool::new(self.pool_config, &self.exec),
+        }
+    }
+
+    /// Build a client with this configuration and the default `HttpConnector`.
+    #[cfg(feature = "tcp")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tcp")))]
+    pub fn build_http<B>(&self) -> Client<HttpConnector, B>
+    where
+        B: HttpBody + Send,
+        B::Data: Send,
+    {
+        let mut connector = HttpConnector::new();
+        if self.pool_config.is_enabled() {
+            connector.set_keepalive(self.pool_config.idle_timeout);
+        }
+        self.build(connector)
+    }
+}
@@ -3,6 +3,8 @@
 use std::error::Error as StdError;
 use std::fmt;
 use std::sync::Arc;
+#[cfg(feature = "runtime")]
+use std::time::Duration;
 
 use http::{Request, Response};
 use httparse::ParserConfig;
@@ -14,7 +16,10 @@ use crate::common::{
     exec::{BoxSendFuture, Exec},
     task, Future, Pin, Poll,
 };
+#[cfg(feature = "runtime")]
+use crate::ext::RequestTimeouts;
 use crate::upgrade::Upgraded;
+use crate::observe::{Observer, ObserverRef};
 use crate::proto;
 use crate::rt::Executor;
 use super::super::dispatch;
@@ -45,16 +50,24 @@ where
 /// After setting options, the builder is used to create a handshake future.
 #[derive(Clone, Debug)]
 pub struct Builder {
-    pub(super) exec: Exec,
+    pub(crate) exec: Exec,
     h09_responses: bool,
     h1_parser_config: ParserConfig,
+    h1_ignore_invalid_headers: bool,
     h1_writev: Option<bool>,
+    h1_chunk_extensions: bool,
     h1_title_case_headers: bool,
     h1_preserve_header_case: bool,
-    #[cfg(feature = "ffi")]
     h1_preserve_header_order: bool,
+    h1_preserve_raw_head: bool,
     h1_read_buf_exact_size: Option<usize>,
     h1_max_buf_size: Option<usize>,
+    h1_max_headers: Option<usize>,
+    #[cfg(feature = "runtime")]
+    h1_expect_continue_timeout: Duration,
+    #[cfg(feature = "runtime")]
+    timeouts: RequestTimeouts,
+    h1_observer: ObserverRef,
 }
 
 /// Returns a handshake future over some IO.
@@ -89,11 +102,13 @@ impl<B> SendRequest<B> {
         })
         .await
     }
+    */
 
-    pub(super) fn is_ready(&self) -> bool {
+    pub(crate) fn is_ready(&self) -> bool {
         self.dispatch.is_ready()
     }
 
+    /*
     pub(super) fn is_closed(&self) -> bool {
         self.dispatch.is_closed()
     }
@@ -226,11 +241,19 @@ impl Builder {
             h1_writev: None,
             h1_read_buf_exact_size: None,
             h1_parser_config: Default::default(),
+            h1_ignore_invalid_headers: false,
+            h1_chunk_extensions: false,
             h1_title_case_headers: false,
             h1_preserve_header_case: false,
-            #[cfg(feature = "ffi")]
             h1_preserve_header_order: false,
+            h1_preserve_raw_head: false,
             h1_max_buf_size: None,
+            h1_max_headers: None,
+            #[cfg(feature = "runtime")]
+            h1_expect_continue_timeout: Duration::from_secs(1),
+            #[cfg(feature = "runtime")]
+            timeouts: RequestTimeouts::new(),
+            h1_observer: ObserverRef::default(),
         }
     }
 
@@ -322,6 +345,23 @@ impl Builder {
         self
     }
 
+    /// Set whether HTTP/1 connections will ignore invalid header lines in
+    /// responses, instead of failing them.
+    ///
+    /// Some legacy servers send a malformed header line, such as a header
+    /// name with a space or a header value with a control character. When
+    /// enabled, such lines are dropped, and the response carries an
+    /// [`IgnoredInvalidHeaders`](crate::ext::IgnoredInvalidHeaders) extension
+    /// with the number of lines that were dropped.
+    ///
+    /// Note that this setting does not affect HTTP/2.
+    ///
+    /// Default is false.
+    pub fn http1_ignore_invalid_headers_in_responses(&mut self, enabled: bool) -> &mut Builder {
+        self.h1_ignore_invalid_headers = enabled;
+        self
+    }
+
     /// Set whether HTTP/1 connections should try to use vectored writes,
     /// or always flatten into a single buffer.
     ///
@@ -339,6 +379,26 @@ impl Builder {
         self
     }
 
+    /// Set whether HTTP/1 connections will surface the chunk extensions of
+    /// incoming chunked bodies, and write those of outgoing ones.
+    ///
+    /// When enabled, the extensions of each chunk are available from
+    /// [`Recv::chunk_extensions`](crate::Recv::chunk_extensions) after
+    /// polling its data. The extensions of a single chunk are limited to
+    /// 16kb, and a chunk with larger extensions is treated as an error.
+    ///
+    /// Outgoing bodies are written with the extensions set through an
+    /// [`OutgoingChunkExtensions`](crate::ext::OutgoingChunkExtensions) in
+    /// the extensions of their message.
+    ///
+    /// Note that this setting does not affect HTTP/2.
+    ///
+    /// Default is false.
+    pub fn http1_chunk_extensions(&mut self, enabled: bool) -> &mut Builder {
+        self.h1_chunk_extensions = enabled;
+        self
+    }
+
     /// Set whether HTTP/1 connections will write header names as title case at
     /// the socket level.
     ///
@@ -352,13 +412,10 @@ impl Builder {
 
     /// Set whether to support preserving original header cases.
     ///
-    /// Currently, this will record the original cases received, and store them
-    /// in a private extension on the `Response`. It will also look for and use
-    /// such an extension in any provided `Request`.
-    ///
-    /// Since the relevant extension is still private, there is no way to
-    /// interact with the original cases. The only effect this can have now is
-    /// to forward the cases in a proxy-like fashion.
+    /// This will record the original cases received, and store them in a
+    /// [`HeaderCaseMap`](crate::ext::HeaderCaseMap) extension on the
+    /// `Response`. Such an extension in any provided `Request` is used
+    /// whether or not this is set.
     ///
     /// Note that this setting does not affect HTTP/2.
     ///
@@ -370,19 +427,33 @@ impl Builder {
 
     /// Set whether to support preserving original header order.
     ///
-    /// Currently, this will record the order in which headers are received, and store this
-    /// ordering in a private extension on the `Response`. It will also look for and use
-    /// such an extension in any provided `Request`.
+    /// This will record the order in which headers are received, and store
+    /// it in an [`OriginalHeaderOrder`](crate::ext::OriginalHeaderOrder)
+    /// extension on the `Response`. Such an extension in any provided
+    /// `Request` is used whether or not this is set.
     ///
     /// Note that this setting does not affect HTTP/2.
     ///
     /// Default is false.
-    #[cfg(feature = "ffi")]
     pub fn http1_preserve_header_order(&mut self, enabled: bool) -> &mut Builder {
         self.h1_preserve_header_order = enabled;
         self
     }
 
+    /// Set whether to keep the raw head of responses.
+    ///
+    /// This will store the status line and header lines, exactly as they
+    /// were received, in a [`RawHead`](crate::ext::RawHead) extension on the
+    /// `Response`.
+    ///
+    /// Note that this setting does not affect HTTP/2.
+    ///
+    /// Default is false.
+    pub fn http1_preserve_raw_head(&mut self, enabled: bool) -> &mut Builder {
+        self.h1_preserve_raw_head = enabled;
+        self
+    }
+
     /// Sets the exact size of the read buffer to *always* use.
     ///
     /// Note that setting this option unsets the `http1_max_buf_size` option.
@@ -416,6 +487,124 @@ impl Builder {
         self
     }
 
+    /// Set the maximum number of headers of a response.
+    ///
+    /// A response with more headers fails to parse. Up to 100 headers are
+    /// parsed on the stack, while a larger maximum allocates room for the
+    /// headers on the heap for each response.
+    ///
+    /// Default is 100.
+    pub fn http1_max_headers(&mut self, max: usize) -> &mut Self {
+        self.h1_max_headers = Some(max);
+        self
+    }
+
+    /// Sets how long a request with an `Expect: 100-continue` header waits
+    /// for the server to respond `100 Continue`, before sending its body
+    /// anyway.
+    ///
+    /// The body is held until `100 Continue`, a final response, or this
+    /// timeout. If a final response that isn't a success comes first, the
+    /// body isn't sent. A chunked body then ends without any data, keeping
+    /// the connection usable, while a body of known length can't, so the
+    /// connection is closed after the response.
+    ///
+    /// Note that this setting does not affect HTTP/2.
+    ///
+    /// Default is 1 second.
+    ///
+    /// # Cargo Feature
+    ///
+    /// Requires the `runtime` cargo feature to be enabled. Without it, the
+    /// body is sent right away.
+    #[cfg(feature = "runtime")]
+    pub fn http1_expect_continue_timeout(&mut self, timeout: Duration) -> &mut Builder {
+        self.h1_expect_continue_timeout = timeout;
+        self
+    }
+
+    /// Sets how long to wait for the response head of a request, from when
+    /// the request starts being sent.
+    ///
+    /// If the response head isn't received in time, the request fails with a
+    /// timeout error, and the connection is closed. A request can override this
+    /// timeout with a [`RequestTimeouts`] in its extensions.
+    ///
+    /// Pass `None` to disable this timeout.
+    ///
+    /// Default is no timeout.
+    ///
+    /// # Cargo Feature
+    ///
+    /// Requires the `runtime` cargo feature to be enabled.
+    ///
+    /// [`RequestTimeouts`]: crate::ext::RequestTimeouts
+    #[cfg(feature = "runtime")]
+    pub fn response_head_timeout(&mut self, timeout: impl Into<Option<Duration>>) -> &mut Builder {
+        self.timeouts.response_head(timeout);
+        self
+    }
+
+    /// Sets how long to wait for each piece of data of a response body.
+    ///
+    /// The timeout doesn't run while the body waits to be read. If it
+    /// expires, the body yields a timeout error, and the connection is closed. A
+    /// request can override this timeout with a [`RequestTimeouts`] in its
+    /// extensions.
+    ///
+    /// Pass `None` to disable this timeout.
+    ///
+    /// Default is no timeout.
+    ///
+    /// # Cargo Feature
+    ///
+    /// Requires the `runtime` cargo feature to be enabled.
+    ///
+    /// [`RequestTimeouts`]: crate::ext::RequestTimeouts
+    #[cfg(feature = "runtime")]
+    pub fn response_body_idle_timeout(
+        &mut self,
+        timeout: impl Into<Option<Duration>>,
+    ) -> &mut Builder {
+        self.timeouts.response_body_idle(timeout);
+        self
+    }
+
+    /// Sets a deadline for the whole response to a request, head and body,
+    /// from when the request starts being sent.
+    ///
+    /// If the deadline passes, the request, or else the response body,
+    /// fails with a timeout error, and the connection is closed. A request can
+    /// override this deadline with a [`RequestTimeouts`] in its extensions.
+    ///
+    /// Pass `None` to disable this deadline.
+    ///
+    /// Default is no deadline.
+    ///
+    /// # Cargo Feature
+    ///
+    /// Requires the `runtime` cargo feature to be enabled.
+    ///
+    /// [`RequestTimeouts`]: crate::ext::RequestTimeouts
+    #[cfg(feature = "runtime")]
+    pub fn total_timeout(&mut self, timeout: impl Into<Option<Duration>>) -> &mut Builder {
+        self.timeouts.total(timeout);
+        self
+    }
+
+    /// Sets an observer of the events of the connection.
+    ///
+    /// See [`Observer`](crate::observe::Observer) for the events reported.
+    ///
+    /// Default is no observer.
+    pub fn observer<O>(&mut self, observer: O) -> &mut Self
+    where
+        O: Observer + 'static,
+    {
+        self.h1_observer = ObserverRef::new(observer);
+        self
+    }
+
     /// Constructs a connection with the configured options and IO.
     /// See [`client::conn`](crate::client::conn) for more.
     ///
@@ -439,6 +628,9 @@ impl Builder {
             let (tx, rx) = dispatch::channel();
             let mut conn = proto::Conn::new(io);
             conn.set_h1_parser_config(opts.h1_parser_config);
+            if opts.h1_ignore_invalid_headers {
+                conn.set_ignore_invalid_headers();
+            }
             if let Some(writev) = opts.h1_writev {
                 if writev {
                     conn.set_write_strategy_queue();
@@ -446,16 +638,21 @@ impl Builder {
                     conn.set_write_strategy_flatten();
                 }
             }
+            if opts.h1_chunk_extensions {
+                conn.set_chunk_extensions();
+            }
             if opts.h1_title_case_headers {
                 conn.set_title_case_headers();
             }
             if opts.h1_preserve_header_case {
                 conn.set_preserve_header_case();
             }
-            #[cfg(feature = "ffi")]
             if opts.h1_preserve_header_order {
                 conn.set_preserve_header_order();
             }
+            if opts.h1_preserve_raw_head {
+                conn.set_preserve_raw_head();
+            }
             if opts.h09_responses {
                 conn.set_h09_responses();
             }
@@ -466,7 +663,16 @@ impl Builder {
             if let Some(max) = opts.h1_max_buf_size {
                 conn.set_max_buf_size(max);
             }
-            let cd = proto::h1::dispatch::Client::new(rx);
+            if let Some(max) = opts.h1_max_headers {
+                conn.set_max_headers(max);
+            }
+            #[cfg(feature = "runtime")]
+            conn.set_expect_continue_timeout(opts.h1_expect_continue_timeout);
+            conn.set_observer(opts.h1_observer);
+            #[cfg_attr(not(feature = "runtime"), allow(unused_mut))]
+            let mut cd = proto::h1::dispatch::Client::new(rx);
+            #[cfg(feature = "runtime")]
+            cd.set_timeouts(opts.timeouts);
             let proto = proto::h1::Dispatcher::new(cd, conn);
 
             Ok((
@@ -16,6 +16,7 @@ use crate::common::{
     exec::{BoxSendFuture, Exec},
     task, Future, Pin, Poll,
 };
+use crate::observe::{Observer, ObserverRef};
 use crate::proto;
 use crate::rt::Executor;
 use super::super::dispatch;
@@ -43,7 +44,7 @@ where
 /// After setting options, the builder is used to create a handshake future.
 #[derive(Clone, Debug)]
 pub struct Builder {
-    pub(super) exec: Exec,
+    pub(crate) exec: Exec,
     h2_builder: proto::h2::client::Config,
 }
 
@@ -83,11 +84,11 @@ impl<B> SendRequest<B> {
         })
         .await
     }
+    */
 
-    pub(super) fn is_ready(&self) -> bool {
+    pub(crate) fn is_ready(&self) -> bool {
         self.dispatch.is_ready()
     }
-    */
 
     pub(super) fn is_closed(&self) -> bool {
         self.dispatch.is_closed()
@@ -163,6 +164,14 @@ where
     */
 }
 
+impl<B> Clone for SendRequest<B> {
+    fn clone(&self) -> SendRequest<B> {
+        SendRequest {
+            dispatch: self.dispatch.clone(),
+        }
+    }
+}
+
 impl<B> fmt::Debug for SendRequest<B> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("SendRequest").finish()
@@ -377,6 +386,88 @@ impl Builder {
         self
     }
 
+    /// Sets how long to wait for the response head of a request, from when
+    /// the request starts being sent.
+    ///
+    /// If the response head isn't received in time, the request fails with a
+    /// timeout error, and its stream is reset. A request can override this
+    /// timeout with a [`RequestTimeouts`] in its extensions.
+    ///
+    /// Pass `None` to disable this timeout.
+    ///
+    /// Default is no timeout.
+    ///
+    /// # Cargo Feature
+    ///
+    /// Requires the `runtime` cargo feature to be enabled.
+    ///
+    /// [`RequestTimeouts`]: crate::ext::RequestTimeouts
+    #[cfg(feature = "runtime")]
+    pub fn response_head_timeout(&mut self, timeout: impl Into<Option<Duration>>) -> &mut Self {
+        self.h2_builder.timeouts.response_head(timeout);
+        self
+    }
+
+    /// Sets how long to wait for each piece of data of a response body.
+    ///
+    /// The timeout doesn't run while the body waits to be read. If it
+    /// expires, the body yields a timeout error, and its stream is reset. A
+    /// request can override this timeout with a [`RequestTimeouts`] in its
+    /// extensions.
+    ///
+    /// Pass `None` to disable this timeout.
+    ///
+    /// Default is no timeout.
+    ///
+    /// # Cargo Feature
+    ///
+    /// Requires the `runtime` cargo feature to be enabled.
+    ///
+    /// [`RequestTimeouts`]: crate::ext::RequestTimeouts
+    #[cfg(feature = "runtime")]
+    pub fn response_body_idle_timeout(
+        &mut self,
+        timeout: impl Into<Option<Duration>>,
+    ) -> &mut Self {
+        self.h2_builder.timeouts.response_body_idle(timeout);
+        self
+    }
+
+    /// Sets a deadline for the whole response to a request, head and body,
+    /// from when the request starts being sent.
+    ///
+    /// If the deadline passes, the request, or else the response body,
+    /// fails with a timeout error, and its stream is reset. A request can
+    /// override this deadline with a [`RequestTimeouts`] in its extensions.
+    ///
+    /// Pass `None` to disable this deadline.
+    ///
+    /// Default is no deadline.
+    ///
+    /// # Cargo Feature
+    ///
+    /// Requires the `runtime` cargo feature to be enabled.
+    ///
+    /// [`RequestTimeouts`]: crate::ext::RequestTimeouts
+    #[cfg(feature = "runtime")]
+    pub fn total_timeout(&mut self, timeout: impl Into<Option<Duration>>) -> &mut Self {
+        self.h2_builder.timeouts.total(timeout);
+        self
+    }
+
+    /// Sets an observer of the events of the connection.
+    ///
+    /// See [`Observer`](crate::observe::Observer) for the events reported.
+    ///
+    /// Default is no observer.
+    pub fn observer<O>(&mut self, observer: O) -> &mut Self
+    where
+        O: Observer + 'static,
+    {
+        self.h2_builder.observer = ObserverRef::new(observer);
+        self
+    }
+
     /// Constructs a connection with the configured options and IO.
     /// See [`client::conn`](crate::client::conn) for more.
     ///
@@ -61,7 +61,7 @@ use std::fmt;
 #[cfg(not(all(feature = "http1", feature = "http2")))]
 use std::marker::PhantomData;
 use std::sync::Arc;
-#[cfg(all(feature = "runtime", feature = "http2"))]
+#[cfg(feature = "runtime")]
 use std::time::Duration;
 
 use bytes::Bytes;
@@ -80,8 +80,11 @@ use crate::common::{
     exec::{BoxSendFuture, Exec},
     task, Future, Pin, Poll,
 };
+use crate::observe::{Observer, ObserverRef};
 use crate::proto;
 use crate::rt::Executor;
+#[cfg(all(feature = "http1", feature = "http2"))]
+use crate::common::{io::Rewind, sync_wrapper::SyncWrapper};
 #[cfg(feature = "http1")]
 use crate::upgrade::Upgraded;
 use crate::{Recv, Request, Response};
@@ -153,6 +156,18 @@ where
     B: HttpBody + 'static,
 {
     inner: Option<ProtoClient<T, B>>,
+    #[cfg(all(feature = "http1", feature = "http2"))]
+    h2c: Option<H2c<B>>,
+}
+
+/// The state of an HTTP/1 connection that may be upgraded to h2c.
+#[cfg(all(feature = "http1", feature = "http2"))]
+enum H2c<B: HttpBody> {
+    /// The options to use once upgraded.
+    Pending(proto::h2::client::Config, Exec),
+    Handshaking(
+        SyncWrapper<Pin<Box<dyn Future<Output = crate::Result<Http2ClientTask<B>>> + Send>>>,
+    ),
 }
 
 /// A builder to configure an HTTP connection.
@@ -163,17 +178,27 @@ pub struct Builder {
     pub(super) exec: Exec,
     h09_responses: bool,
     h1_parser_config: ParserConfig,
+    h1_ignore_invalid_headers: bool,
     h1_writev: Option<bool>,
+    h1_chunk_extensions: bool,
     h1_title_case_headers: bool,
     h1_preserve_header_case: bool,
-    #[cfg(feature = "ffi")]
     h1_preserve_header_order: bool,
+    h1_preserve_raw_head: bool,
     h1_read_buf_exact_size: Option<usize>,
     h1_max_buf_size: Option<usize>,
+    #[cfg(feature = "http1")]
+    h1_max_headers: Option<usize>,
+    #[cfg(all(feature = "http1", feature = "runtime"))]
+    h1_expect_continue_timeout: Duration,
     #[cfg(feature = "ffi")]
     h1_headers_raw: bool,
+    #[cfg(feature = "http1")]
+    h1_observer: ObserverRef,
     #[cfg(feature = "http2")]
     h2_builder: proto::h2::client::Config,
+    #[cfg(all(feature = "http1", feature = "http2"))]
+    h2c_upgrade: bool,
     version: Proto,
 }
 
@@ -303,7 +328,12 @@ where
         match self.inner.expect("already upgraded") {
             #[cfg(feature = "http1")]
             ProtoClient::H1 { h1 } => {
-                let (io, read_buf, _) = h1.into_inner();
+                let (io, read_buf, _dispatch) = h1.into_inner();
+                #[cfg(feature = "http2")]
+                if let Some((cb, _)) = _dispatch.into_h2c_upgrade() {
+                    let err = crate::Error::new_canceled().with("connection upgraded to h2c");
+                    cb.send(Err((err, None)));
+                }
                 Parts {
                     io,
                     read_buf,
@@ -372,6 +402,33 @@ where
     }
 }
 
+#[cfg(all(feature = "http1", feature = "http2"))]
+impl<T, B> Connection<T, B>
+where
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    fn upgrade_h2c(
+        &mut self,
+        io: T,
+        read_buf: Bytes,
+        cb: dispatch::Callback<Request<B>, Response<Recv>>,
+        rx: dispatch::Receiver<Request<B>, Response<Recv>>,
+    ) {
+        trace!("switching to h2c");
+        let (config, exec) = match self.h2c.take() {
+            Some(H2c::Pending(config, exec)) => (config, exec),
+            _ => unreachable!("h2c upgrade expects options"),
+        };
+        let io = Rewind::new_buffered(io, read_buf);
+        let handshake =
+            async move { proto::h2::client::handshake_h2c(io, rx, cb, &config, exec).await };
+        self.h2c = Some(H2c::Handshaking(SyncWrapper::new(Box::pin(handshake))));
+    }
+}
+
 impl<T, B> Future for Connection<T, B>
 where
     T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
@@ -382,20 +439,36 @@ where
     type Output = crate::Result<()>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
-        match ready!(Pin::new(self.inner.as_mut().unwrap()).poll(cx))? {
-            proto::Dispatched::Shutdown => Poll::Ready(Ok(())),
-            #[cfg(feature = "http1")]
-            proto::Dispatched::Upgrade(pending) => match self.inner.take() {
-                Some(ProtoClient::H1 { h1 }) => {
-                    let (io, buf, _) = h1.into_inner();
-                    pending.fulfill(Upgraded::new(io, buf));
-                    Poll::Ready(Ok(()))
-                }
-                _ => {
-                    drop(pending);
-                    unreachable!("Upgrade expects h1");
-                }
-            },
+        loop {
+            #[cfg(all(feature = "http1", feature = "http2"))]
+            if let Some(H2c::Handshaking(ref mut handshake)) = self.h2c {
+                let h2 = ready!(handshake.get_mut().as_mut().poll(cx))?;
+                self.h2c = None;
+                self.inner = Some(ProtoClient::H2 { h2 });
+            }
+
+            match ready!(Pin::new(self.inner.as_mut().unwrap()).poll(cx))? {
+                proto::Dispatched::Shutdown => return Poll::Ready(Ok(())),
+                #[cfg(feature = "http1")]
+                proto::Dispatched::Upgrade(pending) => match self.inner.take() {
+                    Some(ProtoClient::H1 { h1 }) => {
+                        let (io, buf, dispatch) = h1.into_inner();
+                        #[cfg(feature = "http2")]
+                        if let Some((cb, rx)) = dispatch.into_h2c_upgrade() {
+                            self.upgrade_h2c(io, buf, cb, rx);
+                            continue;
+                        }
+                        #[cfg(not(feature = "http2"))]
+                        drop(dispatch);
+                        pending.fulfill(Upgraded::new(io, buf));
+                        return Poll::Ready(Ok(()));
+                    }
+                    _ => {
+                        drop(pending);
+                        unreachable!("Upgrade expects h1");
+                    }
+                },
+            }
         }
     }
 }
@@ -422,15 +495,25 @@ impl Builder {
             h1_writev: None,
             h1_read_buf_exact_size: None,
             h1_parser_config: Default::default(),
+            h1_ignore_invalid_headers: false,
+            h1_chunk_extensions: false,
             h1_title_case_headers: false,
             h1_preserve_header_case: false,
-            #[cfg(feature = "ffi")]
             h1_preserve_header_order: false,
+            h1_preserve_raw_head: false,
             h1_max_buf_size: None,
+            #[cfg(feature = "http1")]
+            h1_max_headers: None,
+            #[cfg(all(feature = "http1", feature = "runtime"))]
+            h1_expect_continue_timeout: Duration::from_secs(1),
             #[cfg(feature = "ffi")]
             h1_headers_raw: false,
+            #[cfg(feature = "http1")]
+            h1_observer: ObserverRef::default(),
             #[cfg(feature = "http2")]
             h2_builder: Default::default(),
+            #[cfg(all(feature = "http1", feature = "http2"))]
+            h2c_upgrade: false,
             #[cfg(feature = "http1")]
             version: Proto::Http1,
             #[cfg(not(feature = "http1"))]
@@ -526,6 +609,23 @@ impl Builder {
         self
     }
 
+    /// Set whether HTTP/1 connections will ignore invalid header lines in
+    /// responses, instead of failing them.
+    ///
+    /// Some legacy servers send a malformed header line, such as a header
+    /// name with a space or a header value with a control character. When
+    /// enabled, such lines are dropped, and the response carries an
+    /// [`IgnoredInvalidHeaders`](crate::ext::IgnoredInvalidHeaders) extension
+    /// with the number of lines that were dropped.
+    ///
+    /// Note that this setting does not affect HTTP/2.
+    ///
+    /// Default is false.
+    pub fn http1_ignore_invalid_headers_in_responses(&mut self, enabled: bool) -> &mut Builder {
+        self.h1_ignore_invalid_headers = enabled;
+        self
+    }
+
     /// Set whether HTTP/1 connections should try to use vectored writes,
     /// or always flatten into a single buffer.
     ///
@@ -543,6 +643,26 @@ impl Builder {
         self
     }
 
+    /// Set whether HTTP/1 connections will surface the chunk extensions of
+    /// incoming chunked bodies, and write those of outgoing ones.
+    ///
+    /// When enabled, the extensions of each chunk are available from
+    /// [`Recv::chunk_extensions`](crate::Recv::chunk_extensions) after
+    /// polling its data. The extensions of a single chunk are limited to
+    /// 16kb, and a chunk with larger extensions is treated as an error.
+    ///
+    /// Outgoing bodies are written with the extensions set through an
+    /// [`OutgoingChunkExtensions`](crate::ext::OutgoingChunkExtensions) in
+    /// the extensions of their message.
+    ///
+    /// Note that this setting does not affect HTTP/2.
+    ///
+    /// Default is false.
+    pub fn http1_chunk_extensions(&mut self, enabled: bool) -> &mut Builder {
+        self.h1_chunk_extensions = enabled;
+        self
+    }
+
     /// Set whether HTTP/1 connections will write header names as title case at
     /// the socket level.
     ///
@@ -556,13 +676,10 @@ impl Builder {
 
     /// Set whether to support preserving original header cases.
     ///
-    /// Currently, this will record the original cases received, and store them
-    /// in a private extension on the `Response`. It will also look for and use
-    /// such an extension in any provided `Request`.
-    ///
-    /// Since the relevant extension is still private, there is no way to
-    /// interact with the original cases. The only effect this can have now is
-    /// to forward the cases in a proxy-like fashion.
+    /// This will record the original cases received, and store them in a
+    /// [`HeaderCaseMap`](crate::ext::HeaderCaseMap) extension on the
+    /// `Response`. Such an extension in any provided `Request` is used
+    /// whether or not this is set.
     ///
     /// Note that this setting does not affect HTTP/2.
     ///
@@ -574,19 +691,33 @@ impl Builder {
 
     /// Set whether to support preserving original header order.
     ///
-    /// Currently, this will record the order in which headers are received, and store this
-    /// ordering in a private extension on the `Response`. It will also look for and use
-    /// such an extension in any provided `Request`.
+    /// This will record the order in which headers are received, and store
+    /// it in an [`OriginalHeaderOrder`](crate::ext::OriginalHeaderOrder)
+    /// extension on the `Response`. Such an extension in any provided
+    /// `Request` is used whether or not this is set.
     ///
     /// Note that this setting does not affect HTTP/2.
     ///
     /// Default is false.
-    #[cfg(feature = "ffi")]
     pub fn http1_preserve_header_order(&mut self, enabled: bool) -> &mut Builder {
         self.h1_preserve_header_order = enabled;
         self
     }
 
+    /// Set whether to keep the raw head of responses.
+    ///
+    /// This will store the status line and header lines, exactly as they
+    /// were received, in a [`RawHead`](crate::ext::RawHead) extension on the
+    /// `Response`.
+    ///
+    /// Note that this setting does not affect HTTP/2.
+    ///
+    /// Default is false.
+    pub fn http1_preserve_raw_head(&mut self, enabled: bool) -> &mut Builder {
+        self.h1_preserve_raw_head = enabled;
+        self
+    }
+
     /// Sets the exact size of the read buffer to *always* use.
     ///
     /// Note that setting this option unsets the `http1_max_buf_size` option.
@@ -620,6 +751,45 @@ impl Builder {
         self
     }
 
+    /// Set the maximum number of headers of a response.
+    ///
+    /// A response with more headers fails to parse. Up to 100 headers are
+    /// parsed on the stack, while a larger maximum allocates room for the
+    /// headers on the heap for each response.
+    ///
+    /// Default is 100.
+    #[cfg(feature = "http1")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http1")))]
+    pub fn http1_max_headers(&mut self, max: usize) -> &mut Self {
+        self.h1_max_headers = Some(max);
+        self
+    }
+
+    /// Sets how long a request with an `Expect: 100-continue` header waits
+    /// for the server to respond `100 Continue`, before sending its body
+    /// anyway.
+    ///
+    /// The body is held until `100 Continue`, a final response, or this
+    /// timeout. If a final response that isn't a success comes first, the
+    /// body isn't sent. A chunked body then ends without any data, keeping
+    /// the connection usable, while a body of known length can't, so the
+    /// connection is closed after the response.
+    ///
+    /// Note that this setting does not affect HTTP/2.
+    ///
+    /// Default is 1 second.
+    ///
+    /// # Cargo Feature
+    ///
+    /// Requires the `runtime` cargo feature to be enabled. Without it, the
+    /// body is sent right away.
+    #[cfg(all(feature = "http1", feature = "runtime"))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http1")))]
+    pub fn http1_expect_continue_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.h1_expect_continue_timeout = timeout;
+        self
+    }
+
     #[cfg(feature = "ffi")]
     pub(crate) fn http1_headers_raw(&mut self, enabled: bool) -> &mut Self {
         self.h1_headers_raw = enabled;
@@ -638,6 +808,33 @@ impl Builder {
         self
     }
 
+    /// Sets whether to ask to upgrade the connection to HTTP/2 with the
+    /// first request, using [h2c][spec].
+    ///
+    /// The first request is sent with HTTP/1.1, along with the `Upgrade: h2c`
+    /// and `HTTP2-Settings` headers. If the server switches protocols, its
+    /// response is received over HTTP/2, and so are the responses of all the
+    /// following requests. If the server declines, the connection keeps
+    /// using HTTP/1.1.
+    ///
+    /// The upgrade is only asked for if the first request is HTTP/1.1, is
+    /// not a `CONNECT`, has no `Upgrade` header, and has an empty body.
+    /// Once upgraded, requests without a scheme in their URI are sent to the
+    /// `http` scheme and the authority from their `Host` header.
+    ///
+    /// This setting has no effect if [`http2_only`](Builder::http2_only)
+    /// is enabled.
+    ///
+    /// Default is false.
+    ///
+    /// [spec]: https://httpwg.org/specs/rfc7540.html#discover-http
+    #[cfg(all(feature = "http1", feature = "http2"))]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "http1", feature = "http2"))))]
+    pub fn h2c_upgrade(&mut self, enabled: bool) -> &mut Builder {
+        self.h2c_upgrade = enabled;
+        self
+    }
+
     /// Sets the [`SETTINGS_INITIAL_WINDOW_SIZE`][spec] option for HTTP2
     /// stream-level flow control.
     ///
@@ -795,6 +992,27 @@ impl Builder {
         self
     }
 
+    /// Sets an observer of the events of the connection.
+    ///
+    /// See [`Observer`](crate::observe::Observer) for the events reported.
+    ///
+    /// Default is no observer.
+    pub fn observer<O>(&mut self, observer: O) -> &mut Self
+    where
+        O: Observer + 'static,
+    {
+        let observer = ObserverRef::new(observer);
+        #[cfg(feature = "http2")]
+        {
+            self.h2_builder.observer = observer.clone();
+        }
+        #[cfg(feature = "http1")]
+        {
+            self.h1_observer = observer;
+        }
+        self
+    }
+
     /// Constructs a connection with the configured options and IO.
     /// See [`client::conn`](crate::client::conn) for more.
     ///
@@ -821,6 +1039,9 @@ impl Builder {
                 Proto::Http1 => {
                     let mut conn = proto::Conn::new(io);
                     conn.set_h1_parser_config(opts.h1_parser_config);
+                    if opts.h1_ignore_invalid_headers {
+                        conn.set_ignore_invalid_headers();
+                    }
                     if let Some(writev) = opts.h1_writev {
                         if writev {
                             conn.set_write_strategy_queue();
@@ -828,16 +1049,21 @@ impl Builder {
                             conn.set_write_strategy_flatten();
                         }
                     }
+                    if opts.h1_chunk_extensions {
+                        conn.set_chunk_extensions();
+                    }
                     if opts.h1_title_case_headers {
                         conn.set_title_case_headers();
                     }
                     if opts.h1_preserve_header_case {
                         conn.set_preserve_header_case();
                     }
-                    #[cfg(feature = "ffi")]
                     if opts.h1_preserve_header_order {
                         conn.set_preserve_header_order();
                     }
+                    if opts.h1_preserve_raw_head {
+                        conn.set_preserve_raw_head();
+                    }
                     if opts.h09_responses {
                         conn.set_h09_responses();
                     }
@@ -851,7 +1077,18 @@ impl Builder {
                     if let Some(max) = opts.h1_max_buf_size {
                         conn.set_max_buf_size(max);
                     }
-                    let cd = proto::h1::dispatch::Client::new(rx);
+                    if let Some(max) = opts.h1_max_headers {
+                        conn.set_max_headers(max);
+                    }
+                    #[cfg(feature = "runtime")]
+                    conn.set_expect_continue_timeout(opts.h1_expect_continue_timeout);
+                    conn.set_observer(opts.h1_observer);
+                    #[cfg_attr(not(feature = "http2"), allow(unused_mut))]
+                    let mut cd = proto::h1::dispatch::Client::new(rx);
+                    #[cfg(feature = "http2")]
+                    if opts.h2c_upgrade {
+                        cd.set_h2c_upgrade(proto::h2::h2c::settings_header(&opts.h2_builder));
+                    }
                     let dispatch = proto::h1::Dispatcher::new(cd, conn);
                     ProtoClient::H1 { h1: dispatch }
                 }
@@ -864,9 +1101,21 @@ impl Builder {
                 }
             };
 
+            #[cfg(all(feature = "http1", feature = "http2"))]
+            let h2c = match proto {
+                ProtoClient::H1 { .. } if opts.h2c_upgrade => {
+                    Some(H2c::Pending(opts.h2_builder, opts.exec))
+                }
+                _ => None,
+            };
+
             Ok((
                 SendRequest { dispatch: tx },
-                Connection { inner: Some(proto) },
+                Connection {
+                    inner: Some(proto),
+                    #[cfg(all(feature = "http1", feature = "http2"))]
+                    h2c,
+                },
             ))
         }
     }
@@ -1,11 +1,19 @@
-#[cfg(feature = "http2")]
+#[cfg(any(feature = "http2", feature = "runtime"))]
 use std::future::Future;
+#[cfg(feature = "runtime")]
+use std::time::Duration;
 
 use tokio::sync::{mpsc, oneshot};
+#[cfg(feature = "runtime")]
+use tokio::time::{Instant, Sleep};
 
-#[cfg(feature = "http2")]
+#[cfg(any(feature = "http2", feature = "runtime"))]
 use crate::common::Pin;
 use crate::common::{task, Poll};
+#[cfg(feature = "runtime")]
+use crate::error::Timeout;
+#[cfg(feature = "runtime")]
+use crate::ext::RequestTimeouts;
 
 #[cfg(test)]
 pub(crate) type RetryPromise<T, U> = oneshot::Receiver<Result<U, (crate::Error, Option<T>)>>;
@@ -59,7 +67,7 @@ impl<T, U> Sender<T, U> {
             .map_err(|_| crate::Error::new_closed())
     }
 
-    #[cfg(test)]
+    #[cfg(any(test, feature = "http1"))]
     pub(crate) fn is_ready(&self) -> bool {
         self.giver.is_wanting()
     }
@@ -117,11 +125,9 @@ impl<T, U> Sender<T, U> {
 
 #[cfg(feature = "http2")]
 impl<T, U> UnboundedSender<T, U> {
-    /*
     pub(crate) fn is_ready(&self) -> bool {
         !self.giver.is_canceled()
     }
-    */
 
     pub(crate) fn is_closed(&self) -> bool {
         self.giver.is_canceled()
@@ -279,6 +285,93 @@ impl<T, U> Callback<T, U> {
     }
 }
 
+/// Enforces the timeouts of a response, from when its request is sent until
+/// its body ends.
+#[cfg(feature = "runtime")]
+pub(crate) struct ResponseTimer {
+    total: Option<Pin<Box<Sleep>>>,
+    /// The response head timeout, and then the body idle timeout.
+    phase: Option<Pin<Box<Sleep>>>,
+    body_idle: Option<Duration>,
+    in_body: bool,
+    /// Whether the body idle timeout is paused, as the body isn't read.
+    paused: bool,
+}
+
+#[cfg(feature = "runtime")]
+impl ResponseTimer {
+    /// Starts the timeouts of a request, returning `None` if there are none.
+    pub(crate) fn new(timeouts: &RequestTimeouts) -> Option<ResponseTimer> {
+        let total = timeouts.total_timeout();
+        let head = timeouts.response_head_timeout();
+        let body_idle = timeouts.response_body_idle_timeout();
+        if total.is_none() && head.is_none() && body_idle.is_none() {
+            return None;
+        }
+
+        Some(ResponseTimer {
+            total: total.map(|timeout| Box::pin(tokio::time::sleep(timeout))),
+            phase: head.map(|timeout| Box::pin(tokio::time::sleep(timeout))),
+            body_idle,
+            in_body: false,
+            paused: false,
+        })
+    }
+
+    /// Moves on to the body of the response, once its head is received,
+    /// returning `None` if no timeout applies to the body.
+    pub(crate) fn into_body_timer(mut self) -> Option<ResponseTimer> {
+        if self.total.is_none() && self.body_idle.is_none() {
+            return None;
+        }
+
+        self.in_body = true;
+        self.phase = self
+            .body_idle
+            .map(|timeout| Box::pin(tokio::time::sleep(timeout)));
+        Some(self)
+    }
+
+    /// Restarts the body idle timeout, as data of the body was received.
+    pub(crate) fn on_body_data(&mut self) {
+        if let (Some(sleep), Some(timeout)) = (self.phase.as_mut(), self.body_idle) {
+            sleep.as_mut().reset(Instant::now() + timeout);
+        }
+    }
+
+    /// Pauses the body idle timeout while the body isn't read, since no
+    /// data is received meanwhile, and restarts it once it is.
+    pub(crate) fn set_body_wanted(&mut self, wanted: bool) {
+        if wanted && self.paused {
+            self.on_body_data();
+        }
+        self.paused = !wanted;
+    }
+
+    /// Polls for a timeout to expire, returning the error to fail the
+    /// response with.
+    pub(crate) fn poll_expired(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Error> {
+        if let Some(ref mut total) = self.total {
+            if total.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(crate::Error::new_timeout(Timeout::Total));
+            }
+        }
+        if !self.paused {
+            if let Some(ref mut phase) = self.phase {
+                if phase.as_mut().poll(cx).is_ready() {
+                    let timeout = if self.in_body {
+                        Timeout::ResponseBodyIdle
+                    } else {
+                        Timeout::ResponseHead
+                    };
+                    return Poll::Ready(crate::Error::new_timeout(timeout));
+                }
+            }
+        }
+        Poll::Pending
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[cfg(feature = "nightly")]
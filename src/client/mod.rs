@@ -1,18 +1,38 @@
 //! HTTP Client
 //!
-//! hyper provides HTTP over a single connection. See the [`conn`](conn) module.
+//! There are two levels of APIs provided for constructing HTTP clients:
+//!
+//! - The higher-level [`Client`](Client) type.
+//! - The lower-level [`conn`](conn) module.
+//!
+//! # Client
+//!
+//! The [`Client`](Client) is the main way to send HTTP requests to a server.
+//! It keeps a pool of idle connections, keyed by the scheme and authority
+//! of each request's `Uri`, and reuses them for later requests to the same
+//! destination. HTTP/2 connections are shared by all requests to a host.
+//!
+//! New connections are made with a [connector](connect), so the `Client`
+//! works over any transport.
 //!
 //! ## Example
 //!
 //! For a small example program simply fetching a URL, take a look at the
 //! [full client example](https://github.com/hyperium/hyper/blob/master/examples/client.rs).
 
-#[cfg(all(test, feature = "runtime"))]
-mod tests;
-
 cfg_feature! {
     #![any(feature = "http1", feature = "http2")]
 
+    pub use self::client::{Builder, Client, ResponseFuture};
+    #[cfg(feature = "tcp")]
+    pub use self::connect::HttpConnector;
+
+    #[allow(clippy::module_inception)]
+    mod client;
     pub mod conn;
+    pub mod connect;
     pub(super) mod dispatch;
+    mod pool;
+    #[cfg(all(test, feature = "runtime"))]
+    mod tests;
 }
@@ -0,0 +1,974 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use tokio::sync::oneshot;
+use tracing::{debug, trace};
+
+use crate::common::exec::Exec;
+#[cfg(feature = "runtime")]
+use crate::common::Never;
+use crate::common::{task, Future, Pin, Poll, Unpin};
+
+// FIXME: allow() required due to `impl Trait` leaking types to this lint
+#[allow(missing_debug_implementations)]
+pub(super) struct Pool<T> {
+    // If the pool is disabled, this is None.
+    inner: Option<Arc<Mutex<PoolInner<T>>>>,
+}
+
+// Before using a pooled connection, make sure the sender is not dead.
+//
+// This is a trait to allow the `client::pool::tests` to work for `i32`.
+//
+// See https://github.com/hyperium/hyper/issues/1429
+pub(super) trait Poolable: Unpin + Send + Sized + 'static {
+    fn is_open(&self) -> bool;
+    /// Reserve this connection.
+    ///
+    /// Allows for HTTP/2 to return a shared reservation.
+    fn reserve(self) -> Reservation<Self>;
+    fn can_share(&self) -> bool;
+}
+
+/// When checking out a pooled connection, it might be that the connection
+/// only supports a single reservation, or it might be usable for many.
+///
+/// Specifically, HTTP/1 requires a unique reservation, but HTTP/2 can be
+/// used for multiple requests.
+// FIXME: allow() required due to `impl Trait` leaking types to this lint
+#[allow(missing_debug_implementations)]
+pub(super) enum Reservation<T> {
+    /// This connection could be used multiple times, the first one will be
+    /// reinserted into the `idle` pool, and the second will be given to
+    /// the `Checkout`.
+    #[cfg(feature = "http2")]
+    Shared(T, T),
+    /// This connection requires unique access. It will be returned after
+    /// use is complete.
+    #[cfg_attr(not(feature = "http1"), allow(dead_code))]
+    Unique(T),
+}
+
+/// Simple type alias in case the key type needs to be adjusted.
+pub(super) type Key = (http::uri::Scheme, http::uri::Authority);
+
+struct PoolInner<T> {
+    // A flag that a connection is being established, and the connection
+    // should be shared. This prevents making multiple HTTP/2 connections
+    // to the same host.
+    connecting: HashSet<Key>,
+    // These are internal Conns sitting in the event loop in the KeepAlive
+    // state, waiting to receive a new Request to send on the socket.
+    idle: HashMap<Key, Vec<Idle<T>>>,
+    max_idle_per_host: usize,
+    // These are outstanding Checkouts that are waiting for a shared
+    // connection that is still being established.
+    waiters: HashMap<Key, VecDeque<oneshot::Sender<T>>>,
+    // A oneshot channel is used to allow the interval to be notified when
+    // the Pool completely drops. That way, the interval can cancel immediately.
+    #[cfg(feature = "runtime")]
+    idle_interval_ref: Option<oneshot::Sender<Never>>,
+    #[cfg(feature = "runtime")]
+    exec: Exec,
+    timeout: Option<Duration>,
+}
+
+// This is because `Weak::new()` *allocates* space for `T`, even if it
+// doesn't need it!
+struct WeakOpt<T>(Option<Weak<T>>);
+
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Config {
+    pub(super) idle_timeout: Option<Duration>,
+    pub(super) max_idle_per_host: usize,
+}
+
+impl Config {
+    pub(super) fn is_enabled(&self) -> bool {
+        self.max_idle_per_host > 0
+    }
+}
+
+/// The result of asking the `Pool` for a connection.
+// FIXME: allow() required due to `impl Trait` leaking types to this lint
+#[allow(missing_debug_implementations)]
+pub(super) enum Checkout<T: Poolable> {
+    /// An idle connection was available.
+    Idle(Pooled<T>),
+    /// A new connection should be established by the caller.
+    Connect(Connecting<T>),
+    /// Another caller is already establishing a shareable connection.
+    Wait(Waiter<T>),
+}
+
+impl<T> Pool<T> {
+    pub(super) fn new(config: Config, exec: &Exec) -> Pool<T> {
+        let inner = if config.is_enabled() {
+            Some(Arc::new(Mutex::new(PoolInner {
+                connecting: HashSet::new(),
+                idle: HashMap::new(),
+                #[cfg(feature = "runtime")]
+                idle_interval_ref: None,
+                max_idle_per_host: config.max_idle_per_host,
+                waiters: HashMap::new(),
+                #[cfg(feature = "runtime")]
+                exec: exec.clone(),
+                timeout: config.idle_timeout,
+            })))
+        } else {
+            None
+        };
+
+        #[cfg(not(feature = "runtime"))]
+        let _ = exec;
+
+        Pool { inner }
+    }
+
+    #[cfg(test)]
+    fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    #[cfg(test)]
+    pub(super) fn no_timer(&self) {
+        // Prevent an actual interval from being created for this pool...
+        #[cfg(feature = "runtime")]
+        {
+            let mut inner = self.inner.as_ref().unwrap().lock().unwrap();
+            assert!(inner.idle_interval_ref.is_none(), "timer already spawned");
+            let (tx, _) = oneshot::channel();
+            inner.idle_interval_ref = Some(tx);
+        }
+    }
+}
+
+impl<T: Poolable> Pool<T> {
+    /// Returns a `Checkout` for the given key.
+    ///
+    /// An idle connection is preferred. Otherwise, if `shareable` is true
+    /// and another connection to the key is already being established, the
+    /// caller should wait for that one instead of connecting again.
+    pub(super) fn checkout(&self, key: Key, shareable: bool) -> Checkout<T> {
+        let enabled = match self.inner {
+            Some(ref enabled) => enabled,
+            None => {
+                return Checkout::Connect(Connecting {
+                    key,
+                    pool: WeakOpt::none(),
+                })
+            }
+        };
+
+        let mut inner = enabled.lock().unwrap();
+
+        if let Some(value) = inner.take_idle(&key) {
+            trace!("checkout; found idle connection for {:?}", key);
+            let pool = if value.can_share() {
+                WeakOpt::none()
+            } else {
+                WeakOpt::downgrade(enabled)
+            };
+            return Checkout::Idle(Pooled {
+                key,
+                pool,
+                value: Some(value),
+            });
+        }
+
+        if shareable {
+            if inner.connecting.contains(&key) {
+                trace!(
+                    "HTTP/2 connecting already in progress for {:?}, waiting on it",
+                    key
+                );
+                let (tx, rx) = oneshot::channel();
+                inner
+                    .waiters
+                    .entry(key.clone())
+                    .or_insert_with(VecDeque::new)
+                    .push_back(tx);
+                return Checkout::Wait(Waiter {
+                    key,
+                    rx,
+                    pool: WeakOpt::downgrade(enabled),
+                });
+            }
+            inner.connecting.insert(key.clone());
+        }
+
+        Checkout::Connect(Connecting {
+            key,
+            pool: WeakOpt::downgrade(enabled),
+        })
+    }
+
+    #[cfg_attr(not(feature = "http2"), allow(unused_mut))]
+    pub(super) fn pooled(&self, mut connecting: Connecting<T>, value: T) -> Pooled<T> {
+        let (value, pool_ref) = if let Some(ref enabled) = self.inner {
+            match value.reserve() {
+                #[cfg(feature = "http2")]
+                Reservation::Shared(to_insert, to_return) => {
+                    let mut inner = enabled.lock().unwrap();
+                    inner.put(connecting.key.clone(), to_insert, enabled);
+                    // Do this here instead of Drop for Connecting because we
+                    // already have a lock, no need to lock the mutex twice.
+                    inner.connected(&connecting.key);
+                    // prevent the Drop of Connecting from repeating inner.connected()
+                    connecting.pool = WeakOpt::none();
+
+                    // Shared reservations don't need a reference to the pool,
+                    // since the pool always keeps a copy.
+                    (to_return, WeakOpt::none())
+                }
+                Reservation::Unique(value) => {
+                    // Unique reservations must take a reference to the pool
+                    // since they hope to reinsert once the reservation is
+                    // completed
+                    (value, WeakOpt::downgrade(enabled))
+                }
+            }
+        } else {
+            // If pool is not enabled, skip all the things...
+
+            // The Connecting should have had no pool ref
+            debug_assert!(connecting.pool.upgrade().is_none());
+
+            (value, WeakOpt::none())
+        };
+        Pooled {
+            key: connecting.key.clone(),
+            pool: pool_ref,
+            value: Some(value),
+        }
+    }
+
+    #[cfg(test)]
+    fn locked(&self) -> std::sync::MutexGuard<'_, PoolInner<T>> {
+        self.inner.as_ref().expect("enabled").lock().expect("lock")
+    }
+
+    #[cfg(test)]
+    pub(super) fn idle_count(&self, key: &Key) -> usize {
+        self.locked()
+            .idle
+            .get(key)
+            .map(|list| list.len())
+            .unwrap_or(0)
+    }
+}
+
+/// Pop off this list, looking for a usable connection that hasn't expired.
+struct IdlePopper<'a, T> {
+    key: &'a Key,
+    list: &'a mut Vec<Idle<T>>,
+}
+
+impl<'a, T: Poolable + 'a> IdlePopper<'a, T> {
+    fn pop(self, expiration: &Expiration) -> Option<Idle<T>> {
+        while let Some(entry) = self.list.pop() {
+            // If the connection has been closed, or is older than our idle
+            // timeout, simply drop it and keep looking...
+            if !entry.value.is_open() {
+                trace!("removing closed connection for {:?}", self.key);
+                continue;
+            }
+            // TODO: Actually, since the `idle` list is pushed to the end always,
+            // that would imply that if *this* entry is expired, then anything
+            // "earlier" in the list would *have* to be expired also... Right?
+            //
+            // In that case, we could just break out of the loop and drop the
+            // whole list...
+            if expiration.expires(entry.idle_at) {
+                trace!("removing expired connection for {:?}", self.key);
+                continue;
+            }
+
+            let value = match entry.value.reserve() {
+                #[cfg(feature = "http2")]
+                Reservation::Shared(to_reinsert, to_checkout) => {
+                    self.list.push(Idle {
+                        idle_at: Instant::now(),
+                        value: to_reinsert,
+                    });
+                    to_checkout
+                }
+                Reservation::Unique(unique) => unique,
+            };
+
+            return Some(Idle {
+                idle_at: entry.idle_at,
+                value,
+            });
+        }
+
+        None
+    }
+}
+
+impl<T: Poolable> PoolInner<T> {
+    fn take_idle(&mut self, key: &Key) -> Option<T> {
+        let expiration = Expiration::new(self.timeout);
+        let mut should_remove = false;
+        let entry = self.idle.get_mut(key).and_then(|list| {
+            let popper = IdlePopper { key, list };
+            let entry = popper.pop(&expiration);
+            should_remove = list.is_empty();
+            entry
+        });
+
+        if should_remove {
+            self.idle.remove(key);
+        }
+
+        entry.map(|e| e.value)
+    }
+
+    fn put(&mut self, key: Key, value: T, __pool_ref: &Arc<Mutex<PoolInner<T>>>) {
+        if value.can_share() && self.idle.contains_key(&key) {
+            trace!("put; existing idle HTTP/2 connection for {:?}", key);
+            return;
+        }
+        trace!("put; add idle connection for {:?}", key);
+        let mut remove_waiters = false;
+        let mut value = Some(value);
+        if let Some(waiters) = self.waiters.get_mut(&key) {
+            while let Some(tx) = waiters.pop_front() {
+                if !tx.is_closed() {
+                    let reserved = value.take().expect("value already sent");
+                    let reserved = match reserved.reserve() {
+                        #[cfg(feature = "http2")]
+                        Reservation::Shared(to_keep, to_send) => {
+                            value = Some(to_keep);
+                            to_send
+                        }
+                        Reservation::Unique(uniq) => uniq,
+                    };
+                    match tx.send(reserved) {
+                        Ok(()) => {
+                            if value.is_none() {
+                                break;
+                            } else {
+                                continue;
+                            }
+                        }
+                        Err(e) => {
+                            value = Some(e);
+                        }
+                    }
+                }
+
+                trace!("put; removing canceled waiter for {:?}", key);
+            }
+            remove_waiters = waiters.is_empty();
+        }
+        if remove_waiters {
+            self.waiters.remove(&key);
+        }
+
+        match value {
+            Some(value) => {
+                // borrow-check scope...
+                {
+                    let idle_list = self.idle.entry(key.clone()).or_default();
+                    if self.max_idle_per_host <= idle_list.len() {
+                        trace!("max idle per host for {:?}, dropping connection", key);
+                        return;
+                    }
+
+                    debug!("pooling idle connection for {:?}", key);
+                    idle_list.push(Idle {
+                        value,
+                        idle_at: Instant::now(),
+                    });
+                }
+
+                #[cfg(feature = "runtime")]
+                {
+                    self.spawn_idle_interval(__pool_ref);
+                }
+            }
+            None => trace!("put; found waiter for {:?}", key),
+        }
+    }
+
+    /// A `Connecting` task is complete. Not necessarily successfully,
+    /// but the lock is going away, so clean up.
+    fn connected(&mut self, key: &Key) {
+        self.connecting.remove(key);
+        // cancel any waiters. if there are any, it's because
+        // this Connecting task didn't complete successfully.
+        // those waiters would never receive a connection.
+        self.waiters.remove(key);
+    }
+
+    #[cfg(feature = "runtime")]
+    fn spawn_idle_interval(&mut self, pool_ref: &Arc<Mutex<PoolInner<T>>>) {
+        let (dur, rx) = {
+            if self.idle_interval_ref.is_some() {
+                return;
+            }
+
+            if let Some(dur) = self.timeout {
+                let (tx, rx) = oneshot::channel();
+                self.idle_interval_ref = Some(tx);
+                (dur, rx)
+            } else {
+                return;
+            }
+        };
+
+        let interval = IdleTask {
+            interval: tokio::time::interval(dur),
+            pool: WeakOpt::downgrade(pool_ref),
+            pool_drop_notifier: rx,
+        };
+
+        self.exec.execute(interval);
+    }
+
+    /// This should *only* be called by the IdleTask
+    #[cfg(feature = "runtime")]
+    fn clear_expired(&mut self) {
+        let dur = self.timeout.expect("interval assumes timeout");
+
+        let now = Instant::now();
+        //self.last_idle_check_at = now;
+
+        self.idle.retain(|key, values| {
+            values.retain(|entry| {
+                if !entry.value.is_open() {
+                    trace!("idle interval evicting closed for {:?}", key);
+                    return false;
+                }
+
+                // Avoid `Instant::sub` to avoid issues like rust-lang/rust#86470.
+                if now.saturating_duration_since(entry.idle_at) > dur {
+                    trace!("idle interval evicting expired for {:?}", key);
+                    return false;
+                }
+
+                // Otherwise, keep this value...
+                true
+            });
+
+            // returning false evicts this key/val
+            !values.is_empty()
+        });
+    }
+}
+
+impl<T> PoolInner<T> {
+    /// Any `Waiter`s that are canceled or dropped are removed lazily, the
+    /// next time a connection for that key is put back.
+    fn clean_waiters(&mut self, key: &Key) {
+        let mut remove_waiters = false;
+        if let Some(waiters) = self.waiters.get_mut(key) {
+            waiters.retain(|tx| !tx.is_closed());
+            remove_waiters = waiters.is_empty();
+        }
+        if remove_waiters {
+            self.waiters.remove(key);
+        }
+    }
+}
+
+impl<T> Clone for Pool<T> {
+    fn clone(&self) -> Pool<T> {
+        Pool {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// A wrapped poolable value that tries to reinsert to the Pool on Drop.
+// Note: The bounds `T: Poolable` is needed for the Drop impl.
+pub(super) struct Pooled<T: Poolable> {
+    value: Option<T>,
+    key: Key,
+    pool: WeakOpt<Mutex<PoolInner<T>>>,
+}
+
+impl<T: Poolable> Pooled<T> {
+    pub(super) fn is_pool_enabled(&self) -> bool {
+        self.pool.0.is_some()
+    }
+
+    fn as_ref(&self) -> &T {
+        self.value.as_ref().expect("not dropped")
+    }
+
+    fn as_mut(&mut self) -> &mut T {
+        self.value.as_mut().expect("not dropped")
+    }
+}
+
+impl<T: Poolable> Deref for Pooled<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.as_ref()
+    }
+}
+
+impl<T: Poolable> DerefMut for Pooled<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.as_mut()
+    }
+}
+
+impl<T: Poolable> Drop for Pooled<T> {
+    fn drop(&mut self) {
+        if let Some(value) = self.value.take() {
+            if !value.is_open() {
+                // If we *already* know the connection is done here,
+                // it shouldn't be re-inserted back into the pool.
+                return;
+            }
+
+            if let Some(pool) = self.pool.upgrade() {
+                if let Ok(mut inner) = pool.lock() {
+                    inner.put(self.key.clone(), value, &pool);
+                }
+            } else if !value.can_share() {
+                trace!("pool dropped, dropping pooled ({:?})", self.key);
+            }
+            // Ver::Http2 is already in the Pool (or dead), so we wouldn't
+            // have an actual reference to the Pool.
+        }
+    }
+}
+
+impl<T: Poolable> fmt::Debug for Pooled<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pooled").field("key", &self.key).finish()
+    }
+}
+
+struct Idle<T> {
+    idle_at: Instant,
+    value: T,
+}
+
+/// A future resolving to a shared connection established by another
+/// `Checkout`.
+///
+/// Yields `None` if that connection attempt failed, in which case the
+/// caller should check out again.
+// FIXME: allow() required due to `impl Trait` leaking types to this lint
+#[allow(missing_debug_implementations)]
+pub(super) struct Waiter<T> {
+    key: Key,
+    rx: oneshot::Receiver<T>,
+    pool: WeakOpt<Mutex<PoolInner<T>>>,
+}
+
+impl<T: Poolable> Future for Waiter<T> {
+    type Output = Option<Pooled<T>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let value = match ready!(Pin::new(&mut self.rx).poll(cx)) {
+            Ok(value) => value,
+            Err(_canceled) => {
+                trace!("checkout waiter canceled for {:?}", self.key);
+                return Poll::Ready(None);
+            }
+        };
+
+        if !value.is_open() {
+            return Poll::Ready(None);
+        }
+
+        let pool = if value.can_share() {
+            WeakOpt::none()
+        } else {
+            self.pool.clone()
+        };
+        Poll::Ready(Some(Pooled {
+            key: self.key.clone(),
+            pool,
+            value: Some(value),
+        }))
+    }
+}
+
+impl<T> Drop for Waiter<T> {
+    fn drop(&mut self) {
+        self.rx.close();
+        if let Some(pool) = self.pool.upgrade() {
+            if let Ok(mut inner) = pool.lock() {
+                inner.clean_waiters(&self.key);
+            }
+        }
+    }
+}
+
+/// A guard marking that a connection to a key is being established.
+///
+/// Dropping it without calling `Pool::pooled` means the attempt failed.
+// FIXME: allow() required due to `impl Trait` leaking types to this lint
+#[allow(missing_debug_implementations)]
+pub(super) struct Connecting<T: Poolable> {
+    key: Key,
+    pool: WeakOpt<Mutex<PoolInner<T>>>,
+}
+
+impl<T: Poolable> Drop for Connecting<T> {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.upgrade() {
+            // No need to panic on drop, that could abort!
+            if let Ok(mut inner) = pool.lock() {
+                inner.connected(&self.key);
+            }
+        }
+    }
+}
+
+struct Expiration(Option<Duration>);
+
+impl Expiration {
+    fn new(dur: Option<Duration>) -> Expiration {
+        Expiration(dur)
+    }
+
+    fn expires(&self, instant: Instant) -> bool {
+        match self.0 {
+            // Avoid `Instant::elapsed` to avoid issues like rust-lang/rust#86470.
+            Some(timeout) => Instant::now().saturating_duration_since(instant) > timeout,
+            None => false,
+        }
+    }
+}
+
+#[cfg(feature = "runtime")]
+pin_project_lite::pin_project! {
+    struct IdleTask<T> {
+        #[pin]
+        interval: tokio::time::Interval,
+        pool: WeakOpt<Mutex<PoolInner<T>>>,
+        // This allows the IdleTask to be notified as soon as the entire
+        // Pool is fully dropped, and shutdown. This channel is never sent on,
+        // but Err(Canceled) will be received when the Pool is dropped.
+        #[pin]
+        pool_drop_notifier: oneshot::Receiver<Never>,
+    }
+}
+
+#[cfg(feature = "runtime")]
+impl<T: Poolable + 'static> Future for IdleTask<T> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        loop {
+            match this.pool_drop_notifier.as_mut().poll(cx) {
+                Poll::Ready(Ok(n)) => match n {},
+                Poll::Pending => (),
+                Poll::Ready(Err(_canceled)) => {
+                    trace!("pool closed, canceling idle interval");
+                    return Poll::Ready(());
+                }
+            }
+
+            ready!(this.interval.as_mut().poll_tick(cx));
+
+            if let Some(inner) = this.pool.upgrade() {
+                if let Ok(mut inner) = inner.lock() {
+                    trace!("idle interval checking for expired");
+                    inner.clear_expired();
+                    continue;
+                }
+            }
+            return Poll::Ready(());
+        }
+    }
+}
+
+impl<T> WeakOpt<T> {
+    fn none() -> Self {
+        WeakOpt(None)
+    }
+
+    fn downgrade(arc: &Arc<T>) -> Self {
+        WeakOpt(Some(Arc::downgrade(arc)))
+    }
+
+    fn upgrade(&self) -> Option<Arc<T>> {
+        self.0.as_ref().and_then(Weak::upgrade)
+    }
+}
+
+impl<T> Clone for WeakOpt<T> {
+    fn clone(&self) -> Self {
+        WeakOpt(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Checkout, Config, Key, Pool, Poolable, Pooled, Reservation};
+    use crate::common::{exec::Exec, Unpin};
+
+    /// Test unique reservations.
+    #[derive(Debug, PartialEq, Eq)]
+    struct Uniq<T>(T);
+
+    impl<T: Send + 'static + Unpin> Poolable for Uniq<T> {
+        fn is_open(&self) -> bool {
+            true
+        }
+
+        fn reserve(self) -> Reservation<Self> {
+            Reservation::Unique(self)
+        }
+
+        fn can_share(&self) -> bool {
+            false
+        }
+    }
+
+    fn host_key(s: &str) -> Key {
+        (http::uri::Scheme::HTTP, s.parse().expect("host key"))
+    }
+
+    fn pool_no_timer<T>() -> Pool<T> {
+        pool_max_idle_no_timer(usize::MAX)
+    }
+
+    fn pool_max_idle_no_timer<T>(max_idle: usize) -> Pool<T> {
+        let pool = Pool::new(
+            Config {
+                idle_timeout: Some(Duration::from_millis(100)),
+                max_idle_per_host: max_idle,
+            },
+            &Exec::Default,
+        );
+        pool.no_timer();
+        pool
+    }
+
+    fn connect<T: Poolable>(pool: &Pool<T>, key: Key, value: T) -> Pooled<T> {
+        match pool.checkout(key, value.can_share()) {
+            Checkout::Connect(connecting) => pool.pooled(connecting, value),
+            _ => panic!("expected to connect"),
+        }
+    }
+
+    fn checkout<T: Poolable>(pool: &Pool<T>, key: Key) -> Option<Pooled<T>> {
+        match pool.checkout(key, false) {
+            Checkout::Idle(pooled) => Some(pooled),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_pool_checkout_smoke() {
+        let pool = pool_no_timer();
+        let key = host_key("foo");
+        let pooled = connect(&pool, key.clone(), Uniq(41));
+
+        drop(pooled);
+
+        let pooled = checkout(&pool, key).expect("idle");
+        assert_eq!(*pooled, Uniq(41));
+    }
+
+    #[test]
+    fn test_pool_checkout_returns_none_if_expired() {
+        let pool = pool_no_timer();
+        let key = host_key("foo");
+        let pooled = connect(&pool, key.clone(), Uniq(41));
+
+        drop(pooled);
+        std::thread::sleep(Duration::from_millis(150));
+        assert!(checkout(&pool, key.clone()).is_none());
+        assert_eq!(pool.idle_count(&key), 0);
+    }
+
+    #[test]
+    fn test_pool_max_idle_per_host() {
+        let pool = pool_max_idle_no_timer(2);
+        let key = host_key("foo");
+
+        let a = connect(&pool, key.clone(), Uniq(41));
+        let b = connect(&pool, key.clone(), Uniq(5));
+        let c = connect(&pool, key.clone(), Uniq(99));
+        drop((a, b, c));
+
+        // pooled and dropped 3, max_idle should only allow 2
+        assert_eq!(pool.idle_count(&key), 2);
+    }
+
+    #[test]
+    fn test_pool_disabled_never_pools() {
+        let pool = Pool::new(
+            Config {
+                idle_timeout: None,
+                max_idle_per_host: 0,
+            },
+            &Exec::Default,
+        );
+        assert!(!pool.is_enabled());
+        let key = host_key("foo");
+
+        let pooled = connect(&pool, key.clone(), Uniq(41));
+        drop(pooled);
+
+        assert!(checkout(&pool, key).is_none());
+    }
+
+    #[test]
+    fn test_pool_closed_connections_are_not_reused() {
+        #[derive(Debug)]
+        struct CanClose {
+            open: bool,
+        }
+
+        impl Poolable for CanClose {
+            fn is_open(&self) -> bool {
+                self.open
+            }
+
+            fn reserve(self) -> Reservation<Self> {
+                Reservation::Unique(self)
+            }
+
+            fn can_share(&self) -> bool {
+                false
+            }
+        }
+
+        let pool = pool_no_timer();
+        let key = host_key("foo");
+
+        let mut pooled = connect(&pool, key.clone(), CanClose { open: true });
+        pooled.open = false;
+        drop(pooled);
+
+        assert_eq!(pool.idle_count(&key), 0);
+        assert!(checkout(&pool, key).is_none());
+    }
+
+    #[cfg(feature = "http2")]
+    mod shared {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        use super::super::{Checkout, Poolable, Reservation};
+        use super::{connect, host_key, pool_no_timer};
+
+        #[derive(Clone, Debug)]
+        struct Shared(Arc<AtomicBool>);
+
+        impl Poolable for Shared {
+            fn is_open(&self) -> bool {
+                self.0.load(Ordering::SeqCst)
+            }
+
+            fn reserve(self) -> Reservation<Self> {
+                Reservation::Shared(self.clone(), self)
+            }
+
+            fn can_share(&self) -> bool {
+                true
+            }
+        }
+
+        #[test]
+        fn test_pool_shares_connections() {
+            let pool = pool_no_timer();
+            let key = host_key("foo");
+            let open = Arc::new(AtomicBool::new(true));
+
+            let first = connect(&pool, key.clone(), Shared(open.clone()));
+
+            // The pool kept a copy while the first is still in use.
+            let second = match pool.checkout(key.clone(), true) {
+                Checkout::Idle(pooled) => pooled,
+                _ => panic!("expected shared idle connection"),
+            };
+            assert!(Arc::ptr_eq(&first.0, &second.0));
+            assert_eq!(pool.idle_count(&key), 1);
+
+            drop((first, second));
+            assert_eq!(pool.idle_count(&key), 1);
+
+            open.store(false, Ordering::SeqCst);
+            assert!(matches!(
+                pool.checkout(key.clone(), true),
+                Checkout::Connect(_)
+            ));
+            assert_eq!(pool.idle_count(&key), 0);
+        }
+
+        #[tokio::test]
+        async fn test_pool_waiters_receive_shared_connection() {
+            let pool = pool_no_timer();
+            let key = host_key("foo");
+
+            let connecting = match pool.checkout(key.clone(), true) {
+                Checkout::Connect(connecting) => connecting,
+                _ => panic!("expected to connect"),
+            };
+            let waiter = match pool.checkout(key.clone(), true) {
+                Checkout::Wait(waiter) => waiter,
+                _ => panic!("expected to wait on connecting"),
+            };
+
+            let open = Arc::new(AtomicBool::new(true));
+            let pooled = pool.pooled(connecting, Shared(open));
+            let waited = waiter.await.expect("waiter gets connection");
+            assert!(Arc::ptr_eq(&pooled.0, &waited.0));
+        }
+
+        #[tokio::test]
+        async fn test_pool_waiters_canceled_if_connect_fails() {
+            let pool = pool_no_timer::<Shared>();
+            let key = host_key("foo");
+
+            let connecting = match pool.checkout(key.clone(), true) {
+                Checkout::Connect(connecting) => connecting,
+                _ => panic!("expected to connect"),
+            };
+            let waiter = match pool.checkout(key.clone(), true) {
+                Checkout::Wait(waiter) => waiter,
+                _ => panic!("expected to wait on connecting"),
+            };
+
+            drop(connecting);
+            assert!(waiter.await.is_none());
+
+            // The next checkout is free to connect again.
+            assert!(matches!(pool.checkout(key, true), Checkout::Connect(_)));
+        }
+    }
+
+    #[cfg(feature = "runtime")]
+    #[tokio::test]
+    async fn test_pool_timer_removes_expired() {
+        let pool = Pool::new(
+            Config {
+                idle_timeout: Some(Duration::from_millis(10)),
+                max_idle_per_host: usize::MAX,
+            },
+            &Exec::Default,
+        );
+
+        let key = host_key("foo");
+
+        let a = connect(&pool, key.clone(), Uniq(41));
+        let b = connect(&pool, key.clone(), Uniq(5));
+        drop((a, b));
+
+        assert_eq!(pool.idle_count(&key), 2);
+
+        // Let the idle interval tick and evict the expired connections.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(pool.idle_count(&key), 0);
+    }
+}
@@ -1,3 +1,331 @@
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "http1")]
+use std::sync::Mutex;
+#[cfg(feature = "http1")]
+use std::time::Duration;
+
+use http::{Request, StatusCode, Uri};
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+
+use super::connect::{Connected, Connection};
+use super::Client;
+use crate::body::Recv;
+use crate::common::{task, Future, Pin, Poll};
+
+type Serve = dyn Fn(DuplexStream) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync;
+
+/// A connector handing out one end of an in-memory pipe per connection,
+/// with the other end served by `serve`.
+#[derive(Clone)]
+struct DuplexConnector {
+    connected: Connected,
+    connects: Arc<AtomicUsize>,
+    serve: Arc<Serve>,
+}
+
+impl DuplexConnector {
+    fn new<F, Fut>(serve: F) -> DuplexConnector
+    where
+        F: Fn(DuplexStream) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        DuplexConnector {
+            connected: Connected::new(),
+            connects: Arc::new(AtomicUsize::new(0)),
+            serve: Arc::new(move |io| Box::pin(serve(io))),
+        }
+    }
+
+    fn connected(mut self, connected: Connected) -> DuplexConnector {
+        self.connected = connected;
+        self
+    }
+
+    fn connects(&self) -> usize {
+        self.connects.load(Ordering::SeqCst)
+    }
+}
+
+impl tower_service::Service<Uri> for DuplexConnector {
+    type Response = MockStream;
+    type Error = io::Error;
+    type Future = std::future::Ready<Result<MockStream, io::Error>>;
+
+    fn poll_ready(&mut self, _: &mut task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        assert_eq!(dst, "http://mock.local/");
+        self.connects.fetch_add(1, Ordering::SeqCst);
+        let (client, server) = tokio::io::duplex(8192);
+        tokio::spawn((self.serve)(server));
+        std::future::ready(Ok(MockStream {
+            connected: self.connected.clone(),
+            io: client,
+        }))
+    }
+}
+
+/// The client end of a `DuplexConnector` pipe, reporting the connector's
+/// configured `Connected` metadata.
+struct MockStream {
+    connected: Connected,
+    io: DuplexStream,
+}
+
+impl Connection for MockStream {
+    fn connected(&self) -> Connected {
+        self.connected.clone()
+    }
+}
+
+impl AsyncRead for MockStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for MockStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+}
+
+/// A tiny HTTP/1.1 server that records each request head, and replies
+/// to every request with a 200.
+#[cfg(feature = "http1")]
+async fn serve_http1(mut io: DuplexStream, heads: Arc<Mutex<Vec<String>>>) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut buf = Vec::new();
+    loop {
+        while let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let head = buf.drain(..end + 4).collect::<Vec<u8>>();
+            heads
+                .lock()
+                .unwrap()
+                .push(String::from_utf8(head).expect("utf8 head"));
+            io.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok")
+                .await
+                .expect("write response");
+        }
+
+        let mut chunk = [0; 1024];
+        match io.read(&mut chunk).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+        }
+    }
+}
+
+fn get(uri: &str) -> Request<Recv> {
+    Request::get(uri).body(Recv::empty()).unwrap()
+}
+
+#[cfg(feature = "http1")]
+fn http1_connector() -> (DuplexConnector, Arc<Mutex<Vec<String>>>) {
+    let heads = Arc::new(Mutex::new(Vec::new()));
+    let heads2 = heads.clone();
+    let connector = DuplexConnector::new(move |io| serve_http1(io, heads2.clone()));
+    (connector, heads)
+}
+
+#[cfg(feature = "http1")]
+#[tokio::test]
+async fn client_http1_reuses_idle_connection() {
+    let (connector, heads) = http1_connector();
+    let client = Client::builder().build::<_, Recv>(connector.clone());
+
+    for path in &["a", "b", "c"] {
+        let res = client
+            .request(get(&format!("http://mock.local/{}", path)))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = crate::body::to_bytes(res.into_body()).await.expect("body");
+        assert_eq!(body, "ok");
+
+        // Let the connection return to the pool.
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    assert_eq!(connector.connects(), 1);
+    let heads = heads.lock().unwrap();
+    assert_eq!(heads.len(), 3);
+    assert!(
+        heads[0].starts_with("GET /a HTTP/1.1\r\n"),
+        "{:?}",
+        heads[0]
+    );
+    assert!(heads[0].contains("host: mock.local\r\n"), "{:?}", heads[0]);
+}
+
+#[cfg(feature = "http1")]
+#[tokio::test]
+async fn client_http1_max_idle_per_host_zero_disables_pool() {
+    let (connector, _heads) = http1_connector();
+    let client = Client::builder()
+        .pool_max_idle_per_host(0)
+        .build::<_, Recv>(connector.clone());
+
+    for _ in 0..2 {
+        let res = client
+            .request(get("http://mock.local/"))
+            .await
+            .expect("response");
+        crate::body::to_bytes(res.into_body()).await.expect("body");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    assert_eq!(connector.connects(), 2);
+}
+
+#[cfg(feature = "http1")]
+#[tokio::test]
+async fn client_http1_idle_timeout_expires_connection() {
+    let (connector, _heads) = http1_connector();
+    let client = Client::builder()
+        .pool_idle_timeout(Duration::from_millis(20))
+        .build::<_, Recv>(connector.clone());
+
+    let res = client
+        .request(get("http://mock.local/"))
+        .await
+        .expect("response");
+    crate::body::to_bytes(res.into_body()).await.expect("body");
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let res = client
+        .request(get("http://mock.local/"))
+        .await
+        .expect("response");
+    crate::body::to_bytes(res.into_body()).await.expect("body");
+
+    assert_eq!(connector.connects(), 2);
+}
+
+#[cfg(feature = "http1")]
+#[tokio::test]
+async fn client_http1_proxied_connection_sends_absolute_form() {
+    let (connector, heads) = http1_connector();
+    let connector = connector.connected(Connected::new().proxy(true));
+    let client = Client::builder().build::<_, Recv>(connector);
+
+    let res = client
+        .request(get("http://mock.local/a?b"))
+        .await
+        .expect("response");
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let heads = heads.lock().unwrap();
+    assert!(
+        heads[0].starts_with("GET http://mock.local/a?b HTTP/1.1\r\n"),
+        "{:?}",
+        heads[0]
+    );
+}
+
+#[cfg(feature = "http2")]
+async fn serve_http2(io: DuplexStream) {
+    let mut conn = h2::server::handshake(io).await.expect("h2 handshake");
+    while let Some(stream) = conn.accept().await {
+        let (req, mut respond) = stream.expect("h2 stream");
+        assert_eq!(req.uri(), "http://mock.local/");
+        let res = http::Response::new(());
+        respond.send_response(res, true).expect("send_response");
+    }
+}
+
+#[cfg(feature = "http2")]
+#[tokio::test]
+async fn client_http2_negotiated_by_connector() {
+    let connector = DuplexConnector::new(serve_http2).connected(Connected::new().negotiated_h2());
+    let client = Client::builder().build::<_, Recv>(connector.clone());
+
+    let res = client
+        .request(get("http://mock.local/"))
+        .await
+        .expect("response");
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.version(), http::Version::HTTP_2);
+}
+
+#[cfg(feature = "http2")]
+#[tokio::test]
+async fn client_http2_shares_one_connection() {
+    let connector = DuplexConnector::new(serve_http2);
+    let client = Client::builder()
+        .http2_only(true)
+        .build::<_, Recv>(connector.clone());
+
+    let reqs = (0..5).map(|_| client.request(get("http://mock.local/")));
+    for res in futures_util::future::join_all(reqs).await {
+        assert_eq!(res.expect("response").status(), StatusCode::OK);
+    }
+
+    let res = client
+        .request(get("http://mock.local/"))
+        .await
+        .expect("response");
+    assert_eq!(res.status(), StatusCode::OK);
+
+    assert_eq!(connector.connects(), 1);
+}
+
+#[tokio::test]
+async fn client_requires_absolute_uri() {
+    let connector = DuplexConnector::new(|_io| async {});
+    let client = Client::builder().build::<_, Recv>(connector.clone());
+
+    let err = client.request(get("/relative")).await.unwrap_err();
+    assert!(err.is_user(), "{:?}", err);
+    assert_eq!(connector.connects(), 0);
+}
+
+#[tokio::test]
+async fn client_connect_error() {
+    #[derive(Clone)]
+    struct Refused;
+
+    impl tower_service::Service<Uri> for Refused {
+        type Response = MockStream;
+        type Error = io::Error;
+        type Future = std::future::Ready<Result<MockStream, io::Error>>;
+
+        fn poll_ready(&mut self, _: &mut task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _dst: Uri) -> Self::Future {
+            std::future::ready(Err(io::ErrorKind::ConnectionRefused.into()))
+        }
+    }
+
+    let client = Client::builder().build::<_, Recv>(Refused);
+    let err = client.request(get("http://mock.local/")).await.unwrap_err();
+    assert!(err.is_connect(), "{:?}", err);
+}
+
 /*
 // FIXME: re-implement tests with `async/await`
 #[test]
@@ -14,6 +14,11 @@ pub trait ConnStreamExec<F, B: HttpBody>: Clone {
     fn execute_h2stream(&mut self, fut: H2Stream<F, B>);
 }
 
+#[cfg(all(feature = "server", any(feature = "http1", feature = "http2")))]
+pub trait NewSvcExec: Clone {
+    fn execute_new_svc(&mut self, fut: BoxSendFuture);
+}
+
 pub(crate) type BoxSendFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
 
 // Either the user provides an executor for background tasks, or we use
@@ -67,6 +72,13 @@ where
     }
 }
 
+#[cfg(all(feature = "server", any(feature = "http1", feature = "http2")))]
+impl NewSvcExec for Exec {
+    fn execute_new_svc(&mut self, fut: BoxSendFuture) {
+        self.execute(fut)
+    }
+}
+
 // ==== impl Executor =====
 
 #[cfg(feature = "server")]
@@ -81,6 +93,16 @@ where
     }
 }
 
+#[cfg(all(feature = "server", any(feature = "http1", feature = "http2")))]
+impl<E> NewSvcExec for E
+where
+    E: Executor<BoxSendFuture> + Clone,
+{
+    fn execute_new_svc(&mut self, fut: BoxSendFuture) {
+        self.execute(fut)
+    }
+}
+
 // If http2 is not enable, we just have a stub here, so that the trait bounds
 // that *would* have been needed are still checked. Why?
 //
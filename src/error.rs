@@ -15,6 +15,9 @@ pub struct Error {
 struct ErrorImpl {
     kind: Kind,
     cause: Option<Cause>,
+    /// The id of the HTTP/2 stream of the request that failed.
+    #[cfg(feature = "http2")]
+    stream_id: Option<u32>,
 }
 
 #[derive(Debug)]
@@ -34,12 +37,25 @@ pub(super) enum Kind {
     /// An `io::Error` that occurred while trying to read or write to a network stream.
     #[cfg(any(feature = "http1", feature = "http2"))]
     Io,
+    /// Error occurred while connecting.
+    #[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+    Connect,
     /// Error creating a TcpListener.
     #[cfg(all(feature = "tcp", feature = "server"))]
     Listen,
+    /// Error accepting on an Incoming stream.
+    #[cfg(all(feature = "server", any(feature = "http1", feature = "http2")))]
+    Accept,
     /// User took too long to send headers
     #[cfg(all(feature = "http1", feature = "server", feature = "runtime"))]
     HeaderTimeout,
+    /// A timeout on a message expired.
+    #[cfg(all(
+        feature = "runtime",
+        any(feature = "client", feature = "server"),
+        any(feature = "http1", feature = "http2")
+    ))]
+    Timeout(Timeout),
     /// Error while reading a body from connection.
     #[cfg(any(feature = "http1", feature = "http2"))]
     Body,
@@ -49,6 +65,9 @@ pub(super) enum Kind {
     /// Error calling AsyncWrite::shutdown()
     #[cfg(feature = "http1")]
     Shutdown,
+    /// An HTTP/1 request asked to upgrade the connection to h2c.
+    #[cfg(all(feature = "http1", feature = "http2", feature = "server"))]
+    H2cUpgrade,
 
     /// A general error from h2.
     #[cfg(feature = "http2")]
@@ -64,8 +83,12 @@ pub(super) enum Parse {
     Uri,
     #[cfg_attr(not(all(feature = "http1", feature = "server")), allow(unused))]
     UriTooLong,
+    #[cfg(all(feature = "http1", feature = "server"))]
+    BareLineFeed,
     Header(Header),
     TooLarge,
+    #[cfg(feature = "http1")]
+    ChunkSizeWhitespace,
     Status,
     #[cfg_attr(debug_assertions, allow(unused))]
     Internal,
@@ -77,9 +100,46 @@ pub(super) enum Header {
     #[cfg(feature = "http1")]
     ContentLengthInvalid,
     #[cfg(all(feature = "http1", feature = "server"))]
+    ContentLengthDuplicate,
+    #[cfg(all(feature = "http1", feature = "server"))]
+    ContentLengthConflict,
+    #[cfg(all(feature = "http1", feature = "server"))]
     TransferEncodingInvalid,
+    #[cfg(all(feature = "http1", feature = "server"))]
+    TransferEncodingChunkedNotLast,
     #[cfg(feature = "http1")]
     TransferEncodingUnexpected,
+    #[cfg(all(feature = "http1", feature = "server"))]
+    ObsoleteLineFolding,
+    #[cfg(all(feature = "http1", feature = "server"))]
+    WhitespaceBeforeColon,
+}
+
+#[derive(Clone, Copy, Debug)]
+#[cfg(all(
+    feature = "runtime",
+    any(feature = "client", feature = "server"),
+    any(feature = "http1", feature = "http2")
+))]
+pub(super) enum Timeout {
+    /// The response head wasn't received in time.
+    #[cfg(feature = "client")]
+    ResponseHead,
+    /// The response body didn't receive data in time.
+    #[cfg(feature = "client")]
+    ResponseBodyIdle,
+    /// The whole response wasn't received in time.
+    #[cfg(feature = "client")]
+    Total,
+    /// The request body didn't receive data in time.
+    #[cfg(feature = "server")]
+    RequestBodyIdle,
+    /// The request body was received below the minimum data rate.
+    #[cfg(feature = "server")]
+    RequestBodyDataRate,
+    /// The response was sent below the minimum data rate.
+    #[cfg(feature = "server")]
+    ResponseDataRate,
 }
 
 #[derive(Debug)]
@@ -92,17 +152,37 @@ pub(super) enum User {
     /// Error from future of user's Service.
     #[cfg(any(feature = "http1", feature = "http2"))]
     Service,
+    /// Error calling user's MakeService.
+    #[cfg(all(feature = "server", any(feature = "http1", feature = "http2")))]
+    MakeService,
     /// User tried to send a certain header in an unexpected context.
     ///
     /// For example, sending both `content-length` and `transfer-encoding`.
     #[cfg(any(feature = "http1", feature = "http2"))]
     #[cfg(feature = "server")]
     UnexpectedHeader,
+    /// User tried to create a Request with bad version.
+    #[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+    UnsupportedVersion,
+    /// User tried to send a Request with Client with non-absolute URI.
+    #[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+    AbsoluteUriRequired,
+
     /// User tried to respond with a 1xx (not 101) response code.
     #[cfg(feature = "http1")]
     #[cfg(feature = "server")]
     UnsupportedStatusCode,
 
+    /// User tried to send an informational response that isn't supported,
+    /// because of its status code or the protocol of the connection.
+    #[cfg(all(feature = "server", any(feature = "http1", feature = "http2")))]
+    UnsupportedInformational,
+
+    /// User sent an informational response while too many were waiting to
+    /// be written.
+    #[cfg(all(feature = "server", feature = "http1"))]
+    TooManyInformational,
+
     /// User tried polling for an upgrade that doesn't exist.
     NoUpgrade,
 
@@ -158,6 +238,19 @@ impl Error {
         matches!(self.inner.kind, Kind::ChannelClosed)
     }
 
+    /// Returns true if this was an error from `Connect`.
+    pub fn is_connect(&self) -> bool {
+        #[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+        {
+            matches!(self.inner.kind, Kind::Connect)
+        }
+
+        #[cfg(not(all(feature = "client", any(feature = "http1", feature = "http2"))))]
+        {
+            false
+        }
+    }
+
     /// Returns true if the connection closed before a message could complete.
     pub fn is_incomplete_message(&self) -> bool {
         matches!(self.inner.kind, Kind::IncompleteMessage)
@@ -173,6 +266,85 @@ impl Error {
         self.find_source::<TimedOut>().is_some()
     }
 
+    /// Returns true if a body was received or sent too slowly.
+    ///
+    /// This is the case when a body didn't receive data within its idle
+    /// timeout, or was transferred below its minimum data rate. Such errors
+    /// are also [timeouts](Error::is_timeout).
+    pub fn is_body_timeout(&self) -> bool {
+        #[cfg(all(
+            feature = "runtime",
+            any(feature = "client", feature = "server"),
+            any(feature = "http1", feature = "http2")
+        ))]
+        {
+            if let Kind::Timeout(ref timeout) = self.inner.kind {
+                return timeout.is_body();
+            }
+        }
+        false
+    }
+
+    /// Returns the HTTP/2 error code of the stream reset (`RST_STREAM`) or
+    /// `GOAWAY` that caused this error, if any.
+    pub fn h2_reason(&self) -> Option<u32> {
+        #[cfg(feature = "http2")]
+        {
+            self.find_source::<h2::Error>()
+                .and_then(|h2_err| h2_err.reason())
+                .map(u32::from)
+        }
+
+        #[cfg(not(feature = "http2"))]
+        {
+            None
+        }
+    }
+
+    /// Returns true if this error was caused by a `GOAWAY` received from
+    /// the HTTP/2 peer.
+    pub fn is_goaway(&self) -> bool {
+        #[cfg(feature = "http2")]
+        {
+            self.find_source::<h2::Error>()
+                .map_or(false, |h2_err| h2_err.is_go_away() && h2_err.is_remote())
+        }
+
+        #[cfg(not(feature = "http2"))]
+        {
+            false
+        }
+    }
+
+    /// Returns true if the HTTP/2 peer didn't process the request, so that
+    /// it is safe to retry it, even when it isn't idempotent.
+    ///
+    /// This is the case when the peer refused the stream with
+    /// `REFUSED_STREAM`, or when the request was sent on a stream above the
+    /// last stream id of a `GOAWAY` from the peer. Requests at or below that
+    /// id may have been processed, and fail with another error if the
+    /// connection closes before they complete.
+    pub fn is_retryable_before_processing(&self) -> bool {
+        #[cfg(feature = "http2")]
+        {
+            self.find_source::<h2::Error>().map_or(false, |h2_err| {
+                if !h2_err.is_remote() {
+                    return false;
+                }
+                // `h2` fails a stream with the peer's GOAWAY only when its id
+                // is above the last stream id, other GOAWAY errors are about
+                // the connection rather than a request.
+                (h2_err.is_go_away() && self.inner.stream_id.is_some())
+                    || (h2_err.is_reset() && h2_err.reason() == Some(h2::Reason::REFUSED_STREAM))
+            })
+        }
+
+        #[cfg(not(feature = "http2"))]
+        {
+            false
+        }
+    }
+
     /// Consumes the error, returning its cause.
     pub fn into_cause(self) -> Option<Box<dyn StdError + Send + Sync>> {
         self.inner.cause
@@ -180,7 +352,12 @@ impl Error {
 
     pub(super) fn new(kind: Kind) -> Error {
         Error {
-            inner: Box::new(ErrorImpl { kind, cause: None }),
+            inner: Box::new(ErrorImpl {
+                kind,
+                cause: None,
+                #[cfg(feature = "http2")]
+                stream_id: None,
+            }),
         }
     }
 
@@ -189,6 +366,12 @@ impl Error {
         self
     }
 
+    #[cfg(all(feature = "client", feature = "http2"))]
+    pub(super) fn with_stream_id(mut self, stream_id: u32) -> Error {
+        self.inner.stream_id = Some(stream_id);
+        self
+    }
+
     #[cfg(any(all(feature = "http1", feature = "server"), feature = "ffi"))]
     pub(super) fn kind(&self) -> &Kind {
         &self.inner.kind
@@ -208,7 +391,7 @@ impl Error {
     }
 
     #[cfg(feature = "http2")]
-    pub(super) fn h2_reason(&self) -> h2::Reason {
+    pub(super) fn h2_reason_or_internal(&self) -> h2::Reason {
         // Find an h2::Reason somewhere in the cause stack, if it exists,
         // otherwise assume an INTERNAL_ERROR.
         self.find_source::<h2::Error>()
@@ -235,6 +418,11 @@ impl Error {
         Error::new(Kind::Parse(Parse::VersionH2))
     }
 
+    #[cfg(all(feature = "http1", feature = "http2", feature = "server"))]
+    pub(super) fn new_h2c_upgrade() -> Error {
+        Error::new(Kind::H2cUpgrade)
+    }
+
     #[cfg(feature = "http1")]
     pub(super) fn new_unexpected_message() -> Error {
         Error::new(Kind::UnexpectedMessage)
@@ -245,11 +433,21 @@ impl Error {
         Error::new(Kind::Io).with(cause)
     }
 
+    #[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+    pub(super) fn new_connect<E: Into<Cause>>(cause: E) -> Error {
+        Error::new(Kind::Connect).with(cause)
+    }
+
     #[cfg(all(feature = "server", feature = "tcp"))]
     pub(super) fn new_listen<E: Into<Cause>>(cause: E) -> Error {
         Error::new(Kind::Listen).with(cause)
     }
 
+    #[cfg(all(feature = "server", any(feature = "http1", feature = "http2")))]
+    pub(super) fn new_accept<E: Into<Cause>>(cause: E) -> Error {
+        Error::new(Kind::Accept).with(cause)
+    }
+
     pub(super) fn new_closed() -> Error {
         Error::new(Kind::ChannelClosed)
     }
@@ -278,17 +476,46 @@ impl Error {
         Error::new_user(User::UnexpectedHeader)
     }
 
+    #[cfg(all(
+        feature = "runtime",
+        any(feature = "client", feature = "server"),
+        any(feature = "http1", feature = "http2")
+    ))]
+    pub(super) fn new_timeout(timeout: Timeout) -> Error {
+        Error::new(Kind::Timeout(timeout)).with(TimedOut)
+    }
+
     #[cfg(all(feature = "http1", feature = "server", feature = "runtime"))]
     pub(super) fn new_header_timeout() -> Error {
         Error::new(Kind::HeaderTimeout)
     }
 
+    #[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+    pub(super) fn new_user_unsupported_version() -> Error {
+        Error::new_user(User::UnsupportedVersion)
+    }
+
+    #[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+    pub(super) fn new_user_absolute_uri_required() -> Error {
+        Error::new_user(User::AbsoluteUriRequired)
+    }
+
     #[cfg(feature = "http1")]
     #[cfg(feature = "server")]
     pub(super) fn new_user_unsupported_status_code() -> Error {
         Error::new_user(User::UnsupportedStatusCode)
     }
 
+    #[cfg(all(feature = "server", any(feature = "http1", feature = "http2")))]
+    pub(super) fn new_user_unsupported_informational() -> Error {
+        Error::new_user(User::UnsupportedInformational)
+    }
+
+    #[cfg(all(feature = "server", feature = "http1"))]
+    pub(super) fn new_user_too_many_informational() -> Error {
+        Error::new_user(User::TooManyInformational)
+    }
+
     pub(super) fn new_user_no_upgrade() -> Error {
         Error::new_user(User::NoUpgrade)
     }
@@ -303,6 +530,11 @@ impl Error {
         Error::new_user(User::Service).with(cause)
     }
 
+    #[cfg(all(feature = "server", any(feature = "http1", feature = "http2")))]
+    pub(super) fn new_user_make_service<E: Into<Cause>>(cause: E) -> Error {
+        Error::new_user(User::MakeService).with(cause)
+    }
+
     #[cfg(any(feature = "http1", feature = "http2"))]
     pub(super) fn new_user_body<E: Into<Cause>>(cause: E) -> Error {
         Error::new_user(User::Body).with(cause)
@@ -345,20 +577,44 @@ impl Error {
             Kind::Parse(Parse::VersionH2) => "invalid HTTP version parsed (found HTTP2 preface)",
             Kind::Parse(Parse::Uri) => "invalid URI",
             Kind::Parse(Parse::UriTooLong) => "URI too long",
+            #[cfg(all(feature = "http1", feature = "server"))]
+            Kind::Parse(Parse::BareLineFeed) => "bare LF line ending parsed",
             Kind::Parse(Parse::Header(Header::Token)) => "invalid HTTP header parsed",
             #[cfg(feature = "http1")]
             Kind::Parse(Parse::Header(Header::ContentLengthInvalid)) => {
                 "invalid content-length parsed"
             }
             #[cfg(all(feature = "http1", feature = "server"))]
+            Kind::Parse(Parse::Header(Header::ContentLengthDuplicate)) => {
+                "duplicate content-length parsed"
+            }
+            #[cfg(all(feature = "http1", feature = "server"))]
+            Kind::Parse(Parse::Header(Header::ContentLengthConflict)) => {
+                "conflicting content-length parsed"
+            }
+            #[cfg(all(feature = "http1", feature = "server"))]
             Kind::Parse(Parse::Header(Header::TransferEncodingInvalid)) => {
                 "invalid transfer-encoding parsed"
             }
+            #[cfg(all(feature = "http1", feature = "server"))]
+            Kind::Parse(Parse::Header(Header::TransferEncodingChunkedNotLast)) => {
+                "chunked transfer-encoding is not the final encoding"
+            }
             #[cfg(feature = "http1")]
             Kind::Parse(Parse::Header(Header::TransferEncodingUnexpected)) => {
                 "unexpected transfer-encoding parsed"
             }
+            #[cfg(all(feature = "http1", feature = "server"))]
+            Kind::Parse(Parse::Header(Header::ObsoleteLineFolding)) => {
+                "obsolete line folding parsed in header"
+            }
+            #[cfg(all(feature = "http1", feature = "server"))]
+            Kind::Parse(Parse::Header(Header::WhitespaceBeforeColon)) => {
+                "whitespace before colon parsed in header"
+            }
             Kind::Parse(Parse::TooLarge) => "message head is too large",
+            #[cfg(feature = "http1")]
+            Kind::Parse(Parse::ChunkSizeWhitespace) => "whitespace parsed in chunk size",
             Kind::Parse(Parse::Status) => "invalid HTTP status-code parsed",
             Kind::Parse(Parse::Internal) => {
                 "internal error inside Hyper and/or its dependencies, please report"
@@ -368,16 +624,60 @@ impl Error {
             Kind::UnexpectedMessage => "received unexpected message from connection",
             Kind::ChannelClosed => "channel closed",
             Kind::Canceled => "operation was canceled",
+            #[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+            Kind::Connect => "error trying to connect",
             #[cfg(all(feature = "server", feature = "tcp"))]
             Kind::Listen => "error creating server listener",
+            #[cfg(all(feature = "server", any(feature = "http1", feature = "http2")))]
+            Kind::Accept => "error accepting connection",
             #[cfg(all(feature = "http1", feature = "server", feature = "runtime"))]
             Kind::HeaderTimeout => "read header from client timeout",
+            #[cfg(all(
+                feature = "client",
+                feature = "runtime",
+                any(feature = "http1", feature = "http2")
+            ))]
+            Kind::Timeout(Timeout::ResponseHead) => "timed out waiting for response head",
+            #[cfg(all(
+                feature = "client",
+                feature = "runtime",
+                any(feature = "http1", feature = "http2")
+            ))]
+            Kind::Timeout(Timeout::ResponseBodyIdle) => "timed out waiting for response body data",
+            #[cfg(all(
+                feature = "client",
+                feature = "runtime",
+                any(feature = "http1", feature = "http2")
+            ))]
+            Kind::Timeout(Timeout::Total) => "response deadline elapsed",
+            #[cfg(all(
+                feature = "server",
+                feature = "runtime",
+                any(feature = "http1", feature = "http2")
+            ))]
+            Kind::Timeout(Timeout::RequestBodyIdle) => "timed out waiting for request body data",
+            #[cfg(all(
+                feature = "server",
+                feature = "runtime",
+                any(feature = "http1", feature = "http2")
+            ))]
+            Kind::Timeout(Timeout::RequestBodyDataRate) => {
+                "request body received below the minimum data rate"
+            }
+            #[cfg(all(
+                feature = "server",
+                feature = "runtime",
+                any(feature = "http1", feature = "http2")
+            ))]
+            Kind::Timeout(Timeout::ResponseDataRate) => "response sent below the minimum data rate",
             #[cfg(any(feature = "http1", feature = "http2"))]
             Kind::Body => "error reading a body from connection",
             #[cfg(any(feature = "http1", feature = "http2"))]
             Kind::BodyWrite => "error writing a body to connection",
             #[cfg(feature = "http1")]
             Kind::Shutdown => "error shutting down connection",
+            #[cfg(all(feature = "http1", feature = "http2", feature = "server"))]
+            Kind::H2cUpgrade => "h2c upgrade requested",
             #[cfg(feature = "http2")]
             Kind::Http2 => "http2 error",
             #[cfg(any(feature = "http1", feature = "http2"))]
@@ -388,14 +688,24 @@ impl Error {
             Kind::User(User::BodyWriteAborted) => "user body write aborted",
             #[cfg(any(feature = "http1", feature = "http2"))]
             Kind::User(User::Service) => "error from user's Service",
+            #[cfg(all(feature = "server", any(feature = "http1", feature = "http2")))]
+            Kind::User(User::MakeService) => "error from user's MakeService",
             #[cfg(any(feature = "http1", feature = "http2"))]
             #[cfg(feature = "server")]
             Kind::User(User::UnexpectedHeader) => "user sent unexpected header",
+            #[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+            Kind::User(User::UnsupportedVersion) => "request has unsupported HTTP version",
+            #[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+            Kind::User(User::AbsoluteUriRequired) => "client requires absolute-form URIs",
             #[cfg(feature = "http1")]
             #[cfg(feature = "server")]
             Kind::User(User::UnsupportedStatusCode) => {
                 "response has 1xx status code, not supported by server"
             }
+            #[cfg(all(feature = "server", any(feature = "http1", feature = "http2")))]
+            Kind::User(User::UnsupportedInformational) => "informational response not supported",
+            #[cfg(all(feature = "server", feature = "http1"))]
+            Kind::User(User::TooManyInformational) => "too many informational responses pending",
             Kind::User(User::NoUpgrade) => "no upgrade available",
             #[cfg(feature = "http1")]
             Kind::User(User::ManualUpgrade) => "upgrade expected but low level API in use",
@@ -452,14 +762,39 @@ impl Parse {
         Parse::Header(Header::ContentLengthInvalid)
     }
 
+    #[cfg(all(feature = "http1", feature = "server"))]
+    pub(crate) fn content_length_duplicate() -> Self {
+        Parse::Header(Header::ContentLengthDuplicate)
+    }
+
+    #[cfg(all(feature = "http1", feature = "server"))]
+    pub(crate) fn content_length_conflict() -> Self {
+        Parse::Header(Header::ContentLengthConflict)
+    }
+
     #[cfg(all(feature = "http1", feature = "server"))]
     pub(crate) fn transfer_encoding_invalid() -> Self {
         Parse::Header(Header::TransferEncodingInvalid)
     }
 
+    #[cfg(all(feature = "http1", feature = "server"))]
+    pub(crate) fn transfer_encoding_chunked_not_last() -> Self {
+        Parse::Header(Header::TransferEncodingChunkedNotLast)
+    }
+
     pub(crate) fn transfer_encoding_unexpected() -> Self {
         Parse::Header(Header::TransferEncodingUnexpected)
     }
+
+    #[cfg(all(feature = "http1", feature = "server"))]
+    pub(crate) fn obsolete_line_folding() -> Self {
+        Parse::Header(Header::ObsoleteLineFolding)
+    }
+
+    #[cfg(all(feature = "http1", feature = "server"))]
+    pub(crate) fn whitespace_before_colon() -> Self {
+        Parse::Header(Header::WhitespaceBeforeColon)
+    }
 }
 
 impl From<httparse::Error> for Parse {
@@ -500,6 +835,26 @@ impl From<http::uri::InvalidUriParts> for Parse {
     }
 }
 
+#[cfg(all(
+    feature = "runtime",
+    any(feature = "client", feature = "server"),
+    any(feature = "http1", feature = "http2")
+))]
+impl Timeout {
+    fn is_body(&self) -> bool {
+        match *self {
+            #[cfg(feature = "client")]
+            Timeout::ResponseHead | Timeout::Total => false,
+            #[cfg(feature = "client")]
+            Timeout::ResponseBodyIdle => true,
+            #[cfg(feature = "server")]
+            Timeout::RequestBodyIdle | Timeout::RequestBodyDataRate | Timeout::ResponseDataRate => {
+                true
+            }
+        }
+    }
+}
+
 #[doc(hidden)]
 trait AssertSendSync: Send + Sync + 'static {}
 #[doc(hidden)]
@@ -529,14 +884,17 @@ mod tests {
     #[test]
     fn h2_reason_unknown() {
         let closed = Error::new_closed();
-        assert_eq!(closed.h2_reason(), h2::Reason::INTERNAL_ERROR);
+        assert_eq!(closed.h2_reason_or_internal(), h2::Reason::INTERNAL_ERROR);
     }
 
     #[cfg(feature = "http2")]
     #[test]
     fn h2_reason_one_level() {
         let body_err = Error::new_user_body(h2::Error::from(h2::Reason::ENHANCE_YOUR_CALM));
-        assert_eq!(body_err.h2_reason(), h2::Reason::ENHANCE_YOUR_CALM);
+        assert_eq!(
+            body_err.h2_reason_or_internal(),
+            h2::Reason::ENHANCE_YOUR_CALM
+        );
     }
 
     #[cfg(feature = "http2")]
@@ -545,6 +903,21 @@ mod tests {
         let recvd = Error::new_h2(h2::Error::from(h2::Reason::HTTP_1_1_REQUIRED));
         // Suppose a user were proxying the received error
         let svc_err = Error::new_user_service(recvd);
-        assert_eq!(svc_err.h2_reason(), h2::Reason::HTTP_1_1_REQUIRED);
+        assert_eq!(
+            svc_err.h2_reason_or_internal(),
+            h2::Reason::HTTP_1_1_REQUIRED
+        );
+    }
+
+    #[cfg(feature = "http2")]
+    #[test]
+    fn h2_reason_code() {
+        assert_eq!(Error::new_closed().h2_reason(), None);
+
+        let body_err = Error::new_user_body(h2::Error::from(h2::Reason::REFUSED_STREAM));
+        assert_eq!(body_err.h2_reason(), Some(0x7));
+        // Not received from the peer.
+        assert!(!body_err.is_goaway());
+        assert!(!body_err.is_retryable_before_processing());
     }
 }
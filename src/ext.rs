@@ -1,20 +1,60 @@
 //! HTTP extensions.
 
+#[cfg(feature = "http1")]
 use bytes::Bytes;
-#[cfg(any(feature = "http1", feature = "ffi"))]
-use http::header::HeaderName;
 #[cfg(feature = "http1")]
-use http::header::{IntoHeaderName, ValueIter};
+use http::header::{HeaderName, IntoHeaderName, ValueIter};
+#[cfg(feature = "http1")]
 use http::HeaderMap;
-#[cfg(feature = "ffi")]
+#[cfg(feature = "http1")]
 use std::collections::HashMap;
 #[cfg(feature = "http2")]
 use std::fmt;
 
+#[cfg(feature = "http1")]
+mod h1_chunk_extensions;
+#[cfg(all(feature = "http1", feature = "server"))]
+mod h1_expect_continue;
+#[cfg(all(feature = "http1", feature = "client"))]
+mod h1_ignored_invalid_headers;
+#[cfg(feature = "http1")]
+mod h1_raw_head;
 #[cfg(any(feature = "http1", feature = "ffi"))]
 mod h1_reason_phrase;
+#[cfg(any(feature = "http1", feature = "http2"))]
+mod informational;
+#[cfg(all(
+    feature = "client",
+    feature = "runtime",
+    any(feature = "http1", feature = "http2")
+))]
+mod timeouts;
+#[cfg(feature = "http1")]
+pub use h1_chunk_extensions::{
+    ChunkExtensions, ChunkExtensionsIter, InvalidChunkExtensions, OutgoingChunkExtensions,
+};
+#[cfg(all(feature = "http1", feature = "server"))]
+pub use h1_expect_continue::ExpectContinue;
+#[cfg(all(feature = "http1", feature = "server"))]
+pub(crate) use h1_expect_continue::{ContinueDecision, Decided};
+#[cfg(all(feature = "http1", feature = "client"))]
+pub use h1_ignored_invalid_headers::IgnoredInvalidHeaders;
+#[cfg(feature = "http1")]
+pub use h1_raw_head::RawHead;
 #[cfg(any(feature = "http1", feature = "ffi"))]
 pub use h1_reason_phrase::ReasonPhrase;
+#[cfg(all(feature = "server", feature = "http1"))]
+pub(crate) use informational::InformationalReceiver;
+#[cfg(all(feature = "server", any(feature = "http1", feature = "http2")))]
+pub use informational::InformationalSender;
+#[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+pub use informational::OnInformational;
+#[cfg(all(
+    feature = "client",
+    feature = "runtime",
+    any(feature = "http1", feature = "http2")
+))]
+pub use timeouts::RequestTimeouts;
 
 #[cfg(feature = "http2")]
 /// Represents the `:protocol` pseudo-header used by
@@ -92,15 +132,45 @@ impl fmt::Debug for Protocol {
 /// })
 /// ```
 ///
-/// [`http1_preserve_header_case`]: /client/struct.Client.html#method.http1_preserve_header_case
-#[derive(Clone, Debug)]
-pub(crate) struct HeaderCaseMap(HeaderMap<Bytes>);
+/// The same goes for requests received by a server with its option
+/// [`http1_preserve_header_case`][server] set.
+///
+/// When a `HeaderCaseMap` is present in the extensions of an outgoing HTTP/1
+/// message, the values of each header name are written with the spellings of
+/// that name in the map, in order. Values without a spelling, and spellings
+/// that don't match their name ignoring case, are written as if there were
+/// no map.
+///
+/// ```
+/// # #[cfg(feature = "http1")]
+/// # fn set(req: &mut hyper::Request<hyper::Recv>) {
+/// use hyper::ext::HeaderCaseMap;
+/// use hyper::header::HeaderValue;
+///
+/// req.headers_mut()
+///     .insert("x-request-id", HeaderValue::from_static("42"));
+/// let mut casing = HeaderCaseMap::new();
+/// casing.append("x-request-id", "X-Request-ID".into());
+/// req.extensions_mut().insert(casing);
+/// # }
+/// ```
+///
+/// [`http1_preserve_header_case`]: crate::client::conn::http1::Builder::http1_preserve_header_case
+/// [server]: crate::server::conn::Http::http1_preserve_header_case
+#[cfg(feature = "http1")]
+#[derive(Clone, Debug, Default)]
+pub struct HeaderCaseMap(HeaderMap<Bytes>);
 
 #[cfg(feature = "http1")]
 impl HeaderCaseMap {
+    /// Creates an empty `HeaderCaseMap`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
     /// Returns a view of all spellings associated with that header name,
     /// in the order they were found.
-    pub(crate) fn get_all<'a>(
+    pub fn get_all<'a>(
         &'a self,
         name: &HeaderName,
     ) -> impl Iterator<Item = impl AsRef<[u8]> + 'a> + 'a {
@@ -113,16 +183,13 @@ impl HeaderCaseMap {
         self.0.get_all(name).into_iter()
     }
 
-    pub(crate) fn default() -> Self {
-        Self(Default::default())
-    }
-
-    #[cfg(any(test, feature = "ffi"))]
-    pub(crate) fn insert(&mut self, name: HeaderName, orig: Bytes) {
+    /// Sets the only spelling of a header name, replacing any previous ones.
+    pub fn insert(&mut self, name: HeaderName, orig: Bytes) {
         self.0.insert(name, orig);
     }
 
-    pub(crate) fn append<N>(&mut self, name: N, orig: Bytes)
+    /// Adds a spelling of a header name, after any previous ones.
+    pub fn append<N>(&mut self, name: N, orig: Bytes)
     where
         N: IntoHeaderName,
     {
@@ -130,10 +197,50 @@ impl HeaderCaseMap {
     }
 }
 
-#[cfg(feature = "ffi")]
-#[derive(Clone, Debug)]
-/// Hashmap<Headername, numheaders with that name>
-pub(crate) struct OriginalHeaderOrder {
+/// The order in which the headers of an HTTP message were received.
+///
+/// If an HTTP/1 response is parsed on a connection whose option
+/// [`http1_preserve_header_order`] was set to true, or an HTTP/1 request is
+/// received by a server with the option [`http1_preserve_header_order`][server]
+/// set, an `OriginalHeaderOrder` is present in its extensions. It records
+/// each header as its name and its index among the values of that name, so
+/// that headers with the same name can be interleaved with others.
+///
+/// When an `OriginalHeaderOrder` is present in the extensions of an outgoing
+/// HTTP/1 message, its headers are written in that order. Headers it doesn't
+/// mention, including those added by hyper, are written after the others.
+///
+/// ```
+/// # #[cfg(feature = "http1")]
+/// # fn main() {
+/// use hyper::ext::OriginalHeaderOrder;
+/// use hyper::header::{HeaderMap, HeaderValue, CONTENT_ENCODING, SET_COOKIE};
+///
+/// let mut headers = HeaderMap::new();
+/// let mut order = OriginalHeaderOrder::new();
+///
+/// headers.append(SET_COOKIE, HeaderValue::from_static("a=b"));
+/// order.append(SET_COOKIE);
+/// headers.append(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+/// order.append(CONTENT_ENCODING);
+/// headers.append(SET_COOKIE, HeaderValue::from_static("c=d"));
+/// order.append(SET_COOKIE);
+///
+/// let values: Vec<_> = order
+///     .get_in_order()
+///     .map(|(name, idx)| headers.get_all(name).iter().nth(*idx).unwrap())
+///     .collect();
+/// assert_eq!(values, ["a=b", "gzip", "c=d"]);
+/// # }
+/// # #[cfg(not(feature = "http1"))]
+/// # fn main() {}
+/// ```
+///
+/// [`http1_preserve_header_order`]: crate::client::conn::http1::Builder::http1_preserve_header_order
+/// [server]: crate::server::conn::Http::http1_preserve_header_order
+#[cfg(feature = "http1")]
+#[derive(Clone, Debug, Default)]
+pub struct OriginalHeaderOrder {
     /// Stores how many entries a Headername maps to. This is used
     /// for accounting.
     num_entries: HashMap<HeaderName, usize>,
@@ -145,16 +252,18 @@ pub(crate) struct OriginalHeaderOrder {
     entry_order: Vec<(HeaderName, usize)>,
 }
 
-#[cfg(all(feature = "http1", feature = "ffi"))]
+#[cfg(feature = "http1")]
 impl OriginalHeaderOrder {
-    pub(crate) fn default() -> Self {
-        OriginalHeaderOrder {
-            num_entries: HashMap::new(),
-            entry_order: Vec::new(),
-        }
+    /// Creates an empty `OriginalHeaderOrder`.
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    pub(crate) fn insert(&mut self, name: HeaderName) {
+    /// Records a header with this name, if there isn't one yet.
+    ///
+    /// This matches `HeaderMap::insert` replacing the values of a name: the
+    /// replacement keeps the position of the first value.
+    pub fn insert(&mut self, name: HeaderName) {
         if !self.num_entries.contains_key(&name) {
             let idx = 0;
             self.num_entries.insert(name.clone(), 1);
@@ -165,7 +274,9 @@ impl OriginalHeaderOrder {
         // header name encountered
     }
 
-    pub(crate) fn append<N>(&mut self, name: N)
+    /// Records a header with this name after the others, as the next value
+    /// of that name.
+    pub fn append<N>(&mut self, name: N)
     where
         N: IntoHeaderName + Into<HeaderName> + Clone,
     {
@@ -181,47 +292,16 @@ impl OriginalHeaderOrder {
         self.entry_order.push((name, idx));
     }
 
-    // No doc test is run here because `RUSTFLAGS='--cfg hyper_unstable_ffi'`
-    // is needed to compile. Once ffi is stablized `no_run` should be removed
-    // here.
-    /// This returns an iterator that provides header names and indexes
-    /// in the original order received.
-    ///
-    /// # Examples
-    /// ```no_run
-    /// use hyper::ext::OriginalHeaderOrder;
-    /// use hyper::header::{HeaderName, HeaderValue, HeaderMap};
-    ///
-    /// let mut h_order = OriginalHeaderOrder::default();
-    /// let mut h_map = Headermap::new();
-    ///
-    /// let name1 = b"Set-CookiE";
-    /// let value1 = b"a=b";
-    /// h_map.append(name1);
-    /// h_order.append(name1);
-    ///
-    /// let name2 = b"Content-Encoding";
-    /// let value2 = b"gzip";
-    /// h_map.append(name2, value2);
-    /// h_order.append(name2);
-    ///
-    /// let name3 = b"SET-COOKIE";
-    /// let value3 = b"c=d";
-    /// h_map.append(name3, value3);
-    /// h_order.append(name3)
-    ///
-    /// let mut iter = h_order.get_in_order()
-    ///
-    /// let (name, idx) = iter.next();
-    /// assert_eq!(b"a=b", h_map.get_all(name).nth(idx).unwrap());
-    ///
-    /// let (name, idx) = iter.next();
-    /// assert_eq!(b"gzip", h_map.get_all(name).nth(idx).unwrap());
-    ///
-    /// let (name, idx) = iter.next();
-    /// assert_eq!(b"c=d", h_map.get_all(name).nth(idx).unwrap());
-    /// ```
-    pub(crate) fn get_in_order(&self) -> impl Iterator<Item = &(HeaderName, usize)> {
+    /// Returns an iterator over the headers in the order they were
+    /// recorded, as a name and the index of the value among those of that
+    /// name.
+    pub fn get_in_order(&self) -> impl Iterator<Item = &(HeaderName, usize)> {
         self.entry_order.iter()
     }
+
+    /// Returns how many headers with this name were recorded.
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn count(&self, name: &HeaderName) -> usize {
+        self.num_entries.get(name).copied().unwrap_or(0)
+    }
 }
@@ -0,0 +1,303 @@
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+
+/// The chunk extensions of a chunk in an HTTP/1 chunked body.
+///
+/// Chunk extensions are the `;name=value` pairs that may follow the size of
+/// each chunk in a body using `Transfer-Encoding: chunked`:
+///
+/// ```text
+/// 5;sig=d7a8fbb3;offset=0\r\n
+/// hello\r\n
+/// ```
+///
+/// A `ChunkExtensions` holds everything after the first `;` of the chunk
+/// size line, up to (but not including) the line ending.
+///
+/// # Receiving
+///
+/// Chunk extensions are ignored by default. If the option
+/// `http1_chunk_extensions` was enabled on the connection, the extensions of
+/// the last chunk returned from [`Recv::poll_data`] are available from
+/// [`Recv::chunk_extensions`].
+///
+/// # Sending
+///
+/// Extensions are written with the chunks of an outgoing body through an
+/// [`OutgoingChunkExtensions`] in the extensions of its message, on a
+/// connection with the option `http1_chunk_extensions` enabled.
+///
+/// [`Recv::poll_data`]: crate::body::HttpBody::poll_data
+/// [`Recv::chunk_extensions`]: crate::Recv::chunk_extensions
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ChunkExtensions(Bytes);
+
+impl ChunkExtensions {
+    /// Gets the chunk extensions as bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Converts a static byte slice to chunk extensions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bytes include a control character, such as CR or LF.
+    pub fn from_static(ext: &'static [u8]) -> Self {
+        if find_invalid_byte(ext).is_some() {
+            panic!("invalid byte in static chunk extensions");
+        }
+        Self(Bytes::from_static(ext))
+    }
+
+    /// Returns an iterator over the `name` and optional `value` of each
+    /// extension.
+    ///
+    /// Whitespace around names and values is trimmed. Quoted values are
+    /// returned as-is, including the quotes.
+    pub fn iter(&self) -> ChunkExtensionsIter<'_> {
+        ChunkExtensionsIter { rest: &self.0 }
+    }
+
+    pub(crate) fn from_bytes_unchecked(ext: Bytes) -> Self {
+        Self(ext)
+    }
+}
+
+impl TryFrom<&[u8]> for ChunkExtensions {
+    type Error = InvalidChunkExtensions;
+
+    fn try_from(ext: &[u8]) -> Result<Self, Self::Error> {
+        if let Some(bad_byte) = find_invalid_byte(ext) {
+            Err(InvalidChunkExtensions { bad_byte })
+        } else {
+            Ok(Self(Bytes::copy_from_slice(ext)))
+        }
+    }
+}
+
+impl TryFrom<Vec<u8>> for ChunkExtensions {
+    type Error = InvalidChunkExtensions;
+
+    fn try_from(ext: Vec<u8>) -> Result<Self, Self::Error> {
+        if let Some(bad_byte) = find_invalid_byte(&ext) {
+            Err(InvalidChunkExtensions { bad_byte })
+        } else {
+            Ok(Self(Bytes::from(ext)))
+        }
+    }
+}
+
+impl TryFrom<String> for ChunkExtensions {
+    type Error = InvalidChunkExtensions;
+
+    fn try_from(ext: String) -> Result<Self, Self::Error> {
+        if let Some(bad_byte) = find_invalid_byte(ext.as_bytes()) {
+            Err(InvalidChunkExtensions { bad_byte })
+        } else {
+            Ok(Self(Bytes::from(ext)))
+        }
+    }
+}
+
+impl TryFrom<Bytes> for ChunkExtensions {
+    type Error = InvalidChunkExtensions;
+
+    fn try_from(ext: Bytes) -> Result<Self, Self::Error> {
+        if let Some(bad_byte) = find_invalid_byte(&ext) {
+            Err(InvalidChunkExtensions { bad_byte })
+        } else {
+            Ok(Self(ext))
+        }
+    }
+}
+
+impl AsRef<[u8]> for ChunkExtensions {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Sets the chunk extensions of the chunks of an outgoing HTTP/1 body.
+///
+/// When an `OutgoingChunkExtensions` is present in the extensions of an
+/// outgoing `http::Request` or `http::Response`, each time data is polled
+/// from its body, the extensions last given to [`set`] are taken and written
+/// with that chunk. The body should call `set` from `poll_data`, right before
+/// returning the data, with a clone of the handle it shares with the message.
+///
+/// Extensions are only written on connections with the option
+/// `http1_chunk_extensions` enabled, for bodies sent with
+/// `Transfer-Encoding: chunked`. They are ignored otherwise.
+///
+/// A proxy can forward the extensions of a received body by setting those
+/// from [`Recv::chunk_extensions`] after polling each chunk of it.
+///
+/// [`set`]: OutgoingChunkExtensions::set
+/// [`Recv::chunk_extensions`]: crate::Recv::chunk_extensions
+#[derive(Clone, Debug, Default)]
+pub struct OutgoingChunkExtensions {
+    next: Arc<Mutex<Option<ChunkExtensions>>>,
+}
+
+impl OutgoingChunkExtensions {
+    /// Creates a handle without extensions set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the extensions to write with the next chunk of the body.
+    pub fn set(&self, ext: ChunkExtensions) {
+        *self.next.lock().unwrap() = Some(ext);
+    }
+
+    /// Takes the extensions to write with the chunk just polled, if any.
+    pub(crate) fn take(&self) -> Option<ChunkExtensions> {
+        self.next.lock().unwrap().take()
+    }
+}
+
+/// An iterator over the extensions in a [`ChunkExtensions`].
+#[derive(Debug)]
+pub struct ChunkExtensionsIter<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Iterator for ChunkExtensionsIter<'a> {
+    type Item = (&'a [u8], Option<&'a [u8]>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.rest.is_empty() {
+                return None;
+            }
+
+            // find the end of this extension, skipping over quoted strings
+            let mut in_quotes = false;
+            let mut escaped = false;
+            let mut end = self.rest.len();
+            for (i, &b) in self.rest.iter().enumerate() {
+                if escaped {
+                    escaped = false;
+                } else if in_quotes && b == b'\\' {
+                    escaped = true;
+                } else if b == b'"' {
+                    in_quotes = !in_quotes;
+                } else if b == b';' && !in_quotes {
+                    end = i;
+                    break;
+                }
+            }
+
+            let ext = &self.rest[..end];
+            self.rest = if end < self.rest.len() {
+                &self.rest[end + 1..]
+            } else {
+                &[]
+            };
+
+            let (name, value) = match ext.iter().position(|&b| b == b'=') {
+                Some(eq) => (&ext[..eq], Some(trim(&ext[eq + 1..]))),
+                None => (ext, None),
+            };
+            let name = trim(name);
+            if name.is_empty() {
+                continue;
+            }
+            return Some((name, value));
+        }
+    }
+}
+
+fn trim(mut bytes: &[u8]) -> &[u8] {
+    while let [b' ' | b'\t', rest @ ..] = bytes {
+        bytes = rest;
+    }
+    while let [rest @ .., b' ' | b'\t'] = bytes {
+        bytes = rest;
+    }
+    bytes
+}
+
+/// Error indicating an invalid byte when constructing `ChunkExtensions`.
+#[derive(Debug)]
+pub struct InvalidChunkExtensions {
+    bad_byte: u8,
+}
+
+impl std::fmt::Display for InvalidChunkExtensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid byte in chunk extensions: {}", self.bad_byte)
+    }
+}
+
+impl std::error::Error for InvalidChunkExtensions {}
+
+const fn is_valid_byte(b: u8) -> bool {
+    // Anything but control characters, which notably includes CR and LF.
+    b == b'\t' || (b >= 0x20 && b != 0x7F)
+}
+
+const fn find_invalid_byte(bytes: &[u8]) -> Option<u8> {
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if !is_valid_byte(b) {
+            return Some(b);
+        }
+        i += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iter_name_and_values() {
+        let ext = ChunkExtensions::from_static(b"sig=abc; offset = 10;last");
+        let items = ext.iter().collect::<Vec<_>>();
+        assert_eq!(
+            items,
+            vec![
+                (&b"sig"[..], Some(&b"abc"[..])),
+                (&b"offset"[..], Some(&b"10"[..])),
+                (&b"last"[..], None),
+            ]
+        );
+    }
+
+    #[test]
+    fn iter_quoted_value() {
+        let ext = ChunkExtensions::from_static(b"name=\"a;b\\\"c\";other");
+        let items = ext.iter().collect::<Vec<_>>();
+        assert_eq!(
+            items,
+            vec![
+                (&b"name"[..], Some(&b"\"a;b\\\"c\""[..])),
+                (&b"other"[..], None),
+            ]
+        );
+    }
+
+    #[test]
+    fn iter_skips_empty() {
+        let ext = ChunkExtensions::from_static(b";;foo;");
+        let items = ext.iter().collect::<Vec<_>>();
+        assert_eq!(items, vec![(&b"foo"[..], None)]);
+    }
+
+    #[test]
+    fn newline_invalid() {
+        assert!(ChunkExtensions::try_from(&b"foo\r\n0\r\n\r\n"[..]).is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn newline_invalid_panic() {
+        ChunkExtensions::from_static(b"foo\nbar");
+    }
+}
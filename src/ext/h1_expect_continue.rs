@@ -0,0 +1,140 @@
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_util::task::AtomicWaker;
+
+const PENDING: usize = 0;
+const ACCEPTED: usize = 1;
+const REFUSED: usize = 2;
+const DROPPED: usize = 3;
+
+/// The decision of a service on the `Expect: 100-continue` of an HTTP/1
+/// request.
+///
+/// When [`Http::http1_manual_continue`] is enabled, a request that expects
+/// `100 Continue` before sending its body has an `ExpectContinue` in its
+/// extensions, and hyper doesn't send `100 Continue` by itself. The service
+/// can first check the request head, and then either [`accept`] the body or
+/// [`refuse`] it, for example by responding `413 Payload Too Large` without
+/// the client ever sending the body.
+///
+/// Reading the body waits for the service to accept it. If the service
+/// responds, or drops the body, before accepting it, the body is refused.
+///
+/// If the `ExpectContinue` is dropped without a decision, `100 Continue` is
+/// sent once the body is read, as if manual continue was disabled.
+///
+/// ```
+/// # #[cfg(all(feature = "server", feature = "http1"))]
+/// # fn decide(req: &mut hyper::Request<hyper::Recv>, authorized: bool) {
+/// use hyper::ext::ExpectContinue;
+///
+/// if let Some(expect) = req.extensions_mut().remove::<ExpectContinue>() {
+///     if authorized {
+///         expect.accept();
+///     } else {
+///         expect.refuse();
+///     }
+/// }
+/// # }
+/// ```
+///
+/// [`Http::http1_manual_continue`]: crate::server::conn::Http::http1_manual_continue
+/// [`accept`]: ExpectContinue::accept
+/// [`refuse`]: ExpectContinue::refuse
+pub struct ExpectContinue {
+    shared: Arc<Shared>,
+}
+
+/// The connection side of an `ExpectContinue`.
+pub(crate) struct ContinueDecision {
+    shared: Arc<Shared>,
+}
+
+/// A decision taken on an `ExpectContinue`.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Decided {
+    Accepted,
+    Refused,
+    /// The `ExpectContinue` was dropped without a decision.
+    Dropped,
+}
+
+struct Shared {
+    state: AtomicUsize,
+    waker: AtomicWaker,
+}
+
+impl ExpectContinue {
+    pub(crate) fn new() -> (ExpectContinue, ContinueDecision) {
+        let shared = Arc::new(Shared {
+            state: AtomicUsize::new(PENDING),
+            waker: AtomicWaker::new(),
+        });
+        (
+            ExpectContinue {
+                shared: shared.clone(),
+            },
+            ContinueDecision { shared },
+        )
+    }
+
+    /// Accepts the request body, sending `100 Continue` to the client.
+    pub fn accept(self) {
+        self.decide(ACCEPTED);
+    }
+
+    /// Refuses the request body.
+    ///
+    /// `100 Continue` isn't sent, and the body of the request ends without
+    /// any data. Once the final response is written, the connection is
+    /// closed or the body drained, as set by
+    /// [`Http::http1_refused_body`](crate::server::conn::Http::http1_refused_body).
+    pub fn refuse(self) {
+        self.decide(REFUSED);
+    }
+
+    fn decide(&self, decision: usize) {
+        if self
+            .shared
+            .state
+            .compare_exchange(PENDING, decision, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            self.shared.waker.wake();
+        }
+    }
+}
+
+impl Drop for ExpectContinue {
+    fn drop(&mut self) {
+        self.decide(DROPPED);
+    }
+}
+
+impl fmt::Debug for ExpectContinue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExpectContinue").finish()
+    }
+}
+
+impl ContinueDecision {
+    /// Polls for the decision of the service.
+    pub(crate) fn poll_decided(&self, cx: &mut Context<'_>) -> Poll<Decided> {
+        self.shared.waker.register(cx.waker());
+        match self.shared.state.load(Ordering::SeqCst) {
+            PENDING => Poll::Pending,
+            ACCEPTED => Poll::Ready(Decided::Accepted),
+            REFUSED => Poll::Ready(Decided::Refused),
+            _ => Poll::Ready(Decided::Dropped),
+        }
+    }
+}
+
+impl fmt::Debug for ContinueDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ContinueDecision").finish()
+    }
+}
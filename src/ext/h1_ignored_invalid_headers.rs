@@ -0,0 +1,33 @@
+/// The number of invalid header lines ignored in an HTTP/1 response.
+///
+/// When a connection is built with
+/// [`http1_ignore_invalid_headers_in_responses`] enabled, invalid header
+/// lines of a response are dropped instead of failing it. An
+/// `IgnoredInvalidHeaders` is then present in the extensions of the
+/// `http::Response` if any line was dropped.
+///
+/// ```no_run
+/// # #[cfg(all(feature = "client", feature = "http1"))]
+/// # fn check(res: &hyper::Response<hyper::Recv>) {
+/// use hyper::ext::IgnoredInvalidHeaders;
+///
+/// if let Some(ignored) = res.extensions().get::<IgnoredInvalidHeaders>() {
+///     eprintln!("ignored {} invalid header lines", ignored.count());
+/// }
+/// # }
+/// ```
+///
+/// [`http1_ignore_invalid_headers_in_responses`]: crate::client::conn::http1::Builder::http1_ignore_invalid_headers_in_responses
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IgnoredInvalidHeaders(usize);
+
+impl IgnoredInvalidHeaders {
+    pub(crate) fn new(count: usize) -> Self {
+        IgnoredInvalidHeaders(count)
+    }
+
+    /// Returns the number of header lines that were ignored.
+    pub fn count(&self) -> usize {
+        self.0
+    }
+}
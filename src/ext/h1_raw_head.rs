@@ -0,0 +1,105 @@
+use bytes::Bytes;
+
+/// The raw bytes of the head of an HTTP/1 message, as they were received.
+///
+/// If a server has its option [`http1_preserve_raw_head`][server] set, a
+/// `RawHead` is present in the extensions of each `http::Request` it
+/// receives, and likewise for the `http::Response`s received on a client
+/// connection with its option [`http1_preserve_raw_head`] set. It
+/// holds the start line and the header lines exactly as the peer sent them,
+/// before any normalization into the `Uri` or `HeaderMap`, for uses such as
+/// verifying signatures computed over them.
+///
+/// The bytes are shared with the read buffer, so they are not copied unless
+/// obsolete line folding is unfolded in responses.
+///
+/// ```no_run
+/// # #[cfg(all(feature = "server", feature = "http1"))]
+/// # fn check(req: &hyper::Request<hyper::Recv>) {
+/// use hyper::ext::RawHead;
+///
+/// if let Some(raw) = req.extensions().get::<RawHead>() {
+///     println!("request line: {:?}", raw.line());
+///     println!("header block: {:?}", raw.headers());
+/// }
+/// # }
+/// ```
+///
+/// [`http1_preserve_raw_head`]: crate::client::conn::http1::Builder::http1_preserve_raw_head
+/// [server]: crate::server::conn::Http::http1_preserve_raw_head
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawHead {
+    line: Bytes,
+    headers: Bytes,
+}
+
+impl RawHead {
+    /// Splits the bytes of a head, up to and including the empty line
+    /// ending it.
+    pub(crate) fn new(mut head: Bytes) -> Self {
+        // Empty lines may precede the start line.
+        let start = head
+            .iter()
+            .position(|&b| b != b'\r' && b != b'\n')
+            .unwrap_or(head.len());
+        let _ = head.split_to(start);
+
+        let line_len = head
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(head.len(), |i| i + 1);
+        let mut line = head.split_to(line_len);
+        line.truncate(trim_line_ending(&line));
+
+        // What remains are the header lines, then the empty line.
+        let mut headers = head;
+        let mut end = headers.len();
+        if end > 0 && headers[end - 1] == b'\n' {
+            end -= 1;
+            if end > 0 && headers[end - 1] == b'\r' {
+                end -= 1;
+            }
+        }
+        headers.truncate(end);
+
+        RawHead { line, headers }
+    }
+
+    /// Returns the request line or status line, without its line ending.
+    pub fn line(&self) -> &Bytes {
+        &self.line
+    }
+
+    /// Returns the header lines, each with its line ending, without the
+    /// empty line ending the head.
+    pub fn headers(&self) -> &Bytes {
+        &self.headers
+    }
+}
+
+fn trim_line_ending(line: &[u8]) -> usize {
+    match line {
+        [.., b'\r', b'\n'] => line.len() - 2,
+        [.., b'\n'] => line.len() - 1,
+        _ => line.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_headers() {
+        let raw = RawHead::new(Bytes::from_static(b"GET / HTTP/1.1\r\n\r\n"));
+        assert_eq!(raw.line(), "GET / HTTP/1.1");
+        assert_eq!(raw.headers(), "");
+    }
+
+    #[test]
+    fn bare_line_feeds() {
+        let raw = RawHead::new(Bytes::from_static(b"\nGET / HTTP/1.1\nHost: a\r\nX: b\n\n"));
+        assert_eq!(raw.line(), "GET / HTTP/1.1");
+        assert_eq!(raw.headers(), "Host: a\r\nX: b\n");
+    }
+}
@@ -0,0 +1,239 @@
+#[cfg(all(feature = "server", feature = "http1"))]
+use std::collections::VecDeque;
+#[cfg(feature = "client")]
+use std::fmt;
+#[cfg(any(feature = "client", all(feature = "server", feature = "http1")))]
+use std::sync::Mutex;
+#[cfg(all(feature = "server", feature = "http1"))]
+use std::sync::{Arc, Weak};
+#[cfg(all(feature = "server", feature = "http1"))]
+use std::task::Waker;
+
+use http::Response;
+#[cfg(feature = "server")]
+use http::StatusCode;
+
+#[cfg(all(feature = "server", feature = "http1"))]
+use crate::common::{task, Poll};
+
+/// The most informational responses of a request waiting to be written.
+#[cfg(all(feature = "server", feature = "http1"))]
+const MAX_PENDING: usize = 16;
+
+/// Sends informational (`1xx`) responses to a request, before its final
+/// response.
+///
+/// A server puts an `InformationalSender` in the extensions of every
+/// `http::Request` it passes to a service. The service can use it to send,
+/// for example, a `103 Early Hints` with the `Link` headers of resources the
+/// client may start to preload, while the final response is prepared.
+///
+/// ```
+/// # #[cfg(all(feature = "server", any(feature = "http1", feature = "http2")))]
+/// # fn handle(req: &hyper::Request<hyper::Recv>) {
+/// use hyper::ext::InformationalSender;
+/// use hyper::{Response, StatusCode};
+///
+/// if let Some(informational) = req.extensions().get::<InformationalSender>() {
+///     let hints = Response::builder()
+///         .status(StatusCode::from_u16(103).unwrap())
+///         .header("link", "</style.css>; rel=preload; as=style")
+///         .body(())
+///         .unwrap();
+///     // The client is still sent the final response if this fails.
+///     let _ = informational.send(hints);
+/// }
+/// # }
+/// ```
+///
+/// Informational responses are only written for HTTP/1.1 requests, as an
+/// HTTP/1.0 client doesn't expect them. They are not supported on HTTP/2
+/// connections, as the `h2` version hyper depends on can't send them, so
+/// `send` returns an error there.
+#[cfg(feature = "server")]
+#[derive(Clone, Debug)]
+pub struct InformationalSender {
+    /// The queue of the connection, and the request this sender is for.
+    #[cfg(feature = "http1")]
+    queue: Option<(Weak<Mutex<Queue>>, u64)>,
+}
+
+/// The informational responses of the request in flight on a connection.
+#[cfg(all(feature = "server", feature = "http1"))]
+#[derive(Debug)]
+struct Queue {
+    /// The request in flight, the senders of other requests are closed.
+    request: u64,
+    responses: VecDeque<Response<()>>,
+    waker: Option<Waker>,
+}
+
+/// Receives the informational responses of the requests of a connection,
+/// with one queue reused by every request.
+#[cfg(all(feature = "server", feature = "http1"))]
+pub(crate) struct InformationalReceiver {
+    queue: Arc<Mutex<Queue>>,
+}
+
+#[cfg(feature = "server")]
+impl InformationalSender {
+    /// Creates a sender for a connection that can't send informational
+    /// responses.
+    #[cfg(feature = "http2")]
+    pub(crate) fn unsupported() -> InformationalSender {
+        InformationalSender {
+            #[cfg(feature = "http1")]
+            queue: None,
+        }
+    }
+
+    /// Sends an informational response.
+    ///
+    /// The version and extensions of `response` are ignored.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the status of `response` isn't a `1xx`
+    /// status that can be sent by a service, or if the connection doesn't
+    /// support informational responses. `100 Continue` is sent by hyper
+    /// itself when the request body is read, and `101 Switching Protocols`
+    /// by responding to an upgrade.
+    ///
+    /// It also returns an error if too many informational responses are
+    /// waiting to be written, if the final response was already sent, or the
+    /// connection closed.
+    pub fn send(&self, response: Response<()>) -> crate::Result<()> {
+        let status = response.status();
+        if !status.is_informational()
+            || status == StatusCode::CONTINUE
+            || status == StatusCode::SWITCHING_PROTOCOLS
+        {
+            return Err(crate::Error::new_user_unsupported_informational());
+        }
+
+        #[cfg(feature = "http1")]
+        if let Some((ref queue, request)) = self.queue {
+            let queue = queue.upgrade().ok_or_else(crate::Error::new_closed)?;
+            let mut queue = queue.lock().unwrap();
+            if queue.request != request {
+                return Err(crate::Error::new_closed());
+            }
+            if queue.responses.len() >= MAX_PENDING {
+                return Err(crate::Error::new_user_too_many_informational());
+            }
+            queue.responses.push_back(response);
+            if let Some(waker) = queue.waker.take() {
+                waker.wake();
+            }
+            return Ok(());
+        }
+
+        Err(crate::Error::new_user_unsupported_informational())
+    }
+}
+
+#[cfg(all(feature = "server", feature = "http1"))]
+impl InformationalReceiver {
+    pub(crate) fn new() -> InformationalReceiver {
+        InformationalReceiver {
+            queue: Arc::new(Mutex::new(Queue {
+                request: 0,
+                responses: VecDeque::new(),
+                waker: None,
+            })),
+        }
+    }
+
+    /// Returns the sender of a new request, closing the senders of the
+    /// previous one.
+    pub(crate) fn sender(&mut self) -> InformationalSender {
+        self.close();
+        let request = self.queue.lock().unwrap().request;
+        InformationalSender {
+            queue: Some((Arc::downgrade(&self.queue), request)),
+        }
+    }
+
+    /// Closes the senders of the request in flight, once its final response
+    /// is ready.
+    pub(crate) fn close(&mut self) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.request += 1;
+        queue.responses.clear();
+        queue.waker = None;
+    }
+
+    pub(crate) fn poll_next(&mut self, cx: &mut task::Context<'_>) -> Poll<Response<()>> {
+        let mut queue = self.queue.lock().unwrap();
+        match queue.responses.pop_front() {
+            Some(response) => Poll::Ready(response),
+            None => {
+                queue.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// A callback receiving the informational (`1xx`) responses to a request,
+/// before its final response.
+///
+/// When an `OnInformational` is in the extensions of an `http::Request` sent
+/// by a client, it is called with each informational response received for
+/// that request, such as a `102 Processing` or a `103 Early Hints`.
+///
+/// ```
+/// # #[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+/// # fn set(req: &mut hyper::Request<hyper::Recv>) {
+/// use hyper::ext::OnInformational;
+///
+/// req.extensions_mut().insert(OnInformational::new(|res| {
+///     for link in res.headers().get_all("link") {
+///         println!("{} hints at {:?}", res.status(), link);
+///     }
+/// }));
+/// # }
+/// ```
+///
+/// Informational responses are only reported on HTTP/1 connections. On
+/// HTTP/2 connections, the `h2` version hyper depends on discards them before
+/// they reach hyper, so the callback is never called.
+#[cfg(feature = "client")]
+pub struct OnInformational {
+    // Only used from the connection task, the Mutex just makes it Sync.
+    #[cfg_attr(not(feature = "http1"), allow(dead_code))]
+    callback: Mutex<Box<dyn FnMut(Response<()>) + Send>>,
+}
+
+#[cfg(feature = "client")]
+impl OnInformational {
+    /// Creates an `OnInformational` calling `callback` with each
+    /// informational response.
+    ///
+    /// The callback is called from within the connection task, and so should
+    /// return quickly.
+    pub fn new<F>(callback: F) -> OnInformational
+    where
+        F: FnMut(Response<()>) + Send + 'static,
+    {
+        OnInformational {
+            callback: Mutex::new(Box::new(callback)),
+        }
+    }
+
+    #[cfg(feature = "http1")]
+    pub(crate) fn call(&mut self, response: Response<()>) {
+        let callback = self
+            .callback
+            .get_mut()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        callback(response);
+    }
+}
+
+#[cfg(feature = "client")]
+impl fmt::Debug for OnInformational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnInformational").finish()
+    }
+}
@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+/// Timeouts on the response to a client request.
+///
+/// A connection built by [`http1::Builder`] or [`http2::Builder`] applies
+/// the timeouts set on the builder to every request. Putting a
+/// `RequestTimeouts` in the extensions of an `http::Request` overrides them
+/// for that request: the timeouts it sets replace those of the connection,
+/// while those it leaves unset are kept.
+///
+/// ```
+/// # #[cfg(all(feature = "client", feature = "runtime"))]
+/// # fn set(req: &mut hyper::Request<hyper::Recv>) {
+/// use std::time::Duration;
+/// use hyper::ext::RequestTimeouts;
+///
+/// let mut timeouts = RequestTimeouts::new();
+/// timeouts
+///     .response_head(Duration::from_secs(5))
+///     .total(None);
+/// req.extensions_mut().insert(timeouts);
+/// # }
+/// ```
+///
+/// When a timeout expires before the response head is received, the request
+/// fails with an error for which [`Error::is_timeout`] returns `true`. When
+/// it expires while receiving the response body, the body yields that error
+/// instead. Either way, the request is canceled: an HTTP/1 connection is
+/// closed, and an HTTP/2 stream is reset with `CANCEL`.
+///
+/// [`http1::Builder`]: crate::client::conn::http1::Builder
+/// [`http2::Builder`]: crate::client::conn::http2::Builder
+/// [`Error::is_timeout`]: crate::Error::is_timeout
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RequestTimeouts {
+    response_head: Option<Option<Duration>>,
+    response_body_idle: Option<Option<Duration>>,
+    total: Option<Option<Duration>>,
+}
+
+impl RequestTimeouts {
+    /// Creates a `RequestTimeouts` leaving every timeout unset.
+    pub fn new() -> RequestTimeouts {
+        RequestTimeouts::default()
+    }
+
+    /// Sets how long to wait for the response head, from when the request
+    /// starts being sent.
+    ///
+    /// Pass `None` to disable this timeout.
+    pub fn response_head(&mut self, timeout: impl Into<Option<Duration>>) -> &mut Self {
+        self.response_head = Some(timeout.into());
+        self
+    }
+
+    /// Sets how long to wait for each piece of data of the response body.
+    ///
+    /// The timeout doesn't run while the body waits for the user to read
+    /// it.
+    ///
+    /// Pass `None` to disable this timeout.
+    pub fn response_body_idle(&mut self, timeout: impl Into<Option<Duration>>) -> &mut Self {
+        self.response_body_idle = Some(timeout.into());
+        self
+    }
+
+    /// Sets a deadline for the whole response, head and body, from when the
+    /// request starts being sent.
+    ///
+    /// Pass `None` to disable this timeout.
+    pub fn total(&mut self, timeout: impl Into<Option<Duration>>) -> &mut Self {
+        self.total = Some(timeout.into());
+        self
+    }
+
+    /// Returns these timeouts, completed by `defaults` where unset.
+    pub(crate) fn or(self, defaults: RequestTimeouts) -> RequestTimeouts {
+        RequestTimeouts {
+            response_head: self.response_head.or(defaults.response_head),
+            response_body_idle: self.response_body_idle.or(defaults.response_body_idle),
+            total: self.total.or(defaults.total),
+        }
+    }
+
+    pub(crate) fn response_head_timeout(&self) -> Option<Duration> {
+        self.response_head.flatten()
+    }
+
+    pub(crate) fn response_body_idle_timeout(&self) -> Option<Duration> {
+        self.response_body_idle.flatten()
+    }
+
+    pub(crate) fn total_timeout(&self) -> Option<Duration> {
+        self.total.flatten()
+    }
+}
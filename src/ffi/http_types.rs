@@ -27,7 +27,7 @@ pub struct hyper_headers {
 
 pub(crate) struct RawHeaders(pub(crate) hyper_buf);
 
-pub(crate) struct OnInformational {
+struct OnInformational {
     func: hyper_request_on_informational_callback,
     data: UserDataPointer,
 }
@@ -216,12 +216,14 @@ ffi_fn! {
     /// be valid after the callback finishes. You must copy any data you wish
     /// to persist.
     fn hyper_request_on_informational(req: *mut hyper_request, callback: hyper_request_on_informational_callback, data: *mut c_void) -> hyper_code {
-        let ext = OnInformational {
+        let mut ext = OnInformational {
             func: callback,
             data: UserDataPointer(data),
         };
         let req = non_null!(&mut *req ?= hyper_code::HYPERE_INVALID_ARG);
-        req.0.extensions_mut().insert(ext);
+        req.0.extensions_mut().insert(crate::ext::OnInformational::new(move |resp| {
+            ext.call(resp.map(|()| Recv::empty()))
+        }));
         hyper_code::HYPERE_OK
     }
 }
@@ -532,7 +534,7 @@ unsafe fn raw_name_value(
 // ===== impl OnInformational =====
 
 impl OnInformational {
-    pub(crate) fn call(&mut self, resp: Response<Recv>) {
+    fn call(&mut self, resp: Response<Recv>) {
         let mut resp = hyper_response::wrap(resp);
         (self.func)(self.data.0, &mut resp);
     }
@@ -28,6 +28,49 @@ fn connection_has(value: &HeaderValue, needle: &str) -> bool {
     false
 }
 
+#[cfg(feature = "http1")]
+pub(super) fn te_trailers(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(http::header::TE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case("trailers"))
+}
+
+#[cfg(all(feature = "http1", feature = "client", feature = "runtime"))]
+pub(super) fn expect_continue(headers: &HeaderMap) -> bool {
+    match headers.get(http::header::EXPECT) {
+        Some(value) => value.as_bytes().eq_ignore_ascii_case(b"100-continue"),
+        None => false,
+    }
+}
+
+// https://www.rfc-editor.org/rfc/rfc9110#section-6.5.1
+#[cfg(feature = "http1")]
+pub(super) fn is_valid_trailer_field(name: &http::HeaderName) -> bool {
+    use http::header::{
+        AUTHORIZATION, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_RANGE, CONTENT_TYPE, HOST,
+        MAX_FORWARDS, SET_COOKIE, TE, TRAILER, TRANSFER_ENCODING,
+    };
+
+    !matches!(
+        *name,
+        AUTHORIZATION
+            | CACHE_CONTROL
+            | CONTENT_ENCODING
+            | CONTENT_LENGTH
+            | CONTENT_RANGE
+            | CONTENT_TYPE
+            | HOST
+            | MAX_FORWARDS
+            | SET_COOKIE
+            | TRAILER
+            | TRANSFER_ENCODING
+            | TE
+    )
+}
+
 #[cfg(all(feature = "http1", feature = "server"))]
 pub(super) fn content_length_parse(value: &HeaderValue) -> Option<u64> {
     from_digits(value.as_bytes())
@@ -133,6 +176,18 @@ pub(super) fn is_chunked_(value: &HeaderValue) -> bool {
     false
 }
 
+#[cfg(all(feature = "http1", feature = "server"))]
+pub(super) fn is_chunked_not_last(value: &HeaderValue) -> bool {
+    // any encoding applied after chunked makes the length of the message
+    // ambiguous
+    if let Ok(s) = value.to_str() {
+        let mut encodings = s.rsplit(',').skip(1);
+        return encodings.any(|encoding| encoding.trim().eq_ignore_ascii_case("chunked"));
+    }
+
+    false
+}
+
 #[cfg(feature = "http1")]
 pub(super) fn add_chunked(mut entry: http::header::OccupiedEntry<'_, HeaderValue>) {
     const CHUNKED: &str = "chunked";
@@ -51,6 +51,8 @@
 //! - `server`: Enables the HTTP `server`.
 //! - `runtime`: Enables convenient integration with `tokio`, providing
 //!   connectors and acceptors for TCP, and a default executor.
+//! - `tcp`: Enables the `HttpConnector`, a TCP connector with DNS resolution
+//!   for the `Client`.
 //!
 //! [feature flags]: https://doc.rust-lang.org/cargo/reference/manifest.html#the-features-section
 
@@ -86,6 +88,7 @@ pub mod ffi;
 
 cfg_proto! {
     mod headers;
+    pub mod observe;
     mod proto;
 }
 
@@ -0,0 +1,237 @@
+//! Observing connections
+//!
+//! An [`Observer`](Observer) is notified of the events of a connection, such
+//! as the bytes read from and written to the transport, the messages
+//! received, or the pings answered by the peer. It can be used to collect
+//! metrics or traces, without wrapping the IO or the services.
+//!
+//! Observers are set with
+//! [`server::conn::Http::observer`](crate::server::conn::Http::observer) and
+//! the `observer` methods of the [`client::conn`](crate::client::conn)
+//! builders. The same observer is used by every connection created with
+//! those options.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Receives the events of connections.
+///
+/// Every method has a default implementation that does nothing, so an
+/// implementation only needs to override the events it is interested in.
+///
+/// The methods are called from within the connection task, and so should
+/// return quickly, and never block.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use hyper::observe::Observer;
+///
+/// #[derive(Default)]
+/// struct Metrics {
+///     bytes_read: AtomicUsize,
+///     requests: AtomicUsize,
+/// }
+///
+/// impl Observer for Metrics {
+///     fn on_read(&self, bytes: usize) {
+///         self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+///     }
+///
+///     fn on_head_parsed(&self) {
+///         self.requests.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+/// ```
+pub trait Observer: Send + Sync {
+    /// Called when bytes have been read from the transport.
+    fn on_read(&self, bytes: usize) {
+        let _ = bytes;
+    }
+
+    /// Called when bytes have been written to the transport.
+    fn on_write(&self, bytes: usize) {
+        let _ = bytes;
+    }
+
+    /// Called when the head of an incoming message has been received.
+    ///
+    /// For servers, this is a request, and for clients a final response.
+    fn on_head_parsed(&self) {}
+
+    /// Called when an incoming HTTP/1 message could not be parsed.
+    fn on_parse_error(&self, err: &crate::Error) {
+        let _ = err;
+    }
+
+    /// Called when data of an incoming body has been received.
+    fn on_body_received(&self, bytes: usize) {
+        let _ = bytes;
+    }
+
+    /// Called when data of an outgoing body has been sent.
+    fn on_body_sent(&self, bytes: usize) {
+        let _ = bytes;
+    }
+
+    /// Called when an incoming message, including its body, has been
+    /// received completely.
+    fn on_message_complete(&self) {}
+
+    /// Called when an HTTP/1 connection is reused for a new message, after a
+    /// previous one was completed with keep-alive.
+    fn on_keep_alive_reuse(&self) {}
+
+    /// Called with the round-trip time of an HTTP/2 ping.
+    ///
+    /// Pings are only sent when keep-alive or the adaptive window are
+    /// enabled.
+    fn on_ping_rtt(&self, rtt: Duration) {
+        let _ = rtt;
+    }
+
+    /// Called when a GOAWAY frame carrying the error code `reason` has been
+    /// received from the HTTP/2 peer.
+    ///
+    /// A GOAWAY is reported when it fails the connection or one of its
+    /// requests, including a graceful one with the `NO_ERROR` code that
+    /// refuses requests above its last stream id. A graceful GOAWAY that
+    /// lets every open stream complete is not reported.
+    fn on_goaway_received(&self, reason: u32) {
+        let _ = reason;
+    }
+}
+
+impl<O: Observer + ?Sized> Observer for Arc<O> {
+    fn on_read(&self, bytes: usize) {
+        (**self).on_read(bytes)
+    }
+
+    fn on_write(&self, bytes: usize) {
+        (**self).on_write(bytes)
+    }
+
+    fn on_head_parsed(&self) {
+        (**self).on_head_parsed()
+    }
+
+    fn on_parse_error(&self, err: &crate::Error) {
+        (**self).on_parse_error(err)
+    }
+
+    fn on_body_received(&self, bytes: usize) {
+        (**self).on_body_received(bytes)
+    }
+
+    fn on_body_sent(&self, bytes: usize) {
+        (**self).on_body_sent(bytes)
+    }
+
+    fn on_message_complete(&self) {
+        (**self).on_message_complete()
+    }
+
+    fn on_keep_alive_reuse(&self) {
+        (**self).on_keep_alive_reuse()
+    }
+
+    fn on_ping_rtt(&self, rtt: Duration) {
+        (**self).on_ping_rtt(rtt)
+    }
+
+    fn on_goaway_received(&self, reason: u32) {
+        (**self).on_goaway_received(reason)
+    }
+}
+
+/// The observer of a connection, if any was configured.
+///
+/// The observer is boxed so that this is a thin pointer, as it is kept in
+/// every HTTP/2 body.
+#[derive(Clone, Default)]
+pub(crate) struct ObserverRef(Option<Arc<Box<dyn Observer>>>);
+
+impl ObserverRef {
+    pub(crate) fn new<O: Observer + 'static>(observer: O) -> ObserverRef {
+        ObserverRef(Some(Arc::new(Box::new(observer))))
+    }
+
+    #[cfg(feature = "http2")]
+    pub(crate) fn is_some(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub(crate) fn on_read(&self, bytes: usize) {
+        if let Some(ref observer) = self.0 {
+            observer.on_read(bytes);
+        }
+    }
+
+    pub(crate) fn on_write(&self, bytes: usize) {
+        if let Some(ref observer) = self.0 {
+            observer.on_write(bytes);
+        }
+    }
+
+    pub(crate) fn on_head_parsed(&self) {
+        if let Some(ref observer) = self.0 {
+            observer.on_head_parsed();
+        }
+    }
+
+    #[cfg(feature = "http1")]
+    pub(crate) fn on_parse_error(&self, err: &crate::Error) {
+        if let Some(ref observer) = self.0 {
+            observer.on_parse_error(err);
+        }
+    }
+
+    pub(crate) fn on_body_received(&self, bytes: usize) {
+        if let Some(ref observer) = self.0 {
+            observer.on_body_received(bytes);
+        }
+    }
+
+    pub(crate) fn on_body_sent(&self, bytes: usize) {
+        if let Some(ref observer) = self.0 {
+            observer.on_body_sent(bytes);
+        }
+    }
+
+    pub(crate) fn on_message_complete(&self) {
+        if let Some(ref observer) = self.0 {
+            observer.on_message_complete();
+        }
+    }
+
+    #[cfg(feature = "http1")]
+    pub(crate) fn on_keep_alive_reuse(&self) {
+        if let Some(ref observer) = self.0 {
+            observer.on_keep_alive_reuse();
+        }
+    }
+
+    #[cfg(feature = "http2")]
+    pub(crate) fn on_ping_rtt(&self, rtt: Duration) {
+        if let Some(ref observer) = self.0 {
+            observer.on_ping_rtt(rtt);
+        }
+    }
+
+    #[cfg(feature = "http2")]
+    pub(crate) fn on_goaway_received(&self, reason: u32) {
+        if let Some(ref observer) = self.0 {
+            observer.on_goaway_received(reason);
+        }
+    }
+}
+
+impl fmt::Debug for ObserverRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ObserverRef")
+            .field(&self.0.is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,134 @@
+use std::cmp;
+use std::time::Duration;
+
+use tokio::time::{Instant, Sleep};
+
+use crate::common::{task, Future, Pin, Poll};
+use crate::error::Timeout;
+use crate::server::conn::MinDataRate;
+
+/// The timeouts on the bodies of the messages of a server connection.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct BodyTimeouts {
+    pub(crate) read_idle: Option<Duration>,
+    pub(crate) min_read_rate: Option<MinDataRate>,
+    pub(crate) min_write_rate: Option<MinDataRate>,
+}
+
+impl BodyTimeouts {
+    /// Returns a timer for a request body, if any timeout applies to it.
+    pub(crate) fn read_timer(&self) -> Option<BodyTimer> {
+        BodyTimer::new(
+            self.read_idle,
+            self.min_read_rate,
+            Timeout::RequestBodyDataRate,
+        )
+    }
+
+    /// Returns a timer for a response body, if any timeout applies to it.
+    pub(crate) fn write_timer(&self) -> Option<BodyTimer> {
+        BodyTimer::new(None, self.min_write_rate, Timeout::ResponseDataRate)
+    }
+}
+
+/// Enforces an idle timeout and a minimum data rate on a body.
+///
+/// Only the time during which the body is active counts: the time spent
+/// waiting on the peer, rather than on the service or the other side of the
+/// connection.
+pub(crate) struct BodyTimer {
+    idle: Option<Duration>,
+    rate: Option<MinDataRate>,
+    /// The timeout to report when the rate is too low.
+    rate_timeout: Timeout,
+    sleep: Pin<Box<Sleep>>,
+    /// How long the body was active, until `active_since`.
+    active: Duration,
+    /// When the body last became active, if it is.
+    active_since: Option<Instant>,
+    /// When data was last transferred, or the body last became active.
+    idle_since: Instant,
+    bytes: u64,
+}
+
+impl BodyTimer {
+    fn new(
+        idle: Option<Duration>,
+        rate: Option<MinDataRate>,
+        rate_timeout: Timeout,
+    ) -> Option<BodyTimer> {
+        if idle.is_none() && rate.is_none() {
+            return None;
+        }
+
+        let now = Instant::now();
+        Some(BodyTimer {
+            idle,
+            rate,
+            rate_timeout,
+            sleep: Box::pin(tokio::time::sleep_until(now)),
+            active: Duration::from_secs(0),
+            active_since: None,
+            idle_since: now,
+            bytes: 0,
+        })
+    }
+
+    /// Starts counting time against the body, waiting on the peer.
+    pub(crate) fn resume(&mut self) {
+        if self.active_since.is_none() {
+            let now = Instant::now();
+            self.active_since = Some(now);
+            self.idle_since = now;
+        }
+    }
+
+    /// Stops counting time against the body, until it is resumed.
+    pub(crate) fn pause(&mut self) {
+        if let Some(since) = self.active_since.take() {
+            self.active += since.elapsed();
+        }
+    }
+
+    /// Records `len` bytes of the body as transferred.
+    pub(crate) fn on_data(&mut self, len: usize) {
+        self.bytes += len as u64;
+        self.idle_since = Instant::now();
+    }
+
+    /// Polls for a timeout to expire while the body is active, returning
+    /// the error to fail the body with.
+    pub(crate) fn poll_expired(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Error> {
+        let since = match self.active_since {
+            Some(since) => since,
+            None => return Poll::Pending,
+        };
+
+        let idle_deadline = self.idle.and_then(|idle| self.idle_since.checked_add(idle));
+        let rate_deadline = self.rate.and_then(|rate| {
+            // The body may be active for as long as its data takes at the
+            // minimum rate, and at least for the grace period.
+            let allowed = cmp::max(
+                rate.grace_period(),
+                Duration::from_secs_f64(self.bytes as f64 / rate.bytes_per_second() as f64),
+            );
+            since.checked_add(allowed.saturating_sub(self.active))
+        });
+        let deadline = match (idle_deadline, rate_deadline) {
+            (Some(idle), Some(rate)) => cmp::min(idle, rate),
+            (Some(deadline), None) | (None, Some(deadline)) => deadline,
+            (None, None) => return Poll::Pending,
+        };
+
+        if self.sleep.deadline() != deadline {
+            self.sleep.as_mut().reset(deadline);
+        }
+        ready!(self.sleep.as_mut().poll(cx));
+
+        let timeout = match idle_deadline {
+            Some(idle) if idle <= deadline => Timeout::RequestBodyIdle,
+            _ => self.rate_timeout,
+        };
+        Poll::Ready(crate::Error::new_timeout(timeout))
+    }
+}
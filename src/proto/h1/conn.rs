@@ -1,7 +1,7 @@
 use std::fmt;
 use std::io;
 use std::marker::PhantomData;
-#[cfg(all(feature = "server", feature = "runtime"))]
+#[cfg(feature = "runtime")]
 use std::time::Duration;
 
 use bytes::{Buf, Bytes};
@@ -14,10 +14,18 @@ use tokio::time::Sleep;
 use tracing::{debug, error, trace};
 
 use super::io::Buffered;
+#[cfg(all(feature = "client", feature = "runtime"))]
+use super::ContinueWait;
 use super::{Decoder, Encode, EncodedBuf, Encoder, Http1Transaction, ParseContext, Wants};
 use crate::body::DecodedLength;
+#[cfg(feature = "runtime")]
+use crate::common::Future;
 use crate::common::{task, Pin, Poll, Unpin};
-use crate::headers::connection_keep_alive;
+use crate::ext::ChunkExtensions;
+use crate::headers::{self, connection_keep_alive};
+use crate::observe::ObserverRef;
+#[cfg(all(feature = "server", feature = "runtime"))]
+use crate::proto::body_timer::{BodyTimeouts, BodyTimer};
 use crate::proto::{BodyLength, MessageHead};
 
 const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
@@ -46,11 +54,38 @@ where
             io: Buffered::new(io),
             state: State {
                 allow_half_close: false,
+                allow_trailer_fields: false,
                 cached_headers: None,
+                chunk_extensions: false,
+                #[cfg(all(feature = "server", feature = "runtime"))]
+                body_timeouts: BodyTimeouts::default(),
+                #[cfg(all(feature = "client", feature = "runtime"))]
+                continue_wait: ContinueWait::Idle,
                 error: None,
+                #[cfg(feature = "server")]
+                expect_continue: None,
+                #[cfg(all(feature = "client", feature = "runtime"))]
+                expect_continue_timeout: None,
                 keep_alive: KA::Busy,
+                #[cfg(all(feature = "server", feature = "runtime"))]
+                keep_alive_timeout: None,
+                #[cfg(all(feature = "server", feature = "runtime"))]
+                keep_alive_timer: None,
+                #[cfg(feature = "server")]
+                max_requests: None,
+                #[cfg(feature = "server")]
+                responses: 0,
                 method: None,
+                #[cfg(feature = "server")]
+                manual_continue: false,
+                observer: ObserverRef::default(),
                 h1_parser_config: ParserConfig::default(),
+                h1_max_headers: None,
+                #[cfg(feature = "server")]
+                h1_max_uri_len: None,
+                h1_strict_parsing: false,
+                #[cfg(feature = "client")]
+                h1_ignore_invalid_headers: false,
                 #[cfg(all(feature = "server", feature = "runtime"))]
                 h1_header_read_timeout: None,
                 #[cfg(all(feature = "server", feature = "runtime"))]
@@ -58,21 +93,31 @@ where
                 #[cfg(all(feature = "server", feature = "runtime"))]
                 h1_header_read_timeout_running: false,
                 preserve_header_case: false,
-                #[cfg(feature = "ffi")]
                 preserve_header_order: false,
+                preserve_raw_head: false,
                 title_case_headers: false,
                 h09_responses: false,
-                #[cfg(feature = "ffi")]
+                #[cfg(feature = "client")]
                 on_informational: None,
                 #[cfg(feature = "ffi")]
                 raw_headers: false,
                 notify_read: false,
+                #[cfg(feature = "server")]
+                refused_body: false,
+                #[cfg(feature = "server")]
+                drain_refused_body: false,
                 reading: Reading::Init,
                 writing: Writing::Init,
                 upgrade: None,
+                recv_chunk_extensions: None,
+                trailers: None,
                 // We assume a modern world where the remote speaks HTTP/1.1.
                 // If they tell us otherwise, we'll downgrade in `read_head`.
                 version: Version::HTTP_11,
+                #[cfg(all(feature = "server", feature = "runtime"))]
+                read_body_timer: None,
+                #[cfg(all(feature = "server", feature = "runtime"))]
+                write_body_timer: None,
             },
             _marker: PhantomData,
         }
@@ -83,6 +128,11 @@ where
         self.io.set_flush_pipeline(enabled);
     }
 
+    pub(crate) fn set_observer(&mut self, observer: ObserverRef) {
+        self.io.set_observer(observer.clone());
+        self.state.observer = observer;
+    }
+
     pub(crate) fn set_write_strategy_queue(&mut self) {
         self.io.set_write_strategy_queue();
     }
@@ -105,6 +155,28 @@ where
         self.state.h1_parser_config = parser_config;
     }
 
+    #[cfg(feature = "client")]
+    pub(crate) fn set_ignore_invalid_headers(&mut self) {
+        self.state
+            .h1_parser_config
+            .ignore_invalid_headers_in_responses(true);
+        self.state.h1_ignore_invalid_headers = true;
+    }
+
+    pub(crate) fn set_max_headers(&mut self, max: usize) {
+        self.state.h1_max_headers = Some(max);
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn set_max_uri_len(&mut self, max: usize) {
+        self.state.h1_max_uri_len = Some(max);
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn set_strict_parsing(&mut self) {
+        self.state.h1_strict_parsing = true;
+    }
+
     pub(crate) fn set_title_case_headers(&mut self) {
         self.state.title_case_headers = true;
     }
@@ -113,11 +185,14 @@ where
         self.state.preserve_header_case = true;
     }
 
-    #[cfg(feature = "ffi")]
     pub(crate) fn set_preserve_header_order(&mut self) {
         self.state.preserve_header_order = true;
     }
 
+    pub(crate) fn set_preserve_raw_head(&mut self) {
+        self.state.preserve_raw_head = true;
+    }
+
     #[cfg(feature = "client")]
     pub(crate) fn set_h09_responses(&mut self) {
         self.state.h09_responses = true;
@@ -128,6 +203,36 @@ where
         self.state.h1_header_read_timeout = Some(val);
     }
 
+    #[cfg(all(feature = "server", feature = "runtime"))]
+    pub(crate) fn set_keep_alive_timeout(&mut self, timeout: Duration) {
+        self.state.keep_alive_timeout = Some(timeout);
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn set_max_requests(&mut self, max: usize) {
+        self.state.max_requests = Some(max);
+    }
+
+    #[cfg(all(feature = "server", feature = "runtime"))]
+    pub(crate) fn set_body_timeouts(&mut self, timeouts: BodyTimeouts) {
+        self.state.body_timeouts = timeouts;
+    }
+
+    #[cfg(all(feature = "client", feature = "runtime"))]
+    pub(crate) fn set_expect_continue_timeout(&mut self, timeout: Duration) {
+        self.state.expect_continue_timeout = Some(timeout);
+    }
+
+    pub(crate) fn set_chunk_extensions(&mut self) {
+        self.state.chunk_extensions = true;
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn set_manual_continue(&mut self, drain_refused_body: bool) {
+        self.state.manual_continue = true;
+        self.state.drain_refused_body = drain_refused_body;
+    }
+
     #[cfg(feature = "server")]
     pub(crate) fn set_allow_half_close(&mut self) {
         self.state.allow_half_close = true;
@@ -168,11 +273,22 @@ where
 
     pub(crate) fn can_read_body(&self) -> bool {
         match self.state.reading {
-            Reading::Body(..) | Reading::Continue(..) => true,
+            Reading::Body(..) => true,
+            Reading::Continue(..) => !self.is_body_refused(),
             _ => false,
         }
     }
 
+    #[cfg(feature = "server")]
+    fn is_body_refused(&self) -> bool {
+        self.state.refused_body
+    }
+
+    #[cfg(not(feature = "server"))]
+    fn is_body_refused(&self) -> bool {
+        false
+    }
+
     fn should_error_on_eof(&self) -> bool {
         // If we're idle, it's probably just the connection closing gracefully.
         T::should_error_on_parse_eof() && !self.state.is_idle()
@@ -190,12 +306,33 @@ where
         debug_assert!(self.can_read_head());
         trace!("Conn::read_head");
 
-        let msg = match ready!(self.io.parse::<T>(
+        #[cfg(all(feature = "server", feature = "runtime"))]
+        if let Some(ref mut timer) = self.state.keep_alive_timer {
+            if !self.io.read_buf().is_empty() {
+                // The next request started arriving, the header read
+                // timeout takes over.
+                self.state.keep_alive_timer = None;
+            } else if timer.as_mut().poll(cx).is_ready() {
+                debug!("keep-alive timeout expired, closing idle connection");
+                self.state.close();
+                return Poll::Ready(None);
+            }
+        }
+
+        #[cfg_attr(not(feature = "server"), allow(unused_mut))]
+        let mut msg = match ready!(self.io.parse::<T>(
             cx,
             ParseContext {
                 cached_headers: &mut self.state.cached_headers,
                 req_method: &mut self.state.method,
                 h1_parser_config: self.state.h1_parser_config.clone(),
+                h1_max_headers: self.state.h1_max_headers,
+                #[cfg(feature = "server")]
+                h1_max_uri_len: self.state.h1_max_uri_len,
+                #[cfg(feature = "server")]
+                h1_strict_parsing: self.state.h1_strict_parsing,
+                #[cfg(feature = "client")]
+                h1_ignore_invalid_headers: self.state.h1_ignore_invalid_headers,
                 #[cfg(all(feature = "server", feature = "runtime"))]
                 h1_header_read_timeout: self.state.h1_header_read_timeout,
                 #[cfg(all(feature = "server", feature = "runtime"))]
@@ -203,11 +340,13 @@ where
                 #[cfg(all(feature = "server", feature = "runtime"))]
                 h1_header_read_timeout_running: &mut self.state.h1_header_read_timeout_running,
                 preserve_header_case: self.state.preserve_header_case,
-                #[cfg(feature = "ffi")]
                 preserve_header_order: self.state.preserve_header_order,
+                preserve_raw_head: self.state.preserve_raw_head,
                 h09_responses: self.state.h09_responses,
-                #[cfg(feature = "ffi")]
+                #[cfg(feature = "client")]
                 on_informational: &mut self.state.on_informational,
+                #[cfg(all(feature = "client", feature = "runtime"))]
+                continue_wait: &mut self.state.continue_wait,
                 #[cfg(feature = "ffi")]
                 raw_headers: self.state.raw_headers,
             }
@@ -220,20 +359,36 @@ where
         // the optimizer doesn't remove the extra copies.
 
         debug!("incoming body is {}", msg.decode);
+        self.state.observer.on_head_parsed();
 
         // Prevent accepting HTTP/0.9 responses after the initial one, if any.
         self.state.h09_responses = false;
 
         // Drop any OnInformational callbacks, we're done there!
-        #[cfg(feature = "ffi")]
+        #[cfg(feature = "client")]
         {
             self.state.on_informational = None;
         }
 
+        #[cfg(all(feature = "client", feature = "runtime"))]
+        if let ContinueWait::Refused = self.state.continue_wait {
+            self.state.continue_wait = ContinueWait::Idle;
+            self.skip_body();
+        }
+
+        #[cfg(feature = "server")]
+        {
+            self.state.refused_body = false;
+        }
+
         self.state.busy();
         self.state.keep_alive &= msg.keep_alive;
         self.state.version = msg.head.version;
 
+        if T::is_server() {
+            self.state.allow_trailer_fields = headers::te_trailers(&msg.head.headers);
+        }
+
         let mut wants = if msg.wants_upgrade {
             Wants::UPGRADE
         } else {
@@ -245,14 +400,36 @@ where
                 debug!("ignoring expect-continue since body is empty");
             }
             self.state.reading = Reading::KeepAlive;
+            self.state.observer.on_message_complete();
             if !T::should_read_first() {
                 self.try_keep_alive(cx);
             }
         } else if msg.expect_continue {
-            self.state.reading = Reading::Continue(Decoder::new(msg.decode));
+            #[cfg(feature = "server")]
+            if self.state.manual_continue {
+                let (expect, decision) = crate::ext::ExpectContinue::new();
+                msg.head.extensions.insert(expect);
+                self.state.expect_continue = Some(decision);
+            }
+            self.state.reading = Reading::Continue(
+                Decoder::new(msg.decode)
+                    .with_chunk_extensions(self.state.chunk_extensions)
+                    .with_strict(self.state.h1_strict_parsing)
+                    .with_max_headers(self.state.h1_max_headers),
+            );
             wants = wants.add(Wants::EXPECT);
         } else {
-            self.state.reading = Reading::Body(Decoder::new(msg.decode));
+            self.state.reading = Reading::Body(
+                Decoder::new(msg.decode)
+                    .with_chunk_extensions(self.state.chunk_extensions)
+                    .with_strict(self.state.h1_strict_parsing)
+                    .with_max_headers(self.state.h1_max_headers),
+            );
+        }
+
+        #[cfg(all(feature = "server", feature = "runtime"))]
+        if T::is_server() && msg.decode != DecodedLength::ZERO {
+            self.state.read_body_timer = self.state.body_timeouts.read_timer();
         }
 
         Poll::Ready(Some(Ok((msg.head, msg.decode, wants))))
@@ -273,6 +450,7 @@ where
                 e,
                 self.io.read_buf().len()
             );
+            self.state.observer.on_parse_error(&e);
             match self.on_parse_error(e) {
                 Ok(()) => Poll::Pending, // XXX: wat?
                 Err(e) => Poll::Ready(Some(Err(e))),
@@ -287,15 +465,37 @@ where
     pub(crate) fn poll_read_body(
         &mut self,
         cx: &mut task::Context<'_>,
-    ) -> Poll<Option<io::Result<Bytes>>> {
+    ) -> Poll<Option<crate::Result<Bytes>>> {
         debug_assert!(self.can_read_body());
 
         let (reading, ret) = match self.state.reading {
             Reading::Body(ref mut decoder) => {
-                match ready!(decoder.decode(cx, &mut self.io)) {
+                #[cfg(all(feature = "server", feature = "runtime"))]
+                if let Some(ref mut timer) = self.state.read_body_timer {
+                    timer.resume();
+                }
+                let decoded = decoder
+                    .decode(cx, &mut self.io)
+                    .map_err(crate::Error::new_body);
+                #[cfg(all(feature = "server", feature = "runtime"))]
+                let decoded = match (decoded, self.state.read_body_timer.as_mut()) {
+                    (Poll::Pending, Some(timer)) => timer.poll_expired(cx).map(Err),
+                    (decoded, _) => decoded,
+                };
+                match ready!(decoded) {
                     Ok(slice) => {
+                        self.state.recv_chunk_extensions = decoder.take_chunk_extensions();
+                        if !slice.is_empty() {
+                            self.state.observer.on_body_received(slice.len());
+                            #[cfg(all(feature = "server", feature = "runtime"))]
+                            if let Some(ref mut timer) = self.state.read_body_timer {
+                                timer.on_data(slice.len());
+                            }
+                        }
                         let (reading, chunk) = if decoder.is_eof() {
                             debug!("incoming body completed");
+                            self.state.observer.on_message_complete();
+                            self.state.trailers = decoder.take_trailers();
                             (
                                 Reading::KeepAlive,
                                 if !slice.is_empty() {
@@ -316,12 +516,22 @@ where
                         (reading, Poll::Ready(chunk))
                     }
                     Err(e) => {
-                        debug!("incoming body decode error: {}", e);
+                        debug!("incoming body error: {}", e);
                         (Reading::Closed, Poll::Ready(Some(Err(e))))
                     }
                 }
             }
             Reading::Continue(ref decoder) => {
+                // Wait for the service to decide, if it does...
+                #[cfg(feature = "server")]
+                if let Some(ref decision) = self.state.expect_continue {
+                    if ready!(decision.poll_decided(cx)) == crate::ext::Decided::Refused {
+                        self.refuse_body();
+                        return Poll::Ready(None);
+                    }
+                    self.state.expect_continue = None;
+                }
+
                 // Write the 100 Continue if not already responded...
                 if let Writing::Init = self.state.writing {
                     trace!("automatically sending 100 Continue");
@@ -337,10 +547,103 @@ where
         };
 
         self.state.reading = reading;
+        #[cfg(all(feature = "server", feature = "runtime"))]
+        {
+            self.state.read_body_timer = None;
+        }
         self.try_keep_alive(cx);
         ret
     }
 
+    /// Pauses the timeouts of the body being read, while its receiver
+    /// doesn't want more data.
+    #[cfg(all(feature = "server", feature = "runtime"))]
+    pub(crate) fn pause_read_body_timer(&mut self) {
+        if let Some(ref mut timer) = self.state.read_body_timer {
+            timer.pause();
+        }
+    }
+
+    /// Takes into account the decision of the service on the
+    /// `Expect: 100-continue` of the current request, if it was taken.
+    ///
+    /// Accepting sends `100 Continue` right away, without waiting for the
+    /// body to be read.
+    #[cfg(feature = "server")]
+    pub(crate) fn poll_expect_continue(&mut self, cx: &mut task::Context<'_>) {
+        let decided = match self.state.expect_continue {
+            Some(ref decision) => decision.poll_decided(cx),
+            None => return,
+        };
+        match decided {
+            Poll::Ready(crate::ext::Decided::Accepted) => {
+                self.state.expect_continue = None;
+                if let Reading::Continue(ref decoder) = self.state.reading {
+                    if let Writing::Init = self.state.writing {
+                        trace!("sending accepted 100 Continue");
+                        let cont = b"HTTP/1.1 100 Continue\r\n\r\n";
+                        self.io.headers_buf().extend_from_slice(cont);
+                    }
+                    self.state.reading = Reading::Body(decoder.clone());
+                }
+            }
+            Poll::Ready(crate::ext::Decided::Refused) => self.refuse_body(),
+            // Sent once the body is read, as usual.
+            Poll::Ready(crate::ext::Decided::Dropped) | Poll::Pending => (),
+        }
+    }
+
+    /// Refuses the body of the current `Expect: 100-continue` request, so
+    /// that `100 Continue` is never sent.
+    ///
+    /// Unless the body is to be drained after the response, reading is
+    /// closed right away.
+    #[cfg(feature = "server")]
+    fn refuse_body(&mut self) {
+        debug!("refusing expect-continue request body");
+        self.state.expect_continue = None;
+        self.state.refused_body = true;
+        if !self.state.drain_refused_body {
+            self.state.close_read();
+        }
+    }
+
+    /// Ends the current request without its body, since a final response
+    /// came instead of `100 Continue`.
+    #[cfg(all(feature = "client", feature = "runtime"))]
+    fn skip_body(&mut self) {
+        debug!("response received before 100 Continue, not sending body");
+        if let Writing::Body(..) = self.state.writing {
+            // A chunked body can end right away. A body of known length
+            // can't, so the connection can't be used again.
+            if self.end_body().is_err() {
+                self.state.close_write();
+            }
+        }
+    }
+
+    /// Polls whether the body of the current request can be written, as it
+    /// may be held until the server responds `100 Continue`.
+    #[cfg(all(feature = "client", feature = "runtime"))]
+    pub(crate) fn poll_continue_wait(&mut self, cx: &mut task::Context<'_>) -> Poll<()> {
+        if let ContinueWait::Waiting(ref mut timeout) = self.state.continue_wait {
+            ready!(timeout.as_mut().poll(cx));
+            debug!("no 100 Continue before timeout, sending body");
+            self.state.continue_wait = ContinueWait::Idle;
+        }
+        Poll::Ready(())
+    }
+
+    /// Takes the extensions of the chunk that the last data read is from.
+    pub(crate) fn take_chunk_extensions(&mut self) -> Option<ChunkExtensions> {
+        self.state.recv_chunk_extensions.take()
+    }
+
+    /// Takes the trailer fields of the incoming body, once it has completed.
+    pub(crate) fn take_trailers(&mut self) -> Option<HeaderMap> {
+        self.state.trailers.take()
+    }
+
     pub(crate) fn wants_read_again(&mut self) -> bool {
         let ret = self.state.notify_read;
         self.state.notify_read = false;
@@ -522,6 +825,21 @@ where
         }
     }
 
+    /// Writes an informational (1xx) head, before the final head of the
+    /// message.
+    #[cfg(feature = "server")]
+    pub(crate) fn write_informational(&mut self, head: MessageHead<T::Outgoing>) {
+        debug_assert!(self.can_write_head());
+
+        // An HTTP/1.0 client doesn't expect any informational response.
+        if let Version::HTTP_10 = self.state.version {
+            debug!("not sending informational response to HTTP/1.0 client");
+            return;
+        }
+
+        T::encode_informational(&head, self.state.title_case_headers, self.io.headers_buf());
+    }
+
     fn encode_head(
         &mut self,
         mut head: MessageHead<T::Outgoing>,
@@ -535,6 +853,15 @@ where
 
         self.enforce_version(&mut head);
 
+        #[cfg(feature = "server")]
+        self.on_refused_body(&mut head);
+
+        #[cfg(feature = "server")]
+        self.on_max_requests(&mut head);
+
+        #[cfg(all(feature = "client", feature = "runtime"))]
+        let expects_continue = T::is_client() && headers::expect_continue(&head.headers);
+
         let buf = self.io.headers_buf();
         match super::role::encode_headers::<T>(
             Encode {
@@ -552,10 +879,24 @@ where
                 debug_assert!(head.headers.is_empty());
                 self.state.cached_headers = Some(head.headers);
 
-                #[cfg(feature = "ffi")]
+                #[cfg(feature = "client")]
                 {
                     self.state.on_informational =
-                        head.extensions.remove::<crate::ffi::OnInformational>();
+                        head.extensions.remove::<crate::ext::OnInformational>();
+                }
+
+                #[cfg(all(feature = "server", feature = "runtime"))]
+                if T::is_server() && !encoder.is_eof() {
+                    self.state.write_body_timer = self.state.body_timeouts.write_timer();
+                }
+
+                #[cfg(all(feature = "client", feature = "runtime"))]
+                if let Some(timeout) = self.state.expect_continue_timeout {
+                    if expects_continue && !encoder.is_eof() {
+                        trace!("holding body until 100 Continue");
+                        self.state.continue_wait =
+                            ContinueWait::Waiting(Box::pin(tokio::time::sleep(timeout)));
+                    }
                 }
 
                 Some(encoder)
@@ -568,6 +909,47 @@ where
         }
     }
 
+    // Applies the refused body policy before the final response is written,
+    // refusing the body if the service didn't decide on it.
+    #[cfg(feature = "server")]
+    fn on_refused_body(&mut self, head: &mut MessageHead<T::Outgoing>) {
+        if self.state.expect_continue.is_some() {
+            self.refuse_body();
+        }
+        if !self.state.refused_body {
+            return;
+        }
+
+        if self.state.drain_refused_body {
+            // The client sends the body anyway, or another request, so
+            // read it now that the response won't be followed by a 100.
+            if let Reading::Continue(ref decoder) = self.state.reading {
+                self.state.reading = Reading::Body(decoder.clone());
+                self.state.notify_read = true;
+            }
+        } else {
+            head.headers
+                .insert(CONNECTION, HeaderValue::from_static("close"));
+        }
+    }
+
+    // Closes the connection after the response that reaches the maximum
+    // number of requests, telling the client with `Connection: close`.
+    #[cfg(feature = "server")]
+    fn on_max_requests(&mut self, head: &mut MessageHead<T::Outgoing>) {
+        let max = match self.state.max_requests {
+            Some(max) => max,
+            None => return,
+        };
+        self.state.responses += 1;
+        if self.state.responses >= max && self.state.wants_keep_alive() {
+            debug!("max requests ({}) reached, closing after response", max);
+            self.state.disable_keep_alive();
+            head.headers
+                .insert(CONNECTION, HeaderValue::from_static("close"));
+        }
+    }
+
     // Fix keep-alive when Connection: keep-alive header is not present
     fn fix_keep_alive(&mut self, head: &mut MessageHead<T::Outgoing>) {
         let outgoing_is_keep_alive = head
@@ -609,14 +991,25 @@ where
         // the user's headers be.
     }
 
-    pub(crate) fn write_body(&mut self, chunk: B) {
+    pub(crate) fn write_body(&mut self, chunk: B, ext: Option<&ChunkExtensions>) {
         debug_assert!(self.can_write_body() && self.can_buffer_body());
         // empty chunks should be discarded at Dispatcher level
         debug_assert!(chunk.remaining() != 0);
+        self.state.observer.on_body_sent(chunk.remaining());
+        #[cfg(all(feature = "server", feature = "runtime"))]
+        if let Some(ref mut timer) = self.state.write_body_timer {
+            timer.on_data(chunk.remaining());
+        }
 
         let state = match self.state.writing {
             Writing::Body(ref mut encoder) => {
-                self.io.buffer(encoder.encode(chunk));
+                let buf = match ext {
+                    Some(ext) if self.state.chunk_extensions => {
+                        encoder.encode_with_extensions(chunk, ext)
+                    }
+                    _ => encoder.encode(chunk),
+                };
+                self.io.buffer(buf);
 
                 if !encoder.is_eof() {
                     return;
@@ -638,6 +1031,11 @@ where
         debug_assert!(self.can_write_body() && self.can_buffer_body());
         // empty chunks should be discarded at Dispatcher level
         debug_assert!(chunk.remaining() != 0);
+        self.state.observer.on_body_sent(chunk.remaining());
+        #[cfg(all(feature = "server", feature = "runtime"))]
+        if let Some(ref mut timer) = self.state.write_body_timer {
+            timer.on_data(chunk.remaining());
+        }
 
         let state = match self.state.writing {
             Writing::Body(ref encoder) => {
@@ -654,6 +1052,35 @@ where
         self.state.writing = state;
     }
 
+    pub(crate) fn write_trailers(&mut self, trailers: HeaderMap) -> crate::Result<()> {
+        debug_assert!(self.can_write_body() && self.can_buffer_body());
+
+        if T::is_server() && !self.state.allow_trailer_fields {
+            debug!("trailers not allowed to be sent, request did not include TE: trailers");
+            return self.end_body();
+        }
+
+        let state = match self.state.writing {
+            Writing::Body(ref encoder) => {
+                match encoder.encode_trailers(trailers, self.state.title_case_headers) {
+                    Some(end) => {
+                        self.io.buffer(end);
+                        if encoder.is_last() || encoder.is_close_delimited() {
+                            Writing::Closed
+                        } else {
+                            Writing::KeepAlive
+                        }
+                    }
+                    None => return self.end_body(),
+                }
+            }
+            _ => unreachable!("write_trailers invalid state: {:?}", self.state.writing),
+        };
+
+        self.state.writing = state;
+        Ok(())
+    }
+
     pub(crate) fn end_body(&mut self) -> crate::Result<()> {
         debug_assert!(self.can_write_body());
 
@@ -709,12 +1136,41 @@ where
     }
 
     pub(crate) fn poll_flush(&mut self, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        #[cfg(all(feature = "server", feature = "runtime"))]
+        if let Some(ref mut timer) = self.state.write_body_timer {
+            timer.resume();
+        }
         ready!(Pin::new(&mut self.io).poll_flush(cx))?;
+        #[cfg(all(feature = "server", feature = "runtime"))]
+        if let Writing::Body(..) = self.state.writing {
+            if let Some(ref mut timer) = self.state.write_body_timer {
+                timer.pause();
+            }
+        } else {
+            self.state.write_body_timer = None;
+        }
         self.try_keep_alive(cx);
         trace!("flushed({}): {:?}", T::LOG, self.state);
         Poll::Ready(Ok(()))
     }
 
+    /// Polls the timeouts of the body being written, while flushing it is
+    /// pending. If one expired, writing is closed.
+    #[cfg(all(feature = "server", feature = "runtime"))]
+    pub(crate) fn poll_write_body_timeout(
+        &mut self,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<crate::Error> {
+        let err = match self.state.write_body_timer {
+            Some(ref mut timer) => ready!(timer.poll_expired(cx)),
+            None => return Poll::Pending,
+        };
+        debug!("outgoing body timed out: {}", err);
+        self.state.write_body_timer = None;
+        self.close_write();
+        Poll::Ready(err)
+    }
+
     pub(crate) fn poll_shutdown(&mut self, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
         match ready!(Pin::new(self.io.io_mut()).poll_shutdown(cx)) {
             Ok(()) => {
@@ -730,6 +1186,17 @@ where
 
     /// If the read side can be cheaply drained, do so. Otherwise, close.
     pub(super) fn poll_drain_or_close_read(&mut self, cx: &mut task::Context<'_>) {
+        #[cfg(feature = "server")]
+        {
+            if self.state.expect_continue.is_some() {
+                // The body was dropped without being accepted.
+                self.refuse_body();
+            }
+            if self.state.refused_body {
+                return self.poll_drain_refused_body(cx);
+            }
+        }
+
         let _ = self.poll_read_body(cx);
 
         // If still in Reading::Body, just give up
@@ -739,6 +1206,17 @@ where
         }
     }
 
+    // Reads and discards a refused body, once the response was written.
+    #[cfg(feature = "server")]
+    fn poll_drain_refused_body(&mut self, cx: &mut task::Context<'_>) {
+        while let Reading::Body(..) = self.state.reading {
+            match self.poll_read_body(cx) {
+                Poll::Ready(Some(Ok(_))) => (),
+                Poll::Ready(Some(Err(_))) | Poll::Ready(None) | Poll::Pending => break,
+            }
+        }
+    }
+
     pub(crate) fn close_read(&mut self) {
         self.state.close_read();
     }
@@ -786,19 +1264,72 @@ impl<I: Unpin, B, T> Unpin for Conn<I, B, T> {}
 
 struct State {
     allow_half_close: bool,
+    /// Whether the peer accepts trailer fields in a chunked body.
+    ///
+    /// A server may only send trailers if the request included
+    /// `TE: trailers`. Clients can always send them.
+    allow_trailer_fields: bool,
     /// Re-usable HeaderMap to reduce allocating new ones.
     cached_headers: Option<HeaderMap>,
+    /// Whether to collect the chunk extensions of incoming bodies, and
+    /// write those of outgoing ones.
+    chunk_extensions: bool,
+    /// Timeouts on the bodies of requests and responses.
+    #[cfg(all(feature = "server", feature = "runtime"))]
+    body_timeouts: BodyTimeouts,
+    /// Whether the body of the current request is held until the server
+    /// responds `100 Continue`.
+    #[cfg(all(feature = "client", feature = "runtime"))]
+    continue_wait: ContinueWait,
     /// If an error occurs when there wasn't a direct way to return it
     /// back to the user, this is set.
     error: Option<crate::Error>,
+    /// The decision of the service on the `Expect: 100-continue` of the
+    /// current request, while it hasn't been taken into account.
+    #[cfg(feature = "server")]
+    expect_continue: Option<crate::ext::ContinueDecision>,
+    /// How long a request with `Expect: 100-continue` holds its body, if
+    /// it does.
+    #[cfg(all(feature = "client", feature = "runtime"))]
+    expect_continue_timeout: Option<Duration>,
     /// Current keep-alive status.
     keep_alive: KA,
+    /// How long an idle connection waits for the next request.
+    #[cfg(all(feature = "server", feature = "runtime"))]
+    keep_alive_timeout: Option<Duration>,
+    /// Set while the connection is idle between requests, if there is a
+    /// keep-alive timeout, until the next request starts arriving.
+    #[cfg(all(feature = "server", feature = "runtime"))]
+    keep_alive_timer: Option<Pin<Box<Sleep>>>,
+    /// How many responses are sent before the connection is closed.
+    #[cfg(feature = "server")]
+    max_requests: Option<usize>,
+    /// How many responses were sent on this connection.
+    #[cfg(feature = "server")]
+    responses: usize,
     /// If mid-message, the HTTP Method that started it.
     ///
     /// This is used to know things such as if the message can include
     /// a body or not.
     method: Option<Method>,
+    /// Whether the service decides on `Expect: 100-continue` requests.
+    #[cfg(feature = "server")]
+    manual_continue: bool,
+    /// Notified of the events of this connection.
+    observer: ObserverRef,
     h1_parser_config: ParserConfig,
+    /// The maximum number of headers of incoming messages, if not the
+    /// default.
+    h1_max_headers: Option<usize>,
+    /// The maximum length of the URI of incoming requests, if not the
+    /// default.
+    #[cfg(feature = "server")]
+    h1_max_uri_len: Option<usize>,
+    /// Whether incoming requests are parsed with the strict profile.
+    h1_strict_parsing: bool,
+    /// Whether invalid header lines of incoming responses are ignored.
+    #[cfg(feature = "client")]
+    h1_ignore_invalid_headers: bool,
     #[cfg(all(feature = "server", feature = "runtime"))]
     h1_header_read_timeout: Option<Duration>,
     #[cfg(all(feature = "server", feature = "runtime"))]
@@ -806,28 +1337,47 @@ struct State {
     #[cfg(all(feature = "server", feature = "runtime"))]
     h1_header_read_timeout_running: bool,
     preserve_header_case: bool,
-    #[cfg(feature = "ffi")]
     preserve_header_order: bool,
+    /// Whether the raw head of incoming messages is kept in a `RawHead`.
+    preserve_raw_head: bool,
     title_case_headers: bool,
     h09_responses: bool,
     /// If set, called with each 1xx informational response received for
     /// the current request. MUST be unset after a non-1xx response is
     /// received.
-    #[cfg(feature = "ffi")]
-    on_informational: Option<crate::ffi::OnInformational>,
+    #[cfg(feature = "client")]
+    on_informational: Option<crate::ext::OnInformational>,
     #[cfg(feature = "ffi")]
     raw_headers: bool,
     /// Set to true when the Dispatcher should poll read operations
     /// again. See the `maybe_notify` method for more.
     notify_read: bool,
+    /// Whether the body of the current request was refused, and so
+    /// `100 Continue` must not be sent.
+    #[cfg(feature = "server")]
+    refused_body: bool,
+    /// Whether a refused body is read and discarded after the response,
+    /// instead of closing the connection.
+    #[cfg(feature = "server")]
+    drain_refused_body: bool,
     /// State of allowed reads
     reading: Reading,
     /// State of allowed writes
     writing: Writing,
     /// An expected pending HTTP upgrade.
     upgrade: Option<crate::upgrade::Pending>,
+    /// Extensions of the chunk last read from the incoming body.
+    recv_chunk_extensions: Option<ChunkExtensions>,
+    /// Trailer fields received at the end of the last incoming body.
+    trailers: Option<HeaderMap>,
     /// Either HTTP/1.0 or 1.1 connection
     version: Version,
+    /// Enforces the timeouts on the body being read, if any.
+    #[cfg(all(feature = "server", feature = "runtime"))]
+    read_body_timer: Option<BodyTimer>,
+    /// Enforces the timeouts on the body being written, if any.
+    #[cfg(all(feature = "server", feature = "runtime"))]
+    write_body_timer: Option<BodyTimer>,
 }
 
 #[derive(Debug)]
@@ -974,8 +1524,10 @@ impl State {
     }
 
     fn busy(&mut self) {
-        if let KA::Disabled = self.keep_alive.status() {
-            return;
+        match self.keep_alive.status() {
+            KA::Disabled => return,
+            KA::Idle => self.observer.on_keep_alive_reuse(),
+            KA::Busy => (),
         }
         self.keep_alive.busy();
     }
@@ -994,6 +1546,15 @@ impl State {
         self.reading = Reading::Init;
         self.writing = Writing::Init;
 
+        #[cfg(all(feature = "server", feature = "runtime"))]
+        if T::is_server() {
+            if let Some(timeout) = self.keep_alive_timeout {
+                self.keep_alive_timer = Some(Box::pin(tokio::time::sleep(timeout)));
+                // Poll the read side again, so the timer gets registered.
+                self.notify_read = true;
+            }
+        }
+
         // !T::should_read_first() means Client.
         //
         // If Client connection has just gone idle, the Dispatcher
@@ -3,16 +3,31 @@ use std::fmt;
 use std::io;
 use std::usize;
 
-use bytes::Bytes;
+use bytes::{BufMut, Bytes, BytesMut};
+use http::header::{HeaderName, HeaderValue};
+use http::HeaderMap;
 use tracing::{debug, trace};
 
 use crate::common::{task, Poll};
+use crate::error::Parse;
+use crate::ext::ChunkExtensions;
+use crate::headers;
 
-use super::io::MemRead;
+use super::io::{MemRead, DEFAULT_MAX_BUFFER_SIZE};
+use super::role::MAX_HEADERS;
 use super::DecodedLength;
 
 use self::Kind::{Chunked, Eof, Length};
 
+/// Maximum amount of bytes allowed in chunked trailers.
+///
+/// This matches the default limit for the size of a message head.
+const TRAILER_LIMIT: usize = DEFAULT_MAX_BUFFER_SIZE;
+
+/// Maximum amount of bytes allowed in the extensions of a single chunk,
+/// when they are being collected.
+const CHUNK_EXTENSIONS_LIMIT: usize = 1024 * 16;
+
 /// Decoders to handle different Transfer-Encodings.
 ///
 /// If a message body does not include a Transfer-Encoding, it *should*
@@ -20,6 +35,20 @@ use self::Kind::{Chunked, Eof, Length};
 #[derive(Clone, PartialEq)]
 pub(crate) struct Decoder {
     kind: Kind,
+    /// Raw trailer section of a chunked body, while it is being read.
+    trailers_buf: Option<BytesMut>,
+    /// Number of trailer fields read so far.
+    trailers_cnt: usize,
+    /// Parsed trailer fields, once the chunked body has ended.
+    trailers: Option<HeaderMap>,
+    /// Raw extensions of the current chunk, if they are being collected.
+    chunk_ext_buf: Option<BytesMut>,
+    /// Extensions of the current chunk, until taken with its first data.
+    chunk_ext: Option<ChunkExtensions>,
+    /// Whether whitespace is rejected in chunk sizes.
+    strict: bool,
+    /// Maximum number of trailer fields.
+    max_headers: usize,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -66,22 +95,49 @@ enum ChunkedState {
 impl Decoder {
     // constructors
 
-    pub(crate) fn length(x: u64) -> Decoder {
+    fn new_kind(kind: Kind) -> Decoder {
         Decoder {
-            kind: Kind::Length(x),
+            kind,
+            trailers_buf: None,
+            trailers_cnt: 0,
+            trailers: None,
+            chunk_ext_buf: None,
+            chunk_ext: None,
+            strict: false,
+            max_headers: MAX_HEADERS,
         }
     }
 
-    pub(crate) fn chunked() -> Decoder {
-        Decoder {
-            kind: Kind::Chunked(ChunkedState::Size, 0),
+    /// Collect chunk extensions instead of ignoring them.
+    pub(super) fn with_chunk_extensions(mut self, enabled: bool) -> Decoder {
+        if enabled {
+            self.chunk_ext_buf = Some(BytesMut::new());
         }
+        self
+    }
+
+    /// Reject whitespace in chunk sizes, rather than skipping it.
+    pub(super) fn with_strict(mut self, enabled: bool) -> Decoder {
+        self.strict = enabled;
+        self
+    }
+
+    /// Limit the number of trailer fields, defaulting to `MAX_HEADERS`.
+    pub(super) fn with_max_headers(mut self, max: Option<usize>) -> Decoder {
+        self.max_headers = max.unwrap_or(MAX_HEADERS);
+        self
+    }
+
+    pub(crate) fn length(x: u64) -> Decoder {
+        Decoder::new_kind(Kind::Length(x))
+    }
+
+    pub(crate) fn chunked() -> Decoder {
+        Decoder::new_kind(Kind::Chunked(ChunkedState::Size, 0))
     }
 
     pub(crate) fn eof() -> Decoder {
-        Decoder {
-            kind: Kind::Eof(false),
-        }
+        Decoder::new_kind(Kind::Eof(false))
     }
 
     pub(super) fn new(len: DecodedLength) -> Self {
@@ -98,6 +154,18 @@ impl Decoder {
         matches!(self.kind, Length(0) | Chunked(ChunkedState::End, _) | Eof(true))
     }
 
+    /// Takes the trailer fields received at the end of a chunked body.
+    pub(crate) fn take_trailers(&mut self) -> Option<HeaderMap> {
+        self.trailers.take()
+    }
+
+    /// Takes the extensions of the chunk that the last decoded data is from.
+    ///
+    /// Only the first data decoded from each chunk has its extensions.
+    pub(crate) fn take_chunk_extensions(&mut self) -> Option<ChunkExtensions> {
+        self.chunk_ext.take()
+    }
+
     pub(crate) fn decode<R: MemRead>(
         &mut self,
         cx: &mut task::Context<'_>,
@@ -128,12 +196,42 @@ impl Decoder {
             Chunked(ref mut state, ref mut size) => {
                 loop {
                     let mut buf = None;
+                    let was_size_lws = *state == ChunkedState::SizeLws;
                     // advances the chunked state
-                    *state = ready!(state.step(cx, body, size, &mut buf))?;
+                    *state = ready!(state.step(
+                        cx,
+                        body,
+                        size,
+                        &mut buf,
+                        &mut self.trailers_buf,
+                        &mut self.trailers_cnt,
+                        self.max_headers,
+                        &mut self.chunk_ext_buf,
+                    ))?;
+                    // whitespace before a `;` is the BWS allowed ahead of an
+                    // extension, only whitespace ending the size line is rejected
+                    if self.strict && was_size_lws && *state == ChunkedState::SizeLf {
+                        debug!("whitespace in chunk size");
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            crate::Error::from(Parse::ChunkSizeWhitespace),
+                        )));
+                    }
                     if *state == ChunkedState::End {
                         trace!("end of chunked");
+                        if let Some(trailers_buf) = self.trailers_buf.take() {
+                            self.trailers = Some(decode_trailers(trailers_buf, self.trailers_cnt)?);
+                        }
                         return Poll::Ready(Ok(Bytes::new()));
                     }
+                    if *state == ChunkedState::Body {
+                        if let Some(ref mut ext_buf) = self.chunk_ext_buf {
+                            if !ext_buf.is_empty() {
+                                let ext = ext_buf.split().freeze();
+                                self.chunk_ext = Some(ChunkExtensions::from_bytes_unchecked(ext));
+                            }
+                        }
+                    }
                     if let Some(buf) = buf {
                         return Poll::Ready(Ok(buf));
                     }
@@ -186,19 +284,25 @@ impl ChunkedState {
         body: &mut R,
         size: &mut u64,
         buf: &mut Option<Bytes>,
+        trailers_buf: &mut Option<BytesMut>,
+        trailers_cnt: &mut usize,
+        max_headers: usize,
+        ext_buf: &mut Option<BytesMut>,
     ) -> Poll<Result<ChunkedState, io::Error>> {
         use self::ChunkedState::*;
         match *self {
             Size => ChunkedState::read_size(cx, body, size),
             SizeLws => ChunkedState::read_size_lws(cx, body),
-            Extension => ChunkedState::read_extension(cx, body),
+            Extension => ChunkedState::read_extension(cx, body, ext_buf),
             SizeLf => ChunkedState::read_size_lf(cx, body, *size),
             Body => ChunkedState::read_body(cx, body, size, buf),
             BodyCr => ChunkedState::read_body_cr(cx, body),
             BodyLf => ChunkedState::read_body_lf(cx, body),
-            Trailer => ChunkedState::read_trailer(cx, body),
-            TrailerLf => ChunkedState::read_trailer_lf(cx, body),
-            EndCr => ChunkedState::read_end_cr(cx, body),
+            Trailer => ChunkedState::read_trailer(cx, body, trailers_buf),
+            TrailerLf => {
+                ChunkedState::read_trailer_lf(cx, body, trailers_buf, trailers_cnt, max_headers)
+            }
+            EndCr => ChunkedState::read_end_cr(cx, body, trailers_buf),
             EndLf => ChunkedState::read_end_lf(cx, body),
             End => Poll::Ready(Ok(ChunkedState::End)),
         }
@@ -267,10 +371,11 @@ impl ChunkedState {
     fn read_extension<R: MemRead>(
         cx: &mut task::Context<'_>,
         rdr: &mut R,
+        ext_buf: &mut Option<BytesMut>,
     ) -> Poll<Result<ChunkedState, io::Error>> {
         trace!("read_extension");
-        // We don't care about extensions really at all. Just ignore them.
-        // They "end" at the next CRLF.
+        // Unless asked to collect them, we don't care about extensions
+        // really at all. Just ignore them. They "end" at the next CRLF.
         //
         // However, some implementations may not check for the CR, so to save
         // them from themselves, we reject extensions containing plain LF as
@@ -281,7 +386,24 @@ impl ChunkedState {
                 io::ErrorKind::InvalidData,
                 "invalid chunk extension contains newline",
             ))),
-            _ => Poll::Ready(Ok(ChunkedState::Extension)), // no supported extensions
+            byte => {
+                if let Some(ref mut ext_buf) = *ext_buf {
+                    if byte != b'\t' && (byte < 0x20 || byte == 0x7F) {
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "invalid chunk extension contains control character",
+                        )));
+                    }
+                    if ext_buf.len() >= CHUNK_EXTENSIONS_LIMIT {
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "chunk extensions over limit",
+                        )));
+                    }
+                    ext_buf.put_u8(byte);
+                }
+                Poll::Ready(Ok(ChunkedState::Extension))
+            }
         }
     }
     fn read_size_lf<R: MemRead>(
@@ -368,9 +490,12 @@ impl ChunkedState {
     fn read_trailer<R: MemRead>(
         cx: &mut task::Context<'_>,
         rdr: &mut R,
+        trailers_buf: &mut Option<BytesMut>,
     ) -> Poll<Result<ChunkedState, io::Error>> {
         trace!("read_trailer");
-        match byte!(rdr, cx) {
+        let byte = byte!(rdr, cx);
+        put_trailer_byte(trailers_buf, byte)?;
+        match byte {
             b'\r' => Poll::Ready(Ok(ChunkedState::TrailerLf)),
             _ => Poll::Ready(Ok(ChunkedState::Trailer)),
         }
@@ -378,9 +503,22 @@ impl ChunkedState {
     fn read_trailer_lf<R: MemRead>(
         cx: &mut task::Context<'_>,
         rdr: &mut R,
+        trailers_buf: &mut Option<BytesMut>,
+        trailers_cnt: &mut usize,
+        max_headers: usize,
     ) -> Poll<Result<ChunkedState, io::Error>> {
         match byte!(rdr, cx) {
-            b'\n' => Poll::Ready(Ok(ChunkedState::EndCr)),
+            b'\n' => {
+                *trailers_cnt += 1;
+                if *trailers_cnt > max_headers {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "chunk trailers count overflow",
+                    )));
+                }
+                put_trailer_byte(trailers_buf, b'\n')?;
+                Poll::Ready(Ok(ChunkedState::EndCr))
+            }
             _ => Poll::Ready(Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 "Invalid trailer end LF",
@@ -391,10 +529,14 @@ impl ChunkedState {
     fn read_end_cr<R: MemRead>(
         cx: &mut task::Context<'_>,
         rdr: &mut R,
+        trailers_buf: &mut Option<BytesMut>,
     ) -> Poll<Result<ChunkedState, io::Error>> {
         match byte!(rdr, cx) {
             b'\r' => Poll::Ready(Ok(ChunkedState::EndLf)),
-            _ => Poll::Ready(Ok(ChunkedState::Trailer)),
+            byte => {
+                put_trailer_byte(trailers_buf, byte)?;
+                Poll::Ready(Ok(ChunkedState::Trailer))
+            }
         }
     }
     fn read_end_lf<R: MemRead>(
@@ -411,6 +553,65 @@ impl ChunkedState {
     }
 }
 
+fn put_trailer_byte(trailers_buf: &mut Option<BytesMut>, byte: u8) -> Result<(), io::Error> {
+    let buf = trailers_buf.get_or_insert_with(BytesMut::new);
+    if buf.len() >= TRAILER_LIMIT {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "chunk trailers bytes over limit",
+        ));
+    }
+    buf.put_u8(byte);
+    Ok(())
+}
+
+fn decode_trailers(mut buf: BytesMut, count: usize) -> Result<HeaderMap, io::Error> {
+    let mut trailers = HeaderMap::new();
+    let mut raw_headers = vec![httparse::EMPTY_HEADER; count];
+    // the trailer section ends with an empty line
+    buf.put_slice(b"\r\n");
+
+    match httparse::parse_headers(&buf, &mut raw_headers) {
+        Ok(httparse::Status::Complete((_, raw_headers))) => {
+            for header in raw_headers.iter() {
+                let name = match HeaderName::from_bytes(header.name.as_bytes()) {
+                    Ok(name) => name,
+                    Err(_) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("Invalid header name: {:?}", &header),
+                        ));
+                    }
+                };
+
+                if !headers::is_valid_trailer_field(&name) {
+                    debug!("ignoring forbidden trailer field: {}", name);
+                    continue;
+                }
+
+                let value = match HeaderValue::from_bytes(header.value) {
+                    Ok(value) => value,
+                    Err(_) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("Invalid header value: {:?}", &header),
+                        ));
+                    }
+                };
+
+                trailers.append(name, value);
+            }
+
+            Ok(trailers)
+        }
+        Ok(httparse::Status::Partial) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Partial header",
+        )),
+        Err(e) => Err(io::Error::new(io::ErrorKind::InvalidInput, e)),
+    }
+}
+
 #[derive(Debug)]
 struct IncompleteBody;
 
@@ -480,9 +681,19 @@ mod tests {
             let rdr = &mut s.as_bytes();
             let mut size = 0;
             loop {
-                let result =
-                    futures_util::future::poll_fn(|cx| state.step(cx, rdr, &mut size, &mut None))
-                        .await;
+                let result = futures_util::future::poll_fn(|cx| {
+                    state.step(
+                        cx,
+                        rdr,
+                        &mut size,
+                        &mut None,
+                        &mut None,
+                        &mut 0,
+                        MAX_HEADERS,
+                        &mut None,
+                    )
+                })
+                .await;
                 let desc = format!("read_size failed for {:?}", s);
                 state = result.expect(desc.as_str());
                 if state == ChunkedState::Body || state == ChunkedState::EndCr {
@@ -497,9 +708,19 @@ mod tests {
             let rdr = &mut s.as_bytes();
             let mut size = 0;
             loop {
-                let result =
-                    futures_util::future::poll_fn(|cx| state.step(cx, rdr, &mut size, &mut None))
-                        .await;
+                let result = futures_util::future::poll_fn(|cx| {
+                    state.step(
+                        cx,
+                        rdr,
+                        &mut size,
+                        &mut None,
+                        &mut None,
+                        &mut 0,
+                        MAX_HEADERS,
+                        &mut None,
+                    )
+                })
+                .await;
                 state = match result {
                     Ok(s) => s,
                     Err(e) => {
@@ -595,6 +816,30 @@ mod tests {
         assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
     }
 
+    #[tokio::test]
+    async fn test_read_chunked_strict_size_whitespace() {
+        let mut mock_buf = &b"3 \r\nabc\r\n0\r\n\r\n"[..];
+        let mut decoder = Decoder::chunked();
+        let buf = decoder.decode_fut(&mut mock_buf).await.expect("decode");
+        assert_eq!(&buf[..], b"abc");
+
+        let mut mock_buf = &b"3 \r\nabc\r\n0\r\n\r\n"[..];
+        let mut decoder = Decoder::chunked().with_strict(true);
+        let e = decoder.decode_fut(&mut mock_buf).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(e.to_string(), "whitespace parsed in chunk size");
+
+        let mut mock_buf = &b"3;ext\r\nabc\r\n0\r\n\r\n"[..];
+        let mut decoder = Decoder::chunked().with_strict(true);
+        let buf = decoder.decode_fut(&mut mock_buf).await.expect("decode");
+        assert_eq!(&buf[..], b"abc");
+
+        let mut mock_buf = &b"3 ;ext\r\nabc\r\n0\r\n\r\n"[..];
+        let mut decoder = Decoder::chunked().with_strict(true);
+        let buf = decoder.decode_fut(&mut mock_buf).await.expect("decode");
+        assert_eq!(&buf[..], b"abc");
+    }
+
     #[tokio::test]
     async fn test_read_chunked_after_eof() {
         let mut mock_buf = &b"10\r\n1234567890abcdef\r\n0\r\n\r\n"[..];
@@ -615,6 +860,93 @@ mod tests {
         assert_eq!(0, buf.len());
     }
 
+    #[tokio::test]
+    async fn test_read_chunked_with_trailers() {
+        let mut mock_buf =
+            &b"10\r\n1234567890abcdef\r\n0\r\nchunky-trailer: header data\r\nx-more: yes\r\n\r\n"[..];
+        let mut decoder = Decoder::chunked();
+
+        let buf = decoder.decode_fut(&mut mock_buf).await.expect("decode");
+        assert_eq!(16, buf.len());
+        assert!(decoder.take_trailers().is_none());
+
+        let buf = decoder.decode_fut(&mut mock_buf).await.expect("decode");
+        assert_eq!(0, buf.len());
+        assert!(decoder.is_eof());
+
+        let trailers = decoder.take_trailers().expect("trailers");
+        assert_eq!(trailers.len(), 2);
+        assert_eq!(trailers["chunky-trailer"], "header data");
+        assert_eq!(trailers["x-more"], "yes");
+    }
+
+    #[tokio::test]
+    async fn test_read_chunked_with_forbidden_trailers() {
+        let mut mock_buf =
+            &b"0\r\ncontent-length: 10\r\nhost: hyper.rs\r\nchunky-trailer: header data\r\n\r\n"[..];
+        let mut decoder = Decoder::chunked();
+
+        let buf = decoder.decode_fut(&mut mock_buf).await.expect("decode");
+        assert_eq!(0, buf.len());
+
+        let trailers = decoder.take_trailers().expect("trailers");
+        assert_eq!(trailers.len(), 1);
+        assert_eq!(trailers["chunky-trailer"], "header data");
+    }
+
+    #[tokio::test]
+    async fn test_read_chunked_with_invalid_trailers() {
+        let mut mock_buf = &b"0\r\nbad trailer\r\n\r\n"[..];
+        let mut decoder = Decoder::chunked();
+        let e = decoder.decode_fut(&mut mock_buf).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn test_read_chunked_trailers_count_overflow() {
+        let mut body = b"0\r\n".to_vec();
+        for i in 0..=MAX_HEADERS {
+            body.extend_from_slice(format!("x-trailer-{}: v\r\n", i).as_bytes());
+        }
+        body.extend_from_slice(b"\r\n");
+
+        let mut mock_buf = &body[..];
+        let mut decoder = Decoder::chunked();
+        let e = decoder.decode_fut(&mut mock_buf).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn test_read_chunked_trailers_count_over_max_headers() {
+        let mut body = b"0\r\n".to_vec();
+        for i in 0..100 {
+            body.extend_from_slice(format!("x-trailer-{}: v\r\n", i).as_bytes());
+        }
+        body.extend_from_slice(b"\r\n");
+
+        let mut mock_buf = &body[..];
+        let mut decoder = Decoder::chunked().with_max_headers(Some(20));
+        let e = decoder.decode_fut(&mut mock_buf).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        let mut mock_buf = &body[..];
+        let mut decoder = Decoder::chunked().with_max_headers(Some(100));
+        assert!(decoder.decode_fut(&mut mock_buf).await.unwrap().is_empty());
+        assert_eq!(decoder.take_trailers().expect("trailers").len(), 100);
+    }
+
+    #[tokio::test]
+    async fn test_read_chunked_trailers_bytes_over_limit() {
+        let mut body = b"0\r\nx-trailer: ".to_vec();
+        body.extend(std::iter::repeat(b'a').take(TRAILER_LIMIT));
+        body.extend_from_slice(b"\r\n\r\n");
+
+        let mut mock_buf = &body[..];
+        let mut decoder = Decoder::chunked();
+        let e = decoder.decode_fut(&mut mock_buf).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
     // perform an async read using a custom buffer size and causing a blocking
     // read at the specified byte
     async fn read_async(mut decoder: Decoder, content: &[u8], block_at: usize) -> String {
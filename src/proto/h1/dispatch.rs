@@ -7,7 +7,10 @@ use tracing::{debug, trace};
 
 use super::{Http1Transaction, Wants};
 use crate::body::{Recv, DecodedLength, HttpBody};
+#[cfg(all(feature = "client", feature = "runtime"))]
+use crate::client::dispatch::ResponseTimer;
 use crate::common::{task, Future, Pin, Poll, Unpin};
+use crate::ext::OutgoingChunkExtensions;
 use crate::proto::{BodyLength, Conn, Dispatched, MessageHead, RequestHead};
 use crate::upgrade::OnUpgrade;
 
@@ -16,6 +19,14 @@ pub(crate) struct Dispatcher<D, Bs: HttpBody, I, T> {
     dispatch: D,
     body_tx: Option<crate::body::Sender>,
     body_rx: Pin<Box<Option<Bs>>>,
+    /// Set once `body_rx` has yielded all its data, and only the
+    /// trailers are left to poll.
+    body_data_done: bool,
+    /// Sets the chunk extensions of the data polled from `body_rx`.
+    body_chunk_extensions: Option<OutgoingChunkExtensions>,
+    /// The timeouts of the response body being received.
+    #[cfg(all(feature = "client", feature = "runtime"))]
+    body_timer: Option<ResponseTimer>,
     is_closing: bool,
 }
 
@@ -31,27 +42,67 @@ pub(crate) trait Dispatch {
     fn recv_msg(&mut self, msg: crate::Result<(Self::RecvItem, Recv)>) -> crate::Result<()>;
     fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> Poll<Result<(), ()>>;
     fn should_poll(&self) -> bool;
+
+    /// Polls for an informational message to write before the message of
+    /// `poll_msg`.
+    #[cfg(feature = "server")]
+    fn poll_informational(&mut self, cx: &mut task::Context<'_>) -> Poll<Option<Self::PollItem>> {
+        let _ = cx;
+        Poll::Ready(None)
+    }
+
+    /// Takes the timeouts of the body of the message last received.
+    #[cfg(all(feature = "client", feature = "runtime"))]
+    fn take_body_timer(&mut self) -> Option<ResponseTimer> {
+        None
+    }
 }
 
 cfg_server! {
+    use crate::ext::InformationalReceiver;
     use crate::service::HttpService;
 
     pub(crate) struct Server<S: HttpService<B>, B> {
         in_flight: Pin<Box<Option<S::Future>>>,
+        /// The informational responses sent for the request in flight,
+        /// created with the first request.
+        informational: Option<InformationalReceiver>,
         pub(crate) service: S,
+        /// Whether the next request may upgrade the connection to h2c.
+        #[cfg(feature = "http2")]
+        h2c_upgrade: bool,
+        #[cfg(feature = "http2")]
+        h2c_request: Option<RequestHead>,
     }
 }
 
 cfg_client! {
-    pin_project_lite::pin_project! {
-        pub(crate) struct Client<B> {
-            callback: Option<crate::client::dispatch::Callback<Request<B>, http::Response<Recv>>>,
-            #[pin]
-            rx: ClientRx<B>,
-            rx_closed: bool,
-        }
+    #[cfg(feature = "runtime")]
+    use crate::ext::RequestTimeouts;
+
+    pub(crate) struct Client<B> {
+        callback: Option<ClientCallback<B>>,
+        rx: ClientRx<B>,
+        rx_closed: bool,
+        /// The `HTTP2-Settings` to ask for an h2c upgrade with the next request.
+        #[cfg(feature = "http2")]
+        h2c_settings: Option<http::HeaderValue>,
+        /// Whether the request waiting for its response asked for an h2c
+        /// upgrade.
+        #[cfg(feature = "http2")]
+        h2c_requested: bool,
+        #[cfg(feature = "http2")]
+        h2c_upgrade: Option<ClientCallback<B>>,
+        /// The timeouts of requests without their own `RequestTimeouts`.
+        #[cfg(feature = "runtime")]
+        timeouts: RequestTimeouts,
+        /// The timeouts of the response in flight.
+        #[cfg(feature = "runtime")]
+        timer: Option<ResponseTimer>,
     }
 
+    type ClientCallback<B> = crate::client::dispatch::Callback<Request<B>, http::Response<Recv>>;
+
     type ClientRx<B> = crate::client::dispatch::Receiver<Request<B>, http::Response<Recv>>;
 }
 
@@ -74,6 +125,10 @@ where
             dispatch,
             body_tx: None,
             body_rx: Box::pin(None),
+            body_data_done: false,
+            body_chunk_extensions: None,
+            #[cfg(all(feature = "client", feature = "runtime"))]
+            body_timer: None,
             is_closing: false,
         }
     }
@@ -185,10 +240,29 @@ where
             } else if self.conn.can_read_head() {
                 ready!(self.poll_read_head(cx))?;
             } else if let Some(mut body) = self.body_tx.take() {
+                #[cfg(feature = "server")]
+                self.conn.poll_expect_continue(cx);
                 if self.conn.can_read_body() {
                     match body.poll_ready(cx) {
-                        Poll::Ready(Ok(())) => (),
+                        Poll::Ready(Ok(())) => {
+                            // The body is read, so its data is expected.
+                            #[cfg(all(feature = "client", feature = "runtime"))]
+                            if let Some(ref mut timer) = self.body_timer {
+                                timer.set_body_wanted(true);
+                            }
+                        }
                         Poll::Pending => {
+                            #[cfg(all(feature = "server", feature = "runtime"))]
+                            self.conn.pause_read_body_timer();
+                            #[cfg(all(feature = "client", feature = "runtime"))]
+                            {
+                                if let Some(ref mut timer) = self.body_timer {
+                                    timer.set_body_wanted(false);
+                                }
+                                if self.poll_body_timeout(cx, &mut body).is_ready() {
+                                    continue;
+                                }
+                            }
                             self.body_tx = Some(body);
                             return Poll::Pending;
                         }
@@ -201,31 +275,60 @@ where
                         }
                     }
                     match self.conn.poll_read_body(cx) {
-                        Poll::Ready(Some(Ok(chunk))) => match body.try_send_data(chunk) {
-                            Ok(()) => {
-                                self.body_tx = Some(body);
+                        Poll::Ready(Some(Ok(chunk))) => {
+                            #[cfg(all(feature = "client", feature = "runtime"))]
+                            if let Some(ref mut timer) = self.body_timer {
+                                timer.on_body_data();
                             }
-                            Err(_canceled) => {
-                                if self.conn.can_read_body() {
-                                    trace!("body receiver dropped before eof, closing");
-                                    self.conn.close_read();
+                            let sent = match self.conn.take_chunk_extensions() {
+                                Some(ext) => body.try_send_data_with_extensions(chunk, ext),
+                                None => body.try_send_data(chunk),
+                            };
+                            match sent {
+                                Ok(()) => {
+                                    self.body_tx = Some(body);
+                                }
+                                Err(_canceled) => {
+                                    if self.conn.can_read_body() {
+                                        trace!("body receiver dropped before eof, closing");
+                                        self.conn.close_read();
+                                    }
                                 }
                             }
-                        },
+                        }
                         Poll::Ready(None) => {
+                            if let Some(trailers) = self.conn.take_trailers() {
+                                if body.try_send_trailers(trailers).is_err() {
+                                    trace!("body receiver dropped before trailers");
+                                }
+                            }
                             // just drop, the body will close automatically
                         }
                         Poll::Pending => {
+                            #[cfg(all(feature = "client", feature = "runtime"))]
+                            if self.poll_body_timeout(cx, &mut body).is_ready() {
+                                continue;
+                            }
                             self.body_tx = Some(body);
                             return Poll::Pending;
                         }
                         Poll::Ready(Some(Err(e))) => {
-                            body.send_error(crate::Error::new_body(e));
+                            body.send_error(e);
                         }
                     }
                 } else {
                     // just drop, the body will close automatically
                 }
+                #[cfg(all(feature = "client", feature = "runtime"))]
+                if self.body_tx.is_none() {
+                    self.body_timer = None;
+                }
+            } else if self.conn.can_read_body() {
+                // A refused body, read after the response without a receiver.
+                self.conn.poll_drain_or_close_read(cx);
+                if self.conn.can_read_body() {
+                    return Poll::Pending;
+                }
             } else {
                 return self.conn.poll_read_keep_alive(cx);
             }
@@ -260,6 +363,11 @@ where
                     head.extensions.insert(upgrade);
                 }
                 self.dispatch.recv_msg(Ok((head, body)))?;
+                #[cfg(all(feature = "client", feature = "runtime"))]
+                {
+                    let timer = self.dispatch.take_body_timer();
+                    self.body_timer = if self.body_tx.is_some() { timer } else { None };
+                }
                 Poll::Ready(Ok(()))
             }
             Some(Err(err)) => {
@@ -292,9 +400,19 @@ where
                 && self.conn.can_write_head()
                 && self.dispatch.should_poll()
             {
-                if let Some(msg) = ready!(Pin::new(&mut self.dispatch).poll_msg(cx)) {
-                    let (head, body) = msg.map_err(crate::Error::new_user_service)?;
+                let msg = Pin::new(&mut self.dispatch).poll_msg(cx);
+
+                // Informational messages are written first, including those
+                // sent right before the final message was ready.
+                #[cfg(feature = "server")]
+                while let Poll::Ready(Some(head)) = self.dispatch.poll_informational(cx) {
+                    self.conn.write_informational(head);
+                }
+
+                if let Some(msg) = ready!(msg) {
+                    let (mut head, body) = msg.map_err(crate::Error::new_user_service)?;
 
+                    self.body_chunk_extensions = head.extensions.remove();
                     let body_type = if body.is_end_stream() {
                         self.body_rx.set(None);
                         None
@@ -305,6 +423,7 @@ where
                             .map(BodyLength::Known)
                             .or_else(|| Some(BodyLength::Unknown));
                         self.body_rx.set(Some(body));
+                        self.body_data_done = false;
                         btype
                     };
                     self.conn.write_head(head, body_type);
@@ -320,6 +439,8 @@ where
                     OptGuard::new(self.body_rx.as_mut()).guard_mut()
                 {
                     debug_assert!(!*clear_body, "opt guard defaults to keeping body");
+                    #[cfg(all(feature = "client", feature = "runtime"))]
+                    ready!(self.conn.poll_continue_wait(cx));
                     if !self.conn.can_write_body() {
                         trace!(
                             "no more write body allowed, user body is_end_stream = {}",
@@ -329,18 +450,39 @@ where
                         continue;
                     }
 
+                    if self.body_data_done {
+                        let trailers = ready!(body.as_mut().poll_trailers(cx)).map_err(|e| {
+                            *clear_body = true;
+                            crate::Error::new_user_body(e)
+                        })?;
+                        *clear_body = true;
+                        if let Some(trailers) = trailers {
+                            self.conn.write_trailers(trailers)?;
+                        } else {
+                            self.conn.end_body()?;
+                        }
+                        continue;
+                    }
+
                     let item = ready!(body.as_mut().poll_data(cx));
                     if let Some(item) = item {
                         let chunk = item.map_err(|e| {
                             *clear_body = true;
                             crate::Error::new_user_body(e)
                         })?;
+                        let ext = self
+                            .body_chunk_extensions
+                            .as_ref()
+                            .and_then(OutgoingChunkExtensions::take);
                         let eos = body.is_end_stream();
                         if eos {
                             *clear_body = true;
                             if chunk.remaining() == 0 {
                                 trace!("discarding empty chunk");
                                 self.conn.end_body()?;
+                            } else if ext.is_some() {
+                                self.conn.write_body(chunk, ext.as_ref());
+                                self.conn.end_body()?;
                             } else {
                                 self.conn.write_body_and_end(chunk);
                             }
@@ -349,11 +491,11 @@ where
                                 trace!("discarding empty chunk");
                                 continue;
                             }
-                            self.conn.write_body(chunk);
+                            self.conn.write_body(chunk, ext.as_ref());
                         }
                     } else {
-                        *clear_body = true;
-                        self.conn.end_body()?;
+                        // loop again to poll_trailers
+                        self.body_data_done = true;
                     }
                 } else {
                     return Poll::Pending;
@@ -363,10 +505,37 @@ where
     }
 
     fn poll_flush(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Result<()>> {
-        self.conn.poll_flush(cx).map_err(|err| {
+        let flushed = self.conn.poll_flush(cx).map_err(|err| {
             debug!("error writing: {}", err);
             crate::Error::new_body_write(err)
-        })
+        });
+        #[cfg(all(feature = "server", feature = "runtime"))]
+        if flushed.is_pending() {
+            if let Poll::Ready(err) = self.conn.poll_write_body_timeout(cx) {
+                return Poll::Ready(Err(err));
+            }
+        }
+        flushed
+    }
+
+    /// Polls the timeouts of the response body being received. If one
+    /// expired, the body gets the timeout error and the connection closes.
+    #[cfg(all(feature = "client", feature = "runtime"))]
+    fn poll_body_timeout(
+        &mut self,
+        cx: &mut task::Context<'_>,
+        body: &mut crate::body::Sender,
+    ) -> Poll<()> {
+        if let Some(ref mut timer) = self.body_timer {
+            if let Poll::Ready(err) = timer.poll_expired(cx) {
+                debug!("response body timed out: {}", err);
+                self.body_timer = None;
+                body.send_error(err);
+                self.close();
+                return Poll::Ready(());
+            }
+        }
+        Poll::Pending
     }
 
     fn close(&mut self) {
@@ -448,10 +617,26 @@ cfg_server! {
         pub(crate) fn new(service: S) -> Server<S, B> {
             Server {
                 in_flight: Box::pin(None),
+                informational: None,
                 service,
+                #[cfg(feature = "http2")]
+                h2c_upgrade: false,
+                #[cfg(feature = "http2")]
+                h2c_request: None,
             }
         }
 
+        #[cfg(feature = "http2")]
+        pub(crate) fn set_h2c_upgrade(&mut self) {
+            self.h2c_upgrade = true;
+        }
+
+        /// Takes the request that asked to upgrade the connection to h2c.
+        #[cfg(feature = "http2")]
+        pub(crate) fn take_h2c_request(&mut self) -> Option<RequestHead> {
+            self.h2c_request.take()
+        }
+
         pub(crate) fn into_service(self) -> S {
             self.service
         }
@@ -497,12 +682,30 @@ cfg_server! {
 
         fn recv_msg(&mut self, msg: crate::Result<(Self::RecvItem, Recv)>) -> crate::Result<()> {
             let (msg, body) = msg?;
+
+            // Only the first request of a connection can upgrade it to h2c,
+            // and its body must have been sent completely.
+            #[cfg(feature = "http2")]
+            if std::mem::replace(&mut self.h2c_upgrade, false)
+                && body.is_end_stream()
+                && crate::proto::h2::h2c::is_upgrade_request(&msg)
+            {
+                debug!("upgrading connection to h2c");
+                self.h2c_request = Some(msg);
+                return Err(crate::Error::new_h2c_upgrade());
+            }
+
             let mut req = Request::new(body);
             *req.method_mut() = msg.subject.0;
             *req.uri_mut() = msg.subject.1;
             *req.headers_mut() = msg.headers;
             *req.version_mut() = msg.version;
             *req.extensions_mut() = msg.extensions;
+            let informational = self
+                .informational
+                .get_or_insert_with(InformationalReceiver::new)
+                .sender();
+            req.extensions_mut().insert(informational);
             let fut = self.service.call(req);
             self.in_flight.set(Some(fut));
             Ok(())
@@ -522,6 +725,34 @@ cfg_server! {
         fn should_poll(&self) -> bool {
             self.in_flight.is_some()
         }
+
+        fn poll_informational(
+            &mut self,
+            cx: &mut task::Context<'_>,
+        ) -> Poll<Option<Self::PollItem>> {
+            let rx = match self.informational {
+                Some(ref mut rx) => rx,
+                None => return Poll::Ready(None),
+            };
+            match rx.poll_next(cx) {
+                Poll::Ready(res) => {
+                    let (parts, ()) = res.into_parts();
+                    Poll::Ready(Some(MessageHead {
+                        version: parts.version,
+                        subject: parts.status,
+                        headers: parts.headers,
+                        extensions: parts.extensions,
+                    }))
+                }
+                Poll::Pending if self.in_flight.is_some() => Poll::Pending,
+                // Once the final response is ready, no informational response
+                // can be sent anymore.
+                Poll::Pending => {
+                    rx.close();
+                    Poll::Ready(None)
+                }
+            }
+        }
     }
 }
 
@@ -534,8 +765,39 @@ cfg_client! {
                 callback: None,
                 rx,
                 rx_closed: false,
+                #[cfg(feature = "http2")]
+                h2c_settings: None,
+                #[cfg(feature = "http2")]
+                h2c_requested: false,
+                #[cfg(feature = "http2")]
+                h2c_upgrade: None,
+                #[cfg(feature = "runtime")]
+                timeouts: RequestTimeouts::default(),
+                #[cfg(feature = "runtime")]
+                timer: None,
             }
         }
+
+        /// Sets the timeouts of the requests sent without their own.
+        #[cfg(feature = "runtime")]
+        pub(crate) fn set_timeouts(&mut self, timeouts: RequestTimeouts) {
+            self.timeouts = timeouts;
+        }
+
+        /// Asks to upgrade the connection to h2c with the first request,
+        /// sending `settings` as its `HTTP2-Settings`.
+        #[cfg(feature = "http2")]
+        pub(crate) fn set_h2c_upgrade(&mut self, settings: http::HeaderValue) {
+            self.h2c_settings = Some(settings);
+        }
+
+        /// Returns the callback of the request that upgraded the connection
+        /// to h2c, and the receiver of the next requests.
+        #[cfg(feature = "http2")]
+        pub(crate) fn into_h2c_upgrade(self) -> Option<(ClientCallback<B>, ClientRx<B>)> {
+            let cb = self.h2c_upgrade?;
+            Some((cb, self.rx))
+        }
     }
 
     impl<B> Dispatch for Client<B>
@@ -563,12 +825,58 @@ cfg_client! {
                         }
                         Poll::Pending => {
                             let (parts, body) = req.into_parts();
-                            let head = RequestHead {
+                            #[cfg_attr(
+                                not(any(feature = "http2", feature = "runtime")),
+                                allow(unused_mut)
+                            )]
+                            let mut head = RequestHead {
                                 version: parts.version,
                                 subject: crate::proto::RequestLine(parts.method, parts.uri),
                                 headers: parts.headers,
                                 extensions: parts.extensions,
                             };
+
+                            // Only the first request can upgrade the connection to
+                            // h2c, and it must not have a body.
+                            #[cfg(feature = "http2")]
+                            if let Some(settings) = this.h2c_settings.take() {
+                                if body.is_end_stream()
+                                    && head.version == http::Version::HTTP_11
+                                    && head.subject.0 != http::Method::CONNECT
+                                    && !head.headers.contains_key(http::header::UPGRADE)
+                                {
+                                    this.h2c_requested = true;
+
+                                    let headers = &mut head.headers;
+                                    headers.append(
+                                        http::header::CONNECTION,
+                                        http::HeaderValue::from_static("Upgrade, HTTP2-Settings"),
+                                    );
+                                    headers.insert(
+                                        http::header::UPGRADE,
+                                        http::HeaderValue::from_static("h2c"),
+                                    );
+                                    headers.insert("http2-settings", settings);
+                                }
+                            }
+
+                            #[cfg(feature = "runtime")]
+                            {
+                                let timeouts = match head.extensions.remove::<RequestTimeouts>() {
+                                    Some(timeouts) => timeouts.or(this.timeouts),
+                                    None => this.timeouts,
+                                };
+                                this.timer = ResponseTimer::new(&timeouts);
+                                // Polled once to be woken when it expires, as
+                                // `poll_ready` may only be called once the
+                                // connection is woken by reading.
+                                if let Some(ref mut timer) = this.timer {
+                                    if timer.poll_expired(cx).is_ready() {
+                                        cx.waker().wake_by_ref();
+                                    }
+                                }
+                            }
+
                             this.callback = Some(cb);
                             Poll::Ready(Some(Ok((head, body))))
                         }
@@ -587,6 +895,18 @@ cfg_client! {
         fn recv_msg(&mut self, msg: crate::Result<(Self::RecvItem, Recv)>) -> crate::Result<()> {
             match msg {
                 Ok((msg, body)) => {
+                    #[cfg(feature = "http2")]
+                    if std::mem::replace(&mut self.h2c_requested, false) {
+                        if crate::proto::h2::h2c::is_upgrade_response(&msg) {
+                            if let Some(cb) = self.callback.take() {
+                                // The response will be received over HTTP/2.
+                                debug!("upgrading connection to h2c");
+                                self.h2c_upgrade = Some(cb);
+                                return Ok(());
+                            }
+                        }
+                    }
+
                     if let Some(cb) = self.callback.take() {
                         let res = msg.into_response(body);
                         cb.send(Ok(res));
@@ -599,6 +919,10 @@ cfg_client! {
                     }
                 }
                 Err(err) => {
+                    #[cfg(feature = "runtime")]
+                    {
+                        self.timer = None;
+                    }
                     if let Some(cb) = self.callback.take() {
                         cb.send(Err((err, None)));
                         Ok(())
@@ -627,12 +951,30 @@ cfg_client! {
                         trace!("callback receiver has dropped");
                         Poll::Ready(Err(()))
                     }
-                    Poll::Pending => Poll::Ready(Ok(())),
+                    Poll::Pending => {
+                        #[cfg(feature = "runtime")]
+                        if let Some(ref mut timer) = self.timer {
+                            if let Poll::Ready(err) = timer.poll_expired(cx) {
+                                debug!("response head timed out: {}", err);
+                                self.timer = None;
+                                if let Some(cb) = self.callback.take() {
+                                    cb.send(Err((err, None)));
+                                }
+                                return Poll::Ready(Err(()));
+                            }
+                        }
+                        Poll::Ready(Ok(()))
+                    }
                 },
                 None => Poll::Ready(Err(())),
             }
         }
 
+        #[cfg(feature = "runtime")]
+        fn take_body_timer(&mut self) -> Option<ResponseTimer> {
+            self.timer.take().and_then(ResponseTimer::into_body_timer)
+        }
+
         fn should_poll(&self) -> bool {
             self.callback.is_none()
         }
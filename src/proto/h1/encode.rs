@@ -1,11 +1,15 @@
 use std::fmt;
-use std::io::IoSlice;
+use std::io::{IoSlice, Write};
 
 use bytes::buf::{Chain, Take};
-use bytes::Buf;
-use tracing::trace;
+use bytes::{Buf, Bytes};
+use http::{HeaderMap, HeaderName, HeaderValue};
+use tracing::{debug, trace};
 
 use super::io::WriteBuf;
+use super::role::{write_headers, write_headers_title_case};
+use crate::ext::ChunkExtensions;
+use crate::headers;
 
 type StaticBuf = &'static [u8];
 
@@ -27,7 +31,10 @@ pub(crate) struct NotEof(u64);
 #[derive(Debug, PartialEq, Clone)]
 enum Kind {
     /// An Encoder for when Transfer-Encoding includes `chunked`.
-    Chunked,
+    ///
+    /// If the message declared a `Trailer` header, its values are kept so
+    /// that only the announced fields are sent in the trailer section.
+    Chunked(Option<Vec<HeaderValue>>),
     /// An Encoder for when Content-Length is set.
     ///
     /// Enforces that the body is not longer than the Content-Length header.
@@ -45,7 +52,9 @@ enum BufKind<B> {
     Exact(B),
     Limited(Take<B>),
     Chunked(Chain<Chain<ChunkSize, B>, StaticBuf>),
+    ChunkedWithExtensions(Chain<Chain<Bytes, B>, StaticBuf>),
     ChunkedEnd(StaticBuf),
+    Trailers(Chain<Chain<StaticBuf, Bytes>, StaticBuf>),
 }
 
 impl Encoder {
@@ -56,7 +65,7 @@ impl Encoder {
         }
     }
     pub(crate) fn chunked() -> Encoder {
-        Encoder::new(Kind::Chunked)
+        Encoder::new(Kind::Chunked(None))
     }
 
     pub(crate) fn length(len: u64) -> Encoder {
@@ -82,6 +91,18 @@ impl Encoder {
         self.is_last
     }
 
+    /// Remember the values of the `Trailer` header, if this is a chunked
+    /// encoder. Other encoders cannot send trailers and are left alone.
+    pub(crate) fn into_chunked_with_trailing_fields(self, trailers: Vec<HeaderValue>) -> Encoder {
+        match self.kind {
+            Kind::Chunked(_) => Encoder {
+                kind: Kind::Chunked(Some(trailers)),
+                is_last: self.is_last,
+            },
+            _ => self,
+        }
+    }
+
     pub(crate) fn is_close_delimited(&self) -> bool {
         match self.kind {
             #[cfg(feature = "server")]
@@ -93,7 +114,7 @@ impl Encoder {
     pub(crate) fn end<B>(&self) -> Result<Option<EncodedBuf<B>>, NotEof> {
         match self.kind {
             Kind::Length(0) => Ok(None),
-            Kind::Chunked => Ok(Some(EncodedBuf {
+            Kind::Chunked(_) => Ok(Some(EncodedBuf {
                 kind: BufKind::ChunkedEnd(b"0\r\n\r\n"),
             })),
             #[cfg(feature = "server")]
@@ -110,7 +131,7 @@ impl Encoder {
         debug_assert!(len > 0, "encode() called with empty buf");
 
         let kind = match self.kind {
-            Kind::Chunked => {
+            Kind::Chunked(_) => {
                 trace!("encoding chunked {}B", len);
                 let buf = ChunkSize::new(len)
                     .chain(msg)
@@ -137,6 +158,41 @@ impl Encoder {
         EncodedBuf { kind }
     }
 
+    /// Encodes a chunk along with its chunk extensions.
+    ///
+    /// The extensions are only written for chunked bodies, other kinds
+    /// encode the data as `encode()` does.
+    pub(crate) fn encode_with_extensions<B>(
+        &mut self,
+        msg: B,
+        ext: &ChunkExtensions,
+    ) -> EncodedBuf<B>
+    where
+        B: Buf,
+    {
+        match self.kind {
+            Kind::Chunked(_) => {
+                let len = msg.remaining();
+                debug_assert!(len > 0, "encode() called with empty buf");
+                trace!("encoding chunked {}B with extensions", len);
+
+                let ext = ext.as_bytes();
+                let mut size_line = Vec::with_capacity(CHUNK_SIZE_MAX_BYTES + ext.len() + 3);
+                write!(&mut size_line, "{:X};", len).expect("Vec<u8>.write() cannot error");
+                size_line.extend_from_slice(ext);
+                size_line.extend_from_slice(b"\r\n");
+
+                let buf = Bytes::from(size_line)
+                    .chain(msg)
+                    .chain(b"\r\n" as &'static [u8]);
+                EncodedBuf {
+                    kind: BufKind::ChunkedWithExtensions(buf),
+                }
+            }
+            _ => self.encode(msg),
+        }
+    }
+
     pub(super) fn encode_and_end<B>(&self, msg: B, dst: &mut WriteBuf<EncodedBuf<B>>) -> bool
     where
         B: Buf,
@@ -145,7 +201,7 @@ impl Encoder {
         debug_assert!(len > 0, "encode() called with empty buf");
 
         match self.kind {
-            Kind::Chunked => {
+            Kind::Chunked(_) => {
                 trace!("encoding chunked {}B", len);
                 let buf = ChunkSize::new(len)
                     .chain(msg)
@@ -180,6 +236,74 @@ impl Encoder {
             }
         }
     }
+
+    /// Encodes the last chunk along with a trailer section.
+    ///
+    /// Only fields announced in the `Trailer` header are written, and fields
+    /// that are not allowed in trailers are dropped. Returns `None` if there
+    /// is nothing left to send, in which case the body should be ended
+    /// normally with `end()`.
+    pub(crate) fn encode_trailers<B>(
+        &self,
+        trailers: HeaderMap,
+        title_case_headers: bool,
+    ) -> Option<EncodedBuf<B>> {
+        trace!("encoding trailers");
+        let allowed = match self.kind {
+            Kind::Chunked(Some(ref allowed)) => allowed,
+            Kind::Chunked(None) => {
+                debug!("trailers were not announced with a Trailer header, skipping");
+                return None;
+            }
+            _ => {
+                debug!("attempted to encode trailers for non-chunked body");
+                return None;
+            }
+        };
+
+        let mut filtered = HeaderMap::new();
+        let mut cur_name = None;
+        for (opt_name, value) in trailers {
+            if let Some(n) = opt_name {
+                cur_name = Some(n);
+            }
+            let name = cur_name.as_ref().expect("current header name");
+            if !is_announced_trailer(allowed, name) {
+                debug!("trailer field not announced in Trailer header: {}", name);
+            } else if !headers::is_valid_trailer_field(name) {
+                debug!("trailer field is not allowed in trailers: {}", name);
+            } else {
+                filtered.append(name.clone(), value);
+            }
+        }
+
+        if filtered.is_empty() {
+            return None;
+        }
+
+        let mut buf = Vec::new();
+        if title_case_headers {
+            write_headers_title_case(&filtered, &mut buf);
+        } else {
+            write_headers(&filtered, &mut buf);
+        }
+
+        Some(EncodedBuf {
+            kind: BufKind::Trailers(
+                (b"0\r\n" as StaticBuf)
+                    .chain(Bytes::from(buf))
+                    .chain(b"\r\n" as StaticBuf),
+            ),
+        })
+    }
+}
+
+fn is_announced_trailer(allowed: &[HeaderValue], name: &HeaderName) -> bool {
+    allowed
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|field| field.trim().eq_ignore_ascii_case(name.as_str()))
 }
 
 impl<B> Buf for EncodedBuf<B>
@@ -192,7 +316,9 @@ where
             BufKind::Exact(ref b) => b.remaining(),
             BufKind::Limited(ref b) => b.remaining(),
             BufKind::Chunked(ref b) => b.remaining(),
+            BufKind::ChunkedWithExtensions(ref b) => b.remaining(),
             BufKind::ChunkedEnd(ref b) => b.remaining(),
+            BufKind::Trailers(ref b) => b.remaining(),
         }
     }
 
@@ -202,7 +328,9 @@ where
             BufKind::Exact(ref b) => b.chunk(),
             BufKind::Limited(ref b) => b.chunk(),
             BufKind::Chunked(ref b) => b.chunk(),
+            BufKind::ChunkedWithExtensions(ref b) => b.chunk(),
             BufKind::ChunkedEnd(ref b) => b.chunk(),
+            BufKind::Trailers(ref b) => b.chunk(),
         }
     }
 
@@ -212,7 +340,9 @@ where
             BufKind::Exact(ref mut b) => b.advance(cnt),
             BufKind::Limited(ref mut b) => b.advance(cnt),
             BufKind::Chunked(ref mut b) => b.advance(cnt),
+            BufKind::ChunkedWithExtensions(ref mut b) => b.advance(cnt),
             BufKind::ChunkedEnd(ref mut b) => b.advance(cnt),
+            BufKind::Trailers(ref mut b) => b.advance(cnt),
         }
     }
 
@@ -222,7 +352,9 @@ where
             BufKind::Exact(ref b) => b.chunks_vectored(dst),
             BufKind::Limited(ref b) => b.chunks_vectored(dst),
             BufKind::Chunked(ref b) => b.chunks_vectored(dst),
+            BufKind::ChunkedWithExtensions(ref b) => b.chunks_vectored(dst),
             BufKind::ChunkedEnd(ref b) => b.chunks_vectored(dst),
+            BufKind::Trailers(ref b) => b.chunks_vectored(dst),
         }
     }
 }
@@ -328,10 +460,15 @@ impl std::error::Error for NotEof {}
 
 #[cfg(test)]
 mod tests {
+    use std::iter::FromIterator;
+
     use bytes::BufMut;
+    use http::header::{AUTHORIZATION, CONTENT_LENGTH, HOST, TE, TRAILER, TRANSFER_ENCODING};
+    use http::{HeaderMap, HeaderName, HeaderValue};
 
     use super::super::io::Cursor;
     use super::Encoder;
+    use crate::ext::ChunkExtensions;
 
     #[test]
     fn chunked() {
@@ -358,6 +495,165 @@ mod tests {
         );
     }
 
+    #[test]
+    fn chunked_with_extensions() {
+        let mut encoder = Encoder::chunked();
+        let mut dst = Vec::new();
+
+        let ext = ChunkExtensions::from_static(b"sig=abc;offset=0");
+        let msg1 = b"foo bar".as_ref();
+        let buf1 = encoder.encode_with_extensions(msg1, &ext);
+        dst.put(buf1);
+        assert_eq!(dst, b"7;sig=abc;offset=0\r\nfoo bar\r\n");
+
+        let msg2 = b"baz".as_ref();
+        let buf2 = encoder.encode(msg2);
+        dst.put(buf2);
+        assert_eq!(dst, b"7;sig=abc;offset=0\r\nfoo bar\r\n3\r\nbaz\r\n");
+    }
+
+    #[test]
+    fn length_ignores_extensions() {
+        let mut encoder = Encoder::length(3);
+        let mut dst = Vec::new();
+
+        let ext = ChunkExtensions::from_static(b"sig=abc");
+        let buf = encoder.encode_with_extensions(b"foo".as_ref(), &ext);
+        dst.put(buf);
+        assert_eq!(dst, b"foo");
+        assert!(encoder.is_eof());
+    }
+
+    #[test]
+    fn chunked_with_valid_trailers() {
+        let encoder = Encoder::chunked();
+        let trailers = vec![HeaderValue::from_static("chunky-trailer")];
+        let encoder = encoder.into_chunked_with_trailing_fields(trailers);
+
+        let headers = HeaderMap::from_iter(vec![
+            (
+                HeaderName::from_static("chunky-trailer"),
+                HeaderValue::from_static("header data"),
+            ),
+            (
+                HeaderName::from_static("should-not-be-included"),
+                HeaderValue::from_static("oops"),
+            ),
+        ]);
+
+        let buf1 = encoder.encode_trailers::<&[u8]>(headers, false).unwrap();
+
+        let mut dst = Vec::new();
+        dst.put(buf1);
+        assert_eq!(dst, b"0\r\nchunky-trailer: header data\r\n\r\n");
+    }
+
+    #[test]
+    fn chunked_with_multiple_trailer_headers() {
+        let encoder = Encoder::chunked();
+        let trailers = vec![
+            HeaderValue::from_static("chunky-trailer"),
+            HeaderValue::from_static("chunky-trailer-2, Chunky-Trailer-3"),
+        ];
+        let encoder = encoder.into_chunked_with_trailing_fields(trailers);
+
+        let headers = HeaderMap::from_iter(vec![
+            (
+                HeaderName::from_static("chunky-trailer"),
+                HeaderValue::from_static("header data"),
+            ),
+            (
+                HeaderName::from_static("chunky-trailer-2"),
+                HeaderValue::from_static("more header data"),
+            ),
+            (
+                HeaderName::from_static("chunky-trailer-3"),
+                HeaderValue::from_static("even more"),
+            ),
+        ]);
+
+        let buf1 = encoder.encode_trailers::<&[u8]>(headers, false).unwrap();
+
+        let mut dst = Vec::new();
+        dst.put(buf1);
+        assert_eq!(
+            dst,
+            b"0\r\nchunky-trailer: header data\r\nchunky-trailer-2: more header data\r\nchunky-trailer-3: even more\r\n\r\n"
+                .as_ref()
+        );
+    }
+
+    #[test]
+    fn chunked_with_no_trailer_header() {
+        let encoder = Encoder::chunked();
+
+        let headers = HeaderMap::from_iter(vec![(
+            HeaderName::from_static("chunky-trailer"),
+            HeaderValue::from_static("header data"),
+        )]);
+
+        assert!(encoder
+            .encode_trailers::<&[u8]>(headers.clone(), false)
+            .is_none());
+
+        let trailers = vec![];
+        let encoder = encoder.into_chunked_with_trailing_fields(trailers);
+
+        assert!(encoder.encode_trailers::<&[u8]>(headers, false).is_none());
+    }
+
+    #[test]
+    fn chunked_with_invalid_trailers() {
+        let encoder = Encoder::chunked();
+
+        let trailers = vec![HeaderValue::from_static(
+            "content-length, transfer-encoding, host, te, trailer, authorization",
+        )];
+        let encoder = encoder.into_chunked_with_trailing_fields(trailers);
+
+        let headers = HeaderMap::from_iter(vec![
+            (CONTENT_LENGTH, HeaderValue::from_static("10")),
+            (TRANSFER_ENCODING, HeaderValue::from_static("chunked")),
+            (HOST, HeaderValue::from_static("hyper.rs")),
+            (TE, HeaderValue::from_static("trailers")),
+            (TRAILER, HeaderValue::from_static("x-foo")),
+            (AUTHORIZATION, HeaderValue::from_static("secret")),
+        ]);
+
+        assert!(encoder.encode_trailers::<&[u8]>(headers, false).is_none());
+    }
+
+    #[test]
+    fn chunked_with_title_case_trailers() {
+        let encoder = Encoder::chunked();
+        let trailers = vec![HeaderValue::from_static("chunky-trailer")];
+        let encoder = encoder.into_chunked_with_trailing_fields(trailers);
+
+        let headers = HeaderMap::from_iter(vec![(
+            HeaderName::from_static("chunky-trailer"),
+            HeaderValue::from_static("header data"),
+        )]);
+        let buf1 = encoder.encode_trailers::<&[u8]>(headers, true).unwrap();
+
+        let mut dst = Vec::new();
+        dst.put(buf1);
+        assert_eq!(dst, b"0\r\nChunky-Trailer: header data\r\n\r\n");
+    }
+
+    #[test]
+    fn length_with_trailers() {
+        let encoder = Encoder::length(8);
+        let trailers = vec![HeaderValue::from_static("chunky-trailer")];
+        let encoder = encoder.into_chunked_with_trailing_fields(trailers);
+
+        let headers = HeaderMap::from_iter(vec![(
+            HeaderName::from_static("chunky-trailer"),
+            HeaderValue::from_static("header data"),
+        )]);
+
+        assert!(encoder.encode_trailers::<&[u8]>(headers, false).is_none());
+    }
+
     #[test]
     fn length() {
         let max_len = 8;
@@ -17,6 +17,7 @@ use tracing::{debug, trace};
 use super::{Http1Transaction, ParseContext, ParsedMessage};
 use crate::common::buf::BufList;
 use crate::common::{task, Pin, Poll};
+use crate::observe::ObserverRef;
 
 /// The initial buffer size allocated before trying to read from IO.
 pub(crate) const INIT_BUFFER_SIZE: usize = 8192;
@@ -39,6 +40,7 @@ const MAX_BUF_LIST_BUFFERS: usize = 16;
 pub(crate) struct Buffered<T, B> {
     flush_pipeline: bool,
     io: T,
+    observer: ObserverRef,
     read_blocked: bool,
     read_buf: BytesMut,
     read_buf_strategy: ReadStrategy,
@@ -72,6 +74,7 @@ where
         Buffered {
             flush_pipeline: false,
             io,
+            observer: ObserverRef::default(),
             read_blocked: false,
             read_buf: BytesMut::with_capacity(0),
             read_buf_strategy: ReadStrategy::default(),
@@ -88,6 +91,10 @@ where
         }
     }
 
+    pub(crate) fn set_observer(&mut self, observer: ObserverRef) {
+        self.observer = observer;
+    }
+
     pub(crate) fn set_max_buf_size(&mut self, max: usize) {
         assert!(
             max >= MINIMUM_MAX_BUFFER_SIZE,
@@ -187,6 +194,13 @@ where
                     cached_headers: parse_ctx.cached_headers,
                     req_method: parse_ctx.req_method,
                     h1_parser_config: parse_ctx.h1_parser_config.clone(),
+                    h1_max_headers: parse_ctx.h1_max_headers,
+                    #[cfg(feature = "server")]
+                    h1_max_uri_len: parse_ctx.h1_max_uri_len,
+                    #[cfg(feature = "server")]
+                    h1_strict_parsing: parse_ctx.h1_strict_parsing,
+                    #[cfg(feature = "client")]
+                    h1_ignore_invalid_headers: parse_ctx.h1_ignore_invalid_headers,
                     #[cfg(all(feature = "server", feature = "runtime"))]
                     h1_header_read_timeout: parse_ctx.h1_header_read_timeout,
                     #[cfg(all(feature = "server", feature = "runtime"))]
@@ -194,11 +208,13 @@ where
                     #[cfg(all(feature = "server", feature = "runtime"))]
                     h1_header_read_timeout_running: parse_ctx.h1_header_read_timeout_running,
                     preserve_header_case: parse_ctx.preserve_header_case,
-                    #[cfg(feature = "ffi")]
                     preserve_header_order: parse_ctx.preserve_header_order,
+                    preserve_raw_head: parse_ctx.preserve_raw_head,
                     h09_responses: parse_ctx.h09_responses,
-                    #[cfg(feature = "ffi")]
+                    #[cfg(feature = "client")]
                     on_informational: parse_ctx.on_informational,
+                    #[cfg(all(feature = "client", feature = "runtime"))]
+                    continue_wait: parse_ctx.continue_wait,
                     #[cfg(feature = "ffi")]
                     raw_headers: parse_ctx.raw_headers,
                 },
@@ -274,6 +290,7 @@ where
                     self.read_buf.advance_mut(n);
                 }
                 self.read_buf_strategy.record(n);
+                self.observer.on_read(n);
                 Poll::Ready(Ok(n))
             }
             Poll::Pending => {
@@ -317,6 +334,7 @@ where
                 // `poll_write_buf` doesn't exist in Tokio 0.3 yet...when
                 // `poll_write_buf` comes back, the manual advance will need to leave!
                 self.write_buf.advance(n);
+                self.observer.on_write(n);
                 debug!("flushed {} bytes", n);
                 if self.write_buf.remaining() == 0 {
                     break;
@@ -340,6 +358,7 @@ where
         loop {
             let n = ready!(Pin::new(&mut self.io).poll_write(cx, self.write_buf.headers.chunk()))?;
             debug!("flushed {} bytes", n);
+            self.observer.on_write(n);
             self.write_buf.headers.advance(n);
             if self.write_buf.headers.remaining() == 0 {
                 self.write_buf.headers.reset();
@@ -735,6 +754,13 @@ mod tests {
                 cached_headers: &mut None,
                 req_method: &mut None,
                 h1_parser_config: Default::default(),
+                h1_max_headers: None,
+                #[cfg(feature = "server")]
+                h1_max_uri_len: None,
+                #[cfg(feature = "server")]
+                h1_strict_parsing: false,
+                #[cfg(feature = "client")]
+                h1_ignore_invalid_headers: false,
                 #[cfg(feature = "runtime")]
                 h1_header_read_timeout: None,
                 #[cfg(feature = "runtime")]
@@ -742,11 +768,13 @@ mod tests {
                 #[cfg(feature = "runtime")]
                 h1_header_read_timeout_running: &mut false,
                 preserve_header_case: false,
-                #[cfg(feature = "ffi")]
                 preserve_header_order: false,
+                preserve_raw_head: false,
                 h09_responses: false,
-                #[cfg(feature = "ffi")]
+                #[cfg(feature = "client")]
                 on_informational: &mut None,
+                #[cfg(all(feature = "client", feature = "runtime"))]
+                continue_wait: &mut crate::proto::h1::ContinueWait::Idle,
                 #[cfg(feature = "ffi")]
                 raw_headers: false,
             };
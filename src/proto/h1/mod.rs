@@ -1,10 +1,12 @@
 #[cfg(all(feature = "server", feature = "runtime"))]
-use std::{pin::Pin, time::Duration};
+use std::time::Duration;
+#[cfg(feature = "runtime")]
+use std::pin::Pin;
 
 use bytes::BytesMut;
 use http::{HeaderMap, Method};
 use httparse::ParserConfig;
-#[cfg(all(feature = "server", feature = "runtime"))]
+#[cfg(feature = "runtime")]
 use tokio::time::Sleep;
 
 use crate::body::DecodedLength;
@@ -41,6 +43,17 @@ pub(crate) trait Http1Transaction {
 
     fn on_error(err: &crate::Error) -> Option<MessageHead<Self::Outgoing>>;
 
+    /// Encodes an informational (1xx) message, sent before the final one.
+    #[cfg(feature = "server")]
+    fn encode_informational(
+        head: &MessageHead<Self::Outgoing>,
+        title_case_headers: bool,
+        dst: &mut Vec<u8>,
+    ) {
+        let _ = (head, title_case_headers, dst);
+        unreachable!("{} can't send informational messages", Self::LOG);
+    }
+
     fn is_client() -> bool {
         !Self::is_server()
     }
@@ -76,6 +89,13 @@ pub(crate) struct ParseContext<'a> {
     cached_headers: &'a mut Option<HeaderMap>,
     req_method: &'a mut Option<Method>,
     h1_parser_config: ParserConfig,
+    h1_max_headers: Option<usize>,
+    #[cfg(feature = "server")]
+    h1_max_uri_len: Option<usize>,
+    #[cfg(feature = "server")]
+    h1_strict_parsing: bool,
+    #[cfg(feature = "client")]
+    h1_ignore_invalid_headers: bool,
     #[cfg(all(feature = "server", feature = "runtime"))]
     h1_header_read_timeout: Option<Duration>,
     #[cfg(all(feature = "server", feature = "runtime"))]
@@ -83,15 +103,30 @@ pub(crate) struct ParseContext<'a> {
     #[cfg(all(feature = "server", feature = "runtime"))]
     h1_header_read_timeout_running: &'a mut bool,
     preserve_header_case: bool,
-    #[cfg(feature = "ffi")]
     preserve_header_order: bool,
+    preserve_raw_head: bool,
     h09_responses: bool,
-    #[cfg(feature = "ffi")]
-    on_informational: &'a mut Option<crate::ffi::OnInformational>,
+    #[cfg(feature = "client")]
+    on_informational: &'a mut Option<crate::ext::OnInformational>,
+    #[cfg(all(feature = "client", feature = "runtime"))]
+    continue_wait: &'a mut ContinueWait,
     #[cfg(feature = "ffi")]
     raw_headers: bool,
 }
 
+/// Whether a client request is holding its body until the server
+/// responds `100 Continue`.
+#[cfg(all(feature = "client", feature = "runtime"))]
+pub(crate) enum ContinueWait {
+    /// The body, if any, is written as usual.
+    Idle,
+    /// The body is held until `100 Continue`, a final response, or the
+    /// timeout.
+    Waiting(Pin<Box<Sleep>>),
+    /// A final response came first, so the body must not be sent.
+    Refused,
+}
+
 /// Passed to Http1Transaction::encode
 pub(crate) struct Encode<'a, T> {
     head: &'a mut MessageHead<T>,
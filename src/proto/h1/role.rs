@@ -16,15 +16,18 @@ use crate::body::DecodedLength;
 use crate::common::date;
 use crate::error::Parse;
 use crate::ext::HeaderCaseMap;
-#[cfg(feature = "ffi")]
 use crate::ext::OriginalHeaderOrder;
+use crate::ext::RawHead;
 use crate::headers;
+#[cfg(all(feature = "client", feature = "runtime"))]
+use crate::proto::h1::ContinueWait;
 use crate::proto::h1::{
     Encode, Encoder, Http1Transaction, ParseContext, ParseResult, ParsedMessage,
 };
 use crate::proto::{BodyLength, MessageHead, RequestHead, RequestLine};
 
-const MAX_HEADERS: usize = 100;
+/// The default maximum number of headers, parsed on the stack.
+pub(super) const MAX_HEADERS: usize = 100;
 const AVERAGE_HEADER_SIZE: usize = 30; // totally scientific
 #[cfg(feature = "server")]
 const MAX_URI_LEN: usize = (u16::MAX - 1) as usize;
@@ -133,27 +136,41 @@ impl Http1Transaction for Server {
         let len;
         let headers_len;
 
+        let max_headers = ctx.h1_max_headers.unwrap_or(MAX_HEADERS);
+        let max_uri_len = ctx.h1_max_uri_len.unwrap_or(MAX_URI_LEN);
+
         // Unsafe: both headers_indices and headers are using uninitialized memory,
         // but we *never* read any of it until after httparse has assigned
         // values into it. By not zeroing out the stack memory, this saves
         // a good ~5% on pipeline benchmarks.
-        let mut headers_indices: [MaybeUninit<HeaderIndices>; MAX_HEADERS] = unsafe {
+        let mut headers_indices_stack: [MaybeUninit<HeaderIndices>; MAX_HEADERS] = unsafe {
             // SAFETY: We can go safely from MaybeUninit array to array of MaybeUninit
             MaybeUninit::uninit().assume_init()
         };
+        let mut headers_indices_heap = Vec::new();
+        let headers_indices = stack_or_heap(
+            &mut headers_indices_stack,
+            &mut headers_indices_heap,
+            max_headers,
+        );
         {
             /* SAFETY: it is safe to go from MaybeUninit array to array of MaybeUninit */
-            let mut headers: [MaybeUninit<httparse::Header<'_>>; MAX_HEADERS] =
+            let mut headers_stack: [MaybeUninit<httparse::Header<'_>>; MAX_HEADERS] =
                 unsafe { MaybeUninit::uninit().assume_init() };
+            let mut headers_heap = Vec::new();
+            let headers = stack_or_heap(&mut headers_stack, &mut headers_heap, max_headers);
             trace!(bytes = buf.len(), "Request.parse");
             let mut req = httparse::Request::new(&mut []);
             let bytes = buf.as_ref();
-            match req.parse_with_uninit_headers(bytes, &mut headers) {
+            match req.parse_with_uninit_headers(bytes, headers) {
                 Ok(httparse::Status::Complete(parsed_len)) => {
                     trace!("Request.parse Complete({})", parsed_len);
+                    if ctx.h1_strict_parsing {
+                        check_strict_head(&bytes[..parsed_len])?;
+                    }
                     len = parsed_len;
                     let uri = req.path.unwrap();
-                    if uri.len() > MAX_URI_LEN {
+                    if uri.len() > max_uri_len {
                         return Err(Parse::UriTooLong);
                     }
                     subject = RequestLine(
@@ -170,11 +187,19 @@ impl Http1Transaction for Server {
                         Version::HTTP_10
                     };
 
-                    record_header_indices(bytes, &req.headers, &mut headers_indices)?;
+                    record_header_indices(bytes, &req.headers, headers_indices)?;
                     headers_len = req.headers.len();
                 }
-                Ok(httparse::Status::Partial) => return Ok(None),
+                Ok(httparse::Status::Partial) => {
+                    if ctx.h1_strict_parsing {
+                        check_strict_tail(bytes)?;
+                    }
+                    return Ok(None);
+                }
                 Err(err) => {
+                    if ctx.h1_strict_parsing {
+                        check_strict_head(bytes)?;
+                    }
                     return Err(match err {
                         // if invalid Token, try to determine if for method or path
                         httparse::Error::Token => {
@@ -215,7 +240,6 @@ impl Http1Transaction for Server {
             None
         };
 
-        #[cfg(feature = "ffi")]
         let mut header_order = if ctx.preserve_header_order {
             Some(OriginalHeaderOrder::default())
         } else {
@@ -242,6 +266,12 @@ impl Http1Transaction for Server {
                         debug!("HTTP/1.0 cannot have Transfer-Encoding header");
                         return Err(Parse::transfer_encoding_unexpected());
                     }
+                    if ctx.h1_strict_parsing
+                        && (is_te_chunked || headers::is_chunked_not_last(&value))
+                    {
+                        debug!("request with chunked transfer-encoding not last, bad request");
+                        return Err(Parse::transfer_encoding_chunked_not_last());
+                    }
                     is_te = true;
                     if headers::is_chunked_(&value) {
                         is_te_chunked = true;
@@ -257,6 +287,14 @@ impl Http1Transaction for Server {
                     let len = headers::content_length_parse(&value)
                         .ok_or_else(Parse::content_length_invalid)?;
                     if let Some(prev) = con_len {
+                        if ctx.h1_strict_parsing {
+                            debug!("multiple Content-Length headers: [{}, {}]", prev, len);
+                            return Err(if prev != len {
+                                Parse::content_length_conflict()
+                            } else {
+                                Parse::content_length_duplicate()
+                            });
+                        }
                         if prev != len {
                             debug!(
                                 "multiple Content-Length headers with different values: [{}, {}]",
@@ -298,7 +336,6 @@ impl Http1Transaction for Server {
                 header_case_map.append(&name, slice.slice(header.name.0..header.name.1));
             }
 
-            #[cfg(feature = "ffi")]
             if let Some(ref mut header_order) = header_order {
                 header_order.append(&name);
             }
@@ -317,11 +354,14 @@ impl Http1Transaction for Server {
             extensions.insert(header_case_map);
         }
 
-        #[cfg(feature = "ffi")]
         if let Some(header_order) = header_order {
             extensions.insert(header_order);
         }
 
+        if ctx.preserve_raw_head {
+            extensions.insert(RawHead::new(slice));
+        }
+
         *ctx.req_method = Some(subject.0.clone());
 
         Ok(Some(ParsedMessage {
@@ -348,10 +388,11 @@ impl Http1Transaction for Server {
 
         let mut wrote_len = false;
 
-        // hyper currently doesn't support returning 1xx status codes as a Response
+        // hyper doesn't support returning 1xx status codes as a Response
         // This is because Service only allows returning a single Response, and
         // so if you try to reply with a e.g. 100 Continue, you have no way of
-        // replying with the latter status code response.
+        // replying with the latter status code response. Informational
+        // responses are sent with an `ext::InformationalSender` instead.
         let (ret, is_last) = if msg.head.subject == StatusCode::SWITCHING_PROTOCOLS {
             (Ok(()), true)
         } else if msg.req_method == &Some(Method::CONNECT) && msg.head.subject.is_success() {
@@ -424,6 +465,7 @@ impl Http1Transaction for Server {
             }
             orig_headers => orig_headers,
         };
+        let orig_order = extensions.get::<OriginalHeaderOrder>();
         let encoder = if let Some(orig_headers) = orig_headers {
             Self::encode_headers_with_original_case(
                 msg,
@@ -432,9 +474,12 @@ impl Http1Transaction for Server {
                 orig_len,
                 wrote_len,
                 orig_headers,
+                orig_order,
             )?
         } else {
-            Self::encode_headers_with_lower_case(msg, dst, is_last, orig_len, wrote_len)?
+            Self::encode_headers_with_lower_case(
+                msg, dst, is_last, orig_len, wrote_len, orig_order,
+            )?
         };
 
         ret.map(|()| encoder)
@@ -446,7 +491,8 @@ impl Http1Transaction for Server {
             Kind::Parse(Parse::Method)
             | Kind::Parse(Parse::Header(_))
             | Kind::Parse(Parse::Uri)
-            | Kind::Parse(Parse::Version) => StatusCode::BAD_REQUEST,
+            | Kind::Parse(Parse::Version)
+            | Kind::Parse(Parse::BareLineFeed) => StatusCode::BAD_REQUEST,
             Kind::Parse(Parse::TooLarge) => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
             Kind::Parse(Parse::UriTooLong) => StatusCode::URI_TOO_LONG,
             _ => return None,
@@ -458,6 +504,39 @@ impl Http1Transaction for Server {
         Some(msg)
     }
 
+    fn encode_informational(
+        head: &MessageHead<Self::Outgoing>,
+        title_case_headers: bool,
+        dst: &mut Vec<u8>,
+    ) {
+        trace!("Server::encode_informational status={:?}", head.subject);
+        debug_assert!(head.subject.is_informational());
+
+        // There is no body, so none of the framing headers of `encode` apply.
+        extend(dst, b"HTTP/1.1 ");
+        extend(dst, head.subject.as_str().as_bytes());
+        extend(dst, b" ");
+        // `http` doesn't know the reason of 103, registered by RFC 8297.
+        let reason = match head.subject.as_u16() {
+            103 => Some("Early Hints"),
+            _ => head.subject.canonical_reason(),
+        };
+        extend(dst, reason.unwrap_or("<none>").as_bytes());
+        extend(dst, b"\r\n");
+
+        for (name, value) in head.headers.iter() {
+            if title_case_headers {
+                title_case(dst, name.as_str().as_bytes());
+            } else {
+                extend(dst, name.as_str().as_bytes());
+            }
+            extend(dst, b": ");
+            extend(dst, value.as_bytes());
+            extend(dst, b"\r\n");
+        }
+        extend(dst, b"\r\n");
+    }
+
     fn is_server() -> bool {
         true
     }
@@ -508,6 +587,7 @@ impl Server {
         is_last: bool,
         orig_len: usize,
         wrote_len: bool,
+        orig_order: Option<&OriginalHeaderOrder>,
     ) -> crate::Result<Encoder> {
         struct LowercaseWriter;
 
@@ -533,12 +613,20 @@ impl Server {
             }
 
             #[inline]
-            fn write_header_name(&mut self, dst: &mut Vec<u8>, name: &HeaderName) {
+            fn write_header_name(&mut self, dst: &mut Vec<u8>, name: &HeaderName, _: usize) {
                 extend(dst, name.as_str().as_bytes())
             }
         }
 
-        Self::encode_headers(msg, dst, is_last, orig_len, wrote_len, LowercaseWriter)
+        Self::encode_headers(
+            msg,
+            dst,
+            is_last,
+            orig_len,
+            wrote_len,
+            orig_order,
+            LowercaseWriter,
+        )
     }
 
     #[cold]
@@ -550,10 +638,11 @@ impl Server {
         orig_len: usize,
         wrote_len: bool,
         orig_headers: &HeaderCaseMap,
+        orig_order: Option<&OriginalHeaderOrder>,
     ) -> crate::Result<Encoder> {
         struct OrigCaseWriter<'map> {
             map: &'map HeaderCaseMap,
-            current: Option<(HeaderName, ValueIter<'map, Bytes>)>,
+            current: Option<(HeaderName, usize, ValueIter<'map, Bytes>)>,
             title_case_headers: bool,
         }
 
@@ -565,7 +654,7 @@ impl Server {
                 _: &str,
                 (name, rest): (HeaderName, &str),
             ) {
-                self.write_header_name(dst, &name);
+                self.write_header_name(dst, &name, 0);
                 extend(dst, rest.as_bytes());
             }
 
@@ -576,24 +665,33 @@ impl Server {
                 _: &str,
                 name: HeaderName,
             ) {
-                self.write_header_name(dst, &name);
+                self.write_header_name(dst, &name, 0);
                 extend(dst, b": ");
             }
 
             #[inline]
-            fn write_header_name(&mut self, dst: &mut Vec<u8>, name: &HeaderName) {
+            fn write_header_name(&mut self, dst: &mut Vec<u8>, name: &HeaderName, idx: usize) {
                 let Self {
                     map,
                     ref mut current,
                     title_case_headers,
                 } = *self;
-                if current.as_ref().map_or(true, |(last, _)| last != name) {
-                    *current = None;
+                if current
+                    .as_ref()
+                    .map_or(true, |(last, next, _)| last != name || *next != idx)
+                {
+                    // the values of a name are interleaved with others when
+                    // written in their original order
+                    let mut values = map.get_all_internal(name);
+                    if idx > 0 {
+                        values.nth(idx - 1);
+                    }
+                    *current = Some((name.clone(), idx, values));
                 }
-                let (_, values) =
-                    current.get_or_insert_with(|| (name.clone(), map.get_all_internal(name)));
+                let (_, next, values) = current.as_mut().expect("current header name");
+                *next += 1;
 
-                if let Some(orig_name) = values.next() {
+                if let Some(orig_name) = values.next().filter(|orig| is_spelling_of(orig, name)) {
                     extend(dst, orig_name);
                 } else if title_case_headers {
                     title_case(dst, name.as_str().as_bytes());
@@ -609,7 +707,15 @@ impl Server {
             title_case_headers: msg.title_case_headers,
         };
 
-        Self::encode_headers(msg, dst, is_last, orig_len, wrote_len, header_name_writer)
+        Self::encode_headers(
+            msg,
+            dst,
+            is_last,
+            orig_len,
+            wrote_len,
+            orig_order,
+            header_name_writer,
+        )
     }
 
     #[inline]
@@ -619,6 +725,7 @@ impl Server {
         mut is_last: bool,
         orig_len: usize,
         mut wrote_len: bool,
+        orig_order: Option<&OriginalHeaderOrder>,
         mut header_name_writer: W,
     ) -> crate::Result<Encoder>
     where
@@ -637,6 +744,7 @@ impl Server {
         let mut is_name_written = false;
         let mut must_write_chunked = false;
         let mut prev_con_len = None;
+        let mut allowed_trailer_fields = Vec::new();
 
         macro_rules! handle_is_name_written {
             () => {{
@@ -657,7 +765,21 @@ impl Server {
             }};
         }
 
-        'headers: for (opt_name, value) in msg.head.headers.drain() {
+        let entries = match orig_order {
+            Some(orig_order) => HeaderEntries::InOrder(
+                headers_in_order(&msg.head.headers, orig_order, |name| {
+                    // the values of these are written on a single line
+                    matches!(
+                        *name,
+                        header::CONTENT_LENGTH | header::TRANSFER_ENCODING | header::CONNECTION
+                    )
+                }),
+                None,
+            ),
+            None => HeaderEntries::Drain(msg.head.headers.drain(), 0),
+        };
+
+        'headers: for (opt_name, idx, value) in entries {
             if let Some(n) = opt_name {
                 cur_name = Some(n);
                 handle_is_name_written!();
@@ -818,6 +940,9 @@ impl Server {
                 header::DATE => {
                     wrote_date = true;
                 }
+                header::TRAILER => {
+                    allowed_trailer_fields.push(value.clone());
+                }
                 _ => (),
             }
             //TODO: this should perhaps instead combine them into
@@ -829,7 +954,7 @@ impl Server {
                 "{:?} set is_name_written and didn't continue loop",
                 name,
             );
-            header_name_writer.write_header_name(dst, name);
+            header_name_writer.write_header_name(dst, name, idx);
             extend(dst, b": ");
             extend(dst, value.as_bytes());
             extend(dst, b"\r\n");
@@ -837,6 +962,10 @@ impl Server {
 
         handle_is_name_written!();
 
+        if orig_order.is_some() {
+            msg.head.headers.clear();
+        }
+
         if !wrote_len {
             encoder = match msg.body {
                 Some(BodyLength::Unknown) => {
@@ -892,6 +1021,10 @@ impl Server {
             encoder = Encoder::length(0);
         }
 
+        if !allowed_trailer_fields.is_empty() {
+            encoder = encoder.into_chunked_with_trailing_fields(allowed_trailer_fields);
+        }
+
         // cached date is much faster than formatting every request
         if !wrote_date {
             dst.reserve(date::DATE_VALUE_LENGTH + 8);
@@ -920,7 +1053,44 @@ trait HeaderNameWriter {
         name_with_colon: &str,
         name: HeaderName,
     );
-    fn write_header_name(&mut self, dst: &mut Vec<u8>, name: &HeaderName);
+    fn write_header_name(&mut self, dst: &mut Vec<u8>, name: &HeaderName, idx: usize);
+}
+
+/// The headers of a response as they are written: each value with the index
+/// among the values of its name, and the name if the previous value had
+/// another.
+#[cfg(feature = "server")]
+enum HeaderEntries<'a, I> {
+    Drain(header::Drain<'a, HeaderValue>, usize),
+    InOrder(I, Option<&'a HeaderName>),
+}
+
+#[cfg(feature = "server")]
+impl<'a, I> Iterator for HeaderEntries<'a, I>
+where
+    I: Iterator<Item = (&'a HeaderName, usize, &'a HeaderValue)>,
+{
+    type Item = (Option<HeaderName>, usize, HeaderValue);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match *self {
+            HeaderEntries::Drain(ref mut drain, ref mut idx) => {
+                let (name, value) = drain.next()?;
+                *idx = if name.is_some() { 0 } else { *idx + 1 };
+                Some((name, *idx, value))
+            }
+            HeaderEntries::InOrder(ref mut entries, ref mut prev) => {
+                let (name, idx, value) = entries.next()?;
+                let name = if *prev == Some(name) {
+                    None
+                } else {
+                    *prev = Some(name);
+                    Some(name.clone())
+                };
+                Some((name, idx, value.clone()))
+            }
+        }
+    }
 }
 
 #[cfg(feature = "client")]
@@ -932,25 +1102,35 @@ impl Http1Transaction for Client {
     fn parse(buf: &mut BytesMut, ctx: ParseContext<'_>) -> ParseResult<StatusCode> {
         debug_assert!(!buf.is_empty(), "parse called with empty buf");
 
+        let max_headers = ctx.h1_max_headers.unwrap_or(MAX_HEADERS);
+
         // Loop to skip information status code headers (100 Continue, etc).
         loop {
+            let mut ignored = 0;
             // Unsafe: see comment in Server Http1Transaction, above.
-            let mut headers_indices: [MaybeUninit<HeaderIndices>; MAX_HEADERS] = unsafe {
+            let mut headers_indices_stack: [MaybeUninit<HeaderIndices>; MAX_HEADERS] = unsafe {
                 // SAFETY: We can go safely from MaybeUninit array to array of MaybeUninit
                 MaybeUninit::uninit().assume_init()
             };
+            let mut headers_indices_heap = Vec::new();
+            let headers_indices = stack_or_heap(
+                &mut headers_indices_stack,
+                &mut headers_indices_heap,
+                max_headers,
+            );
             let (len, status, reason, version, headers_len) = {
                 // SAFETY: We can go safely from MaybeUninit array to array of MaybeUninit
-                let mut headers: [MaybeUninit<httparse::Header<'_>>; MAX_HEADERS] =
+                let mut headers_stack: [MaybeUninit<httparse::Header<'_>>; MAX_HEADERS] =
                     unsafe { MaybeUninit::uninit().assume_init() };
+                let mut headers_heap = Vec::new();
+                let headers = stack_or_heap(&mut headers_stack, &mut headers_heap, max_headers);
                 trace!(bytes = buf.len(), "Response.parse");
                 let mut res = httparse::Response::new(&mut []);
                 let bytes = buf.as_ref();
-                match ctx.h1_parser_config.parse_response_with_uninit_headers(
-                    &mut res,
-                    bytes,
-                    &mut headers,
-                ) {
+                match ctx
+                    .h1_parser_config
+                    .parse_response_with_uninit_headers(&mut res, bytes, headers)
+                {
                     Ok(httparse::Status::Complete(len)) => {
                         trace!("Response.parse Complete({})", len);
                         let status = StatusCode::from_u16(res.code.unwrap())?;
@@ -970,8 +1150,15 @@ impl Http1Transaction for Client {
                         } else {
                             Version::HTTP_10
                         };
-                        record_header_indices(bytes, &res.headers, &mut headers_indices)?;
+                        record_header_indices(bytes, &res.headers, headers_indices)?;
                         let headers_len = res.headers.len();
+                        if ctx.h1_ignore_invalid_headers {
+                            let obs_fold = ctx
+                                .h1_parser_config
+                                .obsolete_multiline_headers_in_responses_are_allowed();
+                            ignored = count_header_lines(&bytes[..len], obs_fold)
+                                .saturating_sub(headers_len);
+                        }
                         (len, status, reason, version, headers_len)
                     }
                     Ok(httparse::Status::Partial) => return Ok(None),
@@ -986,10 +1173,19 @@ impl Http1Transaction for Client {
 
             let mut slice = buf.split_to(len);
 
-            if ctx
+            let obs_fold = ctx
                 .h1_parser_config
-                .obsolete_multiline_headers_in_responses_are_allowed()
-            {
+                .obsolete_multiline_headers_in_responses_are_allowed();
+
+            // Unfolding rewrites the head in place, so keep a copy of it as
+            // it was received.
+            let raw_head = if ctx.preserve_raw_head && obs_fold {
+                Some(Bytes::copy_from_slice(&slice))
+            } else {
+                None
+            };
+
+            if obs_fold {
                 for header in &mut headers_indices[..headers_len] {
                     // SAFETY: array is valid up to `headers_len`
                     let header = unsafe { &mut *header.as_mut_ptr() };
@@ -1010,7 +1206,6 @@ impl Http1Transaction for Client {
                 None
             };
 
-            #[cfg(feature = "ffi")]
             let mut header_order = if ctx.preserve_header_order {
                 Some(OriginalHeaderOrder::default())
             } else {
@@ -1039,7 +1234,6 @@ impl Http1Transaction for Client {
                     header_case_map.append(&name, slice.slice(header.name.0..header.name.1));
                 }
 
-                #[cfg(feature = "ffi")]
                 if let Some(ref mut header_order) = header_order {
                     header_order.append(&name);
                 }
@@ -1053,7 +1247,6 @@ impl Http1Transaction for Client {
                 extensions.insert(header_case_map);
             }
 
-            #[cfg(feature = "ffi")]
             if let Some(header_order) = header_order {
                 extensions.insert(header_order);
             }
@@ -1065,6 +1258,15 @@ impl Http1Transaction for Client {
                 extensions.insert(reason);
             }
 
+            if ctx.preserve_raw_head && version != Version::HTTP_09 {
+                extensions.insert(RawHead::new(raw_head.unwrap_or_else(|| slice.clone())));
+            }
+
+            if ignored > 0 {
+                debug!("ignored {} invalid header lines in response", ignored);
+                extensions.insert(crate::ext::IgnoredInvalidHeaders::new(ignored));
+            }
+
             #[cfg(feature = "ffi")]
             if ctx.raw_headers {
                 extensions.insert(crate::ffi::RawHeaders(crate::ffi::hyper_buf(slice)));
@@ -1077,6 +1279,16 @@ impl Http1Transaction for Client {
                 extensions,
             };
             if let Some((decode, is_upgrade)) = Client::decoder(&head, ctx.req_method)? {
+                // A final response instead of `100 Continue` means the body
+                // isn't wanted, unless it is a success.
+                #[cfg(feature = "runtime")]
+                if let ContinueWait::Waiting(..) = *ctx.continue_wait {
+                    *ctx.continue_wait = if head.subject.is_success() {
+                        ContinueWait::Idle
+                    } else {
+                        ContinueWait::Refused
+                    };
+                }
                 return Ok(Some(ParsedMessage {
                     head,
                     decode,
@@ -1088,10 +1300,17 @@ impl Http1Transaction for Client {
                 }));
             }
 
-            #[cfg(feature = "ffi")]
+            #[cfg(feature = "runtime")]
+            if head.subject == StatusCode::CONTINUE {
+                if let ContinueWait::Waiting(..) = *ctx.continue_wait {
+                    trace!("received 100 Continue, sending body");
+                    *ctx.continue_wait = ContinueWait::Idle;
+                }
+            }
+
             if head.subject.is_informational() {
                 if let Some(callback) = ctx.on_informational {
-                    callback.call(head.into_response(crate::Recv::empty()));
+                    callback.call(head.into_response(()));
                 }
             }
 
@@ -1112,7 +1331,18 @@ impl Http1Transaction for Client {
 
         *msg.req_method = Some(msg.head.subject.0.clone());
 
-        let body = Client::set_length(msg.head, msg.body);
+        let mut body = Client::set_length(msg.head, msg.body);
+
+        if msg.head.headers.contains_key(header::TRAILER) {
+            let allowed_trailer_fields = msg
+                .head
+                .headers
+                .get_all(header::TRAILER)
+                .iter()
+                .cloned()
+                .collect();
+            body = body.into_chunked_with_trailing_fields(allowed_trailer_fields);
+        }
 
         let init_cap = 30 + msg.head.headers.len() * AVERAGE_HEADER_SIZE;
         dst.reserve(init_cap);
@@ -1133,7 +1363,16 @@ impl Http1Transaction for Client {
         }
         extend(dst, b"\r\n");
 
-        if let Some(orig_headers) = msg.head.extensions.get::<HeaderCaseMap>() {
+        let orig_headers = msg.head.extensions.get::<HeaderCaseMap>();
+        if let Some(orig_order) = msg.head.extensions.get::<OriginalHeaderOrder>() {
+            write_headers_in_order(
+                &msg.head.headers,
+                orig_order,
+                orig_headers,
+                dst,
+                msg.title_case_headers,
+            );
+        } else if let Some(orig_headers) = orig_headers {
             write_headers_original_case(
                 &msg.head.headers,
                 orig_headers,
@@ -1435,6 +1674,103 @@ fn set_content_length(headers: &mut HeaderMap, len: u64) -> Encoder {
     }
 }
 
+/// Returns the first `len` slots of `stack`, or `len` slots of `heap` if
+/// `stack` is too small.
+fn stack_or_heap<'a, T>(
+    stack: &'a mut [MaybeUninit<T>],
+    heap: &'a mut Vec<MaybeUninit<T>>,
+    len: usize,
+) -> &'a mut [MaybeUninit<T>] {
+    if len <= stack.len() {
+        &mut stack[..len]
+    } else {
+        heap.resize_with(len, MaybeUninit::uninit);
+        heap
+    }
+}
+
+/// Checks the head of a request for what the strict profile rejects, but
+/// httparse either accepts or reports as an invalid token.
+///
+/// The head may be incomplete: the lines received so far are checked.
+#[cfg(feature = "server")]
+fn check_strict_head(bytes: &[u8]) -> Result<(), Parse> {
+    check_strict_lines(bytes, false)
+}
+
+/// Checks the end of an incomplete head: the last line received in full,
+/// and the line after it. Earlier lines were checked by previous calls, and
+/// the whole head is checked again once it is complete.
+#[cfg(feature = "server")]
+fn check_strict_tail(bytes: &[u8]) -> Result<(), Parse> {
+    let start = match bytes.iter().rposition(|&b| b == b'\n') {
+        Some(last) => bytes[..last]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1),
+        None => 0,
+    };
+    // the request-line may only be preceded by empty lines
+    let in_headers = !bytes[..start].iter().all(|&b| b == b'\r' || b == b'\n');
+    check_strict_lines(&bytes[start..], in_headers)
+}
+
+/// Checks the lines of a head, starting with the request-line unless
+/// `in_headers` is set.
+#[cfg(feature = "server")]
+fn check_strict_lines(bytes: &[u8], mut in_headers: bool) -> Result<(), Parse> {
+    let mut rest = bytes;
+    loop {
+        let (line, complete) = match rest.iter().position(|&b| b == b'\n') {
+            Some(i) => {
+                let line = match rest[..i].split_last() {
+                    Some((b'\r', line)) => line,
+                    _ => return Err(Parse::BareLineFeed),
+                };
+                rest = &rest[i + 1..];
+                (line, true)
+            }
+            None => (rest, false),
+        };
+
+        if in_headers {
+            if complete && line.is_empty() {
+                // the end of the head
+                return Ok(());
+            }
+            if let Some(b' ') | Some(b'\t') = line.first() {
+                return Err(Parse::obsolete_line_folding());
+            }
+            if let Some(colon) = line.iter().position(|&b| b == b':') {
+                if let Some(b' ') | Some(b'\t') = line[..colon].last() {
+                    return Err(Parse::whitespace_before_colon());
+                }
+            }
+        } else if complete && !line.is_empty() {
+            // empty lines before the request-line are skipped
+            in_headers = true;
+        }
+
+        if !complete {
+            return Ok(());
+        }
+    }
+}
+
+/// Counts the header lines of a complete message head. With obsolete line
+/// folding, the lines continuing a header aren't counted.
+#[cfg(feature = "client")]
+fn count_header_lines(head: &[u8], obs_fold: bool) -> usize {
+    head.split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        // empty lines before the start-line are skipped
+        .skip_while(|line| line.is_empty())
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .filter(|line| !(obs_fold && matches!(line.first(), Some(b' ') | Some(b'\t'))))
+        .count()
+}
+
 #[derive(Clone, Copy)]
 struct HeaderIndices {
     name: (usize, usize),
@@ -1488,7 +1824,7 @@ fn title_case(dst: &mut Vec<u8>, name: &[u8]) {
     }
 }
 
-fn write_headers_title_case(headers: &HeaderMap, dst: &mut Vec<u8>) {
+pub(super) fn write_headers_title_case(headers: &HeaderMap, dst: &mut Vec<u8>) {
     for (name, value) in headers {
         title_case(dst, name.as_str().as_bytes());
         extend(dst, b": ");
@@ -1497,7 +1833,7 @@ fn write_headers_title_case(headers: &HeaderMap, dst: &mut Vec<u8>) {
     }
 }
 
-fn write_headers(headers: &HeaderMap, dst: &mut Vec<u8>) {
+pub(super) fn write_headers(headers: &HeaderMap, dst: &mut Vec<u8>) {
     for (name, value) in headers {
         extend(dst, name.as_str().as_bytes());
         extend(dst, b": ");
@@ -1522,7 +1858,10 @@ fn write_headers_original_case(
         let mut names = orig_case.get_all(name);
 
         for value in headers.get_all(name) {
-            if let Some(orig_name) = names.next() {
+            if let Some(orig_name) = names
+                .next()
+                .filter(|orig| is_spelling_of(orig.as_ref(), name))
+            {
                 extend(dst, orig_name.as_ref());
             } else if title_case_headers {
                 title_case(dst, name.as_str().as_bytes());
@@ -1542,6 +1881,80 @@ fn write_headers_original_case(
     }
 }
 
+/// Returns whether `orig` spells `name`, which a user-provided
+/// `HeaderCaseMap` may not.
+fn is_spelling_of(orig: &[u8], name: &HeaderName) -> bool {
+    orig.eq_ignore_ascii_case(name.as_str().as_bytes())
+}
+
+/// Returns the headers in the order of `orig_order`, each value with its
+/// index among the values of its name, followed by the values it doesn't
+/// mention. The values of a `grouped` name all come at the position of the
+/// first one.
+fn headers_in_order<'a>(
+    headers: &'a HeaderMap,
+    orig_order: &'a OriginalHeaderOrder,
+    grouped: fn(&HeaderName) -> bool,
+) -> impl Iterator<Item = (&'a HeaderName, usize, &'a HeaderValue)> + 'a {
+    let values = move |name: &'a HeaderName, skip: usize, take: usize| {
+        headers
+            .get_all(name)
+            .into_iter()
+            .enumerate()
+            .skip(skip)
+            .take(take)
+            .map(move |(idx, value)| (name, idx, value))
+    };
+    let in_order = orig_order.get_in_order().flat_map(move |(name, idx)| {
+        match (grouped(name), *idx) {
+            (false, idx) => values(name, idx, 1),
+            (true, 0) => values(name, 0, usize::MAX),
+            // written with the first one
+            (true, _) => values(name, 0, 0),
+        }
+    });
+    let rest = headers
+        .keys()
+        .flat_map(move |name| match orig_order.count(name) {
+            n if n > 0 && grouped(name) => values(name, 0, 0),
+            n => values(name, n, usize::MAX),
+        });
+    in_order.chain(rest)
+}
+
+/// Writes the headers in the order of `orig_order`, with the spellings of
+/// `orig_case` if there is one.
+#[cfg(feature = "client")]
+fn write_headers_in_order(
+    headers: &HeaderMap,
+    orig_order: &OriginalHeaderOrder,
+    orig_case: Option<&HeaderCaseMap>,
+    dst: &mut Vec<u8>,
+    title_case_headers: bool,
+) {
+    for (name, idx, value) in headers_in_order(headers, orig_order, |_| false) {
+        let orig_name = orig_case
+            .and_then(|orig_case| orig_case.get_all_internal(name).nth(idx))
+            .filter(|orig| is_spelling_of(orig, name));
+        if let Some(orig_name) = orig_name {
+            extend(dst, orig_name);
+        } else if title_case_headers {
+            title_case(dst, name.as_str().as_bytes());
+        } else {
+            extend(dst, name.as_str().as_bytes());
+        }
+
+        // an empty value is written as in write_headers_original_case
+        if value.is_empty() && orig_case.is_some() {
+            extend(dst, b":\r\n");
+        } else {
+            extend(dst, b": ");
+            extend(dst, value.as_bytes());
+            extend(dst, b"\r\n");
+        }
+    }
+}
+
 struct FastWrite<'a>(&'a mut Vec<u8>);
 
 impl<'a> fmt::Write for FastWrite<'a> {
@@ -1568,6 +1981,70 @@ mod tests {
 
     use super::*;
 
+    /// The state borrowed by a `ParseContext`, owned by the test using it.
+    struct CtxState {
+        cached_headers: Option<HeaderMap>,
+        req_method: Option<Method>,
+        #[cfg(all(feature = "server", feature = "runtime"))]
+        h1_header_read_timeout_fut: Option<std::pin::Pin<Box<tokio::time::Sleep>>>,
+        #[cfg(all(feature = "server", feature = "runtime"))]
+        h1_header_read_timeout_running: bool,
+        #[cfg(feature = "client")]
+        on_informational: Option<crate::ext::OnInformational>,
+        #[cfg(all(feature = "client", feature = "runtime"))]
+        continue_wait: ContinueWait,
+    }
+
+    impl CtxState {
+        fn new() -> CtxState {
+            CtxState {
+                cached_headers: None,
+                req_method: None,
+                #[cfg(all(feature = "server", feature = "runtime"))]
+                h1_header_read_timeout_fut: None,
+                #[cfg(all(feature = "server", feature = "runtime"))]
+                h1_header_read_timeout_running: false,
+                #[cfg(feature = "client")]
+                on_informational: None,
+                #[cfg(all(feature = "client", feature = "runtime"))]
+                continue_wait: ContinueWait::Idle,
+            }
+        }
+
+        /// Returns a `ParseContext` with every option off, for tests to
+        /// override the fields they need.
+        fn ctx(&mut self) -> ParseContext<'_> {
+            ParseContext {
+                cached_headers: &mut self.cached_headers,
+                req_method: &mut self.req_method,
+                h1_parser_config: Default::default(),
+                h1_max_headers: None,
+                #[cfg(feature = "server")]
+                h1_max_uri_len: None,
+                #[cfg(feature = "server")]
+                h1_strict_parsing: false,
+                #[cfg(feature = "client")]
+                h1_ignore_invalid_headers: false,
+                #[cfg(all(feature = "server", feature = "runtime"))]
+                h1_header_read_timeout: None,
+                #[cfg(all(feature = "server", feature = "runtime"))]
+                h1_header_read_timeout_fut: &mut self.h1_header_read_timeout_fut,
+                #[cfg(all(feature = "server", feature = "runtime"))]
+                h1_header_read_timeout_running: &mut self.h1_header_read_timeout_running,
+                preserve_header_case: false,
+                preserve_header_order: false,
+                preserve_raw_head: false,
+                h09_responses: false,
+                #[cfg(feature = "client")]
+                on_informational: &mut self.on_informational,
+                #[cfg(all(feature = "client", feature = "runtime"))]
+                continue_wait: &mut self.continue_wait,
+                #[cfg(feature = "ffi")]
+                raw_headers: false,
+            }
+        }
+    }
+
     #[test]
     fn test_parse_request() {
         let _ = pretty_env_logger::try_init();
@@ -1579,6 +2056,13 @@ mod tests {
                 cached_headers: &mut None,
                 req_method: &mut method,
                 h1_parser_config: Default::default(),
+                h1_max_headers: None,
+                #[cfg(feature = "server")]
+                h1_max_uri_len: None,
+                #[cfg(feature = "server")]
+                h1_strict_parsing: false,
+                #[cfg(feature = "client")]
+                h1_ignore_invalid_headers: false,
                 #[cfg(feature = "runtime")]
                 h1_header_read_timeout: None,
                 #[cfg(feature = "runtime")]
@@ -1586,11 +2070,13 @@ mod tests {
                 #[cfg(feature = "runtime")]
                 h1_header_read_timeout_running: &mut false,
                 preserve_header_case: false,
-                #[cfg(feature = "ffi")]
                 preserve_header_order: false,
+                preserve_raw_head: false,
                 h09_responses: false,
-                #[cfg(feature = "ffi")]
+                #[cfg(feature = "client")]
                 on_informational: &mut None,
+                #[cfg(all(feature = "client", feature = "runtime"))]
+                continue_wait: &mut ContinueWait::Idle,
                 #[cfg(feature = "ffi")]
                 raw_headers: false,
             },
@@ -1614,6 +2100,13 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut Some(crate::Method::GET),
             h1_parser_config: Default::default(),
+            h1_max_headers: None,
+            #[cfg(feature = "server")]
+            h1_max_uri_len: None,
+            #[cfg(feature = "server")]
+            h1_strict_parsing: false,
+            #[cfg(feature = "client")]
+            h1_ignore_invalid_headers: false,
             #[cfg(feature = "runtime")]
             h1_header_read_timeout: None,
             #[cfg(feature = "runtime")]
@@ -1621,11 +2114,13 @@ mod tests {
             #[cfg(feature = "runtime")]
             h1_header_read_timeout_running: &mut false,
             preserve_header_case: false,
-            #[cfg(feature = "ffi")]
             preserve_header_order: false,
+            preserve_raw_head: false,
             h09_responses: false,
-            #[cfg(feature = "ffi")]
+            #[cfg(feature = "client")]
             on_informational: &mut None,
+            #[cfg(all(feature = "client", feature = "runtime"))]
+            continue_wait: &mut ContinueWait::Idle,
             #[cfg(feature = "ffi")]
             raw_headers: false,
         };
@@ -1644,6 +2139,13 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut None,
             h1_parser_config: Default::default(),
+            h1_max_headers: None,
+            #[cfg(feature = "server")]
+            h1_max_uri_len: None,
+            #[cfg(feature = "server")]
+            h1_strict_parsing: false,
+            #[cfg(feature = "client")]
+            h1_ignore_invalid_headers: false,
             #[cfg(feature = "runtime")]
             h1_header_read_timeout: None,
             #[cfg(feature = "runtime")]
@@ -1651,11 +2153,13 @@ mod tests {
             #[cfg(feature = "runtime")]
             h1_header_read_timeout_running: &mut false,
             preserve_header_case: false,
-            #[cfg(feature = "ffi")]
             preserve_header_order: false,
+            preserve_raw_head: false,
             h09_responses: false,
-            #[cfg(feature = "ffi")]
+            #[cfg(feature = "client")]
             on_informational: &mut None,
+            #[cfg(all(feature = "client", feature = "runtime"))]
+            continue_wait: &mut ContinueWait::Idle,
             #[cfg(feature = "ffi")]
             raw_headers: false,
         };
@@ -1672,6 +2176,13 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut Some(crate::Method::GET),
             h1_parser_config: Default::default(),
+            h1_max_headers: None,
+            #[cfg(feature = "server")]
+            h1_max_uri_len: None,
+            #[cfg(feature = "server")]
+            h1_strict_parsing: false,
+            #[cfg(feature = "client")]
+            h1_ignore_invalid_headers: false,
             #[cfg(feature = "runtime")]
             h1_header_read_timeout: None,
             #[cfg(feature = "runtime")]
@@ -1679,11 +2190,13 @@ mod tests {
             #[cfg(feature = "runtime")]
             h1_header_read_timeout_running: &mut false,
             preserve_header_case: false,
-            #[cfg(feature = "ffi")]
             preserve_header_order: false,
+            preserve_raw_head: false,
             h09_responses: true,
-            #[cfg(feature = "ffi")]
+            #[cfg(feature = "client")]
             on_informational: &mut None,
+            #[cfg(all(feature = "client", feature = "runtime"))]
+            continue_wait: &mut ContinueWait::Idle,
             #[cfg(feature = "ffi")]
             raw_headers: false,
         };
@@ -1702,6 +2215,13 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut Some(crate::Method::GET),
             h1_parser_config: Default::default(),
+            h1_max_headers: None,
+            #[cfg(feature = "server")]
+            h1_max_uri_len: None,
+            #[cfg(feature = "server")]
+            h1_strict_parsing: false,
+            #[cfg(feature = "client")]
+            h1_ignore_invalid_headers: false,
             #[cfg(feature = "runtime")]
             h1_header_read_timeout: None,
             #[cfg(feature = "runtime")]
@@ -1709,11 +2229,13 @@ mod tests {
             #[cfg(feature = "runtime")]
             h1_header_read_timeout_running: &mut false,
             preserve_header_case: false,
-            #[cfg(feature = "ffi")]
             preserve_header_order: false,
+            preserve_raw_head: false,
             h09_responses: false,
-            #[cfg(feature = "ffi")]
+            #[cfg(feature = "client")]
             on_informational: &mut None,
+            #[cfg(all(feature = "client", feature = "runtime"))]
+            continue_wait: &mut ContinueWait::Idle,
             #[cfg(feature = "ffi")]
             raw_headers: false,
         };
@@ -1736,6 +2258,13 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut Some(crate::Method::GET),
             h1_parser_config,
+            h1_max_headers: None,
+            #[cfg(feature = "server")]
+            h1_max_uri_len: None,
+            #[cfg(feature = "server")]
+            h1_strict_parsing: false,
+            #[cfg(feature = "client")]
+            h1_ignore_invalid_headers: false,
             #[cfg(feature = "runtime")]
             h1_header_read_timeout: None,
             #[cfg(feature = "runtime")]
@@ -1743,11 +2272,13 @@ mod tests {
             #[cfg(feature = "runtime")]
             h1_header_read_timeout_running: &mut false,
             preserve_header_case: false,
-            #[cfg(feature = "ffi")]
             preserve_header_order: false,
+            preserve_raw_head: false,
             h09_responses: false,
-            #[cfg(feature = "ffi")]
+            #[cfg(feature = "client")]
             on_informational: &mut None,
+            #[cfg(all(feature = "client", feature = "runtime"))]
+            continue_wait: &mut ContinueWait::Idle,
             #[cfg(feature = "ffi")]
             raw_headers: false,
         };
@@ -1767,6 +2298,13 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut Some(crate::Method::GET),
             h1_parser_config: Default::default(),
+            h1_max_headers: None,
+            #[cfg(feature = "server")]
+            h1_max_uri_len: None,
+            #[cfg(feature = "server")]
+            h1_strict_parsing: false,
+            #[cfg(feature = "client")]
+            h1_ignore_invalid_headers: false,
             #[cfg(feature = "runtime")]
             h1_header_read_timeout: None,
             #[cfg(feature = "runtime")]
@@ -1774,17 +2312,93 @@ mod tests {
             #[cfg(feature = "runtime")]
             h1_header_read_timeout_running: &mut false,
             preserve_header_case: false,
-            #[cfg(feature = "ffi")]
             preserve_header_order: false,
+            preserve_raw_head: false,
             h09_responses: false,
-            #[cfg(feature = "ffi")]
+            #[cfg(feature = "client")]
             on_informational: &mut None,
+            #[cfg(all(feature = "client", feature = "runtime"))]
+            continue_wait: &mut ContinueWait::Idle,
             #[cfg(feature = "ffi")]
             raw_headers: false,
         };
         Client::parse(&mut raw, ctx).unwrap_err();
     }
 
+    #[test]
+    fn test_parse_ignore_invalid_headers_in_response() {
+        use httparse::ParserConfig;
+
+        let _ = pretty_env_logger::try_init();
+        let mut raw = BytesMut::from(
+            "HTTP/1.1 200 OK\r\n\
+             bad name: a\r\n\
+             x-good: b\r\n\
+             x-folded: c\r\n d\r\n\
+             x-control: \x01\r\n\
+             \r\n",
+        );
+        let mut h1_parser_config = ParserConfig::default();
+        h1_parser_config.ignore_invalid_headers_in_responses(true);
+        let mut state = CtxState::new();
+        state.req_method = Some(crate::Method::GET);
+        let ctx = ParseContext {
+            h1_parser_config,
+            #[cfg(feature = "client")]
+            h1_ignore_invalid_headers: true,
+            ..state.ctx()
+        };
+        let msg = Client::parse(&mut raw, ctx).unwrap().unwrap();
+        assert_eq!(raw.len(), 0);
+        assert_eq!(msg.head.headers.len(), 2);
+        assert_eq!(msg.head.headers["x-good"], "b");
+        // without obsolete line folding, only the continuation is invalid
+        assert_eq!(msg.head.headers["x-folded"], "c");
+        let ignored = msg
+            .head
+            .extensions
+            .get::<crate::ext::IgnoredInvalidHeaders>()
+            .expect("ignored headers");
+        assert_eq!(ignored.count(), 3);
+    }
+
+    #[test]
+    fn test_parse_raw_head_in_request() {
+        let mut raw =
+            BytesMut::from("\r\nGET /a%2Fb?c HTTP/1.1\r\nHost: hyper.rs\r\nX-Sig:  x \r\n\r\nbody");
+        let mut state = CtxState::new();
+        let ctx = ParseContext {
+            preserve_raw_head: true,
+            ..state.ctx()
+        };
+        let msg = Server::parse(&mut raw, ctx).unwrap().unwrap();
+        assert_eq!(raw, "body");
+        let raw_head = msg.head.extensions.get::<RawHead>().expect("raw head");
+        assert_eq!(raw_head.line(), "GET /a%2Fb?c HTTP/1.1");
+        assert_eq!(raw_head.headers(), "Host: hyper.rs\r\nX-Sig:  x \r\n");
+    }
+
+    #[test]
+    fn test_parse_raw_head_in_response_obs_fold() {
+        use httparse::ParserConfig;
+
+        let mut raw = BytesMut::from("HTTP/1.1 200 OK\r\nX-Folded: a\r\n b\r\n\r\n");
+        let mut h1_parser_config = ParserConfig::default();
+        h1_parser_config.allow_obsolete_multiline_headers_in_responses(true);
+        let mut state = CtxState::new();
+        state.req_method = Some(crate::Method::GET);
+        let ctx = ParseContext {
+            h1_parser_config,
+            preserve_raw_head: true,
+            ..state.ctx()
+        };
+        let msg = Client::parse(&mut raw, ctx).unwrap().unwrap();
+        assert_eq!(msg.head.headers["x-folded"], "a b");
+        let raw_head = msg.head.extensions.get::<RawHead>().expect("raw head");
+        assert_eq!(raw_head.line(), "HTTP/1.1 200 OK");
+        assert_eq!(raw_head.headers(), "X-Folded: a\r\n b\r\n");
+    }
+
     #[test]
     fn test_parse_preserve_header_case_in_request() {
         let mut raw =
@@ -1793,6 +2407,13 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut None,
             h1_parser_config: Default::default(),
+            h1_max_headers: None,
+            #[cfg(feature = "server")]
+            h1_max_uri_len: None,
+            #[cfg(feature = "server")]
+            h1_strict_parsing: false,
+            #[cfg(feature = "client")]
+            h1_ignore_invalid_headers: false,
             #[cfg(feature = "runtime")]
             h1_header_read_timeout: None,
             #[cfg(feature = "runtime")]
@@ -1800,11 +2421,13 @@ mod tests {
             #[cfg(feature = "runtime")]
             h1_header_read_timeout_running: &mut false,
             preserve_header_case: true,
-            #[cfg(feature = "ffi")]
             preserve_header_order: false,
+            preserve_raw_head: false,
             h09_responses: false,
-            #[cfg(feature = "ffi")]
+            #[cfg(feature = "client")]
             on_informational: &mut None,
+            #[cfg(all(feature = "client", feature = "runtime"))]
+            continue_wait: &mut ContinueWait::Idle,
             #[cfg(feature = "ffi")]
             raw_headers: false,
         };
@@ -1840,6 +2463,13 @@ mod tests {
                     cached_headers: &mut None,
                     req_method: &mut None,
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
+                    #[cfg(feature = "server")]
+                    h1_max_uri_len: None,
+                    #[cfg(feature = "server")]
+                    h1_strict_parsing: false,
+                    #[cfg(feature = "client")]
+                    h1_ignore_invalid_headers: false,
                     #[cfg(feature = "runtime")]
                     h1_header_read_timeout: None,
                     #[cfg(feature = "runtime")]
@@ -1847,11 +2477,13 @@ mod tests {
                     #[cfg(feature = "runtime")]
                     h1_header_read_timeout_running: &mut false,
                     preserve_header_case: false,
-                    #[cfg(feature = "ffi")]
                     preserve_header_order: false,
+                    preserve_raw_head: false,
                     h09_responses: false,
-                    #[cfg(feature = "ffi")]
+                    #[cfg(feature = "client")]
                     on_informational: &mut None,
+                    #[cfg(all(feature = "client", feature = "runtime"))]
+                    continue_wait: &mut ContinueWait::Idle,
                     #[cfg(feature = "ffi")]
                     raw_headers: false,
                 },
@@ -1868,6 +2500,13 @@ mod tests {
                     cached_headers: &mut None,
                     req_method: &mut None,
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
+                    #[cfg(feature = "server")]
+                    h1_max_uri_len: None,
+                    #[cfg(feature = "server")]
+                    h1_strict_parsing: false,
+                    #[cfg(feature = "client")]
+                    h1_ignore_invalid_headers: false,
                     #[cfg(feature = "runtime")]
                     h1_header_read_timeout: None,
                     #[cfg(feature = "runtime")]
@@ -1875,11 +2514,13 @@ mod tests {
                     #[cfg(feature = "runtime")]
                     h1_header_read_timeout_running: &mut false,
                     preserve_header_case: false,
-                    #[cfg(feature = "ffi")]
                     preserve_header_order: false,
+                    preserve_raw_head: false,
                     h09_responses: false,
-                    #[cfg(feature = "ffi")]
+                    #[cfg(feature = "client")]
                     on_informational: &mut None,
+                    #[cfg(all(feature = "client", feature = "runtime"))]
+                    continue_wait: &mut ContinueWait::Idle,
                     #[cfg(feature = "ffi")]
                     raw_headers: false,
                 },
@@ -2091,6 +2732,111 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_decoder_request_strict() {
+        use crate::error::Header;
+
+        fn parse(s: &str, strict: bool) -> ParseResult<RequestLine> {
+            let mut bytes = BytesMut::from(s);
+            Server::parse(
+                &mut bytes,
+                ParseContext {
+                    #[cfg(feature = "server")]
+                    h1_strict_parsing: strict,
+                    ..CtxState::new().ctx()
+                },
+            )
+        }
+
+        // bare LF line endings
+        let bare_lf = "GET / HTTP/1.1\nhost: example.com\n\n";
+        assert!(parse(bare_lf, false).unwrap().is_some());
+        assert!(matches!(parse(bare_lf, true), Err(Parse::BareLineFeed)));
+        assert!(matches!(
+            parse("GET / HTTP/1.1\r\nhost: example.com\n", true),
+            Err(Parse::BareLineFeed)
+        ));
+
+        // incomplete heads are checked as far as they go
+        assert!(matches!(
+            parse("GET / HTTP/1.1\r\nhost: example.com\r\n", true),
+            Ok(None)
+        ));
+        assert!(matches!(
+            parse("GET / HTTP/1.1\r\nhost : exa", true),
+            Err(Parse::Header(Header::WhitespaceBeforeColon))
+        ));
+
+        // obsolete line folding
+        assert!(matches!(
+            parse("GET / HTTP/1.1\r\nx-a: b\r\n c\r\n\r\n", true),
+            Err(Parse::Header(Header::ObsoleteLineFolding))
+        ));
+
+        // whitespace before the colon
+        assert!(matches!(
+            parse("GET / HTTP/1.1\r\nhost\t: example.com\r\n\r\n", true),
+            Err(Parse::Header(Header::WhitespaceBeforeColon))
+        ));
+
+        // the body isn't part of the head
+        assert!(
+            parse("POST / HTTP/1.1\r\ncontent-length: 3\r\n\r\na\nb", true)
+                .unwrap()
+                .is_some()
+        );
+
+        // duplicate content-lengths
+        let duplicate = "POST / HTTP/1.1\r\ncontent-length: 1\r\ncontent-length: 1\r\n\r\n";
+        assert!(parse(duplicate, false).unwrap().is_some());
+        assert!(matches!(
+            parse(duplicate, true),
+            Err(Parse::Header(Header::ContentLengthDuplicate))
+        ));
+        assert!(matches!(
+            parse(
+                "POST / HTTP/1.1\r\ncontent-length: 1\r\ncontent-length: 2\r\n\r\n",
+                true
+            ),
+            Err(Parse::Header(Header::ContentLengthConflict))
+        ));
+
+        // chunked before another transfer coding
+        assert!(matches!(
+            parse(
+                "POST / HTTP/1.1\r\ntransfer-encoding: chunked, chunked\r\n\r\n",
+                true
+            ),
+            Err(Parse::Header(Header::TransferEncodingChunkedNotLast))
+        ));
+        assert!(matches!(
+            parse(
+                "POST / HTTP/1.1\r\ntransfer-encoding: chunked\r\ntransfer-encoding: chunked\r\n\r\n",
+                true
+            ),
+            Err(Parse::Header(Header::TransferEncodingChunkedNotLast))
+        ));
+        assert!(parse(
+            "POST / HTTP/1.1\r\ntransfer-encoding: gzip, chunked\r\n\r\n",
+            true
+        )
+        .unwrap()
+        .is_some());
+
+        // always rejected
+        assert!(matches!(
+            parse(
+                "POST / HTTP/1.0\r\ntransfer-encoding: chunked\r\n\r\n",
+                true
+            ),
+            Err(Parse::Header(Header::TransferEncodingUnexpected))
+        ));
+        assert!(matches!(
+            parse("G(T / HTTP/1.1\r\n\r\n", true),
+            Err(Parse::Method)
+        ));
+    }
+
     #[test]
     fn test_decoder_response() {
         fn parse(s: &str) -> ParsedMessage<StatusCode> {
@@ -2105,6 +2851,13 @@ mod tests {
                     cached_headers: &mut None,
                     req_method: &mut Some(Method::GET),
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
+                    #[cfg(feature = "server")]
+                    h1_max_uri_len: None,
+                    #[cfg(feature = "server")]
+                    h1_strict_parsing: false,
+                    #[cfg(feature = "client")]
+                    h1_ignore_invalid_headers: false,
                     #[cfg(feature = "runtime")]
                     h1_header_read_timeout: None,
                     #[cfg(feature = "runtime")]
@@ -2112,11 +2865,13 @@ mod tests {
                     #[cfg(feature = "runtime")]
                     h1_header_read_timeout_running: &mut false,
                     preserve_header_case: false,
-                    #[cfg(feature = "ffi")]
                     preserve_header_order: false,
+                    preserve_raw_head: false,
                     h09_responses: false,
-                    #[cfg(feature = "ffi")]
+                    #[cfg(feature = "client")]
                     on_informational: &mut None,
+                    #[cfg(all(feature = "client", feature = "runtime"))]
+                    continue_wait: &mut ContinueWait::Idle,
                     #[cfg(feature = "ffi")]
                     raw_headers: false,
                 }
@@ -2133,6 +2888,13 @@ mod tests {
                     cached_headers: &mut None,
                     req_method: &mut Some(m),
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
+                    #[cfg(feature = "server")]
+                    h1_max_uri_len: None,
+                    #[cfg(feature = "server")]
+                    h1_strict_parsing: false,
+                    #[cfg(feature = "client")]
+                    h1_ignore_invalid_headers: false,
                     #[cfg(feature = "runtime")]
                     h1_header_read_timeout: None,
                     #[cfg(feature = "runtime")]
@@ -2140,11 +2902,13 @@ mod tests {
                     #[cfg(feature = "runtime")]
                     h1_header_read_timeout_running: &mut false,
                     preserve_header_case: false,
-                    #[cfg(feature = "ffi")]
                     preserve_header_order: false,
+                    preserve_raw_head: false,
                     h09_responses: false,
-                    #[cfg(feature = "ffi")]
+                    #[cfg(feature = "client")]
                     on_informational: &mut None,
+                    #[cfg(all(feature = "client", feature = "runtime"))]
+                    continue_wait: &mut ContinueWait::Idle,
                     #[cfg(feature = "ffi")]
                     raw_headers: false,
                 },
@@ -2161,6 +2925,13 @@ mod tests {
                     cached_headers: &mut None,
                     req_method: &mut Some(Method::GET),
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
+                    #[cfg(feature = "server")]
+                    h1_max_uri_len: None,
+                    #[cfg(feature = "server")]
+                    h1_strict_parsing: false,
+                    #[cfg(feature = "client")]
+                    h1_ignore_invalid_headers: false,
                     #[cfg(feature = "runtime")]
                     h1_header_read_timeout: None,
                     #[cfg(feature = "runtime")]
@@ -2168,11 +2939,13 @@ mod tests {
                     #[cfg(feature = "runtime")]
                     h1_header_read_timeout_running: &mut false,
                     preserve_header_case: false,
-                    #[cfg(feature = "ffi")]
                     preserve_header_order: false,
+                    preserve_raw_head: false,
                     h09_responses: false,
-                    #[cfg(feature = "ffi")]
+                    #[cfg(feature = "client")]
                     on_informational: &mut None,
+                    #[cfg(all(feature = "client", feature = "runtime"))]
+                    continue_wait: &mut ContinueWait::Idle,
                     #[cfg(feature = "ffi")]
                     raw_headers: false,
                 },
@@ -2681,6 +3454,147 @@ mod tests {
         assert_eq!(&vec[..expected_response.len()], &expected_response[..]);
     }
 
+    #[test]
+    fn test_client_request_encode_orig_order() {
+        use crate::proto::BodyLength;
+        use http::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+
+        let x_a = HeaderName::from_static("x-a");
+        let x_b = HeaderName::from_static("x-b");
+
+        let mut head = MessageHead::default();
+        head.headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        head.headers.append(&x_a, HeaderValue::from_static("1"));
+        head.headers.append(&x_a, HeaderValue::from_static("3"));
+        head.headers.append(&x_b, HeaderValue::from_static("2"));
+
+        let mut orig_headers = HeaderCaseMap::default();
+        orig_headers.append(&x_a, "X-A".into());
+        orig_headers.append(&x_a, "X-a".into());
+        head.extensions.insert(orig_headers);
+
+        let mut orig_order = OriginalHeaderOrder::default();
+        orig_order.append(&x_a);
+        orig_order.append(&x_b);
+        orig_order.append(&x_a);
+        head.extensions.insert(orig_order);
+
+        let mut vec = Vec::new();
+        Client::encode(
+            Encode {
+                head: &mut head,
+                body: Some(BodyLength::Known(0)),
+                keep_alive: true,
+                req_method: &mut None,
+                title_case_headers: false,
+            },
+            &mut vec,
+        )
+        .unwrap();
+
+        assert_eq!(
+            &*vec,
+            b"GET / HTTP/1.1\r\nX-A: 1\r\nx-b: 2\r\nX-a: 3\r\ncontent-type: text/plain\r\ncontent-length: 0\r\n\r\n"
+                .as_ref(),
+        );
+    }
+
+    #[test]
+    fn test_server_response_encode_orig_order() {
+        use crate::proto::BodyLength;
+        use http::header::{HeaderName, HeaderValue};
+
+        let x_a = HeaderName::from_static("x-a");
+        let x_b = HeaderName::from_static("x-b");
+
+        let mut head = MessageHead::default();
+        head.headers.append(&x_a, HeaderValue::from_static("1"));
+        head.headers.append(&x_a, HeaderValue::from_static("3"));
+        head.headers.append(&x_b, HeaderValue::from_static("2"));
+
+        let mut orig_order = OriginalHeaderOrder::default();
+        orig_order.append(&x_b);
+        orig_order.append(&x_a);
+        orig_order.append(&x_a);
+        head.extensions.insert(orig_order);
+
+        let mut vec = Vec::new();
+        Server::encode(
+            Encode {
+                head: &mut head,
+                body: Some(BodyLength::Known(10)),
+                keep_alive: true,
+                req_method: &mut None,
+                title_case_headers: true,
+            },
+            &mut vec,
+        )
+        .unwrap();
+
+        let expected_response =
+            b"HTTP/1.1 200 OK\r\nX-B: 2\r\nX-A: 1\r\nX-A: 3\r\nContent-Length: 10\r\nDate: ";
+
+        assert_eq!(&vec[..expected_response.len()], &expected_response[..]);
+        assert!(vec.ends_with(b"\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_server_response_encode_orig_order_and_case() {
+        use crate::proto::BodyLength;
+        use http::header::{HeaderName, HeaderValue, CONNECTION, CONTENT_LENGTH};
+
+        let x_a = HeaderName::from_static("x-a");
+        let x_b = HeaderName::from_static("x-b");
+
+        let mut head = MessageHead::default();
+        head.headers.append(&x_a, HeaderValue::from_static("1"));
+        head.headers.append(&x_a, HeaderValue::from_static("3"));
+        head.headers.append(&x_b, HeaderValue::from_static("2"));
+        head.headers
+            .append(CONTENT_LENGTH, HeaderValue::from_static("10"));
+        head.headers
+            .append(CONTENT_LENGTH, HeaderValue::from_static("10"));
+        head.headers
+            .append(CONNECTION, HeaderValue::from_static("keep-alive"));
+        head.headers
+            .append(CONNECTION, HeaderValue::from_static("x-b"));
+
+        let mut orig_headers = HeaderCaseMap::default();
+        orig_headers.append(&x_a, "X-A".into());
+        orig_headers.append(&x_a, "X-a".into());
+        head.extensions.insert(orig_headers);
+
+        let mut orig_order = OriginalHeaderOrder::default();
+        orig_order.append(CONNECTION);
+        orig_order.append(&x_a);
+        orig_order.append(CONTENT_LENGTH);
+        orig_order.append(&x_b);
+        orig_order.append(CONNECTION);
+        orig_order.append(&x_a);
+        orig_order.append(CONTENT_LENGTH);
+        head.extensions.insert(orig_order);
+
+        let mut vec = Vec::new();
+        Server::encode(
+            Encode {
+                head: &mut head,
+                body: Some(BodyLength::Unknown),
+                keep_alive: true,
+                req_method: &mut None,
+                title_case_headers: false,
+            },
+            &mut vec,
+        )
+        .unwrap();
+
+        let expected_response = b"HTTP/1.1 200 OK\r\nconnection: keep-alive, x-b\r\nX-A: 1\r\ncontent-length: 10\r\nx-b: 2\r\nX-a: 3\r\ndate: ";
+
+        assert_eq!(&vec[..expected_response.len()], &expected_response[..]);
+        assert!(vec.ends_with(b"\r\n\r\n"));
+        assert!(head.headers.is_empty());
+    }
+
     #[test]
     fn parse_header_htabs() {
         let mut bytes = BytesMut::from("HTTP/1.1 200 OK\r\nserver: hello\tworld\r\n\r\n");
@@ -2690,6 +3604,13 @@ mod tests {
                 cached_headers: &mut None,
                 req_method: &mut Some(Method::GET),
                 h1_parser_config: Default::default(),
+                h1_max_headers: None,
+                #[cfg(feature = "server")]
+                h1_max_uri_len: None,
+                #[cfg(feature = "server")]
+                h1_strict_parsing: false,
+                #[cfg(feature = "client")]
+                h1_ignore_invalid_headers: false,
                 #[cfg(feature = "runtime")]
                 h1_header_read_timeout: None,
                 #[cfg(feature = "runtime")]
@@ -2697,11 +3618,13 @@ mod tests {
                 #[cfg(feature = "runtime")]
                 h1_header_read_timeout_running: &mut false,
                 preserve_header_case: false,
-                #[cfg(feature = "ffi")]
                 preserve_header_order: false,
+                preserve_raw_head: false,
                 h09_responses: false,
-                #[cfg(feature = "ffi")]
+                #[cfg(feature = "client")]
                 on_informational: &mut None,
+                #[cfg(all(feature = "client", feature = "runtime"))]
+                continue_wait: &mut ContinueWait::Idle,
                 #[cfg(feature = "ffi")]
                 raw_headers: false,
             },
@@ -2746,6 +3669,21 @@ mod tests {
         assert_eq!(dst, b"X-Empty: a\r\nX-EMPTY: b\r\n");
     }
 
+    #[test]
+    fn test_write_headers_orig_case_other_name() {
+        let mut headers = HeaderMap::new();
+        let name = http::header::HeaderName::from_static("x-empty");
+        headers.insert(&name, "a".parse().unwrap());
+
+        let mut orig_cases = HeaderCaseMap::default();
+        orig_cases.insert(name, Bytes::from_static(b"X-Other"));
+
+        let mut dst = Vec::new();
+        super::write_headers_original_case(&headers, &orig_cases, &mut dst, false);
+
+        assert_eq!(dst, b"x-empty: a\r\n");
+    }
+
     #[cfg(feature = "nightly")]
     use test::Bencher;
 
@@ -2782,6 +3720,13 @@ mod tests {
                     cached_headers: &mut headers,
                     req_method: &mut None,
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
+                    #[cfg(feature = "server")]
+                    h1_max_uri_len: None,
+                    #[cfg(feature = "server")]
+                    h1_strict_parsing: false,
+                    #[cfg(feature = "client")]
+                    h1_ignore_invalid_headers: false,
                     #[cfg(feature = "runtime")]
                     h1_header_read_timeout: None,
                     #[cfg(feature = "runtime")]
@@ -2789,11 +3734,13 @@ mod tests {
                     #[cfg(feature = "runtime")]
                     h1_header_read_timeout_running: &mut false,
                     preserve_header_case: false,
-                    #[cfg(feature = "ffi")]
                     preserve_header_order: false,
+                    preserve_raw_head: false,
                     h09_responses: false,
-                    #[cfg(feature = "ffi")]
+                    #[cfg(feature = "client")]
                     on_informational: &mut None,
+                    #[cfg(all(feature = "client", feature = "runtime"))]
+                    continue_wait: &mut ContinueWait::Idle,
                     #[cfg(feature = "ffi")]
                     raw_headers: false,
                 },
@@ -2830,6 +3777,13 @@ mod tests {
                     cached_headers: &mut headers,
                     req_method: &mut None,
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
+                    #[cfg(feature = "server")]
+                    h1_max_uri_len: None,
+                    #[cfg(feature = "server")]
+                    h1_strict_parsing: false,
+                    #[cfg(feature = "client")]
+                    h1_ignore_invalid_headers: false,
                     #[cfg(feature = "runtime")]
                     h1_header_read_timeout: None,
                     #[cfg(feature = "runtime")]
@@ -2837,11 +3791,13 @@ mod tests {
                     #[cfg(feature = "runtime")]
                     h1_header_read_timeout_running: &mut false,
                     preserve_header_case: false,
-                    #[cfg(feature = "ffi")]
                     preserve_header_order: false,
+                    preserve_raw_head: false,
                     h09_responses: false,
-                    #[cfg(feature = "ffi")]
+                    #[cfg(feature = "client")]
                     on_informational: &mut None,
+                    #[cfg(all(feature = "client", feature = "runtime"))]
+                    continue_wait: &mut ContinueWait::Idle,
                     #[cfg(feature = "ffi")]
                     raw_headers: false,
                 },
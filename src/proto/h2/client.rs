@@ -6,16 +6,26 @@ use bytes::Bytes;
 use futures_channel::{mpsc, oneshot};
 use futures_util::future::{self, Either, FutureExt as _, TryFutureExt as _};
 use futures_util::stream::StreamExt as _;
-use h2::client::{Builder, SendRequest};
+use h2::client::{Builder, Connection, ResponseFuture, SendRequest};
+use h2::SendStream;
 use http::{Method, StatusCode};
 use tokio::io::{AsyncRead, AsyncWrite};
 use tracing::{debug, trace, warn};
 
-use super::{ping, H2Upgraded, PipeToSendStream, SendBuf};
+#[cfg(feature = "http1")]
+use super::h2c::H2cClientIo;
+use super::{ping, GoAwayObserver, H2Upgraded, ObservedIo, PipeToSendStream, SendBuf};
 use crate::body::HttpBody;
+#[cfg(feature = "http1")]
+use crate::client::dispatch::Callback;
+#[cfg(feature = "runtime")]
+use crate::client::dispatch::ResponseTimer;
 use crate::common::{exec::Exec, task, Future, Never, Pin, Poll};
 use crate::ext::Protocol;
+#[cfg(feature = "runtime")]
+use crate::ext::RequestTimeouts;
 use crate::headers;
+use crate::observe::ObserverRef;
 use crate::proto::h2::UpgradedSendStream;
 use crate::proto::Dispatched;
 use crate::upgrade::Upgraded;
@@ -53,6 +63,9 @@ pub(crate) struct Config {
     pub(crate) keep_alive_while_idle: bool,
     pub(crate) max_concurrent_reset_streams: Option<usize>,
     pub(crate) max_send_buffer_size: usize,
+    pub(crate) observer: ObserverRef,
+    #[cfg(feature = "runtime")]
+    pub(crate) timeouts: RequestTimeouts,
 }
 
 impl Default for Config {
@@ -70,6 +83,9 @@ impl Default for Config {
             keep_alive_while_idle: false,
             max_concurrent_reset_streams: None,
             max_send_buffer_size: DEFAULT_MAX_SEND_BUF_SIZE,
+            observer: ObserverRef::default(),
+            #[cfg(feature = "runtime")]
+            timeouts: RequestTimeouts::new(),
         }
     }
 }
@@ -101,6 +117,7 @@ fn new_ping_config(config: &Config) -> ping::Config {
         keep_alive_timeout: config.keep_alive_timeout,
         #[cfg(feature = "runtime")]
         keep_alive_while_idle: config.keep_alive_while_idle,
+        observer: config.observer.clone(),
     }
 }
 
@@ -115,11 +132,54 @@ where
     B: HttpBody,
     B::Data: Send + 'static,
 {
-    let (h2_tx, mut conn) = new_builder(config)
+    let io = ObservedIo::new(io, config.observer.clone());
+    let (h2_tx, conn) = new_builder(config)
+        .handshake::<_, SendBuf<B::Data>>(io)
+        .await
+        .map_err(crate::Error::new_h2)?;
+
+    Ok(new_client_task(h2_tx, conn, req_rx, config, exec))
+}
+
+/// Starts HTTP/2 on a connection that was upgraded from HTTP/1.1, where the
+/// response to the request that asked for it is delivered to `cb`.
+#[cfg(feature = "http1")]
+pub(crate) async fn handshake_h2c<T, B>(
+    io: T,
+    req_rx: ClientRx<B>,
+    cb: Callback<Request<B>, Response<Recv>>,
+    config: &Config,
+    exec: Exec,
+) -> crate::Result<ClientTask<B>>
+where
+    T: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    B: HttpBody,
+    B::Data: Send + 'static,
+{
+    let io = H2cClientIo::new(io);
+    let io = ObservedIo::new(io, config.observer.clone());
+    let (h2_tx, conn) = new_builder(config)
         .handshake::<_, SendBuf<B::Data>>(io)
         .await
         .map_err(crate::Error::new_h2)?;
 
+    let mut task = new_client_task(h2_tx, conn, req_rx, config, exec);
+    task.h2c = Some(H2cUpgrade { cb: Some(cb) });
+    Ok(task)
+}
+
+fn new_client_task<T, B>(
+    h2_tx: SendRequest<SendBuf<B::Data>>,
+    mut conn: Connection<T, SendBuf<B::Data>>,
+    req_rx: ClientRx<B>,
+    config: &Config,
+    exec: Exec,
+) -> ClientTask<B>
+where
+    T: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    B: HttpBody,
+    B::Data: Send + 'static,
+{
     // An mpsc channel is used entirely to detect when the
     // 'Client' has been dropped. This is to get around a bug
     // in h2 where dropping all SendRequests won't notify a
@@ -157,20 +217,31 @@ where
         });
         (Either::Left(conn), recorder)
     } else {
-        (Either::Right(conn), ping::disabled())
+        (Either::Right(conn), ping::disabled(ping_config.observer))
     };
-    let conn = conn.map_err(|e| debug!("connection error: {}", e));
+    let go_away = GoAwayObserver::new(config.observer.clone());
+    let conn_go_away = go_away.clone();
+    let conn = conn.map_err(move |e| {
+        conn_go_away.observe(&e);
+        debug!("connection error: {}", e)
+    });
 
     exec.execute(conn_task(conn, conn_drop_rx, cancel_tx));
 
-    Ok(ClientTask {
+    ClientTask {
         ping,
+        observer: config.observer.clone(),
+        go_away,
         conn_drop_ref,
         conn_eof,
         executor: exec,
         h2_tx,
         req_rx,
-    })
+        #[cfg(feature = "http1")]
+        h2c: None,
+        #[cfg(feature = "runtime")]
+        timeouts: config.timeouts,
+    }
 }
 
 async fn conn_task<C, D>(conn: C, drop_rx: D, cancel_tx: oneshot::Sender<Never>)
@@ -198,11 +269,25 @@ where
     B: HttpBody,
 {
     ping: ping::Recorder,
+    observer: ObserverRef,
+    go_away: GoAwayObserver,
     conn_drop_ref: ConnDropRef,
     conn_eof: ConnEof,
     executor: Exec,
     h2_tx: SendRequest<SendBuf<B::Data>>,
     req_rx: ClientRx<B>,
+    #[cfg(feature = "http1")]
+    h2c: Option<H2cUpgrade<B>>,
+    /// The timeouts of requests without their own `RequestTimeouts`.
+    #[cfg(feature = "runtime")]
+    timeouts: RequestTimeouts,
+}
+
+/// The request of a connection upgraded from HTTP/1.1, which is stream 1.
+#[cfg(feature = "http1")]
+struct H2cUpgrade<B> {
+    /// The callback of the request, until stream 1 is opened.
+    cb: Option<Callback<Request<B>, Response<Recv>>>,
 }
 
 impl<B> ClientTask<B>
@@ -228,6 +313,7 @@ where
                 Ok(()) => (),
                 Err(err) => {
                     self.ping.ensure_not_timed_out()?;
+                    self.go_away.observe(&err);
                     return if err.reason() == Some(::h2::Reason::NO_ERROR) {
                         trace!("connection gracefully shutdown");
                         Poll::Ready(Ok(Dispatched::Shutdown))
@@ -237,6 +323,34 @@ where
                 }
             };
 
+            #[cfg(feature = "http1")]
+            if let Some(ref mut h2c) = self.h2c {
+                if let Some(cb) = h2c.cb.take() {
+                    // The request of the upgrade is stream 1, so it must be
+                    // opened before any other. It was already sent with
+                    // HTTP/1.1, so a stand-in is sent and removed by the IO.
+                    let req = super::h2c::stream_1_request();
+                    match self.h2_tx.send_request(req, true) {
+                        Ok((fut, _)) => {
+                            let fut = map_response(
+                                fut,
+                                self.ping.clone(),
+                                self.observer.clone(),
+                                self.go_away.clone(),
+                                None,
+                            );
+                            self.executor.execute(cb.send_when(fut));
+                        }
+                        Err(err) => {
+                            debug!("client send request error: {}", err);
+                            self.go_away.observe(&err);
+                            cb.send(Err((crate::Error::new_h2(err), None)));
+                        }
+                    }
+                    continue;
+                }
+            }
+
             match self.req_rx.poll_recv(cx) {
                 Poll::Ready(Some((req, cb))) => {
                     // check that future hasn't been canceled already
@@ -246,6 +360,10 @@ where
                     }
                     let (head, body) = req.into_parts();
                     let mut req = ::http::Request::from_parts(head, ());
+                    #[cfg(feature = "http1")]
+                    if self.h2c.is_some() {
+                        super::h2c::set_absolute_uri(&mut req);
+                    }
                     super::strip_connection_headers(req.headers_mut(), true);
                     if let Some(len) = body.size_hint().exact() {
                         if len != 0 || headers::method_has_defined_payload_semantics(req.method()) {
@@ -274,10 +392,30 @@ where
                         req.extensions_mut().insert(protocol.into_inner());
                     }
 
+                    #[cfg(feature = "runtime")]
+                    let timer = {
+                        let timeouts = match req.extensions_mut().remove::<RequestTimeouts>() {
+                            Some(timeouts) => timeouts.or(self.timeouts),
+                            None => self.timeouts,
+                        };
+                        ResponseTimer::new(&timeouts)
+                    };
+                    // Lets a timeout stop sending the request body, so the
+                    // stream can be reset.
+                    #[cfg(feature = "runtime")]
+                    let (cancel_pipe, cancel_rx) = match timer {
+                        Some(_) => {
+                            let (tx, rx) = oneshot::channel::<()>();
+                            (Some(tx), Some(rx))
+                        }
+                        None => (None, None),
+                    };
+
                     let (fut, body_tx) = match self.h2_tx.send_request(req, !is_connect && eos) {
                         Ok(ok) => ok,
                         Err(err) => {
                             debug!("client send request error: {}", err);
+                            self.go_away.observe(&err);
                             cb.send(Err((crate::Error::new_h2(err), None)));
                             continue;
                         }
@@ -285,12 +423,32 @@ where
 
                     let send_stream = if !is_connect {
                         if !eos {
-                            let mut pipe =
-                                Box::pin(PipeToSendStream::new(body, body_tx)).map(|res| {
-                                    if let Err(e) = res {
-                                        debug!("client request body error: {}", e);
-                                    }
+                            #[cfg_attr(feature = "runtime", allow(unused_mut))]
+                            let mut pipe = Box::pin(PipeToSendStream::new(
+                                body,
+                                body_tx,
+                                self.observer.clone(),
+                            ))
+                            .map(|res| {
+                                if let Err(e) = res {
+                                    debug!("client request body error: {}", e);
+                                }
+                            });
+                            #[cfg(feature = "runtime")]
+                            let mut pipe = {
+                                let mut cancel_rx = cancel_rx;
+                                let canceled = future::poll_fn(move |cx| match cancel_rx {
+                                    Some(ref mut rx) => match ready!(Pin::new(rx).poll(cx)) {
+                                        Ok(()) => Poll::Ready(()),
+                                        Err(_dropped) => {
+                                            cancel_rx = None;
+                                            Poll::Pending
+                                        }
+                                    },
+                                    None => Poll::Pending,
                                 });
+                                future::select(pipe, canceled).map(|_| ())
+                            };
 
                             // eagerly see if the body pipe is ready and
                             // can thus skip allocating in the executor
@@ -317,55 +475,19 @@ where
                         Some(body_tx)
                     };
 
-                    let fut = fut.map(move |result| match result {
-                        Ok(res) => {
-                            // record that we got the response headers
-                            ping.record_non_data();
-
-                            let content_length = headers::content_length_parse_all(res.headers());
-                            if let (Some(mut send_stream), StatusCode::OK) =
-                                (send_stream, res.status())
-                            {
-                                if content_length.map_or(false, |len| len != 0) {
-                                    warn!("h2 connect response with non-zero body not supported");
-
-                                    send_stream.send_reset(h2::Reason::INTERNAL_ERROR);
-                                    return Err((
-                                        crate::Error::new_h2(h2::Reason::INTERNAL_ERROR.into()),
-                                        None,
-                                    ));
-                                }
-                                let (parts, recv_stream) = res.into_parts();
-                                let mut res = Response::from_parts(parts, Recv::empty());
-
-                                let (pending, on_upgrade) = crate::upgrade::pending();
-                                let io = H2Upgraded {
-                                    ping,
-                                    send_stream: unsafe { UpgradedSendStream::new(send_stream) },
-                                    recv_stream,
-                                    buf: Bytes::new(),
-                                };
-                                let upgraded = Upgraded::new(io, Bytes::new());
-
-                                pending.fulfill(upgraded);
-                                res.extensions_mut().insert(on_upgrade);
-
-                                Ok(res)
-                            } else {
-                                let res = res.map(|stream| {
-                                    let ping = ping.for_stream(&stream);
-                                    crate::Recv::h2(stream, content_length.into(), ping)
-                                });
-                                Ok(res)
-                            }
-                        }
-                        Err(err) => {
-                            ping.ensure_not_timed_out().map_err(|e| (e, None))?;
-
-                            debug!("client response error: {}", err);
-                            Err((crate::Error::new_h2(err), None))
-                        }
-                    });
+                    let fut = map_response(
+                        fut,
+                        ping,
+                        self.observer.clone(),
+                        self.go_away.clone(),
+                        send_stream,
+                    );
+                    #[cfg(feature = "runtime")]
+                    if let Some(timer) = timer {
+                        let fut = timeout_response(fut, timer, cancel_pipe, self.executor.clone());
+                        self.executor.execute(cb.send_when(fut));
+                        continue;
+                    }
                     self.executor.execute(cb.send_when(fut));
                     continue;
                 }
@@ -386,3 +508,199 @@ where
         }
     }
 }
+
+/// Maps the response of a request sent with `h2` to a `Response<Recv>`.
+///
+/// `send_stream` is the stream of a CONNECT request, which becomes upgraded
+/// if the response is successful.
+fn map_response<B>(
+    fut: ResponseFuture,
+    ping: ping::Recorder,
+    observer: ObserverRef,
+    go_away: GoAwayObserver,
+    send_stream: Option<SendStream<SendBuf<B::Data>>>,
+) -> impl Future<Output = Result<Response<Recv>, (crate::Error, Option<Request<B>>)>> + Unpin
+where
+    B: HttpBody,
+    B::Data: Send + 'static,
+{
+    let stream_id = fut.stream_id().as_u32();
+    fut.map(move |result| match result {
+        Ok(res) => {
+            // record that we got the response headers
+            ping.record_non_data();
+            observer.on_head_parsed();
+
+            let content_length = headers::content_length_parse_all(res.headers());
+            if let (Some(mut send_stream), StatusCode::OK) = (send_stream, res.status()) {
+                if content_length.map_or(false, |len| len != 0) {
+                    warn!("h2 connect response with non-zero body not supported");
+
+                    send_stream.send_reset(h2::Reason::INTERNAL_ERROR);
+                    return Err((
+                        crate::Error::new_h2(h2::Reason::INTERNAL_ERROR.into()),
+                        None,
+                    ));
+                }
+                let (parts, recv_stream) = res.into_parts();
+                let mut res = Response::from_parts(parts, Recv::empty());
+
+                let (pending, on_upgrade) = crate::upgrade::pending();
+                let io = H2Upgraded {
+                    ping,
+                    send_stream: unsafe { UpgradedSendStream::new(send_stream) },
+                    recv_stream,
+                    buf: Bytes::new(),
+                };
+                let upgraded = Upgraded::new(io, Bytes::new());
+
+                pending.fulfill(upgraded);
+                res.extensions_mut().insert(on_upgrade);
+
+                Ok(res)
+            } else {
+                let res = res.map(|stream| {
+                    let ping = ping.for_stream(&stream);
+                    crate::Recv::h2(stream, content_length.into(), ping)
+                });
+                Ok(res)
+            }
+        }
+        Err(err) => {
+            ping.ensure_not_timed_out().map_err(|e| (e, None))?;
+
+            debug!("client response error: {}", err);
+            go_away.observe(&err);
+            Err((crate::Error::new_h2(err).with_stream_id(stream_id), None))
+        }
+    })
+}
+
+/// Enforces the timeouts of a request on its response.
+///
+/// When a timeout expires, the response, or else its body, fails with a
+/// timeout error. `cancel_pipe` then stops sending the request body, so the
+/// stream is reset once the response is dropped.
+#[cfg(feature = "runtime")]
+fn timeout_response<F, B>(
+    mut fut: F,
+    timer: ResponseTimer,
+    mut cancel_pipe: Option<oneshot::Sender<()>>,
+    exec: Exec,
+) -> impl Future<Output = F::Output> + Unpin
+where
+    F: Future<Output = Result<Response<Recv>, (crate::Error, Option<Request<B>>)>> + Unpin,
+{
+    let mut timer = Some(timer);
+    future::poll_fn(move |cx| {
+        if let Poll::Ready(result) = Pin::new(&mut fut).poll(cx) {
+            let res = match result {
+                Ok(res) if !res.body().is_end_stream() => res,
+                other => return Poll::Ready(other),
+            };
+            let timer = match timer.take().and_then(ResponseTimer::into_body_timer) {
+                Some(timer) => timer,
+                None => return Poll::Ready(Ok(res)),
+            };
+
+            // The body is forwarded by a task enforcing its timeouts.
+            let (parts, body) = res.into_parts();
+            let content_length = headers::content_length_parse_all(&parts.headers);
+            let (tx, rx) = Recv::new_channel(content_length.into(), false);
+            exec.execute(TimedBody {
+                body,
+                tx,
+                timer,
+                cancel_pipe: cancel_pipe.take(),
+                data_done: false,
+            });
+            return Poll::Ready(Ok(Response::from_parts(parts, rx)));
+        }
+
+        let timer = timer.as_mut().expect("polled after complete");
+        let err = ready!(timer.poll_expired(cx));
+        debug!("response head timed out: {}", err);
+        if let Some(tx) = cancel_pipe.take() {
+            let _ = tx.send(());
+        }
+        Poll::Ready(Err((err, None)))
+    })
+}
+
+/// Forwards the body of a response, failing it with a timeout error if one
+/// of the timeouts of its request expires.
+///
+/// Dropping `body` resets the stream if it isn't finished.
+#[cfg(feature = "runtime")]
+struct TimedBody {
+    body: Recv,
+    tx: crate::body::Sender,
+    timer: ResponseTimer,
+    cancel_pipe: Option<oneshot::Sender<()>>,
+    data_done: bool,
+}
+
+#[cfg(feature = "runtime")]
+impl TimedBody {
+    fn poll_timeout(&mut self, cx: &mut task::Context<'_>) -> Poll<()> {
+        let err = ready!(self.timer.poll_expired(cx));
+        debug!("response body timed out: {}", err);
+        self.tx.send_error(err);
+        if let Some(tx) = self.cancel_pipe.take() {
+            let _ = tx.send(());
+        }
+        Poll::Ready(())
+    }
+}
+
+#[cfg(feature = "runtime")]
+impl Future for TimedBody {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        loop {
+            if this.data_done {
+                match Pin::new(&mut this.body).poll_trailers(cx) {
+                    Poll::Ready(Ok(Some(trailers))) => {
+                        let _ = this.tx.try_send_trailers(trailers);
+                        return Poll::Ready(());
+                    }
+                    Poll::Ready(Ok(None)) => return Poll::Ready(()),
+                    Poll::Ready(Err(err)) => {
+                        this.tx.send_error(err);
+                        return Poll::Ready(());
+                    }
+                    Poll::Pending => return this.poll_timeout(cx),
+                }
+            }
+
+            match this.tx.poll_ready(cx) {
+                Poll::Ready(Ok(())) => this.timer.set_body_wanted(true),
+                Poll::Ready(Err(_closed)) => {
+                    trace!("response body receiver dropped");
+                    return Poll::Ready(());
+                }
+                Poll::Pending => {
+                    this.timer.set_body_wanted(false);
+                    return this.poll_timeout(cx);
+                }
+            }
+
+            match Pin::new(&mut this.body).poll_data(cx) {
+                Poll::Ready(Some(Ok(data))) => {
+                    this.timer.on_body_data();
+                    if this.tx.try_send_data(data).is_err() {
+                        return Poll::Ready(());
+                    }
+                }
+                Poll::Ready(Some(Err(err))) => {
+                    this.tx.send_error(err);
+                    return Poll::Ready(());
+                }
+                Poll::Ready(None) => this.data_done = true,
+                Poll::Pending => return this.poll_timeout(cx),
+            }
+        }
+    }
+}
@@ -0,0 +1,567 @@
+//! The client side of h2c upgrades.
+//!
+//! The request asking for the upgrade is sent with HTTP/1.1, and the server
+//! answers it on stream 1 once it switched protocols. The `h2` client only
+//! accepts a response for a stream it opened, so a stand-in request is sent
+//! with `h2` to open stream 1, and its header block is removed before
+//! reaching the server.
+//!
+//! Removing a header block would desync the HPACK state of both peers if it
+//! added entries to the dynamic table. The stand-in only has fields of the
+//! static table, and the removed block is checked to not add any. The `h2`
+//! client may start it with dynamic table size updates, following the
+//! `SETTINGS_HEADER_TABLE_SIZE` of the server, so those are moved to the
+//! next header block instead.
+
+use std::cmp;
+use std::convert::TryFrom;
+use std::io;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use http::header::{HeaderValue, HOST, UPGRADE};
+use http::uri::{self, Uri};
+use http::StatusCode;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use super::{has_token, put_frame_head, CONTINUATION, END_HEADERS, FRAME_HEADER_LEN, HEADERS};
+use crate::common::{task, Pin, Poll};
+use crate::proto::h2::client::Config;
+use crate::proto::ResponseHead;
+
+const PREFACE_LEN: usize = 24;
+
+const END_STREAM: u8 = 0x1;
+const PADDED: u8 = 0x8;
+const PRIORITY: u8 = 0x20;
+
+const SETTINGS_ENABLE_PUSH: u16 = 0x2;
+const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+const SETTINGS_MAX_FRAME_SIZE: u16 = 0x5;
+
+/// The IO of an HTTP/2 client connection that was upgraded from HTTP/1.1.
+#[derive(Debug)]
+pub(crate) struct H2cClientIo<T> {
+    inner: T,
+    write: Write,
+    /// Bytes accepted from the `h2` client, but not yet written.
+    pending_write: BytesMut,
+    /// The header block of stream 1, until it is complete.
+    stream_1_block: BytesMut,
+    /// The dynamic table size updates removed with the header block of
+    /// stream 1, to send with the next header block.
+    size_updates: Option<Bytes>,
+}
+
+#[derive(Debug)]
+enum Write {
+    /// Writing the remaining bytes of the client preface.
+    Preface(usize),
+    /// Buffering the header of the next frame.
+    Head(BytesMut),
+    /// Writing, or removing, the remaining payload of a frame.
+    Payload {
+        remaining: usize,
+        skip: bool,
+        end_headers: bool,
+    },
+    /// The request of stream 1 was removed, writes go straight to the inner
+    /// IO.
+    Passthrough,
+}
+
+impl<T> H2cClientIo<T> {
+    /// Wraps the IO of an HTTP/1.1 connection that was upgraded to h2c.
+    pub(crate) fn new(io: T) -> Self {
+        H2cClientIo {
+            inner: io,
+            write: Write::Preface(PREFACE_LEN),
+            pending_write: BytesMut::new(),
+            stream_1_block: BytesMut::new(),
+            size_updates: None,
+        }
+    }
+
+    /// Buffers the bytes of `buf` that should reach the server.
+    fn buffer_write(&mut self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.write {
+                Write::Preface(ref mut remaining) => {
+                    let n = cmp::min(*remaining, buf.len());
+                    self.pending_write.extend_from_slice(&buf[..n]);
+                    buf = &buf[n..];
+                    *remaining -= n;
+                    if *remaining == 0 {
+                        self.write = Write::Head(BytesMut::with_capacity(FRAME_HEADER_LEN));
+                    }
+                }
+                Write::Head(ref mut head) => {
+                    let n = cmp::min(FRAME_HEADER_LEN - head.len(), buf.len());
+                    head.extend_from_slice(&buf[..n]);
+                    buf = &buf[n..];
+                    if head.len() < FRAME_HEADER_LEN {
+                        continue;
+                    }
+
+                    let len = (&head[..3]).get_uint(3) as usize;
+                    let (kind, flags) = (head[3], head[4]);
+                    let stream_id = (&head[5..]).get_u32() & !(1 << 31);
+
+                    if kind == HEADERS && flags & (PADDED | PRIORITY) != 0 {
+                        // Never sent by the `h2` client, so not worth
+                        // finding where the header block starts.
+                        return Err(unexpected_header_block());
+                    }
+
+                    if let Some(updates) = self.size_updates.take() {
+                        if kind == HEADERS {
+                            // The size updates get a HEADERS frame of their
+                            // own, continued by this one.
+                            put_frame_head(
+                                &mut self.pending_write,
+                                updates.len(),
+                                HEADERS,
+                                flags & END_STREAM,
+                                stream_id,
+                            );
+                            self.pending_write.extend_from_slice(&updates);
+                            head[3] = CONTINUATION;
+                            head[4] = flags & END_HEADERS;
+                            self.pending_write.extend_from_slice(head);
+                            self.write = Write::Passthrough;
+                            continue;
+                        }
+                        self.size_updates = Some(updates);
+                    }
+
+                    let skip = stream_id == 1 && (kind == HEADERS || kind == CONTINUATION);
+                    if !skip {
+                        self.pending_write.extend_from_slice(head);
+                    }
+                    self.write = Write::Payload {
+                        remaining: len,
+                        skip,
+                        end_headers: skip && flags & END_HEADERS != 0,
+                    };
+                    self.end_payload()?;
+                }
+                Write::Payload {
+                    ref mut remaining,
+                    skip,
+                    ..
+                } => {
+                    let n = cmp::min(*remaining, buf.len());
+                    if skip {
+                        self.stream_1_block.extend_from_slice(&buf[..n]);
+                    } else {
+                        self.pending_write.extend_from_slice(&buf[..n]);
+                    }
+                    buf = &buf[n..];
+                    *remaining -= n;
+                    self.end_payload()?;
+                }
+                Write::Passthrough => {
+                    self.pending_write.extend_from_slice(buf);
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn end_payload(&mut self) -> io::Result<()> {
+        if let Write::Payload {
+            remaining: 0,
+            end_headers,
+            ..
+        } = self.write
+        {
+            if !end_headers {
+                self.write = Write::Head(BytesMut::with_capacity(FRAME_HEADER_LEN));
+                return Ok(());
+            }
+
+            let updates = size_updates(&self.stream_1_block)?;
+            self.stream_1_block = BytesMut::new();
+            if updates.is_empty() {
+                self.write = Write::Passthrough;
+            } else {
+                self.size_updates = Some(updates);
+                self.write = Write::Head(BytesMut::with_capacity(FRAME_HEADER_LEN));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T> H2cClientIo<T>
+where
+    T: AsyncWrite + Unpin,
+{
+    fn poll_write_pending(&mut self, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        while !self.pending_write.is_empty() {
+            let n = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.pending_write))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.pending_write.advance(n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> AsyncRead for H2cClientIo<T>
+where
+    T: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<T> AsyncWrite for H2cClientIo<T>
+where
+    T: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.poll_write_pending(cx))?;
+        if let Write::Passthrough = self.write {
+            return Pin::new(&mut self.inner).poll_write(cx, buf);
+        }
+
+        self.buffer_write(buf)?;
+        // The bytes were accepted, so only errors matter when trying to
+        // write them right away.
+        if let Poll::Ready(Err(err)) = self.poll_write_pending(cx) {
+            return Poll::Ready(Err(err));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        if let Write::Passthrough = self.write {
+            ready!(self.poll_write_pending(cx))?;
+            return Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+        }
+
+        let buf = bufs
+            .iter()
+            .find(|buf| !buf.is_empty())
+            .map_or(&[][..], |buf| &**buf);
+        self.poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_write_pending(cx))?;
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_write_pending(cx))?;
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
+
+/// Encodes the settings of an `h2` client with `config` for the
+/// `HTTP2-Settings` header.
+pub(crate) fn settings_header(config: &Config) -> HeaderValue {
+    let mut settings = BytesMut::with_capacity(18);
+    for &(id, value) in &[
+        (SETTINGS_ENABLE_PUSH, 0),
+        (
+            SETTINGS_INITIAL_WINDOW_SIZE,
+            config.initial_stream_window_size,
+        ),
+        (SETTINGS_MAX_FRAME_SIZE, config.max_frame_size),
+    ] {
+        settings.put_u16(id);
+        settings.put_u32(value);
+    }
+
+    HeaderValue::from_maybe_shared(encode_base64url(&settings))
+        .expect("base64url is a valid header value")
+}
+
+/// Returns the request sent with `h2` to open stream 1, standing in for the
+/// request of the upgrade.
+///
+/// Its fields are all in the HPACK static table, so its header block
+/// doesn't add entries to the dynamic table.
+pub(crate) fn stream_1_request() -> http::Request<()> {
+    http::Request::new(())
+}
+
+/// Returns whether `head` accepts to upgrade the connection to h2c.
+pub(crate) fn is_upgrade_response(head: &ResponseHead) -> bool {
+    head.subject == StatusCode::SWITCHING_PROTOCOLS && has_token(&head.headers, &UPGRADE, "h2c")
+}
+
+/// Makes the URI of `req` absolute, as HTTP/2 requires a scheme and an
+/// authority, which HTTP/1.1 requests usually leave to the `Host` header.
+pub(crate) fn set_absolute_uri<B>(req: &mut http::Request<B>) {
+    if req.uri().scheme().is_some() {
+        return;
+    }
+
+    let authority = match req.uri().authority() {
+        Some(authority) => authority.clone(),
+        None => match req
+            .headers()
+            .get(HOST)
+            .and_then(|host| uri::Authority::try_from(host.as_bytes()).ok())
+        {
+            Some(authority) => authority,
+            None => return,
+        },
+    };
+
+    let mut parts = uri::Parts::from(std::mem::take(req.uri_mut()));
+    parts.scheme = Some(uri::Scheme::HTTP);
+    parts.authority = Some(authority);
+    if parts.path_and_query.is_none() {
+        parts.path_and_query = Some(uri::PathAndQuery::from_static("/"));
+    }
+    if let Ok(uri) = Uri::from_parts(parts) {
+        *req.uri_mut() = uri;
+    }
+}
+
+/// Checks that the header block of stream 1 doesn't add entries to the
+/// HPACK dynamic table, and returns the dynamic table size updates it starts
+/// with.
+///
+/// See [RFC 7541 section 6](https://www.rfc-editor.org/rfc/rfc7541#section-6).
+fn size_updates(block: &[u8]) -> io::Result<Bytes> {
+    let mut pos = 0;
+    let mut updates_end = 0;
+    while pos < block.len() {
+        let b = block[pos];
+        if b & 0x80 != 0 {
+            // Indexed header field.
+            decode_int(block, &mut pos, 7)?;
+        } else if b & 0xc0 == 0x40 {
+            // Literal header field with incremental indexing.
+            return Err(unexpected_header_block());
+        } else if b & 0xe0 == 0x20 {
+            if pos != updates_end {
+                return Err(unexpected_header_block());
+            }
+            decode_int(block, &mut pos, 5)?;
+            updates_end = pos;
+        } else {
+            // Literal header field without indexing, or never indexed.
+            if decode_int(block, &mut pos, 4)? == 0 {
+                skip_string(block, &mut pos)?;
+            }
+            skip_string(block, &mut pos)?;
+        }
+    }
+    Ok(Bytes::copy_from_slice(&block[..updates_end]))
+}
+
+fn decode_int(block: &[u8], pos: &mut usize, prefix_len: u8) -> io::Result<usize> {
+    let mask = (1u8 << prefix_len) - 1;
+    let mut value = usize::from(block[*pos] & mask);
+    *pos += 1;
+    if value < usize::from(mask) {
+        return Ok(value);
+    }
+
+    let mut shift = 0;
+    loop {
+        let b = *block.get(*pos).ok_or_else(unexpected_header_block)?;
+        *pos += 1;
+        value += usize::from(b & 0x7f) << shift;
+        if b & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+        if shift > 21 {
+            return Err(unexpected_header_block());
+        }
+    }
+}
+
+fn skip_string(block: &[u8], pos: &mut usize) -> io::Result<()> {
+    if *pos == block.len() {
+        return Err(unexpected_header_block());
+    }
+    let len = decode_int(block, pos, 7)?;
+    match pos.checked_add(len) {
+        Some(end) if end <= block.len() => {
+            *pos = end;
+            Ok(())
+        }
+        _ => Err(unexpected_header_block()),
+    }
+}
+
+fn unexpected_header_block() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "unexpected h2c stream 1 header block",
+    )
+}
+
+// https://www.rfc-editor.org/rfc/rfc4648#section-5, with the padding omitted.
+fn encode_base64url(src: &[u8]) -> Bytes {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+    let mut dst = BytesMut::with_capacity((src.len() * 4 + 2) / 3);
+    for chunk in src.chunks(3) {
+        let mut acc = 0u32;
+        for (i, &b) in chunk.iter().enumerate() {
+            acc |= u32::from(b) << (16 - 8 * i);
+        }
+        for i in 0..=chunk.len() {
+            dst.put_u8(ALPHABET[(acc >> (18 - 6 * i)) as usize & 0x3f]);
+        }
+    }
+    dst.freeze()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+    const SETTINGS: u8 = 0x4;
+    const WINDOW_UPDATE: u8 = 0x8;
+    const ACK: u8 = 0x1;
+
+    #[test]
+    fn base64url() {
+        assert_eq!(encode_base64url(b""), "");
+        assert_eq!(encode_base64url(b"f"), "Zg");
+        assert_eq!(encode_base64url(b"fo"), "Zm8");
+        assert_eq!(encode_base64url(b"foo"), "Zm9v");
+        assert_eq!(encode_base64url(&[0xfb, 0xff]), "-_8");
+    }
+
+    #[test]
+    fn settings_header_default() {
+        let config = Config {
+            initial_stream_window_size: 100,
+            max_frame_size: 65_535,
+            ..Default::default()
+        };
+        assert_eq!(settings_header(&config), "AAIAAAAAAAQAAABkAAUAAP__");
+    }
+
+    #[test]
+    fn absolute_uri_from_host() {
+        let mut req = http::Request::builder()
+            .uri("/foo?bar")
+            .header(HOST, "example.local:8080")
+            .body(())
+            .unwrap();
+        set_absolute_uri(&mut req);
+        assert_eq!(req.uri(), "http://example.local:8080/foo?bar");
+
+        let mut req = http::Request::builder()
+            .uri("https://example.local/foo")
+            .header(HOST, "other.local")
+            .body(())
+            .unwrap();
+        set_absolute_uri(&mut req);
+        assert_eq!(req.uri(), "https://example.local/foo");
+    }
+
+    #[test]
+    fn stream_1_block_size_updates() {
+        // :method GET, :scheme http, :path /
+        assert_eq!(size_updates(&[0x82, 0x86, 0x84]).unwrap(), "");
+        // table sizes 0 and 8192, then a field never indexed
+        let block = [0x20, 0x3f, 0xe1, 0x3f, 0x82, 0x10, 0x01, b'a', 0x01, b'b'];
+        assert_eq!(size_updates(&block).unwrap(), &block[..4]);
+
+        // a literal with incremental indexing adds an entry
+        size_updates(&[0x82, 0x41, 0x01, b'a']).unwrap_err();
+        // size updates must come first
+        size_updates(&[0x82, 0x20]).unwrap_err();
+        // truncated string
+        size_updates(&[0x04, 0x02, b'a']).unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn write_skips_stream_1_headers() {
+        let mut client = Vec::new();
+        client.extend_from_slice(PREFACE);
+        client.extend_from_slice(&[0, 0, 0, SETTINGS, 0, 0, 0, 0, 0]);
+        client.extend_from_slice(&[0, 0, 0, SETTINGS, ACK, 0, 0, 0, 0]);
+        let kept = client.len();
+        client.extend_from_slice(&[0, 0, 2, HEADERS, 0, 0, 0, 0, 1, 0x82, 0x86]);
+        client.extend_from_slice(&[0, 0, 1, CONTINUATION, END_HEADERS, 0, 0, 0, 1, 0x84]);
+        let skipped = client.len();
+        client.extend_from_slice(&[0, 0, 1, HEADERS, END_HEADERS, 0, 0, 0, 3, 0x82]);
+
+        let mut expected = client[..kept].to_vec();
+        expected.extend_from_slice(&client[skipped..]);
+
+        let mock = tokio_test::io::Builder::new().write(&expected).build();
+        let mut io = H2cClientIo::new(mock);
+
+        // written in small pieces, so frames are split across writes
+        for chunk in client.chunks(5) {
+            io.write_all(chunk).await.unwrap();
+        }
+        io.flush().await.unwrap();
+        assert!(matches!(io.write, Write::Passthrough));
+    }
+
+    #[tokio::test]
+    async fn write_moves_size_updates() {
+        let mut client = Vec::new();
+        client.extend_from_slice(PREFACE);
+        let kept = client.len();
+        client.extend_from_slice(&[0, 0, 4, HEADERS, END_HEADERS | END_STREAM, 0, 0, 0, 1]);
+        client.extend_from_slice(&[0x3f, 0xe1, 0x3f, 0x82]);
+        let skipped = client.len();
+        client.extend_from_slice(&[0, 0, 4, WINDOW_UPDATE, 0, 0, 0, 0, 1, 0, 0, 0, 1]);
+        client.extend_from_slice(&[0, 0, 1, HEADERS, END_HEADERS | END_STREAM, 0, 0, 0, 3]);
+        client.push(0x82);
+
+        let mut expected = client[..kept].to_vec();
+        expected.extend_from_slice(&client[skipped..skipped + 13]);
+        expected.extend_from_slice(&[0, 0, 3, HEADERS, END_STREAM, 0, 0, 0, 3, 0x3f, 0xe1, 0x3f]);
+        expected.extend_from_slice(&[0, 0, 1, CONTINUATION, END_HEADERS, 0, 0, 0, 3, 0x82]);
+
+        let mock = tokio_test::io::Builder::new().write(&expected).build();
+        let mut io = H2cClientIo::new(mock);
+
+        for chunk in client.chunks(3) {
+            io.write_all(chunk).await.unwrap();
+        }
+        io.flush().await.unwrap();
+        assert!(matches!(io.write, Write::Passthrough));
+    }
+
+    #[tokio::test]
+    async fn write_rejects_stream_1_indexing() {
+        let mut client = Vec::new();
+        client.extend_from_slice(PREFACE);
+        client.extend_from_slice(&[0, 0, 4, HEADERS, END_HEADERS, 0, 0, 0, 1]);
+        client.extend_from_slice(&[0x41, 0x01, b'a', 0x82]);
+
+        let mock = tokio_test::io::Builder::new().build();
+        let mut io = H2cClientIo::new(mock);
+        let err = io.write_all(&client).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
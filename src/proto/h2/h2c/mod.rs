@@ -0,0 +1,63 @@
+//! Cleartext HTTP/2 (h2c) upgrades of HTTP/1.1 connections.
+//!
+//! See [RFC 7540 section 3.2](https://httpwg.org/specs/rfc7540.html#discover-http).
+//!
+//! With an upgrade, the request that asked for it becomes stream 1 of the
+//! HTTP/2 connection, without ever being sent as HTTP/2. Both sides make up
+//! for it by rewriting the frames between the IO and the `h2` crate.
+
+#[cfg(feature = "http1")]
+use bytes::{BufMut, BytesMut};
+#[cfg(feature = "http1")]
+use http::header::{HeaderMap, HeaderName};
+
+#[cfg(all(feature = "server", feature = "http1"))]
+mod server;
+
+#[cfg(all(feature = "server", feature = "http1"))]
+pub(crate) use self::server::{is_upgrade_request, H2cIo};
+
+#[cfg(all(feature = "client", feature = "http1"))]
+mod client;
+
+#[cfg(all(feature = "client", feature = "http1"))]
+pub(crate) use self::client::{
+    is_upgrade_response, set_absolute_uri, settings_header, stream_1_request, H2cClientIo,
+};
+
+#[cfg(feature = "http1")]
+const FRAME_HEADER_LEN: usize = 9;
+// The smallest SETTINGS_MAX_FRAME_SIZE allowed, so any peer accepts it.
+#[cfg(feature = "http1")]
+const MAX_FRAME_SIZE: usize = 16_384;
+
+#[cfg(feature = "http1")]
+const HEADERS: u8 = 0x1;
+#[cfg(all(feature = "server", feature = "http1"))]
+const SETTINGS: u8 = 0x4;
+#[cfg(feature = "http1")]
+const CONTINUATION: u8 = 0x9;
+
+#[cfg(all(feature = "server", feature = "http1"))]
+const ACK: u8 = 0x1;
+#[cfg(feature = "http1")]
+const END_HEADERS: u8 = 0x4;
+
+#[cfg(feature = "http1")]
+fn has_token(headers: &HeaderMap, name: &HeaderName, token: &str) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
+#[cfg(feature = "http1")]
+fn put_frame_head(dst: &mut BytesMut, len: usize, kind: u8, flags: u8, stream_id: u32) {
+    debug_assert!(len <= MAX_FRAME_SIZE);
+    dst.put_uint(len as u64, 3);
+    dst.put_u8(kind);
+    dst.put_u8(flags);
+    dst.put_u32(stream_id);
+}
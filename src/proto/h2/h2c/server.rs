@@ -0,0 +1,525 @@
+//! The server side of h2c upgrades.
+//!
+//! The `h2` crate has no notion of an upgraded connection, so the upgrade is
+//! done by rewriting the bytes the client sends once it switched protocols:
+//! the settings from the `HTTP2-Settings` header are merged into the client's
+//! first SETTINGS frame, and the original request is added right after it as
+//! a HEADERS frame on stream 1. To the `h2` server, it looks as if the client
+//! had sent that request over HTTP/2 itself.
+
+use std::cmp;
+use std::io;
+
+use bytes::{Buf, Bytes, BytesMut};
+use http::header::{HeaderName, CONNECTION, HOST, TE, TRANSFER_ENCODING, UPGRADE};
+use http::{HeaderMap, Method, Version};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use super::{
+    has_token, put_frame_head, ACK, CONTINUATION, END_HEADERS, FRAME_HEADER_LEN, HEADERS,
+    MAX_FRAME_SIZE, SETTINGS,
+};
+use crate::common::{task, Pin, Poll};
+use crate::proto::RequestHead;
+
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+const SWITCHING_PROTOCOLS: &[u8] =
+    b"HTTP/1.1 101 Switching Protocols\r\nconnection: Upgrade\r\nupgrade: h2c\r\n\r\n";
+
+const END_STREAM: u8 = 0x1;
+
+/// The IO of an HTTP/2 server connection upgraded from HTTP/1.1.
+#[derive(Debug)]
+pub(crate) struct H2cIo<T> {
+    inner: T,
+    /// Bytes to write before anything else, i.e. the `101` response.
+    pending_write: Bytes,
+    read: Read,
+}
+
+#[derive(Debug)]
+enum Read {
+    /// Reads go straight to the inner IO.
+    Passthrough,
+    /// Buffering the client preface and its first SETTINGS frame, to add
+    /// the settings and request of the upgrade once they are complete.
+    Preface {
+        buf: BytesMut,
+        settings: Bytes,
+        request: Bytes,
+    },
+    /// Rewritten bytes to read before going back to the inner IO.
+    Replay(Bytes),
+}
+
+impl<T> H2cIo<T> {
+    /// Wraps the IO of an HTTP/1.1 connection whose client asked to upgrade
+    /// to h2c with `head`.
+    ///
+    /// The `101 Switching Protocols` response is written before the server
+    /// preface, and `head` is received as the request of stream 1.
+    pub(crate) fn upgrade(io: T, head: &RequestHead) -> Self {
+        H2cIo {
+            inner: io,
+            pending_write: Bytes::from_static(SWITCHING_PROTOCOLS),
+            read: Read::Preface {
+                buf: BytesMut::new(),
+                settings: decode_settings(&head.headers).unwrap_or_default(),
+                request: encode_request(head),
+            },
+        }
+    }
+}
+
+impl<T> H2cIo<T>
+where
+    T: AsyncWrite + Unpin,
+{
+    fn poll_write_pending(&mut self, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        while !self.pending_write.is_empty() {
+            let n = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.pending_write))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.pending_write.advance(n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> AsyncRead for H2cIo<T>
+where
+    T: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            let me = &mut *self;
+            match me.read {
+                Read::Passthrough => break,
+                Read::Preface {
+                    buf: ref mut preface,
+                    ref settings,
+                    ref request,
+                } => {
+                    if let Some(bytes) = rewrite_preface(preface, settings, request) {
+                        me.read = Read::Replay(bytes);
+                        continue;
+                    }
+
+                    let mut chunk = [0; 1024];
+                    let mut chunk_buf = ReadBuf::new(&mut chunk);
+                    ready!(Pin::new(&mut me.inner).poll_read(cx, &mut chunk_buf))?;
+                    if chunk_buf.filled().is_empty() {
+                        // EOF, just hand over whatever was received.
+                        me.read = Read::Replay(preface.split().freeze());
+                    } else {
+                        preface.extend_from_slice(chunk_buf.filled());
+                    }
+                }
+                Read::Replay(ref mut bytes) => {
+                    if !bytes.is_empty() {
+                        let n = cmp::min(bytes.len(), buf.remaining());
+                        buf.put_slice(&bytes[..n]);
+                        bytes.advance(n);
+                        if bytes.is_empty() {
+                            me.read = Read::Passthrough;
+                        }
+                        return Poll::Ready(Ok(()));
+                    }
+                    me.read = Read::Passthrough;
+                }
+            }
+        }
+
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<T> AsyncWrite for H2cIo<T>
+where
+    T: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.poll_write_pending(cx))?;
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.poll_write_pending(cx))?;
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_write_pending(cx))?;
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_write_pending(cx))?;
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
+
+/// Returns whether `head` asks to upgrade the connection to h2c.
+pub(crate) fn is_upgrade_request(head: &RequestHead) -> bool {
+    head.version == Version::HTTP_11
+        && head.subject.0 != Method::CONNECT
+        && has_token(&head.headers, &UPGRADE, "h2c")
+        && has_token(&head.headers, &CONNECTION, "upgrade")
+        && has_token(&head.headers, &CONNECTION, "http2-settings")
+        && decode_settings(&head.headers).is_some()
+}
+
+/// Decodes the SETTINGS payload from the `HTTP2-Settings` header.
+///
+/// The header must be present exactly once.
+fn decode_settings(headers: &HeaderMap) -> Option<Bytes> {
+    let mut values = headers.get_all("http2-settings").iter();
+    let value = values.next()?;
+    if values.next().is_some() {
+        return None;
+    }
+
+    let settings = decode_base64url(value.as_bytes())?;
+    // Each setting is a 16-bit identifier followed by a 32-bit value.
+    if settings.len() % 6 != 0 || settings.len() > MAX_FRAME_SIZE {
+        return None;
+    }
+    Some(Bytes::from(settings))
+}
+
+// https://www.rfc-editor.org/rfc/rfc4648#section-5, with the padding omitted.
+fn decode_base64url(src: &[u8]) -> Option<Vec<u8>> {
+    let mut src = src;
+    while let [rest @ .., b'='] = src {
+        src = rest;
+    }
+    if src.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut dst = Vec::with_capacity(src.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for &b in src {
+        let val = match b {
+            b'A'..=b'Z' => b - b'A',
+            b'a'..=b'z' => b - b'a' + 26,
+            b'0'..=b'9' => b - b'0' + 52,
+            b'-' => 62,
+            b'_' => 63,
+            _ => return None,
+        };
+        acc = (acc << 6) | u32::from(val);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            dst.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Some(dst)
+}
+
+/// Encodes `head` as the HEADERS (and CONTINUATION) frames of stream 1.
+///
+/// The header block only uses literals that are never indexed, so the
+/// decoder's dynamic table is left as the client expects it to be.
+fn encode_request(head: &RequestHead) -> Bytes {
+    let uri = &head.subject.1;
+    let authority = uri
+        .authority()
+        .map(|authority| authority.as_str().as_bytes())
+        .or_else(|| head.headers.get(HOST).map(|host| host.as_bytes()));
+    let path = uri
+        .path_and_query()
+        .map(|path| path.as_str())
+        .filter(|path| !path.is_empty())
+        .unwrap_or("/");
+
+    let mut block = Vec::new();
+    encode_field(&mut block, b":method", head.subject.0.as_str().as_bytes());
+    encode_field(&mut block, b":scheme", b"http");
+    if let Some(authority) = authority {
+        encode_field(&mut block, b":authority", authority);
+    }
+    encode_field(&mut block, b":path", path.as_bytes());
+
+    // Connection-specific headers are forbidden in HTTP/2, this includes
+    // those named in the `Connection` header, like `HTTP2-Settings`.
+    let is_connection_specific = |name: &HeaderName| {
+        matches!(
+            name.as_str(),
+            "keep-alive" | "proxy-connection" | "http2-settings"
+        ) || *name == CONNECTION
+            || *name == HOST
+            || *name == TRANSFER_ENCODING
+            || *name == UPGRADE
+            || has_token(&head.headers, &CONNECTION, name.as_str())
+    };
+    for (name, value) in head.headers.iter() {
+        if is_connection_specific(name) || (*name == TE && value != "trailers") {
+            continue;
+        }
+        encode_field(&mut block, name.as_str().as_bytes(), value.as_bytes());
+    }
+
+    let frames = (block.len() + MAX_FRAME_SIZE - 1) / MAX_FRAME_SIZE;
+    let mut dst = BytesMut::with_capacity(block.len() + frames * FRAME_HEADER_LEN);
+    let mut chunks = block.chunks(MAX_FRAME_SIZE).peekable();
+    let (mut kind, mut flags) = (HEADERS, END_STREAM);
+    while let Some(chunk) = chunks.next() {
+        if chunks.peek().is_none() {
+            flags |= END_HEADERS;
+        }
+        put_frame_head(&mut dst, chunk.len(), kind, flags, 1);
+        dst.extend_from_slice(chunk);
+        kind = CONTINUATION;
+        flags = 0;
+    }
+    dst.freeze()
+}
+
+// https://www.rfc-editor.org/rfc/rfc7541#section-6.2.3
+fn encode_field(dst: &mut Vec<u8>, name: &[u8], value: &[u8]) {
+    dst.push(0x10);
+    encode_str(dst, name);
+    encode_str(dst, value);
+}
+
+// https://www.rfc-editor.org/rfc/rfc7541#section-5.2, without Huffman coding.
+fn encode_str(dst: &mut Vec<u8>, s: &[u8]) {
+    const PREFIX_MAX: usize = 0x7f;
+
+    let mut len = s.len();
+    if len < PREFIX_MAX {
+        dst.push(len as u8);
+    } else {
+        dst.push(PREFIX_MAX as u8);
+        len -= PREFIX_MAX;
+        while len >= 0x80 {
+            dst.push((len & 0x7f) as u8 | 0x80);
+            len >>= 7;
+        }
+        dst.push(len as u8);
+    }
+    dst.extend_from_slice(s);
+}
+
+/// Rewrites the client preface and first SETTINGS frame in `buf`, once
+/// they are complete, to include the `settings` and `request` of the upgrade.
+///
+/// If the client didn't send a preface followed by a SETTINGS frame, the
+/// bytes are returned as-is, and the h2 server will reject them.
+fn rewrite_preface(buf: &mut BytesMut, settings: &[u8], request: &[u8]) -> Option<Bytes> {
+    let prefix_len = cmp::min(buf.len(), PREFACE.len());
+    if buf[..prefix_len] != PREFACE[..prefix_len] {
+        return Some(buf.split().freeze());
+    }
+
+    let head_end = PREFACE.len() + FRAME_HEADER_LEN;
+    if buf.len() < head_end {
+        return None;
+    }
+    let head = &buf[PREFACE.len()..head_end];
+    let len = (&head[..3]).get_uint(3) as usize;
+    if head[3] != SETTINGS || head[4] & ACK != 0 || len > MAX_FRAME_SIZE {
+        return Some(buf.split().freeze());
+    }
+
+    let frame_end = head_end + len;
+    if buf.len() < frame_end {
+        return None;
+    }
+
+    // Settings are applied in order, so the client's own SETTINGS frame
+    // takes precedence over the `HTTP2-Settings` header. If both don't fit
+    // in one frame, the frame from the client is enough.
+    let settings = if len + settings.len() <= MAX_FRAME_SIZE {
+        settings
+    } else {
+        &[]
+    };
+
+    let mut dst = BytesMut::with_capacity(buf.len() + settings.len() + request.len());
+    dst.extend_from_slice(PREFACE);
+    put_frame_head(&mut dst, len + settings.len(), SETTINGS, 0, 0);
+    dst.extend_from_slice(settings);
+    dst.extend_from_slice(&buf[head_end..frame_end]);
+    dst.extend_from_slice(request);
+    dst.extend_from_slice(&buf[frame_end..]);
+    buf.clear();
+    Some(dst.freeze())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::{MessageHead, RequestLine};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn upgrade_head(settings: &'static str) -> RequestHead {
+        let mut head = MessageHead {
+            version: Version::HTTP_11,
+            subject: RequestLine(Method::GET, "/foo?bar".parse().unwrap()),
+            ..Default::default()
+        };
+        head.headers.insert(HOST, "example.local".parse().unwrap());
+        head.headers
+            .insert(CONNECTION, "Upgrade, HTTP2-Settings".parse().unwrap());
+        head.headers.insert(UPGRADE, "h2c".parse().unwrap());
+        head.headers
+            .insert("http2-settings", settings.parse().unwrap());
+        head.headers.insert("x-foo", "bar".parse().unwrap());
+        head
+    }
+
+    #[test]
+    fn upgrade_request() {
+        let head = upgrade_head("AAMAAABkAAQAAP__");
+        assert!(is_upgrade_request(&head));
+        assert_eq!(
+            decode_settings(&head.headers).unwrap(),
+            &[0, 3, 0, 0, 0, 100, 0, 4, 0, 0, 0xff, 0xff][..]
+        );
+
+        let mut head = upgrade_head("AAMAAABkAAQAAP__");
+        head.headers.remove(CONNECTION);
+        head.headers.insert(CONNECTION, "upgrade".parse().unwrap());
+        assert!(
+            !is_upgrade_request(&head),
+            "connection missing http2-settings"
+        );
+
+        let mut head = upgrade_head("AAMAAABkAAQAAP__");
+        head.version = Version::HTTP_10;
+        assert!(!is_upgrade_request(&head), "HTTP/1.0");
+
+        let mut head = upgrade_head("AAMAAABkAAQAAP__");
+        head.headers.insert(UPGRADE, "websocket".parse().unwrap());
+        assert!(!is_upgrade_request(&head), "upgrade to websocket");
+    }
+
+    #[test]
+    fn upgrade_request_invalid_settings() {
+        // not a multiple of 6 bytes
+        assert!(!is_upgrade_request(&upgrade_head("AAMAAABkAA")));
+        // not base64url
+        assert!(!is_upgrade_request(&upgrade_head("AAMAAABkAAQAAP//")));
+
+        let mut head = upgrade_head("");
+        head.headers.append("http2-settings", "".parse().unwrap());
+        assert!(!is_upgrade_request(&head), "multiple HTTP2-Settings");
+    }
+
+    #[test]
+    fn encode_request_fields() {
+        let frames = encode_request(&upgrade_head(""));
+        assert_eq!(frames[3], HEADERS);
+        assert_eq!(frames[4], END_STREAM | END_HEADERS);
+        assert_eq!(&frames[5..9], &[0, 0, 0, 1]);
+
+        let block = &frames[FRAME_HEADER_LEN..];
+        assert_eq!(block.len(), (&frames[..3]).get_uint(3) as usize);
+
+        let mut expected = Vec::new();
+        encode_field(&mut expected, b":method", b"GET");
+        encode_field(&mut expected, b":scheme", b"http");
+        encode_field(&mut expected, b":authority", b"example.local");
+        encode_field(&mut expected, b":path", b"/foo?bar");
+        encode_field(&mut expected, b"x-foo", b"bar");
+        assert_eq!(block, &expected[..]);
+    }
+
+    #[test]
+    fn encode_request_continuation() {
+        let mut head = upgrade_head("");
+        head.headers
+            .insert("x-big", "a".repeat(MAX_FRAME_SIZE).parse().unwrap());
+        let frames = encode_request(&head);
+
+        assert_eq!((&frames[..3]).get_uint(3) as usize, MAX_FRAME_SIZE);
+        assert_eq!(frames[3], HEADERS);
+        assert_eq!(frames[4], END_STREAM);
+
+        let next = &frames[FRAME_HEADER_LEN + MAX_FRAME_SIZE..];
+        assert_eq!(next[3], CONTINUATION);
+        assert_eq!(next[4], END_HEADERS);
+        assert_eq!(
+            next.len(),
+            FRAME_HEADER_LEN + (&next[..3]).get_uint(3) as usize
+        );
+    }
+
+    #[test]
+    fn encode_str_long() {
+        let mut dst = Vec::new();
+        encode_str(&mut dst, &[b'a'; 1337]);
+        // 1337 - 127 = 1210 = 0b1001_0111010
+        assert_eq!(&dst[..3], &[0x7f, 0xba, 0x09]);
+        assert_eq!(dst.len(), 3 + 1337);
+    }
+
+    #[tokio::test]
+    async fn upgrade_io() {
+        let head = upgrade_head("AAMAAABk");
+        let request = encode_request(&head);
+
+        let mut client = Vec::new();
+        client.extend_from_slice(PREFACE);
+        client.extend_from_slice(&[0, 0, 6, SETTINGS, 0, 0, 0, 0, 0]);
+        client.extend_from_slice(&[0, 4, 0, 1, 0, 0]);
+        client.extend_from_slice(b"more");
+
+        let mock = tokio_test::io::Builder::new()
+            .read(&client[..30])
+            .read(&client[30..])
+            .write(SWITCHING_PROTOCOLS)
+            .write(b"server preface")
+            .build();
+        let mut io = H2cIo::upgrade(mock, &head);
+
+        io.write_all(b"server preface").await.unwrap();
+
+        let mut expected = Vec::new();
+        expected.extend_from_slice(PREFACE);
+        expected.extend_from_slice(&[0, 0, 12, SETTINGS, 0, 0, 0, 0, 0]);
+        expected.extend_from_slice(&[0, 3, 0, 0, 0, 100]);
+        expected.extend_from_slice(&[0, 4, 0, 1, 0, 0]);
+        expected.extend_from_slice(&request);
+        expected.extend_from_slice(b"more");
+
+        let mut received = vec![0; expected.len()];
+        io.read_exact(&mut received).await.unwrap();
+        assert_eq!(received, expected);
+    }
+
+    #[tokio::test]
+    async fn upgrade_io_not_h2() {
+        let head = upgrade_head("AAMAAABk");
+        let mock = tokio_test::io::Builder::new()
+            .read(b"GET / HTTP/1.1\r\n\r\n")
+            .build();
+        let mut io = H2cIo::upgrade(mock, &head);
+
+        let mut received = Vec::new();
+        io.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received, b"GET / HTTP/1.1\r\n\r\n");
+    }
+}
@@ -6,14 +6,24 @@ use pin_project_lite::pin_project;
 use std::error::Error as StdError;
 use std::io::{self, Cursor, IoSlice};
 use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::task::Context;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tracing::{debug, trace, warn};
 
 use crate::body::HttpBody;
 use crate::common::{task, Future, Pin, Poll};
+use crate::observe::ObserverRef;
+#[cfg(all(feature = "server", feature = "runtime"))]
+use crate::proto::body_timer::BodyTimer;
 use crate::proto::h2::ping::Recorder;
 
+// `pin_project!` doesn't allow `#[cfg]` on fields, so without body timeouts
+// the body adapters hold an `Option<Never>`, which is always `None`.
+#[cfg(not(all(feature = "server", feature = "runtime")))]
+type BodyTimer = crate::common::Never;
+
 pub(crate) mod ping;
 
 cfg_client! {
@@ -21,6 +31,9 @@ cfg_client! {
     pub(crate) use self::client::ClientTask;
 }
 
+#[cfg(any(feature = "server", all(feature = "client", feature = "http1")))]
+pub(crate) mod h2c;
+
 cfg_server! {
     pub(crate) mod server;
     pub(crate) use self::server::Server;
@@ -91,6 +104,9 @@ pin_project! {
     {
         body_tx: SendStream<SendBuf<S::Data>>,
         data_done: bool,
+        observer: ObserverRef,
+        // Enforces the minimum rate at which the body is sent, if any.
+        timer: Option<BodyTimer>,
         #[pin]
         stream: S,
     }
@@ -100,13 +116,25 @@ impl<S> PipeToSendStream<S>
 where
     S: HttpBody,
 {
-    fn new(stream: S, tx: SendStream<SendBuf<S::Data>>) -> PipeToSendStream<S> {
+    fn new(
+        stream: S,
+        tx: SendStream<SendBuf<S::Data>>,
+        observer: ObserverRef,
+    ) -> PipeToSendStream<S> {
         PipeToSendStream {
             body_tx: tx,
             data_done: false,
+            observer,
+            timer: None,
             stream,
         }
     }
+
+    #[cfg(all(feature = "server", feature = "runtime"))]
+    fn with_timer(mut self, timer: Option<BodyTimer>) -> PipeToSendStream<S> {
+        self.timer = timer;
+        self
+    }
 }
 
 impl<S> Future for PipeToSendStream<S>
@@ -127,9 +155,32 @@ where
 
                 if me.body_tx.capacity() == 0 {
                     loop {
-                        match ready!(me.body_tx.poll_capacity(cx)) {
+                        let capacity = match me.body_tx.poll_capacity(cx) {
+                            Poll::Ready(capacity) => capacity,
+                            Poll::Pending => {
+                                // Waiting on the peer to receive more of
+                                // the body.
+                                #[cfg(all(feature = "server", feature = "runtime"))]
+                                if let Some(ref mut timer) = me.timer {
+                                    timer.resume();
+                                    if let Poll::Ready(err) = timer.poll_expired(cx) {
+                                        debug!("send body timed out: {}", err);
+                                        me.body_tx.send_reset(Reason::CANCEL);
+                                        return Poll::Ready(Err(err));
+                                    }
+                                }
+                                return Poll::Pending;
+                            }
+                        };
+                        match capacity {
                             Some(Ok(0)) => {}
-                            Some(Ok(_)) => break,
+                            Some(Ok(_)) => {
+                                #[cfg(all(feature = "server", feature = "runtime"))]
+                                if let Some(ref mut timer) = me.timer {
+                                    timer.pause();
+                                }
+                                break;
+                            }
                             Some(Err(e)) => {
                                 return Poll::Ready(Err(crate::Error::new_body_write(e)))
                             }
@@ -163,6 +214,11 @@ where
                             is_eos,
                         );
 
+                        me.observer.on_body_sent(chunk.remaining());
+                        #[cfg(all(feature = "server", feature = "runtime"))]
+                        if let Some(ref mut timer) = me.timer {
+                            timer.on_data(chunk.remaining());
+                        }
                         let buf = SendBuf::Buf(chunk);
                         me.body_tx
                             .send_data(buf, is_eos)
@@ -228,7 +284,7 @@ impl<B: Buf> SendStreamExt for SendStream<SendBuf<B>> {
     {
         let err = crate::Error::new_user_body(err);
         debug!("send body user stream error: {}", err);
-        self.send_reset(err.h2_reason());
+        self.send_reset(err.h2_reason_or_internal());
         err
     }
 
@@ -283,6 +339,111 @@ impl<B: Buf> Buf for SendBuf<B> {
     }
 }
 
+/// Reports a GOAWAY received from the peer to the observer, from the errors
+/// `h2` fails the connection and its streams with.
+///
+/// `h2` only reports a GOAWAY through those errors, so it is reported once
+/// per connection, by the first of them.
+#[derive(Clone)]
+struct GoAwayObserver {
+    observer: ObserverRef,
+    /// Whether the GOAWAY was already reported, only kept with an observer.
+    reported: Option<Arc<AtomicBool>>,
+}
+
+impl GoAwayObserver {
+    fn new(observer: ObserverRef) -> GoAwayObserver {
+        let reported = if observer.is_some() {
+            Some(Arc::new(AtomicBool::new(false)))
+        } else {
+            None
+        };
+        GoAwayObserver { observer, reported }
+    }
+
+    fn observe(&self, err: &h2::Error) {
+        let reported = match self.reported {
+            Some(ref reported) => reported,
+            None => return,
+        };
+        if err.is_go_away() && err.is_remote() {
+            if let Some(reason) = err.reason() {
+                if !reported.swap(true, Ordering::Relaxed) {
+                    self.observer.on_goaway_received(reason.into());
+                }
+            }
+        }
+    }
+}
+
+/// An IO that reports the bytes read and written to an observer.
+struct ObservedIo<T> {
+    io: T,
+    observer: ObserverRef,
+}
+
+impl<T> ObservedIo<T> {
+    fn new(io: T, observer: ObserverRef) -> ObservedIo<T> {
+        ObservedIo { io, observer }
+    }
+}
+
+impl<T> AsyncRead for ObservedIo<T>
+where
+    T: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut self.io).poll_read(cx, buf))?;
+        let n = buf.filled().len() - before;
+        if n > 0 {
+            self.observer.on_read(n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> AsyncWrite for ObservedIo<T>
+where
+    T: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let n = ready!(Pin::new(&mut self.io).poll_write(cx, buf))?;
+        self.observer.on_write(n);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let n = ready!(Pin::new(&mut self.io).poll_write_vectored(cx, bufs))?;
+        self.observer.on_write(n);
+        Poll::Ready(Ok(n))
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+}
+
 struct H2Upgraded<B>
 where
     B: Buf,
@@ -36,10 +36,15 @@ use h2::{Ping, PingPong};
 use tokio::time::{Instant, Sleep};
 use tracing::{debug, trace};
 
+use crate::observe::ObserverRef;
+
 type WindowSize = u32;
 
-pub(super) fn disabled() -> Recorder {
-    Recorder { shared: None }
+pub(super) fn disabled(observer: ObserverRef) -> Recorder {
+    Recorder {
+        shared: None,
+        observer,
+    }
 }
 
 pub(super) fn channel(ping_pong: PingPong, config: Config) -> (Recorder, Ponger) {
@@ -88,11 +93,13 @@ pub(super) fn channel(ping_pong: PingPong, config: Config) -> (Recorder, Ponger)
     (
         Recorder {
             shared: Some(shared.clone()),
+            observer: config.observer.clone(),
         },
         Ponger {
             bdp,
             #[cfg(feature = "runtime")]
             keep_alive,
+            observer: config.observer,
             shared,
         },
     )
@@ -111,17 +118,21 @@ pub(super) struct Config {
     /// If true, sends pings even when there are no active streams.
     #[cfg(feature = "runtime")]
     pub(super) keep_alive_while_idle: bool,
+    /// Notified of received data and of the round-trip time of pings.
+    pub(super) observer: ObserverRef,
 }
 
 #[derive(Clone)]
 pub(crate) struct Recorder {
     shared: Option<Arc<Mutex<Shared>>>,
+    observer: ObserverRef,
 }
 
 pub(super) struct Ponger {
     bdp: Option<Bdp>,
     #[cfg(feature = "runtime")]
     keep_alive: Option<KeepAlive>,
+    observer: ObserverRef,
     shared: Arc<Mutex<Shared>>,
 }
 
@@ -213,6 +224,8 @@ impl Config {
 
 impl Recorder {
     pub(crate) fn record_data(&self, len: usize) {
+        self.observer.on_body_received(len);
+
         let shared = if let Some(ref shared) = self.shared {
             shared
         } else {
@@ -262,12 +275,19 @@ impl Recorder {
         }
     }
 
+    /// Records that an incoming message was received completely.
+    ///
+    /// Only the first call is reported, later ones are ignored.
+    pub(crate) fn record_end_of_stream(&mut self) {
+        std::mem::take(&mut self.observer).on_message_complete();
+    }
+
     /// If the incoming stream is already closed, convert self into
     /// a disabled reporter.
     #[cfg(feature = "client")]
     pub(super) fn for_stream(self, stream: &h2::RecvStream) -> Self {
         if stream.is_end_stream() {
-            disabled()
+            disabled(self.observer)
         } else {
             self
         }
@@ -319,6 +339,7 @@ impl Ponger {
                 locked.ping_sent_at = None;
                 let rtt = now - start;
                 trace!("recv pong");
+                self.observer.on_ping_rtt(rtt);
 
                 #[cfg(feature = "runtime")]
                 {
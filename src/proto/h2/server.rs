@@ -1,4 +1,5 @@
 use std::error::Error as StdError;
+use std::io::{self, IoSlice};
 use std::marker::Unpin;
 #[cfg(feature = "runtime")]
 use std::time::Duration;
@@ -8,18 +9,27 @@ use h2::server::{Connection, Handshake, SendResponse};
 use h2::{Reason, RecvStream};
 use http::{Method, Request};
 use pin_project_lite::pin_project;
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+#[cfg(feature = "runtime")]
+use tokio::time::Sleep;
 use tracing::{debug, trace, warn};
 
-use super::{ping, PipeToSendStream, SendBuf};
+#[cfg(feature = "http1")]
+use super::h2c::H2cIo;
+use super::{ping, BodyTimer, GoAwayObserver, ObservedIo, PipeToSendStream, SendBuf};
 use crate::body::HttpBody;
 use crate::common::exec::ConnStreamExec;
 use crate::common::{date, task, Future, Pin, Poll};
-use crate::ext::Protocol;
+use crate::ext::{InformationalSender, Protocol};
 use crate::headers;
+use crate::observe::ObserverRef;
+#[cfg(feature = "runtime")]
+use crate::proto::body_timer::BodyTimeouts;
 use crate::proto::h2::ping::Recorder;
 use crate::proto::h2::{H2Upgraded, UpgradedSendStream};
 use crate::proto::Dispatched;
+#[cfg(feature = "http1")]
+use crate::proto::RequestHead;
 use crate::service::HttpService;
 
 use crate::upgrade::{OnUpgrade, Pending, Upgraded};
@@ -52,6 +62,17 @@ pub(crate) struct Config {
     pub(crate) keep_alive_timeout: Duration,
     pub(crate) max_send_buffer_size: usize,
     pub(crate) max_header_list_size: u32,
+    pub(crate) observer: ObserverRef,
+    #[cfg(feature = "runtime")]
+    pub(crate) body_timeouts: BodyTimeouts,
+    #[cfg(feature = "runtime")]
+    pub(crate) graceful_shutdown_timeout: Option<Duration>,
+    #[cfg(feature = "runtime")]
+    pub(crate) graceful_shutdown_reason: Reason,
+    #[cfg(feature = "runtime")]
+    pub(crate) max_connection_age: Option<Duration>,
+    #[cfg(feature = "runtime")]
+    pub(crate) max_connection_age_grace: Option<Duration>,
 }
 
 impl Default for Config {
@@ -69,6 +90,17 @@ impl Default for Config {
             keep_alive_timeout: Duration::from_secs(20),
             max_send_buffer_size: DEFAULT_MAX_SEND_BUF_SIZE,
             max_header_list_size: DEFAULT_SETTINGS_MAX_HEADER_LIST_SIZE,
+            observer: ObserverRef::default(),
+            #[cfg(feature = "runtime")]
+            body_timeouts: BodyTimeouts::default(),
+            #[cfg(feature = "runtime")]
+            graceful_shutdown_timeout: None,
+            #[cfg(feature = "runtime")]
+            graceful_shutdown_reason: Reason::CANCEL,
+            #[cfg(feature = "runtime")]
+            max_connection_age: None,
+            #[cfg(feature = "runtime")]
+            max_connection_age_grace: None,
         }
     }
 }
@@ -81,7 +113,7 @@ pin_project! {
     {
         exec: E,
         service: S,
-        state: State<T, B>,
+        state: State<ObservedIo<ServerIo<T>>, B>,
     }
 }
 
@@ -91,6 +123,10 @@ where
 {
     Handshaking {
         ping_config: ping::Config,
+        #[cfg(feature = "runtime")]
+        body_timeouts: BodyTimeouts,
+        #[cfg(feature = "runtime")]
+        shutdown: ShutdownConfig,
         hs: Handshake<T, SendBuf<B::Data>>,
     },
     Serving(Serving<T, B>),
@@ -104,6 +140,112 @@ where
     ping: Option<(ping::Recorder, ping::Ponger)>,
     conn: Connection<T, SendBuf<B::Data>>,
     closing: Option<crate::Error>,
+    observer: ObserverRef,
+    go_away: GoAwayObserver,
+    #[cfg(feature = "runtime")]
+    body_timeouts: BodyTimeouts,
+    #[cfg(feature = "runtime")]
+    shutdown: ShutdownConfig,
+    /// Set once a graceful shutdown starts, if it has a timeout.
+    #[cfg(feature = "runtime")]
+    shutdown_timer: Option<Pin<Box<Sleep>>>,
+    /// Starts a graceful shutdown once the connection reaches its maximum
+    /// age, if any.
+    #[cfg(feature = "runtime")]
+    max_age_timer: Option<Pin<Box<Sleep>>>,
+}
+
+/// When a server connection shuts down, and how long it waits for the
+/// streams in progress when it does.
+#[cfg(feature = "runtime")]
+#[derive(Clone, Copy)]
+struct ShutdownConfig {
+    /// How long a graceful shutdown waits for the streams to complete.
+    timeout: Option<Duration>,
+    /// The error code closing the connection once a graceful shutdown times
+    /// out.
+    reason: Reason,
+    max_age: Option<Duration>,
+    /// How long the graceful shutdown started by the maximum age waits, if
+    /// not `timeout`.
+    max_age_grace: Option<Duration>,
+}
+
+/// The IO of a server connection, only wrapped if it was upgraded to h2c.
+enum ServerIo<T> {
+    Plain(T),
+    #[cfg(feature = "http1")]
+    H2c(H2cIo<T>),
+}
+
+impl<T> AsyncRead for ServerIo<T>
+where
+    T: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match *self.get_mut() {
+            ServerIo::Plain(ref mut io) => Pin::new(io).poll_read(cx, buf),
+            #[cfg(feature = "http1")]
+            ServerIo::H2c(ref mut io) => Pin::new(io).poll_read(cx, buf),
+        }
+    }
+}
+
+impl<T> AsyncWrite for ServerIo<T>
+where
+    T: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match *self.get_mut() {
+            ServerIo::Plain(ref mut io) => Pin::new(io).poll_write(cx, buf),
+            #[cfg(feature = "http1")]
+            ServerIo::H2c(ref mut io) => Pin::new(io).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        match *self.get_mut() {
+            ServerIo::Plain(ref mut io) => Pin::new(io).poll_write_vectored(cx, bufs),
+            #[cfg(feature = "http1")]
+            ServerIo::H2c(ref mut io) => Pin::new(io).poll_write_vectored(cx, bufs),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        match *self {
+            ServerIo::Plain(ref io) => io.is_write_vectored(),
+            #[cfg(feature = "http1")]
+            ServerIo::H2c(ref io) => io.is_write_vectored(),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        match *self.get_mut() {
+            ServerIo::Plain(ref mut io) => Pin::new(io).poll_flush(cx),
+            #[cfg(feature = "http1")]
+            ServerIo::H2c(ref mut io) => Pin::new(io).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        match *self.get_mut() {
+            ServerIo::Plain(ref mut io) => Pin::new(io).poll_shutdown(cx),
+            #[cfg(feature = "http1")]
+            ServerIo::H2c(ref mut io) => Pin::new(io).poll_shutdown(cx),
+        }
+    }
 }
 
 impl<T, S, B, E> Server<T, S, B, E>
@@ -115,6 +257,27 @@ where
     E: ConnStreamExec<S::Future, B>,
 {
     pub(crate) fn new(io: T, service: S, config: &Config, exec: E) -> Server<T, S, B, E> {
+        Server::with_io(ServerIo::Plain(io), service, config, exec)
+    }
+
+    /// Creates a server for an HTTP/1.1 connection upgraded to h2c with the
+    /// request in `head`.
+    ///
+    /// The `101 Switching Protocols` response is written by the server, and
+    /// the request is served as stream 1.
+    #[cfg(feature = "http1")]
+    pub(crate) fn new_h2c(
+        io: T,
+        head: RequestHead,
+        service: S,
+        config: &Config,
+        exec: E,
+    ) -> Server<T, S, B, E> {
+        let io = ServerIo::H2c(H2cIo::upgrade(io, &head));
+        Server::with_io(io, service, config, exec)
+    }
+
+    fn with_io(io: ServerIo<T>, service: S, config: &Config, exec: E) -> Server<T, S, B, E> {
         let mut builder = h2::server::Builder::default();
         builder
             .initial_window_size(config.initial_stream_window_size)
@@ -128,7 +291,7 @@ where
         if config.enable_connect_protocol {
             builder.enable_connect_protocol();
         }
-        let handshake = builder.handshake(io);
+        let handshake = builder.handshake(ObservedIo::new(io, config.observer.clone()));
 
         let bdp = if config.adaptive_window {
             Some(config.initial_stream_window_size)
@@ -146,12 +309,22 @@ where
             // idle, so it can more aggressively close dead connections.
             #[cfg(feature = "runtime")]
             keep_alive_while_idle: true,
+            observer: config.observer.clone(),
         };
 
         Server {
             exec,
             state: State::Handshaking {
                 ping_config,
+                #[cfg(feature = "runtime")]
+                body_timeouts: config.body_timeouts,
+                #[cfg(feature = "runtime")]
+                shutdown: ShutdownConfig {
+                    timeout: config.graceful_shutdown_timeout,
+                    reason: config.graceful_shutdown_reason,
+                    max_age: config.max_connection_age,
+                    max_age_grace: config.max_connection_age_grace,
+                },
                 hs: handshake,
             },
             service,
@@ -166,7 +339,7 @@ where
             }
             State::Serving(ref mut srv) => {
                 if srv.closing.is_none() {
-                    srv.conn.graceful_shutdown();
+                    srv.graceful_shutdown();
                 }
                 return;
             }
@@ -195,6 +368,10 @@ where
                 State::Handshaking {
                     ref mut hs,
                     ref ping_config,
+                    #[cfg(feature = "runtime")]
+                    body_timeouts,
+                    #[cfg(feature = "runtime")]
+                    shutdown,
                 } => {
                     let mut conn = ready!(Pin::new(hs).poll(cx).map_err(crate::Error::new_h2))?;
                     let ping = if ping_config.is_enabled() {
@@ -207,6 +384,18 @@ where
                         ping,
                         conn,
                         closing: None,
+                        observer: ping_config.observer.clone(),
+                        go_away: GoAwayObserver::new(ping_config.observer.clone()),
+                        #[cfg(feature = "runtime")]
+                        body_timeouts,
+                        #[cfg(feature = "runtime")]
+                        shutdown,
+                        #[cfg(feature = "runtime")]
+                        shutdown_timer: None,
+                        #[cfg(feature = "runtime")]
+                        max_age_timer: shutdown
+                            .max_age
+                            .map(|age| Box::pin(tokio::time::sleep(jitter(age)))),
                     })
                 }
                 State::Serving(ref mut srv) => {
@@ -243,6 +432,10 @@ where
         if self.closing.is_none() {
             loop {
                 self.poll_ping(cx);
+                #[cfg(feature = "runtime")]
+                self.poll_max_age(cx);
+                #[cfg(feature = "runtime")]
+                self.poll_shutdown_timer(cx);
 
                 // Check that the service is ready to accept a new request.
                 //
@@ -253,7 +446,11 @@ where
                     Poll::Pending => {
                         // use `poll_closed` instead of `poll_accept`,
                         // in order to avoid accepting a request.
-                        ready!(self.conn.poll_closed(cx).map_err(crate::Error::new_h2))?;
+                        let go_away = &self.go_away;
+                        ready!(self.conn.poll_closed(cx).map_err(|e| {
+                            go_away.observe(&e);
+                            crate::Error::new_h2(e)
+                        }))?;
                         trace!("incoming connection complete");
                         return Poll::Ready(Ok(()));
                     }
@@ -261,7 +458,7 @@ where
                         let err = crate::Error::new_user_service(err);
                         debug!("service closed: {}", err);
 
-                        let reason = err.h2_reason();
+                        let reason = err.h2_reason_or_internal();
                         if reason == Reason::NO_ERROR {
                             // NO_ERROR is only used for graceful shutdowns...
                             trace!("interpreting NO_ERROR user error as graceful_shutdown");
@@ -279,12 +476,14 @@ where
                 match ready!(self.conn.poll_accept(cx)) {
                     Some(Ok((req, mut respond))) => {
                         trace!("incoming request");
+                        self.observer.on_head_parsed();
                         let content_length = headers::content_length_parse_all(req.headers());
+                        let observer = &self.observer;
                         let ping = self
                             .ping
                             .as_ref()
                             .map(|ping| ping.0.clone())
-                            .unwrap_or_else(ping::disabled);
+                            .unwrap_or_else(|| ping::disabled(observer.clone()));
 
                         // Record the headers received
                         ping.record_non_data();
@@ -321,11 +520,30 @@ where
                         if let Some(protocol) = req.extensions_mut().remove::<h2::ext::Protocol>() {
                             req.extensions_mut().insert(Protocol::from_inner(protocol));
                         }
-
-                        let fut = H2Stream::new(service.call(req), connect_parts, respond);
+                        // h2 can't send informational responses.
+                        req.extensions_mut()
+                            .insert(InformationalSender::unsupported());
+
+                        #[cfg(feature = "runtime")]
+                        let (request_body, response_timer) = (
+                            TimedRequestBody::wrap(req.body_mut(), &self.body_timeouts),
+                            self.body_timeouts.write_timer(),
+                        );
+                        #[cfg(not(feature = "runtime"))]
+                        let (request_body, response_timer) = (None, None);
+
+                        let fut = H2Stream::new(
+                            service.call(req),
+                            connect_parts,
+                            respond,
+                            self.observer.clone(),
+                            request_body,
+                            response_timer,
+                        );
                         exec.execute_h2stream(fut);
                     }
                     Some(Err(e)) => {
+                        self.go_away.observe(&e);
                         return Poll::Ready(Err(crate::Error::new_h2(e)));
                     }
                     None => {
@@ -346,11 +564,62 @@ where
             "poll_server broke loop without closing"
         );
 
-        ready!(self.conn.poll_closed(cx).map_err(crate::Error::new_h2))?;
+        let go_away = &self.go_away;
+        ready!(self.conn.poll_closed(cx).map_err(|e| {
+            go_away.observe(&e);
+            crate::Error::new_h2(e)
+        }))?;
 
         Poll::Ready(Err(self.closing.take().expect("polled after error")))
     }
 
+    fn graceful_shutdown(&mut self) {
+        // h2 sends a first GOAWAY right away, and the one with the last
+        // stream actually processed once the client acknowledges a ping.
+        self.conn.graceful_shutdown();
+
+        #[cfg(feature = "runtime")]
+        {
+            self.max_age_timer = None;
+            if let Some(timeout) = self.shutdown.timeout {
+                self.start_shutdown_timer(timeout);
+            }
+        }
+    }
+
+    #[cfg(feature = "runtime")]
+    fn start_shutdown_timer(&mut self, timeout: Duration) {
+        if self.shutdown_timer.is_none() {
+            self.shutdown_timer = Some(Box::pin(tokio::time::sleep(timeout)));
+        }
+    }
+
+    #[cfg(feature = "runtime")]
+    fn poll_max_age(&mut self, cx: &mut task::Context<'_>) {
+        if let Some(ref mut timer) = self.max_age_timer {
+            if timer.as_mut().poll(cx).is_ready() {
+                self.max_age_timer = None;
+                debug!("max connection age reached, shutting down gracefully");
+                self.conn.graceful_shutdown();
+                if let Some(grace) = self.shutdown.max_age_grace.or(self.shutdown.timeout) {
+                    self.start_shutdown_timer(grace);
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "runtime")]
+    fn poll_shutdown_timer(&mut self, cx: &mut task::Context<'_>) {
+        if let Some(ref mut timer) = self.shutdown_timer {
+            if timer.as_mut().poll(cx).is_ready() {
+                self.shutdown_timer = None;
+                let reason = self.shutdown.reason;
+                debug!("graceful shutdown timed out, closing with {:?}", reason);
+                self.conn.abrupt_shutdown(reason);
+            }
+        }
+    }
+
     fn poll_ping(&mut self, cx: &mut task::Context<'_>) {
         if let Some((_, ref mut estimator)) = self.ping {
             match estimator.poll(cx) {
@@ -369,6 +638,19 @@ where
     }
 }
 
+/// Spreads `age` by up to 10% either way, so that connections opened
+/// together don't all shut down at once.
+#[cfg(feature = "runtime")]
+fn jitter(age: Duration) -> Duration {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    // Every `RandomState` is randomly seeded, which is random enough here.
+    let random = RandomState::new().build_hasher().finish();
+    let factor = 0.9 + 0.2 * (random as f64 / u64::MAX as f64);
+    age.mul_f64(factor)
+}
+
 pin_project! {
     #[allow(missing_debug_implementations)]
     pub struct H2Stream<F, B>
@@ -376,6 +658,12 @@ pin_project! {
         B: HttpBody,
     {
         reply: SendResponse<SendBuf<B::Data>>,
+        observer: ObserverRef,
+        // Forwards the request body to the service, enforcing its
+        // timeouts, if any.
+        request_body: Option<TimedRequestBody>,
+        // Enforces the minimum rate of the response body, if any.
+        response_timer: Option<BodyTimer>,
         #[pin]
         state: H2StreamState<F, B>,
     }
@@ -396,6 +684,7 @@ pin_project! {
             #[pin]
             pipe: PipeToSendStream<B>,
         },
+        Done,
     }
 }
 
@@ -413,9 +702,15 @@ where
         fut: F,
         connect_parts: Option<ConnectParts>,
         respond: SendResponse<SendBuf<B::Data>>,
+        observer: ObserverRef,
+        request_body: Option<TimedRequestBody>,
+        response_timer: Option<BodyTimer>,
     ) -> H2Stream<F, B> {
         H2Stream {
             reply: respond,
+            observer,
+            request_body,
+            response_timer,
             state: H2StreamState::Service { fut, connect_parts },
         }
     }
@@ -466,7 +761,7 @@ where
                         Poll::Ready(Err(e)) => {
                             let err = crate::Error::new_user_service(e);
                             warn!("http2 service errored: {}", err);
-                            me.reply.send_reset(err.h2_reason());
+                            me.reply.send_reset(err.h2_reason_or_internal());
                             return Poll::Ready(Err(err));
                         }
                     };
@@ -510,9 +805,10 @@ where
                         }
 
                         let body_tx = reply!(me, res, false);
-                        H2StreamState::Body {
-                            pipe: PipeToSendStream::new(body, body_tx),
-                        }
+                        let pipe = PipeToSendStream::new(body, body_tx, me.observer.clone());
+                        #[cfg(feature = "runtime")]
+                        let pipe = pipe.with_timer(me.response_timer.take());
+                        H2StreamState::Body { pipe }
                     } else {
                         reply!(me, res, true);
                         return Poll::Ready(Ok(()));
@@ -521,6 +817,7 @@ where
                 H2StreamStateProj::Body { pipe } => {
                     return pipe.poll(cx);
                 }
+                H2StreamStateProj::Done => return Poll::Ready(Ok(())),
             };
             me.state.set(next);
         }
@@ -537,11 +834,116 @@ where
 {
     type Output = ();
 
-    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
-        self.poll2(cx).map(|res| {
-            if let Err(e) = res {
-                debug!("stream error: {}", e);
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        // The request body is forwarded until it ends, even after the
+        // response, since the service may still read it.
+        #[cfg(feature = "runtime")]
+        let forwarding = {
+            let request_body = self.as_mut().project().request_body;
+            if let Some(ref mut body) = *request_body {
+                if body.poll_forward(cx).is_ready() {
+                    *request_body = None;
+                }
             }
+            request_body.is_some()
+        };
+        #[cfg(not(feature = "runtime"))]
+        let forwarding = false;
+
+        if let Err(e) = ready!(self.as_mut().poll2(cx)) {
+            debug!("stream error: {}", e);
+        }
+        self.project().state.set(H2StreamState::Done);
+
+        if forwarding {
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    }
+}
+
+/// Forwards a request body to the service, enforcing its timeouts.
+#[cfg(feature = "runtime")]
+struct TimedRequestBody {
+    body: Recv,
+    tx: crate::body::Sender,
+    timer: BodyTimer,
+    data_done: bool,
+}
+
+// `pin_project!` doesn't allow `#[cfg]` on fields, so `H2Stream` holds an
+// `Option<Never>`, which is always `None`, when bodies aren't timed.
+#[cfg(not(feature = "runtime"))]
+type TimedRequestBody = crate::common::Never;
+
+#[cfg(feature = "runtime")]
+impl TimedRequestBody {
+    /// Replaces `body` with one forwarded from it, if a timeout applies.
+    fn wrap(body: &mut Recv, timeouts: &BodyTimeouts) -> Option<TimedRequestBody> {
+        if body.is_end_stream() {
+            return None;
+        }
+        let timer = timeouts.read_timer()?;
+        let (tx, rx) = Recv::new_channel(body.size_hint().exact().into(), false);
+        Some(TimedRequestBody {
+            body: std::mem::replace(body, rx),
+            tx,
+            timer,
+            data_done: false,
         })
     }
+
+    fn poll_forward(&mut self, cx: &mut task::Context<'_>) -> Poll<()> {
+        loop {
+            if self.data_done {
+                match Pin::new(&mut self.body).poll_trailers(cx) {
+                    Poll::Ready(Ok(Some(trailers))) => {
+                        let _ = self.tx.try_send_trailers(trailers);
+                        return Poll::Ready(());
+                    }
+                    Poll::Ready(Ok(None)) => return Poll::Ready(()),
+                    Poll::Ready(Err(err)) => {
+                        self.tx.send_error(err);
+                        return Poll::Ready(());
+                    }
+                    Poll::Pending => return self.poll_timeout(cx),
+                }
+            }
+
+            match self.tx.poll_ready(cx) {
+                Poll::Ready(Ok(())) => self.timer.resume(),
+                Poll::Ready(Err(_closed)) => {
+                    trace!("request body receiver dropped");
+                    return Poll::Ready(());
+                }
+                Poll::Pending => {
+                    self.timer.pause();
+                    return Poll::Pending;
+                }
+            }
+
+            match Pin::new(&mut self.body).poll_data(cx) {
+                Poll::Ready(Some(Ok(data))) => {
+                    self.timer.on_data(data.len());
+                    if self.tx.try_send_data(data).is_err() {
+                        return Poll::Ready(());
+                    }
+                }
+                Poll::Ready(Some(Err(err))) => {
+                    self.tx.send_error(err);
+                    return Poll::Ready(());
+                }
+                Poll::Ready(None) => self.data_done = true,
+                Poll::Pending => return self.poll_timeout(cx),
+            }
+        }
+    }
+
+    fn poll_timeout(&mut self, cx: &mut task::Context<'_>) -> Poll<()> {
+        let err = ready!(self.timer.poll_expired(cx));
+        debug!("request body timed out: {}", err);
+        self.tx.send_error(err);
+        Poll::Ready(())
+    }
 }
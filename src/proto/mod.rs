@@ -16,6 +16,9 @@ cfg_feature! {
 #[cfg(feature = "http2")]
 pub(crate) mod h2;
 
+#[cfg(all(feature = "server", feature = "runtime"))]
+pub(crate) mod body_timer;
+
 /// An Incoming Message head. Includes request/status line, and headers.
 #[derive(Debug, Default)]
 pub(crate) struct MessageHead<S> {
@@ -53,6 +53,8 @@ use std::time::Duration;
 use crate::common::io::Rewind;
 #[cfg(all(feature = "http1", feature = "http2"))]
 use crate::error::{Kind, Parse};
+#[cfg(all(any(feature = "http1", feature = "http2"), feature = "runtime"))]
+use crate::proto::body_timer::BodyTimeouts;
 #[cfg(feature = "http1")]
 use crate::upgrade::Upgraded;
 
@@ -72,6 +74,7 @@ cfg_feature! {
     #[cfg(not(all(feature = "http1", feature = "http2")))]
     use crate::common::Never;
     use crate::common::exec::{ConnStreamExec, Exec};
+    use crate::observe::{Observer, ObserverRef};
     use crate::proto;
     use crate::service::HttpService;
 
@@ -88,18 +91,117 @@ pub struct Http<E = Exec> {
     pub(crate) exec: E,
     h1_half_close: bool,
     h1_keep_alive: bool,
+    #[cfg(all(feature = "http1", feature = "runtime"))]
+    h1_keep_alive_timeout: Option<Duration>,
+    #[cfg(feature = "http1")]
+    h1_max_requests: Option<usize>,
+    h1_chunk_extensions: bool,
     h1_title_case_headers: bool,
     h1_preserve_header_case: bool,
+    h1_preserve_header_order: bool,
+    h1_preserve_raw_head: bool,
+    #[cfg(feature = "http1")]
+    h1_max_headers: Option<usize>,
+    #[cfg(feature = "http1")]
+    h1_max_uri_len: Option<usize>,
+    h1_strict_parsing: bool,
     #[cfg(all(feature = "http1", feature = "runtime"))]
     h1_header_read_timeout: Option<Duration>,
+    #[cfg(all(feature = "http1", feature = "runtime"))]
+    h1_body_timeouts: BodyTimeouts,
+    h1_manual_continue: bool,
+    #[cfg(feature = "http1")]
+    h1_refused_body: RefusedBody,
     h1_writev: Option<bool>,
+    #[cfg(feature = "http1")]
+    h1_observer: ObserverRef,
     #[cfg(feature = "http2")]
     h2_builder: proto::h2::server::Config,
+    #[cfg(all(feature = "http1", feature = "http2"))]
+    h2c_upgrade: bool,
     mode: ConnectionMode,
     max_buf_size: Option<usize>,
     pipeline_flush: bool,
 }
 
+/// What an HTTP/1 connection does with the body of an
+/// `Expect: 100-continue` request that the service didn't accept.
+///
+/// The client may send the body without waiting for `100 Continue`, so the
+/// connection can't just read the next request.
+///
+/// See [`Http::http1_manual_continue`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg(feature = "http1")]
+#[cfg_attr(docsrs, doc(cfg(feature = "http1")))]
+pub enum RefusedBody {
+    /// Close the connection after the response, adding `Connection: close`
+    /// to it.
+    Close,
+    /// Read and discard the body after the response, keeping the
+    /// connection open for other requests.
+    Drain,
+}
+
+#[cfg(feature = "http1")]
+impl Default for RefusedBody {
+    fn default() -> RefusedBody {
+        RefusedBody::Close
+    }
+}
+
+/// A minimum rate at which a body must be transferred.
+///
+/// The rate only counts the time spent waiting on the client: while reading
+/// a request body the service doesn't consume, or while writing a response
+/// body the service didn't produce yet, the clock is paused.
+///
+/// See [`Http::min_request_body_data_rate`] and
+/// [`Http::min_response_data_rate`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg(all(any(feature = "http1", feature = "http2"), feature = "runtime"))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(all(any(feature = "http1", feature = "http2"), feature = "runtime")))
+)]
+pub struct MinDataRate {
+    bytes_per_second: u64,
+    grace_period: Duration,
+}
+
+#[cfg(all(any(feature = "http1", feature = "http2"), feature = "runtime"))]
+impl MinDataRate {
+    /// Creates a rate of `bytes_per_second`, only enforced once the body
+    /// was transferred for longer than `grace_period`.
+    ///
+    /// The grace period lets a transfer get up to speed: until then, any
+    /// rate is accepted.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `bytes_per_second` is zero.
+    pub fn new(bytes_per_second: u64, grace_period: Duration) -> MinDataRate {
+        assert!(
+            bytes_per_second > 0,
+            "the minimum data rate must be positive"
+        );
+        MinDataRate {
+            bytes_per_second,
+            grace_period,
+        }
+    }
+
+    /// Returns the rate, in bytes per second.
+    pub fn bytes_per_second(&self) -> u64 {
+        self.bytes_per_second
+    }
+
+    /// Returns the grace period before the rate is enforced.
+    pub fn grace_period(&self) -> Duration {
+        self.grace_period
+    }
+}
+
 /// The internal mode of HTTP protocol which indicates the behavior when a parse error occurs.
 #[cfg(any(feature = "http1", feature = "http2"))]
 #[derive(Clone, Debug, PartialEq)]
@@ -227,13 +329,34 @@ impl Http {
             exec: Exec::Default,
             h1_half_close: false,
             h1_keep_alive: true,
+            #[cfg(all(feature = "http1", feature = "runtime"))]
+            h1_keep_alive_timeout: None,
+            #[cfg(feature = "http1")]
+            h1_max_requests: None,
+            h1_chunk_extensions: false,
             h1_title_case_headers: false,
             h1_preserve_header_case: false,
+            h1_preserve_header_order: false,
+            h1_preserve_raw_head: false,
+            #[cfg(feature = "http1")]
+            h1_max_headers: None,
+            #[cfg(feature = "http1")]
+            h1_max_uri_len: None,
+            h1_strict_parsing: false,
             #[cfg(all(feature = "http1", feature = "runtime"))]
             h1_header_read_timeout: None,
+            #[cfg(all(feature = "http1", feature = "runtime"))]
+            h1_body_timeouts: BodyTimeouts::default(),
+            h1_manual_continue: false,
+            #[cfg(feature = "http1")]
+            h1_refused_body: RefusedBody::default(),
             h1_writev: None,
+            #[cfg(feature = "http1")]
+            h1_observer: ObserverRef::default(),
             #[cfg(feature = "http2")]
             h2_builder: Default::default(),
+            #[cfg(all(feature = "http1", feature = "http2"))]
+            h2c_upgrade: false,
             mode: ConnectionMode::default(),
             max_buf_size: None,
             pipeline_flush: false,
@@ -285,6 +408,64 @@ impl<E> Http<E> {
         self
     }
 
+    /// Set how long an idle HTTP/1 connection waits for the next request.
+    ///
+    /// Once a response is sent, if the next request doesn't start arriving
+    /// within this time, the connection is closed, without an error. The
+    /// time to read the rest of the request head is limited by
+    /// [`http1_header_read_timeout`](Http::http1_header_read_timeout)
+    /// instead.
+    ///
+    /// Default is None, waiting for as long as the client keeps the
+    /// connection open.
+    #[cfg(all(feature = "http1", feature = "runtime"))]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "http1", feature = "runtime"))))]
+    pub fn http1_keep_alive_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.h1_keep_alive_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the maximum number of requests served on an HTTP/1 connection.
+    ///
+    /// The response to the last request includes `Connection: close`, and
+    /// the connection is closed once it is sent.
+    ///
+    /// Default is None, serving requests for as long as the connection is
+    /// kept alive.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is 0.
+    #[cfg(feature = "http1")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http1")))]
+    pub fn http1_max_requests(&mut self, max: usize) -> &mut Self {
+        assert!(max > 0, "max requests must be greater than 0");
+        self.h1_max_requests = Some(max);
+        self
+    }
+
+    /// Set whether HTTP/1 connections will surface the chunk extensions of
+    /// incoming chunked bodies, and write those of outgoing ones.
+    ///
+    /// When enabled, the extensions of each chunk are available from
+    /// [`Recv::chunk_extensions`](crate::Recv::chunk_extensions) after
+    /// polling its data. The extensions of a single chunk are limited to
+    /// 16kb, and a chunk with larger extensions is treated as an error.
+    ///
+    /// Outgoing bodies are written with the extensions set through an
+    /// [`OutgoingChunkExtensions`](crate::ext::OutgoingChunkExtensions) in
+    /// the extensions of their message.
+    ///
+    /// Note that this setting does not affect HTTP/2.
+    ///
+    /// Default is false.
+    #[cfg(feature = "http1")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http1")))]
+    pub fn http1_chunk_extensions(&mut self, enabled: bool) -> &mut Self {
+        self.h1_chunk_extensions = enabled;
+        self
+    }
+
     /// Set whether HTTP/1 connections will write header names as title case at
     /// the socket level.
     ///
@@ -300,13 +481,10 @@ impl<E> Http<E> {
 
     /// Set whether to support preserving original header cases.
     ///
-    /// Currently, this will record the original cases received, and store them
-    /// in a private extension on the `Request`. It will also look for and use
-    /// such an extension in any provided `Response`.
-    ///
-    /// Since the relevant extension is still private, there is no way to
-    /// interact with the original cases. The only effect this can have now is
-    /// to forward the cases in a proxy-like fashion.
+    /// This will record the original cases received, and store them in a
+    /// [`HeaderCaseMap`](crate::ext::HeaderCaseMap) extension on the
+    /// `Request`. Such an extension in any provided `Response` is used
+    /// whether or not this is set.
     ///
     /// Note that this setting does not affect HTTP/2.
     ///
@@ -318,6 +496,93 @@ impl<E> Http<E> {
         self
     }
 
+    /// Set whether to support preserving original header order.
+    ///
+    /// This will record the order in which headers are received, and store
+    /// it in an [`OriginalHeaderOrder`](crate::ext::OriginalHeaderOrder)
+    /// extension on the `Request`. Such an extension in any provided
+    /// `Response` is used whether or not this is set.
+    ///
+    /// Note that this setting does not affect HTTP/2.
+    ///
+    /// Default is false.
+    #[cfg(feature = "http1")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http1")))]
+    pub fn http1_preserve_header_order(&mut self, enabled: bool) -> &mut Self {
+        self.h1_preserve_header_order = enabled;
+        self
+    }
+
+    /// Set whether to keep the raw head of requests.
+    ///
+    /// This will store the request line and header lines, exactly as they
+    /// were received, in a [`RawHead`](crate::ext::RawHead) extension on the
+    /// `Request`.
+    ///
+    /// Note that this setting does not affect HTTP/2.
+    ///
+    /// Default is false.
+    #[cfg(feature = "http1")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http1")))]
+    pub fn http1_preserve_raw_head(&mut self, enabled: bool) -> &mut Self {
+        self.h1_preserve_raw_head = enabled;
+        self
+    }
+
+    /// Set the maximum number of headers of a request.
+    ///
+    /// A request with more headers is answered with `431 Request Header
+    /// Fields Too Large`, and the connection is closed. Up to 100 headers
+    /// are parsed on the stack, while a larger maximum allocates room for
+    /// the headers on the heap for each request.
+    ///
+    /// Default is 100.
+    #[cfg(feature = "http1")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http1")))]
+    pub fn http1_max_headers(&mut self, max: usize) -> &mut Self {
+        self.h1_max_headers = Some(max);
+        self
+    }
+
+    /// Set the maximum length of the URI of a request, in bytes.
+    ///
+    /// A request with a longer URI is answered with `414 URI Too Long`, and
+    /// the connection is closed.
+    ///
+    /// Default is 65534.
+    #[cfg(feature = "http1")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http1")))]
+    pub fn http1_max_uri_len(&mut self, max: usize) -> &mut Self {
+        self.h1_max_uri_len = Some(max);
+        self
+    }
+
+    /// Set whether HTTP/1 requests should be parsed strictly.
+    ///
+    /// Parsers disagreeing on where a request ends allow request smuggling
+    /// through a proxy. The strict profile rejects requests that some
+    /// parsers accept, with a distinct parse error for each:
+    ///
+    /// - a bare LF line ending,
+    /// - obsolete line folding in a header,
+    /// - whitespace between a header name and the colon,
+    /// - duplicate `Content-Length` headers, even with the same value,
+    /// - `chunked` that isn't the final transfer coding,
+    /// - whitespace in a chunk size.
+    ///
+    /// Requests with conflicting `Content-Length` headers, a
+    /// `Transfer-Encoding` in HTTP/1.0, or a method that isn't a token are
+    /// always rejected. A request rejected before its body is answered with
+    /// `400 Bad Request`, and the connection is closed.
+    ///
+    /// Default is false.
+    #[cfg(feature = "http1")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http1")))]
+    pub fn http1_strict_parsing(&mut self, enabled: bool) -> &mut Self {
+        self.h1_strict_parsing = enabled;
+        self
+    }
+
     /// Set a timeout for reading client request headers. If a client does not
     /// transmit the entire header within this time, the connection is closed.
     ///
@@ -329,6 +594,39 @@ impl<E> Http<E> {
         self
     }
 
+    /// Set whether the service decides when to send `100 Continue` to HTTP/1
+    /// requests with an `Expect: 100-continue` header.
+    ///
+    /// By default, `100 Continue` is sent once the service reads the body
+    /// of the request. When enabled, such a request instead has an
+    /// [`ExpectContinue`](crate::ext::ExpectContinue) in its extensions,
+    /// with which the service accepts or refuses the body. A final response
+    /// written before the body is accepted refuses it, and the connection
+    /// then closes or drains the body, as set by
+    /// [`http1_refused_body`](Http::http1_refused_body).
+    ///
+    /// Default is false.
+    #[cfg(feature = "http1")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http1")))]
+    pub fn http1_manual_continue(&mut self, enabled: bool) -> &mut Self {
+        self.h1_manual_continue = enabled;
+        self
+    }
+
+    /// Set what HTTP/1 connections do with a refused
+    /// `Expect: 100-continue` request body.
+    ///
+    /// This only applies when
+    /// [`http1_manual_continue`](Http::http1_manual_continue) is enabled.
+    ///
+    /// Default is [`RefusedBody::Close`].
+    #[cfg(feature = "http1")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http1")))]
+    pub fn http1_refused_body(&mut self, policy: RefusedBody) -> &mut Self {
+        self.h1_refused_body = policy;
+        self
+    }
+
     /// Set whether HTTP/1 connections should try to use vectored writes,
     /// or always flatten into a single buffer.
     ///
@@ -366,6 +664,26 @@ impl<E> Http<E> {
         self
     }
 
+    /// Sets whether HTTP/1.1 connections can be upgraded to cleartext
+    /// HTTP/2 (h2c).
+    ///
+    /// If enabled, and the first request of a connection includes the
+    /// `Upgrade: h2c` and `HTTP2-Settings` headers, the server replies with
+    /// `101 Switching Protocols` and continues the connection with HTTP/2.
+    /// The request is then served as the first HTTP/2 stream.
+    ///
+    /// The upgrade is ignored if the request has a body, and the request is
+    /// served with HTTP/1.1 instead. It is also ignored if `http1_only` is
+    /// enabled.
+    ///
+    /// Default is false.
+    #[cfg(all(feature = "http1", feature = "http2"))]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "http1", feature = "http2"))))]
+    pub fn h2c_upgrade(&mut self, enabled: bool) -> &mut Self {
+        self.h2c_upgrade = enabled;
+        self
+    }
+
     /// Sets the [`SETTINGS_INITIAL_WINDOW_SIZE`][spec] option for HTTP2
     /// stream-level flow control.
     ///
@@ -486,6 +804,101 @@ impl<E> Http<E> {
         self
     }
 
+    /// Sets how long a graceful shutdown of an HTTP/2 connection waits for
+    /// the streams in progress to complete.
+    ///
+    /// A graceful shutdown, started by [`Connection::graceful_shutdown`],
+    /// tells the client to stop sending requests, still serving those
+    /// already sent. Once this timeout expires, the connection is closed
+    /// with the error code set by
+    /// [`http2_graceful_shutdown_error_code`](Http::http2_graceful_shutdown_error_code),
+    /// failing the remaining streams, and the `Connection` completes without
+    /// an error.
+    ///
+    /// Pass `None` to wait for the streams for as long as they take.
+    ///
+    /// Default is `None`.
+    ///
+    /// # Cargo Feature
+    ///
+    /// Requires the `runtime` cargo feature to be enabled.
+    #[cfg(feature = "runtime")]
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub fn http2_graceful_shutdown_timeout(
+        &mut self,
+        timeout: impl Into<Option<Duration>>,
+    ) -> &mut Self {
+        self.h2_builder.graceful_shutdown_timeout = timeout.into();
+        self
+    }
+
+    /// Sets the HTTP/2 error code closing a connection whose graceful
+    /// shutdown timed out.
+    ///
+    /// The client receives this code in the final GOAWAY frame, failing the
+    /// streams still in progress. Does nothing if
+    /// `http2_graceful_shutdown_timeout` is disabled.
+    ///
+    /// Default is `CANCEL` (`0x8`).
+    ///
+    /// # Cargo Feature
+    ///
+    /// Requires the `runtime` cargo feature to be enabled.
+    #[cfg(feature = "runtime")]
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub fn http2_graceful_shutdown_error_code(&mut self, code: u32) -> &mut Self {
+        self.h2_builder.graceful_shutdown_reason = code.into();
+        self
+    }
+
+    /// Sets the maximum age of an HTTP/2 connection.
+    ///
+    /// Once a connection reaches this age, give or take a random 10%, it
+    /// starts a graceful shutdown, as if [`Connection::graceful_shutdown`]
+    /// was called. The client then opens a new connection for its next
+    /// requests, which spreads long-lived clients across servers.
+    ///
+    /// Pass `None` to keep connections open regardless of their age.
+    ///
+    /// Default is `None`.
+    ///
+    /// # Cargo Feature
+    ///
+    /// Requires the `runtime` cargo feature to be enabled.
+    #[cfg(feature = "runtime")]
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub fn http2_max_connection_age(&mut self, age: impl Into<Option<Duration>>) -> &mut Self {
+        self.h2_builder.max_connection_age = age.into();
+        self
+    }
+
+    /// Sets how long the graceful shutdown of an HTTP/2 connection that
+    /// reached its maximum age waits for the streams in progress to
+    /// complete, before closing the connection.
+    ///
+    /// Pass `None` to use the
+    /// [`http2_graceful_shutdown_timeout`](Http::http2_graceful_shutdown_timeout)
+    /// instead. Does nothing if `http2_max_connection_age` is disabled.
+    ///
+    /// Default is `None`.
+    ///
+    /// # Cargo Feature
+    ///
+    /// Requires the `runtime` cargo feature to be enabled.
+    #[cfg(feature = "runtime")]
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub fn http2_max_connection_age_grace(
+        &mut self,
+        grace: impl Into<Option<Duration>>,
+    ) -> &mut Self {
+        self.h2_builder.max_connection_age_grace = grace.into();
+        self
+    }
+
     /// Set the maximum write buffer size for each HTTP/2 stream.
     ///
     /// Default is currently ~400KB, but may change.
@@ -548,6 +961,108 @@ impl<E> Http<E> {
         self
     }
 
+    /// Set a timeout for receiving each piece of data of a request body.
+    ///
+    /// If the client doesn't send any data of a request body within this
+    /// time, while the service waits for it, the body yields an error for
+    /// which [`Error::is_body_timeout`](crate::Error::is_body_timeout)
+    /// returns `true`, and the rest of the body is discarded. The service
+    /// can still respond, after which an HTTP/1 connection is closed.
+    ///
+    /// Unlike [`http1_header_read_timeout`](Http::http1_header_read_timeout),
+    /// this applies to both HTTP/1 and HTTP/2 connections.
+    ///
+    /// Pass `None` to disable this timeout.
+    ///
+    /// Default is None.
+    ///
+    /// # Cargo Feature
+    ///
+    /// Requires the `runtime` cargo feature to be enabled.
+    #[cfg(feature = "runtime")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "runtime")))]
+    pub fn body_read_timeout(&mut self, timeout: impl Into<Option<Duration>>) -> &mut Self {
+        let timeout = timeout.into();
+        self.update_body_timeouts(|timeouts| timeouts.read_idle = timeout);
+        self
+    }
+
+    /// Set the minimum rate at which request bodies must be received.
+    ///
+    /// If the client sends a request body slower than this rate, the body
+    /// fails the same way as with
+    /// [`body_read_timeout`](Http::body_read_timeout).
+    ///
+    /// Pass `None` to disable this minimum.
+    ///
+    /// Default is None.
+    ///
+    /// # Cargo Feature
+    ///
+    /// Requires the `runtime` cargo feature to be enabled.
+    #[cfg(feature = "runtime")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "runtime")))]
+    pub fn min_request_body_data_rate(
+        &mut self,
+        rate: impl Into<Option<MinDataRate>>,
+    ) -> &mut Self {
+        let rate = rate.into();
+        self.update_body_timeouts(|timeouts| timeouts.min_read_rate = rate);
+        self
+    }
+
+    /// Set the minimum rate at which response bodies must be sent.
+    ///
+    /// If the client reads a response body slower than this rate, an HTTP/1
+    /// connection is closed with an error for which
+    /// [`Error::is_body_timeout`](crate::Error::is_body_timeout) returns
+    /// `true`, and an HTTP/2 stream is reset with `CANCEL`.
+    ///
+    /// Pass `None` to disable this minimum.
+    ///
+    /// Default is None.
+    ///
+    /// # Cargo Feature
+    ///
+    /// Requires the `runtime` cargo feature to be enabled.
+    #[cfg(feature = "runtime")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "runtime")))]
+    pub fn min_response_data_rate(&mut self, rate: impl Into<Option<MinDataRate>>) -> &mut Self {
+        let rate = rate.into();
+        self.update_body_timeouts(|timeouts| timeouts.min_write_rate = rate);
+        self
+    }
+
+    #[cfg(feature = "runtime")]
+    fn update_body_timeouts(&mut self, update: impl Fn(&mut BodyTimeouts)) {
+        #[cfg(feature = "http2")]
+        update(&mut self.h2_builder.body_timeouts);
+        #[cfg(feature = "http1")]
+        update(&mut self.h1_body_timeouts);
+    }
+
+    /// Sets an observer of the events of the connections.
+    ///
+    /// The observer is shared by every connection served with these options.
+    /// See [`Observer`](crate::observe::Observer) for the events reported.
+    ///
+    /// Default is no observer.
+    pub fn observer<O>(&mut self, observer: O) -> &mut Self
+    where
+        O: Observer + 'static,
+    {
+        let observer = ObserverRef::new(observer);
+        #[cfg(feature = "http2")]
+        {
+            self.h2_builder.observer = observer.clone();
+        }
+        #[cfg(feature = "http1")]
+        {
+            self.h1_observer = observer;
+        }
+        self
+    }
+
     /// Set the executor used to spawn background tasks.
     ///
     /// Default uses implicit default (like `tokio::spawn`).
@@ -556,13 +1071,34 @@ impl<E> Http<E> {
             exec,
             h1_half_close: self.h1_half_close,
             h1_keep_alive: self.h1_keep_alive,
+            #[cfg(all(feature = "http1", feature = "runtime"))]
+            h1_keep_alive_timeout: self.h1_keep_alive_timeout,
+            #[cfg(feature = "http1")]
+            h1_max_requests: self.h1_max_requests,
+            h1_chunk_extensions: self.h1_chunk_extensions,
             h1_title_case_headers: self.h1_title_case_headers,
             h1_preserve_header_case: self.h1_preserve_header_case,
+            h1_preserve_header_order: self.h1_preserve_header_order,
+            h1_preserve_raw_head: self.h1_preserve_raw_head,
+            #[cfg(feature = "http1")]
+            h1_max_headers: self.h1_max_headers,
+            #[cfg(feature = "http1")]
+            h1_max_uri_len: self.h1_max_uri_len,
+            h1_strict_parsing: self.h1_strict_parsing,
             #[cfg(all(feature = "http1", feature = "runtime"))]
             h1_header_read_timeout: self.h1_header_read_timeout,
+            #[cfg(all(feature = "http1", feature = "runtime"))]
+            h1_body_timeouts: self.h1_body_timeouts,
+            h1_manual_continue: self.h1_manual_continue,
+            #[cfg(feature = "http1")]
+            h1_refused_body: self.h1_refused_body,
             h1_writev: self.h1_writev,
+            #[cfg(feature = "http1")]
+            h1_observer: self.h1_observer,
             #[cfg(feature = "http2")]
             h2_builder: self.h2_builder,
+            #[cfg(all(feature = "http1", feature = "http2"))]
+            h2c_upgrade: self.h2c_upgrade,
             mode: self.mode,
             max_buf_size: self.max_buf_size,
             pipeline_flush: self.pipeline_flush,
@@ -613,19 +1149,50 @@ impl<E> Http<E> {
                 if !self.h1_keep_alive {
                     conn.disable_keep_alive();
                 }
+                #[cfg(all(feature = "http1", feature = "runtime"))]
+                if let Some(timeout) = self.h1_keep_alive_timeout {
+                    conn.set_keep_alive_timeout(timeout);
+                }
+                if let Some(max) = self.h1_max_requests {
+                    conn.set_max_requests(max);
+                }
                 if self.h1_half_close {
                     conn.set_allow_half_close();
                 }
+                conn.set_observer(self.h1_observer.clone());
+                if self.h1_chunk_extensions {
+                    conn.set_chunk_extensions();
+                }
                 if self.h1_title_case_headers {
                     conn.set_title_case_headers();
                 }
                 if self.h1_preserve_header_case {
                     conn.set_preserve_header_case();
                 }
+                if self.h1_preserve_header_order {
+                    conn.set_preserve_header_order();
+                }
+                if self.h1_preserve_raw_head {
+                    conn.set_preserve_raw_head();
+                }
+                if let Some(max) = self.h1_max_headers {
+                    conn.set_max_headers(max);
+                }
+                if let Some(max) = self.h1_max_uri_len {
+                    conn.set_max_uri_len(max);
+                }
+                if self.h1_strict_parsing {
+                    conn.set_strict_parsing();
+                }
                 #[cfg(all(feature = "http1", feature = "runtime"))]
                 if let Some(header_read_timeout) = self.h1_header_read_timeout {
                     conn.set_http1_header_read_timeout(header_read_timeout);
                 }
+                #[cfg(all(feature = "http1", feature = "runtime"))]
+                conn.set_body_timeouts(self.h1_body_timeouts);
+                if self.h1_manual_continue {
+                    conn.set_manual_continue(self.h1_refused_body == RefusedBody::Drain);
+                }
                 if let Some(writev) = self.h1_writev {
                     if writev {
                         conn.set_write_strategy_queue();
@@ -637,7 +1204,12 @@ impl<E> Http<E> {
                 if let Some(max) = self.max_buf_size {
                     conn.set_max_buf_size(max);
                 }
-                let sd = proto::h1::dispatch::Server::new(service);
+                #[cfg_attr(not(feature = "http2"), allow(unused_mut))]
+                let mut sd = proto::h1::dispatch::Server::new(service);
+                #[cfg(feature = "http2")]
+                if self.h2c_upgrade && self.mode == ConnectionMode::Fallback {
+                    sd.set_h2c_upgrade();
+                }
                 ProtoServer::H1 {
                     h1: proto::h1::Dispatcher::new(sd, conn),
                 }
@@ -776,6 +1348,10 @@ where
                                 self.upgrade_h2();
                                 continue;
                             }
+                            Kind::H2cUpgrade if self.fallback.to_h2() => {
+                                self.upgrade_h2c();
+                                continue;
+                            }
                             _ => (),
                         }
 
@@ -835,6 +1411,37 @@ where
         self.conn = Some(ProtoServer::H2 { h2 });
     }
 
+    #[cfg(all(feature = "http1", feature = "http2"))]
+    fn upgrade_h2c(&mut self) {
+        trace!("Upgrading connection to h2c");
+        let conn = self.conn.take();
+
+        let (io, read_buf, mut dispatch) = match conn.unwrap() {
+            ProtoServer::H1 { h1, .. } => h1.into_inner(),
+            ProtoServer::H2 { .. } => {
+                panic!("h2 cannot into_inner");
+            }
+        };
+        let head = dispatch
+            .take_h2c_request()
+            .expect("upgrade_h2c without h2c request");
+        let rewind_io = Rewind::new_buffered(io, read_buf);
+        let (builder, exec) = match self.fallback {
+            Fallback::ToHttp2(ref builder, ref exec) => (builder, exec),
+            Fallback::Http1Only => unreachable!("upgrade_h2c with Fallback::Http1Only"),
+        };
+        let h2 = proto::h2::Server::new_h2c(
+            rewind_io,
+            head,
+            dispatch.into_service(),
+            builder,
+            exec.clone(),
+        );
+
+        debug_assert!(self.conn.is_none());
+        self.conn = Some(ProtoServer::H2 { h2 });
+    }
+
     /// Enable this connection to support higher-level HTTP upgrades.
     ///
     /// See [the `upgrade` module](crate::upgrade) for more.
@@ -883,6 +1490,10 @@ where
                             self.upgrade_h2();
                             continue;
                         }
+                        Kind::H2cUpgrade if self.fallback.to_h2() => {
+                            self.upgrade_h2c();
+                            continue;
+                        }
                         _ => (),
                     }
 
@@ -1024,6 +1635,10 @@ mod upgrades {
                                 self.inner.upgrade_h2();
                                 continue;
                             }
+                            Kind::H2cUpgrade if self.inner.fallback.to_h2() => {
+                                self.inner.upgrade_h2c();
+                                continue;
+                            }
                             _ => (),
                         }
 
@@ -3,8 +3,36 @@
 //! A "server" is usually created by listening on a port for new connections,
 //! parse HTTP requests, and hand them off to a `Service`.
 //!
-//! How exactly you choose to listen for connections is not something hyper
-//! concerns itself with. After you have a connection, you can handle HTTP over
-//! it with the types in the [`conn`](conn) module.
+//! There are two levels of APIs provided for constructing HTTP servers:
+//!
+//! - The higher-level [`Server`](Server) type.
+//! - The lower-level [`conn`](conn) module.
+//!
+//! # Server
+//!
+//! The [`Server`](Server) is the main way to start listening for HTTP requests.
+//! It wraps an [`Accept`](accept::Accept) of incoming connections, such as a
+//! `TcpListener`, and a "make service" that creates a `Service` for each of
+//! them. Every connection is served on its own task.
+//!
+//! With [`Server::with_graceful_shutdown`](Server::with_graceful_shutdown),
+//! the server stops accepting once a signal completes, asks all live
+//! connections to shut down gracefully, and waits until they have.
+//!
+//! # Conn
+//!
+//! After you have a connection from elsewhere, you can handle HTTP over it
+//! with the types in the [`conn`](conn) module.
 pub mod conn;
 
+cfg_feature! {
+    #![any(feature = "http1", feature = "http2")]
+
+    pub use self::server::{Builder, Server};
+    pub use self::shutdown::Graceful;
+
+    pub mod accept;
+    #[allow(clippy::module_inception)]
+    mod server;
+    mod shutdown;
+}
@@ -0,0 +1,374 @@
+use std::error::Error as StdError;
+use std::fmt;
+#[cfg(feature = "tcp")]
+use std::net::SocketAddr;
+#[cfg(feature = "runtime")]
+use std::time::Duration;
+
+use pin_project_lite::pin_project;
+use tokio::io::{AsyncRead, AsyncWrite};
+#[cfg(feature = "tcp")]
+use tokio::net::TcpListener;
+#[cfg(feature = "runtime")]
+use tokio::time::Sleep;
+#[cfg(feature = "runtime")]
+use tracing::error;
+use tracing::{debug, trace};
+
+use super::accept::Accept;
+use super::conn::Http;
+use super::shutdown::Graceful;
+use crate::body::{HttpBody, Recv};
+use crate::common::drain::Watch;
+use crate::common::exec::{ConnStreamExec, Exec, NewSvcExec};
+use crate::common::{task, Future, Pin, Poll};
+use crate::service::{HttpService, MakeServiceRef};
+
+pin_project! {
+    /// A listening HTTP server that accepts connections in both HTTP1 and HTTP2 by default.
+    ///
+    /// `Server` is a `Future` mapping a bound listener with a set of service
+    /// handlers. It is built using the [`Builder`](Builder), and the future
+    /// completes when the server has been shutdown. It should be run by an
+    /// `Executor`.
+    ///
+    /// Each accepted connection is served on its own task, spawned with the
+    /// executor of the [`Http`](super::conn::Http) configuration.
+    ///
+    /// With the `runtime` feature, errors accepting a connection are logged,
+    /// and accepting is tried again a second later, as they are usually
+    /// temporary, such as running out of file descriptors. Otherwise, the
+    /// future completes with the error.
+    pub struct Server<I, S, E = Exec> {
+        #[pin]
+        incoming: I,
+        make_service: S,
+        protocol: Http<E>,
+        accept_backoff: Option<AcceptBackoff>,
+    }
+}
+
+#[cfg(feature = "runtime")]
+type AcceptBackoff = Pin<Box<Sleep>>;
+
+// `pin_project!` doesn't allow `#[cfg]` on fields, so without a timer the
+// `Server` holds an `Option<Never>`, which is always `None`.
+#[cfg(not(feature = "runtime"))]
+type AcceptBackoff = crate::common::Never;
+
+/// How long to wait before accepting again after an error.
+#[cfg(feature = "runtime")]
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_secs(1);
+
+/// A builder for a [`Server`](Server).
+#[derive(Debug)]
+pub struct Builder<I, E = Exec> {
+    incoming: I,
+    protocol: Http<E>,
+}
+
+// ===== impl Server =====
+
+impl<I> Server<I, ()> {
+    /// Starts a [`Builder`](Builder) with the provided incoming stream.
+    ///
+    /// Anything implementing [`Accept`](super::accept::Accept) can be used,
+    /// such as a `tokio::net::TcpListener`, or a stream adapted with
+    /// [`accept::from_stream`](super::accept::from_stream).
+    pub fn builder(incoming: I) -> Builder<I> {
+        Builder {
+            incoming,
+            protocol: Http::new(),
+        }
+    }
+}
+
+#[cfg(feature = "tcp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tcp")))]
+impl Server<TcpListener, ()> {
+    /// Binds to the provided address, and returns a [`Builder`](Builder).
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if binding to the address fails. For a method
+    /// to bind to an address and return a `Result`, see `Server::try_bind`.
+    ///
+    /// It must also be called from within a tokio runtime.
+    pub fn bind(addr: &SocketAddr) -> Builder<TcpListener> {
+        Server::try_bind(addr).unwrap_or_else(|e| panic!("error binding to {}: {}", addr, e))
+    }
+
+    /// Tries to bind to the provided address, and returns a [`Builder`](Builder).
+    pub fn try_bind(addr: &SocketAddr) -> crate::Result<Builder<TcpListener>> {
+        let std_listener = std::net::TcpListener::bind(addr).map_err(crate::Error::new_listen)?;
+        std_listener
+            .set_nonblocking(true)
+            .map_err(crate::Error::new_listen)?;
+        let listener = TcpListener::from_std(std_listener).map_err(crate::Error::new_listen)?;
+        Ok(Server::builder(listener))
+    }
+}
+
+#[cfg(feature = "tcp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tcp")))]
+impl<S, E> Server<TcpListener, S, E> {
+    /// Returns the local address that this server is bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.incoming
+            .local_addr()
+            .expect("bound listener has a local address")
+    }
+}
+
+impl<I, IO, IE, S, E, B> Server<I, S, E>
+where
+    I: Accept<Conn = IO, Error = IE>,
+    IE: Into<Box<dyn StdError + Send + Sync>>,
+    IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: MakeServiceRef<IO, Recv, ResBody = B>,
+    S::MakeError: 'static,
+    S::Future: Send + 'static,
+    S::Service: Send + 'static,
+    <S::Service as HttpService<Recv>>::Future: Send + 'static,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: ConnStreamExec<<S::Service as HttpService<Recv>>::Future, B> + NewSvcExec,
+    E: Send + Sync + 'static,
+{
+    /// Prepares a server to handle graceful shutdown when the provided future
+    /// completes.
+    ///
+    /// Once the signal completes, the server stops accepting new connections,
+    /// and calls `graceful_shutdown` on every live connection. The returned
+    /// future completes once all of them have finished.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() {}
+    /// # #[cfg(feature = "tcp")]
+    /// # async fn run() {
+    /// # use hyper::server::Server;
+    /// # use hyper::service::{make_service_fn, service_fn};
+    /// # use hyper::{Recv, Request, Response};
+    /// # use http_body_util::Full;
+    /// # use bytes::Bytes;
+    /// # use std::convert::Infallible;
+    /// # let make_service = make_service_fn(|_: &tokio::net::TcpStream| async {
+    /// #     Ok::<_, Infallible>(service_fn(|_: Request<Recv>| async {
+    /// #         Ok::<_, Infallible>(Response::new(Full::new(Bytes::new())))
+    /// #     }))
+    /// # });
+    /// // Make a server from the previous examples...
+    /// let server = Server::bind(&([127, 0, 0, 1], 3000).into())
+    ///     .serve(make_service);
+    ///
+    /// // Prepare some signal for when the server should start shutting down...
+    /// let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    /// let graceful = server
+    ///     .with_graceful_shutdown(async {
+    ///         rx.await.ok();
+    ///     });
+    ///
+    /// // Await the `server` receiving the signal...
+    /// if let Err(e) = graceful.await {
+    ///     eprintln!("server error: {}", e);
+    /// }
+    ///
+    /// // And later, trigger the signal by calling `tx.send(())`.
+    /// let _ = tx.send(());
+    /// # }
+    /// ```
+    pub fn with_graceful_shutdown<F>(self, signal: F) -> Graceful<I, S, F, E>
+    where
+        F: Future<Output = ()>,
+    {
+        Graceful::new(self, signal)
+    }
+
+    pub(super) fn poll_watch(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        watch: Option<&Watch>,
+    ) -> Poll<crate::Result<()>> {
+        let mut me = self.project();
+        loop {
+            #[cfg(feature = "runtime")]
+            if let Some(backoff) = me.accept_backoff.as_mut() {
+                ready!(backoff.as_mut().poll(cx));
+                *me.accept_backoff = None;
+            }
+
+            ready!(me.make_service.poll_ready_ref(cx))
+                .map_err(crate::Error::new_user_make_service)?;
+
+            match ready!(me.incoming.as_mut().poll_accept(cx)) {
+                Some(Ok(io)) => {
+                    trace!("accepted new connection");
+                    let new_svc = me.make_service.make_service_ref(&io);
+                    let fut = serve_connection(io, new_svc, me.protocol.clone(), watch.cloned());
+                    me.protocol.exec.execute_new_svc(Box::pin(fut));
+                }
+                Some(Err(err)) => {
+                    let err = crate::Error::new_accept(err);
+                    #[cfg(feature = "runtime")]
+                    {
+                        error!("{}", err);
+                        *me.accept_backoff =
+                            Some(Box::pin(tokio::time::sleep(ACCEPT_ERROR_BACKOFF)));
+                        continue;
+                    }
+                    #[cfg(not(feature = "runtime"))]
+                    return Poll::Ready(Err(err));
+                }
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+    }
+}
+
+async fn serve_connection<IO, F, S, ME, B, E>(
+    io: IO,
+    new_svc: F,
+    protocol: Http<E>,
+    watch: Option<Watch>,
+) where
+    IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    F: Future<Output = Result<S, ME>>,
+    ME: Into<Box<dyn StdError + Send + Sync>>,
+    S: HttpService<Recv, ResBody = B>,
+    B: HttpBody + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: ConnStreamExec<S::Future, B>,
+{
+    let service = match new_svc.await {
+        Ok(service) => service,
+        Err(err) => {
+            debug!(
+                "connection error: {}",
+                crate::Error::new_user_make_service(err)
+            );
+            return;
+        }
+    };
+
+    let conn = protocol.serve_connection(io, service).with_upgrades();
+    let res = match watch {
+        Some(watch) => watch.watch(conn, |conn| conn.graceful_shutdown()).await,
+        None => conn.await,
+    };
+
+    if let Err(err) = res {
+        debug!("connection error: {}", err);
+    }
+}
+
+impl<I, IO, IE, S, B, E> Future for Server<I, S, E>
+where
+    I: Accept<Conn = IO, Error = IE>,
+    IE: Into<Box<dyn StdError + Send + Sync>>,
+    IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: MakeServiceRef<IO, Recv, ResBody = B>,
+    S::MakeError: 'static,
+    S::Future: Send + 'static,
+    S::Service: Send + 'static,
+    <S::Service as HttpService<Recv>>::Future: Send + 'static,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: ConnStreamExec<<S::Service as HttpService<Recv>>::Future, B> + NewSvcExec,
+    E: Send + Sync + 'static,
+{
+    type Output = crate::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        self.poll_watch(cx, None)
+    }
+}
+
+impl<I: fmt::Debug, S: fmt::Debug> fmt::Debug for Server<I, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Server")
+            .field("listener", &self.incoming)
+            .finish()
+    }
+}
+
+// ===== impl Builder =====
+
+impl<I, E> Builder<I, E> {
+    /// Start a new builder, wrapping an incoming stream and low-level options.
+    pub fn new(incoming: I, protocol: Http<E>) -> Self {
+        Builder { incoming, protocol }
+    }
+
+    /// Returns the low-level options used for every accepted connection.
+    pub fn http(&mut self) -> &mut Http<E> {
+        &mut self.protocol
+    }
+
+    /// Sets the `Executor` to deal with connection tasks.
+    ///
+    /// Default is `tokio::spawn`.
+    pub fn executor<E2>(self, executor: E2) -> Builder<I, E2> {
+        Builder {
+            incoming: self.incoming,
+            protocol: self.protocol.with_executor(executor),
+        }
+    }
+
+    /// Consume this `Builder`, creating a [`Server`](Server).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "tcp")]
+    /// # async fn run() {
+    /// use bytes::Bytes;
+    /// use http_body_util::Full;
+    /// use hyper::{Recv, Error, Response};
+    /// use hyper::server::Server;
+    /// use hyper::service::{make_service_fn, service_fn};
+    ///
+    /// // Construct our SocketAddr to listen on...
+    /// let addr = ([127, 0, 0, 1], 3000).into();
+    ///
+    /// // And a MakeService to handle each connection...
+    /// let make_service = make_service_fn(|_: &tokio::net::TcpStream| async {
+    ///     Ok::<_, Error>(service_fn(|_req| async {
+    ///         Ok::<_, Error>(Response::new(Full::new(Bytes::from("Hello World"))))
+    ///     }))
+    /// });
+    ///
+    /// // Then bind and serve...
+    /// let server = Server::bind(&addr)
+    ///     .serve(make_service);
+    ///
+    /// // Run forever-ish...
+    /// if let Err(err) = server.await {
+    ///     eprintln!("server error: {}", err);
+    /// }
+    /// # }
+    /// # fn main() {}
+    /// ```
+    pub fn serve<S, B>(self, make_service: S) -> Server<I, S, E>
+    where
+        I: Accept,
+        I::Error: Into<Box<dyn StdError + Send + Sync>>,
+        I::Conn: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        S: MakeServiceRef<I::Conn, Recv, ResBody = B>,
+        S::Error: Into<Box<dyn StdError + Send + Sync>>,
+        B: HttpBody + 'static,
+        B::Error: Into<Box<dyn StdError + Send + Sync>>,
+        E: NewSvcExec,
+        E: ConnStreamExec<<S::Service as HttpService<Recv>>::Future, B>,
+    {
+        Server {
+            incoming: self.incoming,
+            make_service,
+            protocol: self.protocol,
+            accept_backoff: None,
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::error::Error as StdError;
+
+use pin_project_lite::pin_project;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tracing::debug;
+
+use super::accept::Accept;
+use super::server::Server;
+use crate::body::{HttpBody, Recv};
+use crate::common::drain::{self, Draining, Signal, Watch};
+use crate::common::exec::{ConnStreamExec, NewSvcExec};
+use crate::common::{task, Future, Pin, Poll};
+use crate::service::{HttpService, MakeServiceRef};
+
+pin_project! {
+    /// A future that serves connections until a shutdown signal completes,
+    /// and then waits for every live connection to finish.
+    ///
+    /// Returned by [`Server::with_graceful_shutdown`](Server::with_graceful_shutdown).
+    #[allow(missing_debug_implementations)]
+    pub struct Graceful<I, S, F, E> {
+        #[pin]
+        state: State<I, S, F, E>,
+    }
+}
+
+pin_project! {
+    #[project = StateProj]
+    pub(super) enum State<I, S, F, E> {
+        Running {
+            drain: Option<(Signal, Watch)>,
+            #[pin]
+            server: Server<I, S, E>,
+            #[pin]
+            signal: F,
+        },
+        Draining { draining: Draining },
+    }
+}
+
+impl<I, S, F, E> Graceful<I, S, F, E> {
+    pub(super) fn new(server: Server<I, S, E>, signal: F) -> Self {
+        let drain = Some(drain::channel());
+        Graceful {
+            state: State::Running {
+                drain,
+                server,
+                signal,
+            },
+        }
+    }
+}
+
+impl<I, IO, IE, S, B, F, E> Future for Graceful<I, S, F, E>
+where
+    I: Accept<Conn = IO, Error = IE>,
+    IE: Into<Box<dyn StdError + Send + Sync>>,
+    IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: MakeServiceRef<IO, Recv, ResBody = B>,
+    S::MakeError: 'static,
+    S::Future: Send + 'static,
+    S::Service: Send + 'static,
+    <S::Service as HttpService<Recv>>::Future: Send + 'static,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    F: Future<Output = ()>,
+    E: ConnStreamExec<<S::Service as HttpService<Recv>>::Future, B> + NewSvcExec,
+    E: Send + Sync + 'static,
+{
+    type Output = crate::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let mut me = self.project();
+        loop {
+            let next = {
+                match me.state.as_mut().project() {
+                    StateProj::Running {
+                        drain,
+                        server,
+                        signal,
+                    } => match signal.poll(cx) {
+                        Poll::Ready(()) => {
+                            debug!("signal received, starting graceful shutdown");
+                            // Dropping the watch, and the server with its
+                            // listener, leaves only the connections to wait on.
+                            let (signal, _watch) = drain.take().expect("drain channel");
+                            State::Draining {
+                                draining: signal.drain(),
+                            }
+                        }
+                        Poll::Pending => {
+                            let watch = drain.as_ref().map(|(_, watch)| watch);
+                            return server.poll_watch(cx, watch);
+                        }
+                    },
+                    StateProj::Draining { ref mut draining } => {
+                        return Pin::new(draining).poll(cx).map(Ok);
+                    }
+                }
+            };
+            me.state.set(next);
+        }
+    }
+}
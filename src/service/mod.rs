@@ -10,6 +10,9 @@
 //!
 //! - `HttpService`: This is blanketly implemented for all types that
 //!   implement `Service<http::Request<B1>, Response = http::Response<B2>>`.
+//! - `MakeService`: When a `Service` returns another `Service`, it's really a
+//!   "make service". The [`server::Server`](crate::server::Server) calls one
+//!   for every accepted connection, to create the `Service` for it.
 //!
 //! # HttpService
 //!
@@ -20,13 +23,38 @@
 //! The helper [`service_fn`](service_fn) should be sufficient for most cases, but
 //! if you need to implement `Service` for a type manually, you can follow the example
 //! in `service_struct_impl.rs`.
+//!
+//! # MakeService
+//!
+//! Since a `Service` is bound to a single connection, a
+//! [`Server`](crate::server::Server) needs a way to make them as it accepts
+//! connections. This is what a `MakeService` does.
+//!
+//! Resources that need to be shared by all `Service`s can be put into a
+//! `MakeService`, and then passed to individual `Service`s when `call`
+//! is called. The helper [`make_service_fn`](make_service_fn) takes care
+//! of most of the boilerplate.
 
 pub use tower_service::Service;
 
 mod http;
+#[cfg(all(any(feature = "http1", feature = "http2"), feature = "server"))]
+mod make;
+#[cfg(all(any(feature = "http1", feature = "http2"), feature = "client"))]
+mod oneshot;
 mod util;
 
 #[cfg(all(any(feature = "http1", feature = "http2"), feature = "server"))]
 pub(super) use self::http::HttpService;
+#[cfg(all(any(feature = "http1", feature = "http2"), feature = "server"))]
+pub(super) use self::make::MakeServiceRef;
+#[cfg(all(any(feature = "http1", feature = "http2"), feature = "client"))]
+pub(super) use self::oneshot::{oneshot, Oneshot};
 
+#[cfg(all(any(feature = "http1", feature = "http2"), feature = "server"))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(all(any(feature = "http1", feature = "http2"), feature = "server")))
+)]
+pub use self::make::make_service_fn;
 pub use self::util::service_fn;
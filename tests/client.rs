@@ -1335,13 +1335,14 @@ mod conn {
     use bytes::{Buf, Bytes};
     use futures_channel::oneshot;
     use futures_util::future::{self, poll_fn, FutureExt, TryFutureExt};
-    use http_body_util::Empty;
+    use http_body_util::{Empty, Full};
     use hyper::upgrade::OnUpgrade;
     use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _, ReadBuf};
     use tokio::net::{TcpListener as TkTcpListener, TcpStream};
 
     use hyper::body::HttpBody;
     use hyper::client::conn;
+    use hyper::ext::RequestTimeouts;
     use hyper::{self, Method, Recv, Request, Response, StatusCode};
 
     use super::{concat, s, support, tcp_connect, FutureHyperExt};
@@ -1390,6 +1391,535 @@ mod conn {
         future::join(server, client).await;
     }
 
+    #[tokio::test]
+    async fn http1_max_headers_above_stack_size() {
+        let _ = ::pretty_env_logger::try_init();
+        let listener = TkTcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            sock.read(&mut buf).await.expect("read 1");
+
+            let headers: String = (0..150).map(|i| format!("x-{}: {}\r\n", i, i)).collect();
+            let response = format!("HTTP/1.1 200 OK\r\n{}content-length: 0\r\n\r\n", headers);
+            sock.write_all(response.as_bytes()).await.unwrap();
+        };
+
+        let client = async move {
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = conn::http1::Builder::new()
+                .http1_max_headers(200)
+                .handshake(tcp)
+                .await
+                .expect("handshake");
+
+            tokio::task::spawn(async move {
+                conn.await.expect("http conn");
+            });
+
+            let req = Request::builder()
+                .uri("/a")
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            let res = client.send_request(req).await.expect("send_request");
+            assert_eq!(res.status(), hyper::StatusCode::OK);
+            assert_eq!(res.headers().len(), 151);
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn http1_ignore_invalid_headers_in_responses() {
+        let _ = ::pretty_env_logger::try_init();
+        let listener = TkTcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            sock.read(&mut buf).await.expect("read 1");
+            sock.write_all(
+                b"HTTP/1.1 200 OK\r\nbad header: 1\r\nx-good: 2\r\ncontent-length: 0\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        };
+
+        let client = async move {
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = conn::http1::Builder::new()
+                .http1_ignore_invalid_headers_in_responses(true)
+                .handshake(tcp)
+                .await
+                .expect("handshake");
+
+            tokio::task::spawn(async move {
+                conn.await.expect("http conn");
+            });
+
+            let req = Request::builder()
+                .uri("/a")
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            let res = client.send_request(req).await.expect("send_request");
+            assert_eq!(res.status(), hyper::StatusCode::OK);
+            assert_eq!(res.headers().len(), 2);
+            assert_eq!(res.headers()["x-good"], "2");
+            let ignored = res
+                .extensions()
+                .get::<hyper::ext::IgnoredInvalidHeaders>()
+                .expect("ignored headers");
+            assert_eq!(ignored.count(), 1);
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn get_informational_responses() {
+        use hyper::ext::OnInformational;
+        use std::sync::{Arc, Mutex};
+
+        let _ = ::pretty_env_logger::try_init();
+        let listener = TkTcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            sock.read(&mut buf).await.expect("read 1");
+            sock.write_all(
+                b"\
+                HTTP/1.1 102 Processing\r\n\
+                \r\n\
+                HTTP/1.1 103 Early Hints\r\n\
+                Link: </style.css>; rel=preload; as=style\r\n\
+                \r\n\
+                HTTP/1.1 200 OK\r\n\
+                Content-Length: 0\r\n\
+                \r\n\
+            ",
+            )
+            .await
+            .unwrap();
+        };
+
+        let informational = Arc::new(Mutex::new(Vec::new()));
+        let received = informational.clone();
+        let client = async move {
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = conn::handshake(tcp).await.expect("handshake");
+
+            tokio::task::spawn(async move {
+                conn.await.expect("http conn");
+            });
+
+            let mut req = Request::builder()
+                .uri("/a")
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            req.extensions_mut()
+                .insert(OnInformational::new(move |res| {
+                    received.lock().unwrap().push(res)
+                }));
+            let res = client.send_request(req).await.expect("send_request");
+            assert_eq!(res.status(), hyper::StatusCode::OK);
+        };
+
+        future::join(server, client).await;
+
+        let informational = informational.lock().unwrap();
+        assert_eq!(informational.len(), 2);
+        assert_eq!(informational[0].status(), hyper::StatusCode::PROCESSING);
+        assert_eq!(informational[1].status().as_u16(), 103);
+        assert_eq!(
+            informational[1].headers()["link"],
+            "</style.css>; rel=preload; as=style"
+        );
+    }
+
+    #[tokio::test]
+    async fn http2_informational_responses_not_reported() {
+        use hyper::ext::OnInformational;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let _ = ::pretty_env_logger::try_init();
+        let listener = TkTcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            // Empty SETTINGS, then a SETTINGS ack.
+            sock.write_all(b"\0\0\0\x04\0\0\0\0\0\0\0\0\x04\x01\0\0\0\0")
+                .await
+                .unwrap();
+            // Let the client send its request.
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            // HEADERS with `:status: 103`, then HEADERS with `:status: 200`
+            // ending stream 1.
+            sock.write_all(b"\0\0\x05\x01\x04\0\0\0\x01\x08\x03103")
+                .await
+                .unwrap();
+            sock.write_all(b"\0\0\x01\x01\x05\0\0\0\x01\x88")
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        };
+
+        let informational = Arc::new(AtomicUsize::new(0));
+        let received = informational.clone();
+        let client = async move {
+            let io = tcp_connect(&addr).await.expect("tcp connect");
+            let (mut client, conn) = conn::http2::Builder::new()
+                .handshake::<_, Empty<Bytes>>(io)
+                .await
+                .expect("http handshake");
+
+            tokio::spawn(async move {
+                let _ = conn.await;
+            });
+
+            let mut req = Request::builder()
+                .uri("http://localhost/a")
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            req.extensions_mut().insert(OnInformational::new(move |_| {
+                received.fetch_add(1, Ordering::SeqCst);
+            }));
+            let res = client.send_request(req).await.expect("send_request");
+            assert_eq!(res.status(), hyper::StatusCode::OK);
+        };
+
+        future::join(server, client).await;
+
+        // h2 discards informational responses, only the final one is seen.
+        assert_eq!(informational.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn http1_expect_continue_waits_for_100() {
+        let _ = ::pretty_env_logger::try_init();
+        let listener = TkTcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            let n = sock.read(&mut buf).await.expect("read 1");
+            assert!(buf[..n].ends_with(b"\r\n\r\n"));
+
+            // The body is held until 100 Continue.
+            let early = tokio::time::timeout(Duration::from_millis(50), sock.read(&mut buf)).await;
+            assert!(early.is_err(), "body sent before 100 Continue");
+
+            sock.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+                .await
+                .unwrap();
+            let mut body = [0; 5];
+            sock.read_exact(&mut body).await.expect("read 2");
+            assert_eq!(&body, b"hello");
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        };
+
+        let client = async move {
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = conn::http1::Builder::new()
+                .http1_expect_continue_timeout(Duration::from_secs(10))
+                .handshake(tcp)
+                .await
+                .expect("handshake");
+
+            tokio::task::spawn(async move {
+                conn.await.expect("http conn");
+            });
+
+            let req = Request::builder()
+                .method(Method::POST)
+                .uri("/a")
+                .header("expect", "100-continue")
+                .body(Full::new(Bytes::from_static(b"hello")))
+                .unwrap();
+            let res = client.send_request(req).await.expect("send_request");
+            assert_eq!(res.status(), StatusCode::OK);
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn http1_expect_continue_timeout_sends_body() {
+        let _ = ::pretty_env_logger::try_init();
+        let listener = TkTcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            let n = sock.read(&mut buf).await.expect("read 1");
+            assert!(buf[..n].ends_with(b"\r\n\r\n"));
+
+            // No 100 Continue, the client sends the body anyway.
+            let mut body = [0; 5];
+            sock.read_exact(&mut body).await.expect("read 2");
+            assert_eq!(&body, b"hello");
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        };
+
+        let client = async move {
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = conn::http1::Builder::new()
+                .http1_expect_continue_timeout(Duration::from_millis(50))
+                .handshake(tcp)
+                .await
+                .expect("handshake");
+
+            tokio::task::spawn(async move {
+                conn.await.expect("http conn");
+            });
+
+            let req = Request::builder()
+                .method(Method::POST)
+                .uri("/a")
+                .header("expect", "100-continue")
+                .body(Full::new(Bytes::from_static(b"hello")))
+                .unwrap();
+            let res = client.send_request(req).await.expect("send_request");
+            assert_eq!(res.status(), StatusCode::OK);
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn http1_expect_continue_error_skips_chunked_body() {
+        let _ = ::pretty_env_logger::try_init();
+        let listener = TkTcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            let n = sock.read(&mut buf).await.expect("read 1");
+            assert!(buf[..n].ends_with(b"\r\n\r\n"));
+            sock.write_all(b"HTTP/1.1 417 Expectation Failed\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+
+            // The body ends without any data, followed by the next request.
+            let mut buf = Vec::new();
+            while !buf.ends_with(b"\r\n\r\n") || !buf.starts_with(b"0\r\n\r\n") || buf.len() <= 5 {
+                let mut chunk = [0; 4096];
+                let n = sock.read(&mut chunk).await.expect("read 2");
+                assert_ne!(n, 0, "unexpected eof: {:?}", s(&buf));
+                buf.extend_from_slice(&chunk[..n]);
+            }
+            assert!(
+                s(&buf[5..]).starts_with("GET /b HTTP/1.1\r\n"),
+                "{:?}",
+                s(&buf)
+            );
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        };
+
+        let client = async move {
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = conn::http1::Builder::new()
+                .handshake(tcp)
+                .await
+                .expect("handshake");
+
+            tokio::task::spawn(async move {
+                conn.await.expect("http conn");
+            });
+
+            // A body of unknown length, that never has any data.
+            let (_tx, body) = Recv::channel();
+            let req = Request::builder()
+                .method(Method::POST)
+                .uri("/a")
+                .header("expect", "100-continue")
+                .body(body.boxed())
+                .unwrap();
+            let res = client.send_request(req).await.expect("send_request 1");
+            assert_eq!(res.status(), StatusCode::EXPECTATION_FAILED);
+            concat(res).await.expect("body 1");
+
+            future::poll_fn(|cx| client.poll_ready(cx))
+                .await
+                .expect("connection still usable");
+            let req = Request::builder()
+                .uri("/b")
+                .body(Empty::new().map_err(|never| match never {}).boxed())
+                .unwrap();
+            let res = client.send_request(req).await.expect("send_request 2");
+            assert_eq!(res.status(), StatusCode::OK);
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn http1_expect_continue_error_closes_with_known_length() {
+        let _ = ::pretty_env_logger::try_init();
+        let listener = TkTcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            let n = sock.read(&mut buf).await.expect("read 1");
+            assert!(buf[..n].ends_with(b"\r\n\r\n"));
+            sock.write_all(b"HTTP/1.1 413 Payload Too Large\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+
+            // The body is never sent, and the client closes the connection.
+            let n = sock.read(&mut buf).await.expect("read 2");
+            assert_eq!(n, 0, "unexpected data: {:?}", s(&buf[..n]));
+        };
+
+        let client = async move {
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = conn::http1::Builder::new()
+                .handshake(tcp)
+                .await
+                .expect("handshake");
+
+            let conn = tokio::task::spawn(conn);
+
+            let req = Request::builder()
+                .method(Method::POST)
+                .uri("/a")
+                .header("expect", "100-continue")
+                .body(Full::new(Bytes::from_static(b"hello")))
+                .unwrap();
+            let res = client.send_request(req).await.expect("send_request");
+            assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+            concat(res).await.expect("body");
+
+            conn.await.unwrap().expect("http conn");
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn http1_response_head_timeout_closes() {
+        let _ = ::pretty_env_logger::try_init();
+        let listener = TkTcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            let n = sock.read(&mut buf).await.expect("read 1");
+            assert!(buf[..n].ends_with(b"\r\n\r\n"));
+
+            // No response, until the client closes the connection.
+            let n = sock.read(&mut buf).await.expect("read 2");
+            assert_eq!(n, 0, "unexpected data: {:?}", s(&buf[..n]));
+        };
+
+        let client = async move {
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = conn::http1::Builder::new()
+                .response_head_timeout(Duration::from_millis(50))
+                .handshake(tcp)
+                .await
+                .expect("handshake");
+
+            let conn = tokio::task::spawn(conn);
+
+            let req = Request::builder()
+                .uri("/a")
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            let err = client.send_request(req).await.unwrap_err();
+            assert!(err.is_timeout(), "{:?}", err);
+
+            conn.await.unwrap().expect("http conn");
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn http1_response_body_idle_timeout_per_request() {
+        let _ = ::pretty_env_logger::try_init();
+        let listener = TkTcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            let n = sock.read(&mut buf).await.expect("read 1");
+            assert!(buf[..n].ends_with(b"\r\n\r\n"));
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello")
+                .await
+                .unwrap();
+
+            let n = sock.read(&mut buf).await.expect("read 2");
+            assert_eq!(n, 0, "unexpected data: {:?}", s(&buf[..n]));
+        };
+
+        let client = async move {
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = conn::http1::Builder::new()
+                .handshake::<_, Empty<Bytes>>(tcp)
+                .await
+                .expect("handshake");
+
+            let conn = tokio::task::spawn(conn);
+
+            let mut timeouts = RequestTimeouts::new();
+            timeouts.response_body_idle(Duration::from_millis(50));
+            let mut req = Request::builder().uri("/a").body(Empty::new()).unwrap();
+            req.extensions_mut().insert(timeouts);
+            let res = client.send_request(req).await.expect("send_request");
+            assert_eq!(res.status(), StatusCode::OK);
+
+            let mut body = res.into_body();
+            let chunk = body.data().await.unwrap().expect("data");
+            assert_eq!(chunk, "hello");
+            let err = body.data().await.unwrap().unwrap_err();
+            assert!(err.is_timeout(), "{:?}", err);
+
+            conn.await.unwrap().expect("http conn");
+        };
+
+        future::join(server, client).await;
+    }
+
     #[tokio::test]
     async fn get_custom_reason_phrase() {
         let _ = ::pretty_env_logger::try_init();
@@ -2108,10 +2638,91 @@ mod conn {
 
         let io = tcp_connect(&addr).await.expect("tcp connect");
         let (mut client, conn) = conn::Builder::new()
-            .http2_only(true)
-            .http2_keep_alive_interval(Duration::from_secs(1))
-            .http2_keep_alive_timeout(Duration::from_secs(1))
-            .handshake::<_, Recv>(io)
+            .http2_only(true)
+            .http2_keep_alive_interval(Duration::from_secs(1))
+            .http2_keep_alive_timeout(Duration::from_secs(1))
+            .handshake::<_, Recv>(io)
+            .await
+            .expect("http handshake");
+
+        tokio::spawn(async move {
+            conn.await.expect("client conn shouldn't error");
+        });
+
+        // Use a channel to keep request stream open
+        let (_tx, body) = hyper::Recv::channel();
+        let req1 = http::Request::new(body);
+        let _resp = client.send_request(req1).await.expect("send_request");
+
+        // sleep longer than keepalive would trigger
+        tokio::time::sleep(Duration::from_secs(4)).await;
+
+        future::poll_fn(|ctx| client.poll_ready(ctx))
+            .await
+            .expect("client should be open");
+    }
+
+    async fn h2c_server(h2c_upgrade: bool) -> SocketAddr {
+        use hyper::service::service_fn;
+
+        let listener = TkTcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // Responds with the version, path and x-foo header of each request
+        tokio::spawn(async move {
+            let sock = listener.accept().await.unwrap().0;
+            hyper::server::conn::Http::new()
+                .h2c_upgrade(h2c_upgrade)
+                .serve_connection(
+                    sock,
+                    service_fn(|req: Request<Recv>| async move {
+                        let body = format!(
+                            "{:?} {} {:?}",
+                            req.version(),
+                            req.uri().path(),
+                            req.headers().get("x-foo"),
+                        );
+                        Ok::<_, hyper::Error>(Response::new(http_body_util::Full::new(
+                            Bytes::from(body),
+                        )))
+                    }),
+                )
+                .await
+                .expect("serve_connection");
+        });
+
+        addr
+    }
+
+    async fn h2c_get(
+        client: &mut conn::SendRequest<Empty<Bytes>>,
+        path: &str,
+    ) -> (http::Version, Bytes) {
+        future::poll_fn(|ctx| client.poll_ready(ctx))
+            .await
+            .expect("poll_ready");
+        let req = Request::get(path)
+            .header("host", "example.local")
+            .header("x-foo", "bar")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res = client.send_request(req).await.expect("send_request");
+        assert_eq!(res.status(), StatusCode::OK);
+        let version = res.version();
+        (version, concat(res).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn h2c_upgrade() {
+        let _ = pretty_env_logger::try_init();
+
+        let addr = h2c_server(true).await;
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (mut client, conn) = conn::Builder::new()
+            .h2c_upgrade(true)
+            .handshake(io)
             .await
             .expect("http handshake");
 
@@ -2119,17 +2730,58 @@ mod conn {
             conn.await.expect("client conn shouldn't error");
         });
 
-        // Use a channel to keep request stream open
-        let (_tx, body) = hyper::Recv::channel();
-        let req1 = http::Request::new(body);
-        let _resp = client.send_request(req1).await.expect("send_request");
+        let (version, body) = h2c_get(&mut client, "/first").await;
+        assert_eq!(version, http::Version::HTTP_2);
+        assert_eq!(body, r#"HTTP/2.0 /first Some("bar")"#);
 
-        // sleep longer than keepalive would trigger
-        tokio::time::sleep(Duration::from_secs(4)).await;
+        // The same headers are sent again, which the HPACK decoder of the
+        // server must still understand.
+        let (version, body) = h2c_get(&mut client, "/second").await;
+        assert_eq!(version, http::Version::HTTP_2);
+        assert_eq!(body, r#"HTTP/2.0 /second Some("bar")"#);
 
+        // Requests are multiplexed once upgraded
+        let req = |path| {
+            Request::get(path)
+                .header("host", "example.local")
+                .body(Empty::<Bytes>::new())
+                .unwrap()
+        };
+        let res1 = client.send_request(req("/a"));
         future::poll_fn(|ctx| client.poll_ready(ctx))
             .await
-            .expect("client should be open");
+            .expect("poll_ready");
+        let res2 = client.send_request(req("/b"));
+        let (res1, res2) = future::join(res1, res2).await;
+        let body1 = concat(res1.expect("res1")).await.unwrap();
+        let body2 = concat(res2.expect("res2")).await.unwrap();
+        assert_eq!(body1, "HTTP/2.0 /a None");
+        assert_eq!(body2, "HTTP/2.0 /b None");
+    }
+
+    #[tokio::test]
+    async fn h2c_upgrade_declined() {
+        let _ = pretty_env_logger::try_init();
+
+        let addr = h2c_server(false).await;
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (mut client, conn) = conn::Builder::new()
+            .h2c_upgrade(true)
+            .handshake(io)
+            .await
+            .expect("http handshake");
+
+        tokio::spawn(async move {
+            conn.await.expect("client conn shouldn't error");
+        });
+
+        let (version, body) = h2c_get(&mut client, "/first").await;
+        assert_eq!(version, http::Version::HTTP_11);
+        assert_eq!(body, r#"HTTP/1.1 /first Some("bar")"#);
+
+        let (version, body) = h2c_get(&mut client, "/second").await;
+        assert_eq!(version, http::Version::HTTP_11);
+        assert_eq!(body, r#"HTTP/1.1 /second Some("bar")"#);
     }
 
     #[tokio::test]
@@ -2193,6 +2845,242 @@ mod conn {
         upgraded.shutdown().await.unwrap();
     }
 
+    #[tokio::test]
+    async fn h2_response_head_timeout_resets_stream() {
+        let _ = pretty_env_logger::try_init();
+
+        let listener = TkTcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = async move {
+            let sock = listener.accept().await.unwrap().0;
+            let mut h2 = h2::server::handshake(sock).await.unwrap();
+
+            let (_req, mut respond) = h2.accept().await.unwrap().unwrap();
+            tokio::spawn(async move {
+                poll_fn(|cx| h2.poll_closed(cx)).await.unwrap();
+            });
+
+            let reason = poll_fn(|cx| respond.poll_reset(cx)).await.unwrap();
+            assert_eq!(reason, h2::Reason::CANCEL);
+        };
+
+        let client = async move {
+            let io = tcp_connect(&addr).await.expect("tcp connect");
+            let (mut client, conn) = conn::http2::Builder::new()
+                .response_head_timeout(Duration::from_millis(50))
+                .handshake::<_, Empty<Bytes>>(io)
+                .await
+                .expect("http handshake");
+
+            tokio::spawn(async move {
+                conn.await.expect("client conn shouldn't error");
+            });
+
+            let req = Request::get("http://localhost/a")
+                .body(Empty::new())
+                .unwrap();
+            let err = client.send_request(req).await.unwrap_err();
+            assert!(err.is_timeout(), "{:?}", err);
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn h2_response_total_timeout_fails_body() {
+        let _ = pretty_env_logger::try_init();
+
+        let listener = TkTcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = async move {
+            let sock = listener.accept().await.unwrap().0;
+            let mut h2 = h2::server::handshake(sock).await.unwrap();
+
+            let (_req, mut respond) = h2.accept().await.unwrap().unwrap();
+            tokio::spawn(async move {
+                poll_fn(|cx| h2.poll_closed(cx)).await.unwrap();
+            });
+
+            let mut send_stream = respond.send_response(Response::new(()), false).unwrap();
+            send_stream.send_data("hello".into(), false).unwrap();
+
+            let reason = poll_fn(|cx| send_stream.poll_reset(cx)).await.unwrap();
+            assert_eq!(reason, h2::Reason::CANCEL);
+        };
+
+        let client = async move {
+            let io = tcp_connect(&addr).await.expect("tcp connect");
+            let (mut client, conn) = conn::http2::Builder::new()
+                .total_timeout(Duration::from_millis(100))
+                .handshake::<_, Empty<Bytes>>(io)
+                .await
+                .expect("http handshake");
+
+            tokio::spawn(async move {
+                conn.await.expect("client conn shouldn't error");
+            });
+
+            let req = Request::get("http://localhost/a")
+                .body(Empty::new())
+                .unwrap();
+            let res = client.send_request(req).await.expect("send_request");
+            assert_eq!(res.status(), StatusCode::OK);
+
+            let mut body = res.into_body();
+            let chunk = body.data().await.unwrap().expect("data");
+            assert_eq!(chunk, "hello");
+            let err = body.data().await.unwrap().unwrap_err();
+            assert!(err.is_timeout(), "{:?}", err);
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn h2_refused_stream_is_retryable() {
+        let _ = pretty_env_logger::try_init();
+
+        let listener = TkTcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = async move {
+            let sock = listener.accept().await.unwrap().0;
+            let mut h2 = h2::server::handshake(sock).await.unwrap();
+
+            let (_req, mut respond) = h2.accept().await.unwrap().unwrap();
+            respond.send_reset(h2::Reason::REFUSED_STREAM);
+            poll_fn(|cx| h2.poll_closed(cx)).await.unwrap();
+        };
+
+        let client = async move {
+            let io = tcp_connect(&addr).await.expect("tcp connect");
+            let (mut client, conn) = conn::http2::Builder::new()
+                .handshake::<_, Empty<Bytes>>(io)
+                .await
+                .expect("http handshake");
+
+            tokio::spawn(async move {
+                conn.await.expect("client conn shouldn't error");
+            });
+
+            let req = Request::post("http://localhost/a")
+                .body(Empty::new())
+                .unwrap();
+            let err = client.send_request(req).await.unwrap_err();
+            assert_eq!(err.h2_reason(), Some(0x7), "{:?}", err);
+            assert!(!err.is_goaway(), "{:?}", err);
+            assert!(err.is_retryable_before_processing(), "{:?}", err);
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn h2_goaway_above_last_stream_is_retryable() {
+        let _ = pretty_env_logger::try_init();
+
+        let listener = TkTcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            // Empty SETTINGS, then a SETTINGS ack.
+            sock.write_all(b"\0\0\0\x04\0\0\0\0\0\0\0\0\x04\x01\0\0\0\0")
+                .await
+                .unwrap();
+            // Let the client send both requests.
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            // GOAWAY, with stream 1 as the last stream and NO_ERROR.
+            sock.write_all(b"\0\0\x08\x07\0\0\0\0\0\0\0\0\x01\0\0\0\0")
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        };
+
+        let client = async move {
+            let io = tcp_connect(&addr).await.expect("tcp connect");
+            let (client, conn) = conn::http2::Builder::new()
+                .handshake::<_, Empty<Bytes>>(io)
+                .await
+                .expect("http handshake");
+
+            tokio::spawn(async move {
+                let _ = conn.await;
+            });
+
+            let send = |path| {
+                let mut client = client.clone();
+                async move {
+                    let req = Request::post(path).body(Empty::new()).unwrap();
+                    client.send_request(req).await.unwrap_err()
+                }
+            };
+            let (processed, refused) =
+                future::join(send("http://localhost/1"), send("http://localhost/3")).await;
+
+            assert!(!processed.is_goaway(), "{:?}", processed);
+            assert!(
+                !processed.is_retryable_before_processing(),
+                "{:?}",
+                processed
+            );
+
+            assert!(refused.is_goaway(), "{:?}", refused);
+            assert_eq!(refused.h2_reason(), Some(0x0), "{:?}", refused);
+            assert!(refused.is_retryable_before_processing(), "{:?}", refused);
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn h2_goaway_connection_error_is_not_retryable() {
+        let _ = pretty_env_logger::try_init();
+
+        let listener = TkTcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            // Empty SETTINGS, then a SETTINGS ack.
+            sock.write_all(b"\0\0\0\x04\0\0\0\0\0\0\0\0\x04\x01\0\0\0\0")
+                .await
+                .unwrap();
+            // GOAWAY, with no stream processed and ENHANCE_YOUR_CALM.
+            sock.write_all(b"\0\0\x08\x07\0\0\0\0\0\0\0\0\0\0\0\0\x0b")
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        };
+
+        let client = async move {
+            let io = tcp_connect(&addr).await.expect("tcp connect");
+            let (_client, conn) = conn::http2::Builder::new()
+                .handshake::<_, Empty<Bytes>>(io)
+                .await
+                .expect("http handshake");
+
+            let err = conn.await.unwrap_err();
+            assert!(err.is_goaway(), "{:?}", err);
+            assert_eq!(err.h2_reason(), Some(0xb), "{:?}", err);
+            assert!(!err.is_retryable_before_processing(), "{:?}", err);
+        };
+
+        future::join(server, client).await;
+    }
+
     #[tokio::test]
     async fn h2_connect_rejected() {
         let _ = pretty_env_logger::try_init();
@@ -2249,6 +3137,131 @@ mod conn {
         done_tx.send(()).unwrap();
     }
 
+    #[tokio::test]
+    async fn http2_observer_reports_goaway() {
+        use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        #[derive(Default)]
+        struct Observed {
+            read: AtomicUsize,
+            written: AtomicUsize,
+            goaway: AtomicU32,
+        }
+
+        impl hyper::observe::Observer for Observed {
+            fn on_read(&self, bytes: usize) {
+                self.read.fetch_add(bytes, Ordering::SeqCst);
+            }
+
+            fn on_write(&self, bytes: usize) {
+                self.written.fetch_add(bytes, Ordering::SeqCst);
+            }
+
+            fn on_goaway_received(&self, reason: u32) {
+                self.goaway.store(reason, Ordering::SeqCst);
+            }
+        }
+
+        let _ = pretty_env_logger::try_init();
+
+        let listener = TkTcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut h2 = h2::server::handshake(&mut sock).await.unwrap();
+            h2.abrupt_shutdown(h2::Reason::ENHANCE_YOUR_CALM);
+            let _ = poll_fn(|cx| h2.poll_closed(cx)).await;
+            drop(h2);
+            // Keep reading, so the client sees the GOAWAY and not a reset.
+            let _ = drain_til_eof(sock).await;
+        });
+
+        let observed = Arc::new(Observed::default());
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (_client, conn) = conn::Builder::new()
+            .http2_only(true)
+            .observer(observed.clone())
+            .handshake::<_, Empty<Bytes>>(io)
+            .await
+            .expect("http handshake");
+
+        conn.await.expect_err("conn should error on GOAWAY");
+
+        assert!(observed.read.load(Ordering::SeqCst) > 0);
+        assert!(observed.written.load(Ordering::SeqCst) > 0);
+        assert_eq!(
+            observed.goaway.load(Ordering::SeqCst),
+            u32::from(h2::Reason::ENHANCE_YOUR_CALM)
+        );
+    }
+
+    #[tokio::test]
+    async fn http2_observer_reports_graceful_goaway() {
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Default)]
+        struct Observed {
+            goaways: Mutex<Vec<u32>>,
+        }
+
+        impl hyper::observe::Observer for Observed {
+            fn on_goaway_received(&self, reason: u32) {
+                self.goaways.lock().unwrap().push(reason);
+            }
+        }
+
+        let _ = pretty_env_logger::try_init();
+
+        let listener = TkTcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            // Empty SETTINGS, then a SETTINGS ack.
+            sock.write_all(b"\0\0\0\x04\0\0\0\0\0\0\0\0\x04\x01\0\0\0\0")
+                .await
+                .unwrap();
+            // Let the client send both requests.
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            // GOAWAY, with stream 1 as the last stream and NO_ERROR.
+            sock.write_all(b"\0\0\x08\x07\0\0\0\0\0\0\0\0\x01\0\0\0\0")
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        });
+
+        let observed = Arc::new(Observed::default());
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (client, conn) = conn::http2::Builder::new()
+            .observer(observed.clone())
+            .handshake::<_, Empty<Bytes>>(io)
+            .await
+            .expect("http handshake");
+
+        tokio::spawn(async move {
+            let _ = conn.await;
+        });
+
+        let send = |path| {
+            let mut client = client.clone();
+            async move {
+                let req = Request::post(path).body(Empty::new()).unwrap();
+                client.send_request(req).await.unwrap_err()
+            }
+        };
+        // Stream 3 is refused by the GOAWAY, which is reported once.
+        let _ = future::join(send("http://localhost/1"), send("http://localhost/3")).await;
+
+        let goaways = observed.goaways.lock().unwrap();
+        assert_eq!(*goaways, [u32::from(h2::Reason::NO_ERROR)]);
+    }
+
     async fn drain_til_eof<T: AsyncRead + Unpin>(mut sock: T) -> io::Result<()> {
         let mut buf = [0u8; 1024];
         loop {
@@ -7,7 +7,7 @@ use std::io::{self, Read, Write};
 use std::net::TcpListener as StdTcpListener;
 use std::net::{Shutdown, SocketAddr, TcpStream};
 use std::pin::Pin;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
@@ -26,8 +26,11 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener as TkTcpListener, TcpListener, TcpStream as TkTcpStream};
 
 use hyper::body::HttpBody;
-use hyper::server::conn::Http;
-use hyper::service::service_fn;
+use hyper::ext::{ExpectContinue, HeaderCaseMap, InformationalSender, OriginalHeaderOrder};
+use hyper::observe::Observer;
+use hyper::server::conn::{Http, MinDataRate, RefusedBody};
+use hyper::server::{accept, Server};
+use hyper::service::{make_service_fn, service_fn};
 use hyper::{Method, Recv, Request, Response, StatusCode, Uri, Version};
 
 mod support;
@@ -973,6 +976,201 @@ async fn expect_continue_waits_for_body_poll() {
     child.join().expect("client thread");
 }
 
+#[tokio::test]
+async fn manual_continue_accept_sends_100() {
+    let _ = pretty_env_logger::try_init();
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (continued_tx, continued_rx) = oneshot::channel::<()>();
+
+    let child = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+
+        tcp.write_all(
+            b"\
+            POST /foo HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Expect: 100-continue\r\n\
+            Content-Length: 5\r\n\
+            Connection: Close\r\n\
+            \r\n\
+        ",
+        )
+        .expect("write 1");
+
+        let msg = b"HTTP/1.1 100 Continue\r\n\r\n";
+        let mut buf = vec![0; msg.len()];
+        tcp.read_exact(&mut buf).expect("read 1");
+        assert_eq!(buf, msg);
+        continued_tx.send(()).unwrap();
+
+        tcp.write_all(b"hello").expect("write 2");
+
+        let mut resp = String::new();
+        tcp.read_to_string(&mut resp).expect("read 2");
+        assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "{:?}", resp);
+        assert!(resp.ends_with("hello"), "{:?}", resp);
+    });
+
+    let (socket, _) = listener.accept().await.expect("accept");
+    let mut continued_rx = Some(continued_rx);
+
+    Http::new()
+        .http1_manual_continue(true)
+        .serve_connection(
+            socket,
+            service_fn(move |mut req: Request<Recv>| {
+                let continued_rx = continued_rx.take().expect("one request");
+                async move {
+                    req.extensions_mut()
+                        .remove::<ExpectContinue>()
+                        .expect("ExpectContinue extension")
+                        .accept();
+                    // The 100 is sent without polling the body.
+                    continued_rx.await.expect("100 Continue received");
+                    let body = hyper::body::to_bytes(req.into_body()).await?;
+                    Ok::<_, hyper::Error>(Response::new(Full::new(body)))
+                }
+            }),
+        )
+        .await
+        .expect("serve_connection");
+
+    child.join().expect("client thread");
+}
+
+#[tokio::test]
+async fn manual_continue_response_before_accept_closes() {
+    let _ = pretty_env_logger::try_init();
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let child = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+
+        tcp.write_all(
+            b"\
+            POST /foo HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Expect: 100-continue\r\n\
+            Content-Length: 100\r\n\
+            \r\n\
+        ",
+        )
+        .expect("write");
+
+        // Closed by the server, even though the request is keep-alive.
+        let mut resp = String::new();
+        tcp.read_to_string(&mut resp).expect("read");
+        assert!(
+            resp.starts_with("HTTP/1.1 413 Payload Too Large\r\n"),
+            "{:?}",
+            resp
+        );
+        assert!(resp.contains("connection: close\r\n"), "{:?}", resp);
+        assert!(!resp.contains("100 Continue"), "{:?}", resp);
+    });
+
+    let (socket, _) = listener.accept().await.expect("accept");
+
+    Http::new()
+        .http1_manual_continue(true)
+        .serve_connection(
+            socket,
+            service_fn(|req: Request<Recv>| {
+                assert!(req.extensions().get::<ExpectContinue>().is_some());
+                drop(req);
+                future::ok::<_, hyper::Error>(
+                    Response::builder()
+                        .status(StatusCode::PAYLOAD_TOO_LARGE)
+                        .body(Empty::<Bytes>::new())
+                        .unwrap(),
+                )
+            }),
+        )
+        .await
+        .expect("serve_connection");
+
+    child.join().expect("client thread");
+}
+
+#[tokio::test]
+async fn manual_continue_refused_body_drained() {
+    let _ = pretty_env_logger::try_init();
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let child = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+
+        tcp.write_all(
+            b"\
+            POST /foo HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Expect: 100-continue\r\n\
+            Content-Length: 5\r\n\
+            \r\n\
+        ",
+        )
+        .expect("write 1");
+
+        let resp = read_until(&mut tcp, |buf| buf.ends_with(b"\r\n\r\n")).expect("read 1");
+        let resp = String::from_utf8(resp).unwrap();
+        assert!(
+            resp.starts_with("HTTP/1.1 417 Expectation Failed\r\n"),
+            "{:?}",
+            resp
+        );
+        assert!(!resp.contains("connection: close"), "{:?}", resp);
+
+        // The client sends the body anyway, followed by another request.
+        tcp.write_all(
+            b"\
+            hello\
+            GET /bar HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Connection: Close\r\n\
+            \r\n\
+        ",
+        )
+        .expect("write 2");
+
+        let mut resp = String::new();
+        tcp.read_to_string(&mut resp).expect("read 2");
+        assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "{:?}", resp);
+    });
+
+    let (socket, _) = listener.accept().await.expect("accept");
+
+    Http::new()
+        .http1_manual_continue(true)
+        .http1_refused_body(RefusedBody::Drain)
+        .serve_connection(
+            socket,
+            service_fn(|mut req: Request<Recv>| async move {
+                if req.uri() == "/bar" {
+                    return Ok(Response::new(Empty::<Bytes>::new()));
+                }
+                req.extensions_mut()
+                    .remove::<ExpectContinue>()
+                    .expect("ExpectContinue extension")
+                    .refuse();
+                let body = hyper::body::to_bytes(req.into_body()).await?;
+                assert!(body.is_empty());
+                Ok::<_, hyper::Error>(
+                    Response::builder()
+                        .status(StatusCode::EXPECTATION_FAILED)
+                        .body(Empty::<Bytes>::new())
+                        .unwrap(),
+                )
+            }),
+        )
+        .await
+        .expect("serve_connection");
+
+    child.join().expect("client thread");
+}
+
 #[test]
 fn pipeline_disabled() {
     let server = serve();
@@ -1108,6 +1306,120 @@ fn http_11_uri_too_long() {
     assert_eq!(s(&buf[..expected.len()]), expected);
 }
 
+async fn http1_limits_response(http: Http, request: String) -> String {
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let client = tokio::spawn(async move {
+        let mut tcp = connect_async(addr).await;
+        tcp.write_all(request.as_bytes()).await.unwrap();
+        let mut buf = Vec::new();
+        tcp.read_to_end(&mut buf).await.unwrap();
+        String::from_utf8(buf).unwrap()
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let _ = http.serve_connection(socket, HelloWorld).await;
+
+    client.await.unwrap()
+}
+
+#[tokio::test]
+async fn http1_max_headers_above_stack_size() {
+    let _ = pretty_env_logger::try_init();
+    let headers: String = (0..150).map(|i| format!("x-{}: {}\r\n", i, i)).collect();
+    let request = format!("GET / HTTP/1.1\r\n{}connection: close\r\n\r\n", headers);
+
+    let mut http = Http::new();
+    http.http1_max_headers(200);
+    let response = http1_limits_response(http, request).await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+}
+
+#[tokio::test]
+async fn http1_max_headers_exceeded() {
+    let _ = pretty_env_logger::try_init();
+    let headers: String = (0..11).map(|i| format!("x-{}: {}\r\n", i, i)).collect();
+    let request = format!("GET / HTTP/1.1\r\n{}\r\n", headers);
+
+    let mut http = Http::new();
+    http.http1_max_headers(10);
+    let response = http1_limits_response(http, request).await;
+    let expected = "HTTP/1.1 431 Request Header Fields Too Large\r\n";
+    assert!(response.starts_with(expected), "{}", response);
+}
+
+#[tokio::test]
+async fn http1_max_uri_len_exceeded() {
+    let _ = pretty_env_logger::try_init();
+    let request = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(16));
+
+    let mut http = Http::new();
+    http.http1_max_uri_len(16);
+    let response = http1_limits_response(http, request).await;
+    let expected = "HTTP/1.1 414 URI Too Long\r\n";
+    assert!(response.starts_with(expected), "{}", response);
+}
+
+#[tokio::test]
+async fn http1_strict_parsing_rejects_bare_lf() {
+    let _ = pretty_env_logger::try_init();
+    let request = "GET / HTTP/1.1\nconnection: close\n\n".to_string();
+
+    let response = http1_limits_response(Http::new(), request.clone()).await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+
+    let mut http = Http::new();
+    http.http1_strict_parsing(true);
+    let response = http1_limits_response(http, request).await;
+    let expected = "HTTP/1.1 400 Bad Request\r\n";
+    assert!(response.starts_with(expected), "{}", response);
+}
+
+#[tokio::test]
+async fn http1_preserve_header_case_and_order() {
+    let _ = pretty_env_logger::try_init();
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let client = tokio::spawn(async move {
+        let mut tcp = connect_async(addr).await;
+        tcp.write_all(b"GET / HTTP/1.1\r\nX-B: 1\r\nx-a: 2\r\nX-b: 3\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut buf = Vec::new();
+        tcp.read_to_end(&mut buf).await.unwrap();
+        String::from_utf8(buf).unwrap()
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    Http::new()
+        .http1_preserve_header_case(true)
+        .http1_preserve_header_order(true)
+        .serve_connection(
+            socket,
+            service_fn(|req: Request<Recv>| async move {
+                let mut res = Response::new(Empty::<Bytes>::new());
+                *res.headers_mut() = req.headers().clone();
+                let case = req.extensions().get::<HeaderCaseMap>().unwrap().clone();
+                let order = req
+                    .extensions()
+                    .get::<OriginalHeaderOrder>()
+                    .unwrap()
+                    .clone();
+                res.extensions_mut().insert(case);
+                res.extensions_mut().insert(order);
+                Ok::<_, hyper::Error>(res)
+            }),
+        )
+        .await
+        .unwrap();
+
+    let response = client.await.unwrap();
+    let expected = "X-B: 1\r\nx-a: 2\r\nX-b: 3\r\nConnection: close\r\n";
+    assert!(response.contains(expected), "{}", response);
+}
+
 #[tokio::test]
 async fn disable_keep_alive_mid_request() {
     let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
@@ -1265,47 +1577,68 @@ async fn http1_allow_half_close() {
     t1.join().expect("client thread");
 }
 
-#[cfg(feature = "http1")]
 #[tokio::test]
-async fn disconnect_after_reading_request_before_responding() {
+async fn http1_trailer_fields() {
     let _ = pretty_env_logger::try_init();
     let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
     let addr = listener.local_addr().unwrap();
 
-    thread::spawn(move || {
+    let t1 = thread::spawn(move || {
         let mut tcp = connect(&addr);
-        tcp.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        tcp.write_all(b"GET / HTTP/1.1\r\nTE: trailers\r\n\r\n")
+            .unwrap();
+
+        let expected = "0\r\nchunky-trailer: header data\r\n\r\n";
+        let buf = read_until(&mut tcp, |buf| buf.ends_with(expected.as_bytes())).unwrap();
+        let resp = s(&buf);
+        assert!(has_header(resp, "trailer"), "{:?}", resp);
+        assert!(resp.contains("3\r\nfoo\r\n0\r\n"), "{:?}", resp);
     });
 
     let (socket, _) = listener.accept().await.unwrap();
     Http::new()
-        .http1_half_close(false)
         .serve_connection(
             socket,
-            service_fn(|_| {
-                tokio::time::sleep(Duration::from_secs(2)).map(
-                    |_| -> Result<Response<Recv>, hyper::Error> {
-                        panic!("response future should have been dropped");
-                    },
-                )
+            service_fn(|_| async move {
+                let mut trailers = http::HeaderMap::new();
+                trailers.insert("chunky-trailer", HeaderValue::from_static("header data"));
+                Response::builder()
+                    .header("trailer", "chunky-trailer")
+                    .body(TrailersBody::new(Bytes::from_static(b"foo"), trailers))
             }),
         )
         .await
-        .expect_err("socket disconnected");
+        .unwrap();
+
+    t1.join().expect("client thread");
 }
 
 #[tokio::test]
-async fn returning_1xx_response_is_error() {
+async fn http1_trailer_recv_fields() {
+    let _ = pretty_env_logger::try_init();
     let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
     let addr = listener.local_addr().unwrap();
 
-    thread::spawn(move || {
+    let t1 = thread::spawn(move || {
         let mut tcp = connect(&addr);
-        tcp.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        tcp.write_all(
+            b"\
+            POST / HTTP/1.1\r\n\
+            transfer-encoding: chunked\r\n\
+            trailer: chunky-trailer\r\n\
+            \r\n\
+            3\r\n\
+            foo\r\n\
+            0\r\n\
+            chunky-trailer: header data\r\n\
+            \r\n\
+            ",
+        )
+        .unwrap();
+
         let mut buf = [0; 256];
         tcp.read(&mut buf).unwrap();
-
-        let expected = "HTTP/1.1 500 ";
+        let expected = "HTTP/1.1 200 OK\r\n";
         assert_eq!(s(&buf[..expected.len()]), expected);
     });
 
@@ -1313,50 +1646,364 @@ async fn returning_1xx_response_is_error() {
     Http::new()
         .serve_connection(
             socket,
-            service_fn(|_| async move {
-                Ok::<_, hyper::Error>(
-                    Response::builder()
-                        .status(StatusCode::CONTINUE)
-                        .body(Empty::<Bytes>::new())
-                        .unwrap(),
-                )
+            service_fn(|mut req: Request<Recv>| async move {
+                let body = req.body_mut();
+                let mut data = Vec::new();
+                while let Some(chunk) = body.data().await {
+                    data.extend_from_slice(&chunk?);
+                }
+                assert_eq!(data, b"foo");
+
+                let trailers = body.trailers().await?.expect("trailers");
+                assert_eq!(trailers["chunky-trailer"], "header data");
+
+                Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
             }),
         )
         .await
-        .expect_err("1xx status code should error");
-}
-
-#[test]
-fn header_name_too_long() {
-    let server = serve();
-
-    let mut req = connect(server.addr());
-    let mut write = Vec::with_capacity(1024 * 66);
-    write.extend_from_slice(b"GET / HTTP/1.1\r\n");
-    for _ in 0..(1024 * 65) {
-        write.push(b'x');
-    }
-    write.extend_from_slice(b": foo\r\n\r\n");
-    req.write_all(&write).unwrap();
+        .unwrap();
 
-    let mut buf = [0; 1024];
-    let n = req.read(&mut buf).unwrap();
-    assert!(s(&buf[..n]).starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"));
+    t1.join().expect("client thread");
 }
 
 #[tokio::test]
-async fn header_read_timeout_slow_writes() {
+async fn http1_trailer_recv_fields_over_max_headers() {
+    let _ = pretty_env_logger::try_init();
     let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
     let addr = listener.local_addr().unwrap();
 
-    thread::spawn(move || {
+    let t1 = thread::spawn(move || {
         let mut tcp = connect(&addr);
-        tcp.write_all(
-            b"\
-            GET / HTTP/1.1\r\n\
-        ",
-        )
-        .expect("write 1");
+        let trailers: String = (0..100).map(|i| format!("x-{}: {}\r\n", i, i)).collect();
+        let request = format!(
+            "POST / HTTP/1.1\r\ntransfer-encoding: chunked\r\n\r\n0\r\n{}\r\n",
+            trailers
+        );
+        tcp.write_all(request.as_bytes()).unwrap();
+        let mut buf = Vec::new();
+        let _ = tcp.read_to_end(&mut buf);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let mut http = Http::new();
+    http.http1_max_headers(20);
+    let _ = http
+        .serve_connection(
+            socket,
+            service_fn(|mut req: Request<Recv>| async move {
+                let body = req.body_mut();
+                loop {
+                    match body.data().await {
+                        Some(Ok(_)) => continue,
+                        Some(Err(_)) => break,
+                        None => panic!("trailer fields over http1_max_headers accepted"),
+                    }
+                }
+                Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+            }),
+        )
+        .await;
+
+    t1.join().expect("client thread");
+}
+
+#[tokio::test]
+async fn http1_chunk_extensions_recv() {
+    let _ = pretty_env_logger::try_init();
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let t1 = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            POST / HTTP/1.1\r\n\
+            transfer-encoding: chunked\r\n\
+            \r\n\
+            3;sig=abc\r\n\
+            foo\r\n\
+            3\r\n\
+            bar\r\n\
+            0\r\n\
+            \r\n\
+            ",
+        )
+        .unwrap();
+
+        let mut buf = [0; 256];
+        tcp.read(&mut buf).unwrap();
+        let expected = "HTTP/1.1 200 OK\r\n";
+        assert_eq!(s(&buf[..expected.len()]), expected);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    Http::new()
+        .http1_chunk_extensions(true)
+        .serve_connection(
+            socket,
+            service_fn(|mut req: Request<Recv>| async move {
+                let body = req.body_mut();
+
+                let chunk = body.data().await.expect("first chunk")?;
+                assert_eq!(chunk, "foo");
+                let ext = body.chunk_extensions().expect("chunk extensions");
+                assert_eq!(ext.as_bytes(), b"sig=abc");
+
+                let chunk = body.data().await.expect("second chunk")?;
+                assert_eq!(chunk, "bar");
+                assert!(body.chunk_extensions().is_none());
+
+                Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+            }),
+        )
+        .await
+        .unwrap();
+
+    t1.join().expect("client thread");
+}
+
+#[tokio::test]
+async fn http1_chunk_extensions_ignored_by_default() {
+    let _ = pretty_env_logger::try_init();
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let t1 = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            POST / HTTP/1.1\r\n\
+            transfer-encoding: chunked\r\n\
+            \r\n\
+            3;sig=abc\r\n\
+            foo\r\n\
+            0\r\n\
+            \r\n\
+            ",
+        )
+        .unwrap();
+
+        let mut buf = [0; 256];
+        tcp.read(&mut buf).unwrap();
+        let expected = "HTTP/1.1 200 OK\r\n";
+        assert_eq!(s(&buf[..expected.len()]), expected);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    Http::new()
+        .serve_connection(
+            socket,
+            service_fn(|mut req: Request<Recv>| async move {
+                let body = req.body_mut();
+                let chunk = body.data().await.expect("first chunk")?;
+                assert_eq!(chunk, "foo");
+                assert!(body.chunk_extensions().is_none());
+
+                Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+            }),
+        )
+        .await
+        .unwrap();
+
+    t1.join().expect("client thread");
+}
+
+async fn http1_chunk_extensions_send_response(enabled: bool) -> String {
+    use futures_util::StreamExt;
+
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let t1 = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        tcp.read_to_string(&mut response).unwrap();
+        response
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    Http::new()
+        .http1_chunk_extensions(enabled)
+        .serve_connection(
+            socket,
+            service_fn(|_| async move {
+                let chunk_ext = hyper::ext::OutgoingChunkExtensions::new();
+                let body_ext = chunk_ext.clone();
+                let chunks = vec![
+                    (Bytes::from_static(b"foo"), Some(&b"sig=abc"[..])),
+                    (Bytes::from_static(b"bar"), None),
+                ];
+                let body =
+                    StreamBody::new(futures_util::stream::iter(chunks).map(move |(data, ext)| {
+                        if let Some(ext) = ext {
+                            body_ext.set(hyper::ext::ChunkExtensions::from_static(ext));
+                        }
+                        Ok::<_, hyper::Error>(data)
+                    }));
+                let mut res = Response::builder()
+                    .header("connection", "close")
+                    .body(body)
+                    .unwrap();
+                res.extensions_mut().insert(chunk_ext);
+                Ok::<_, hyper::Error>(res)
+            }),
+        )
+        .await
+        .unwrap();
+
+    t1.join().expect("client thread")
+}
+
+#[tokio::test]
+async fn http1_chunk_extensions_send() {
+    let _ = pretty_env_logger::try_init();
+    let response = http1_chunk_extensions_send_response(true).await;
+    assert!(
+        response.ends_with("\r\n\r\n3;sig=abc\r\nfoo\r\n3\r\nbar\r\n0\r\n\r\n"),
+        "response: {:?}",
+        response
+    );
+}
+
+#[tokio::test]
+async fn http1_chunk_extensions_send_disabled() {
+    let _ = pretty_env_logger::try_init();
+    let response = http1_chunk_extensions_send_response(false).await;
+    assert!(
+        response.ends_with("\r\n\r\n3\r\nfoo\r\n3\r\nbar\r\n0\r\n\r\n"),
+        "response: {:?}",
+        response
+    );
+}
+
+#[tokio::test]
+async fn http1_trailer_fields_not_allowed() {
+    let _ = pretty_env_logger::try_init();
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let t1 = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+
+        let buf = read_until(&mut tcp, |buf| buf.ends_with(b"0\r\n\r\n")).unwrap();
+        let resp = s(&buf);
+        assert!(has_header(resp, "trailer"), "{:?}", resp);
+        assert!(!resp.contains("chunky-trailer: header data"), "{:?}", resp);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    Http::new()
+        .serve_connection(
+            socket,
+            service_fn(|_| async move {
+                let mut trailers = http::HeaderMap::new();
+                trailers.insert("chunky-trailer", HeaderValue::from_static("header data"));
+                Response::builder()
+                    .header("trailer", "chunky-trailer")
+                    .body(TrailersBody::new(Bytes::from_static(b"foo"), trailers))
+            }),
+        )
+        .await
+        .unwrap();
+
+    t1.join().expect("client thread");
+}
+
+#[cfg(feature = "http1")]
+#[tokio::test]
+async fn disconnect_after_reading_request_before_responding() {
+    let _ = pretty_env_logger::try_init();
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    Http::new()
+        .http1_half_close(false)
+        .serve_connection(
+            socket,
+            service_fn(|_| {
+                tokio::time::sleep(Duration::from_secs(2)).map(
+                    |_| -> Result<Response<Recv>, hyper::Error> {
+                        panic!("response future should have been dropped");
+                    },
+                )
+            }),
+        )
+        .await
+        .expect_err("socket disconnected");
+}
+
+#[tokio::test]
+async fn returning_1xx_response_is_error() {
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        let mut buf = [0; 256];
+        tcp.read(&mut buf).unwrap();
+
+        let expected = "HTTP/1.1 500 ";
+        assert_eq!(s(&buf[..expected.len()]), expected);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    Http::new()
+        .serve_connection(
+            socket,
+            service_fn(|_| async move {
+                Ok::<_, hyper::Error>(
+                    Response::builder()
+                        .status(StatusCode::CONTINUE)
+                        .body(Empty::<Bytes>::new())
+                        .unwrap(),
+                )
+            }),
+        )
+        .await
+        .expect_err("1xx status code should error");
+}
+
+#[test]
+fn header_name_too_long() {
+    let server = serve();
+
+    let mut req = connect(server.addr());
+    let mut write = Vec::with_capacity(1024 * 66);
+    write.extend_from_slice(b"GET / HTTP/1.1\r\n");
+    for _ in 0..(1024 * 65) {
+        write.push(b'x');
+    }
+    write.extend_from_slice(b": foo\r\n\r\n");
+    req.write_all(&write).unwrap();
+
+    let mut buf = [0; 1024];
+    let n = req.read(&mut buf).unwrap();
+    assert!(s(&buf[..n]).starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"));
+}
+
+#[tokio::test]
+async fn header_read_timeout_slow_writes() {
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            GET / HTTP/1.1\r\n\
+        ",
+        )
+        .expect("write 1");
         thread::sleep(Duration::from_secs(3));
         tcp.write_all(
             b"\
@@ -1466,30 +2113,443 @@ async fn header_read_timeout_slow_writes_multiple_requests() {
 }
 
 #[tokio::test]
-async fn upgrades() {
+async fn http1_keep_alive_timeout_closes_idle_connection() {
     let _ = pretty_env_logger::try_init();
     let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
     let addr = listener.local_addr().unwrap();
-    let (tx, rx) = oneshot::channel();
 
-    thread::spawn(move || {
-        let mut tcp = connect(&addr);
-        tcp.write_all(
-            b"\
-            GET / HTTP/1.1\r\n\
-            Upgrade: foobar\r\n\
-            Connection: upgrade\r\n\
-            \r\n\
-            eagerly optimistic\
-        ",
-        )
-        .expect("write 1");
-        let mut buf = [0; 256];
-        tcp.read(&mut buf).expect("read 1");
+    let client = tokio::spawn(async move {
+        let mut tcp = connect_async(addr).await;
+        tcp.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
 
-        let expected = "HTTP/1.1 101 Switching Protocols\r\n";
-        assert_eq!(s(&buf[..expected.len()]), expected);
-        let _ = tx.send(());
+        // The connection is closed by the server once idle, without any
+        // error response.
+        let mut buf = Vec::new();
+        tcp.read_to_end(&mut buf).await.unwrap();
+        let response = s(&buf);
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.ends_with(HELLO), "{}", response);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    Http::new()
+        .http1_keep_alive_timeout(Duration::from_millis(200))
+        .serve_connection(socket, HelloWorld)
+        .await
+        .expect("idle connection closes cleanly");
+
+    client.await.unwrap();
+}
+
+#[tokio::test]
+async fn http1_max_requests_closes_after_last_response() {
+    let _ = pretty_env_logger::try_init();
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let client = tokio::spawn(async move {
+        let mut tcp = connect_async(addr).await;
+        tcp.write_all(
+            b"\
+            GET /1 HTTP/1.1\r\n\
+            \r\n\
+            GET /2 HTTP/1.1\r\n\
+            \r\n\
+            ",
+        )
+        .await
+        .unwrap();
+
+        let mut buf = Vec::new();
+        tcp.read_to_end(&mut buf).await.unwrap();
+        let response = s(&buf);
+        let mut responses = response.split("HTTP/1.1 200 OK\r\n").skip(1);
+        let first = responses.next().expect("first response");
+        assert!(!first.contains("connection: close"), "{}", first);
+        let second = responses.next().expect("second response");
+        assert!(second.contains("connection: close"), "{}", second);
+        assert_eq!(responses.next(), None);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    Http::new()
+        .http1_max_requests(2)
+        .serve_connection(socket, HelloWorld)
+        .await
+        .unwrap();
+
+    client.await.unwrap();
+}
+
+#[tokio::test]
+async fn body_read_timeout_stalled_body() {
+    let _ = pretty_env_logger::try_init();
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let client = tokio::spawn(async move {
+        let mut tcp = connect_async(addr).await;
+        tcp.write_all(
+            b"\
+            POST / HTTP/1.1\r\n\
+            content-length: 10\r\n\
+            \r\n\
+            hello\
+            ",
+        )
+        .await
+        .unwrap();
+
+        let mut buf = Vec::new();
+        tcp.read_to_end(&mut buf).await.unwrap();
+        let expected = "HTTP/1.1 408 Request Timeout\r\n";
+        assert_eq!(s(&buf[..expected.len()]), expected);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    Http::new()
+        .body_read_timeout(Duration::from_millis(200))
+        .serve_connection(
+            socket,
+            service_fn(|mut req: Request<Recv>| async move {
+                let chunk = req.body_mut().data().await.expect("chunk")?;
+                assert_eq!(chunk, "hello");
+                let err = req.body_mut().data().await.expect("error").unwrap_err();
+                assert!(err.is_timeout());
+                assert!(err.is_body_timeout());
+                let res = Response::builder()
+                    .status(408)
+                    .body(Empty::<Bytes>::new())
+                    .unwrap();
+                Ok::<_, hyper::Error>(res)
+            }),
+        )
+        .await
+        .unwrap();
+
+    client.await.unwrap();
+}
+
+#[tokio::test]
+async fn min_request_body_data_rate_trickled_body() {
+    let _ = pretty_env_logger::try_init();
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let mut tcp = connect_async(addr).await;
+        tcp.write_all(
+            b"\
+            POST / HTTP/1.1\r\n\
+            content-length: 100\r\n\
+            \r\n\
+            ",
+        )
+        .await
+        .unwrap();
+        // Each byte comes before the idle timeout, but far below the rate.
+        for _ in 0..100 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            if tcp.write_all(b"x").await.is_err() {
+                return;
+            }
+        }
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    Http::new()
+        .body_read_timeout(Duration::from_secs(1))
+        .min_request_body_data_rate(MinDataRate::new(1000, Duration::from_millis(300)))
+        .serve_connection(
+            socket,
+            service_fn(|req: Request<Recv>| async move {
+                let err = hyper::body::to_bytes(req.into_body()).await.unwrap_err();
+                assert!(err.is_body_timeout());
+                Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+            }),
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn min_response_data_rate_unread_response() {
+    let _ = pretty_env_logger::try_init();
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let (closed_tx, closed_rx) = oneshot::channel::<()>();
+    tokio::spawn(async move {
+        let mut tcp = connect_async(addr).await;
+        tcp.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        // Never read the response, until the server gave up.
+        let _ = closed_rx.await;
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let err = Http::new()
+        .min_response_data_rate(MinDataRate::new(
+            10 * 1024 * 1024,
+            Duration::from_millis(200),
+        ))
+        .serve_connection(
+            socket,
+            service_fn(|_| async {
+                let chunk = Bytes::from(vec![b'x'; 16 * 1024]);
+                let body = StreamBody::new(futures_util::stream::repeat_with(move || {
+                    Ok::<_, hyper::Error>(chunk.clone())
+                }));
+                Ok::<_, hyper::Error>(Response::new(body))
+            }),
+        )
+        .await
+        .expect_err("response rate timeout");
+    assert!(err.is_body_timeout());
+    drop(closed_tx);
+}
+
+#[tokio::test]
+async fn h2_body_read_timeout_stalled_body() {
+    let _ = pretty_env_logger::try_init();
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let conn = connect_async(addr).await;
+        let (h2, connection) = h2::client::handshake(conn).await.unwrap();
+        tokio::spawn(async move {
+            connection.await.unwrap();
+        });
+        let mut h2 = h2.ready().await.unwrap();
+
+        let request = Request::post("http://localhost/").body(()).unwrap();
+        let (response, mut send_stream) = h2.send_request(request, false).unwrap();
+        send_stream.send_data("hello".into(), false).unwrap();
+
+        let response = response.await.unwrap();
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+        drop(send_stream);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    Http::new()
+        .http2_only(true)
+        .body_read_timeout(Duration::from_millis(200))
+        .serve_connection(
+            socket,
+            service_fn(|mut req: Request<Recv>| async move {
+                let chunk = req.body_mut().data().await.expect("chunk")?;
+                assert_eq!(chunk, "hello");
+                let err = req.body_mut().data().await.expect("error").unwrap_err();
+                assert!(err.is_body_timeout());
+                let res = Response::builder()
+                    .status(408)
+                    .body(Empty::<Bytes>::new())
+                    .unwrap();
+                Ok::<_, hyper::Error>(res)
+            }),
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn h2_graceful_shutdown_timeout_closes_remaining_streams() {
+    let _ = pretty_env_logger::try_init();
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = oneshot::channel();
+
+    let client = tokio::spawn(async move {
+        let conn = connect_async(addr).await;
+        let (h2, connection) = h2::client::handshake(conn).await.unwrap();
+        let connection = tokio::spawn(connection);
+        let mut h2 = h2.ready().await.unwrap();
+
+        let request = Request::get("http://localhost/").body(()).unwrap();
+        let (response, _) = h2.send_request(request, true).unwrap();
+        response.await.expect_err("stream should be closed");
+
+        // The final GOAWAY carries the error code.
+        let err = connection.await.unwrap().expect_err("connection error");
+        assert_eq!(err.reason(), Some(h2::Reason::CANCEL));
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let mut tx = Some(tx);
+    let conn = Http::new()
+        .http2_only(true)
+        .http2_graceful_shutdown_timeout(Duration::from_millis(100))
+        .serve_connection(
+            socket,
+            service_fn(move |_| {
+                tx.take().unwrap().send(()).unwrap();
+                future::pending::<Result<Response<Empty<Bytes>>, hyper::Error>>()
+            }),
+        );
+    let mut conn = match future::select(conn, rx).await {
+        Either::Left(_) => panic!("expected request first"),
+        Either::Right((_, conn)) => conn,
+    };
+    Pin::new(&mut conn).graceful_shutdown();
+    tokio::time::timeout(Duration::from_secs(5), conn)
+        .await
+        .expect("graceful shutdown timed out")
+        .unwrap();
+
+    client.await.unwrap();
+}
+
+#[tokio::test]
+async fn h2_graceful_shutdown_timeout_waits_for_streams() {
+    let _ = pretty_env_logger::try_init();
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = oneshot::channel();
+
+    let client = tokio::spawn(async move {
+        let conn = connect_async(addr).await;
+        let (h2, connection) = h2::client::handshake(conn).await.unwrap();
+        tokio::spawn(async move {
+            connection.await.unwrap();
+        });
+        let mut h2 = h2.ready().await.unwrap();
+
+        let request = Request::get("http://localhost/").body(()).unwrap();
+        let (response, _) = h2.send_request(request, true).unwrap();
+        let response = response.await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let mut tx = Some(tx);
+    let conn = Http::new()
+        .http2_only(true)
+        .http2_graceful_shutdown_timeout(Duration::from_secs(5))
+        .serve_connection(
+            socket,
+            service_fn(move |_| {
+                tx.take().unwrap().send(()).unwrap();
+                async {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+                }
+            }),
+        );
+    let mut conn = match future::select(conn, rx).await {
+        Either::Left(_) => panic!("expected request first"),
+        Either::Right((_, conn)) => conn,
+    };
+    Pin::new(&mut conn).graceful_shutdown();
+    conn.await.unwrap();
+
+    client.await.unwrap();
+}
+
+#[tokio::test]
+async fn h2_max_connection_age_shuts_down_gracefully() {
+    let _ = pretty_env_logger::try_init();
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let client = tokio::spawn(async move {
+        let conn = connect_async(addr).await;
+        let (h2, connection) = h2::client::handshake(conn).await.unwrap();
+        let connection = tokio::spawn(connection);
+        let mut h2 = h2.ready().await.unwrap();
+
+        let request = Request::get("http://localhost/").body(()).unwrap();
+        let (response, _) = h2.send_request(request, true).unwrap();
+        let response = response.await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        connection
+            .await
+            .unwrap()
+            .expect("connection closes cleanly");
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let conn = Http::new()
+        .http2_only(true)
+        .http2_max_connection_age(Duration::from_millis(100))
+        .serve_connection(
+            socket,
+            service_fn(|_| async {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+            }),
+        );
+    tokio::time::timeout(Duration::from_secs(5), conn)
+        .await
+        .expect("max connection age")
+        .unwrap();
+
+    client.await.unwrap();
+}
+
+#[tokio::test]
+async fn h2_max_connection_age_grace_closes_remaining_streams() {
+    let _ = pretty_env_logger::try_init();
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let client = tokio::spawn(async move {
+        let conn = connect_async(addr).await;
+        let (h2, connection) = h2::client::handshake(conn).await.unwrap();
+        let connection = tokio::spawn(connection);
+        let mut h2 = h2.ready().await.unwrap();
+
+        let request = Request::get("http://localhost/").body(()).unwrap();
+        let (response, _) = h2.send_request(request, true).unwrap();
+        response.await.expect_err("stream should be closed");
+
+        let err = connection.await.unwrap().expect_err("connection error");
+        assert_eq!(err.reason(), Some(h2::Reason::CANCEL));
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let conn = Http::new()
+        .http2_only(true)
+        .http2_max_connection_age(Duration::from_millis(100))
+        .http2_max_connection_age_grace(Duration::from_millis(100))
+        .serve_connection(
+            socket,
+            service_fn(|_| future::pending::<Result<Response<Empty<Bytes>>, hyper::Error>>()),
+        );
+    tokio::time::timeout(Duration::from_secs(5), conn)
+        .await
+        .expect("max connection age grace")
+        .unwrap();
+
+    client.await.unwrap();
+}
+
+#[tokio::test]
+async fn upgrades() {
+    let _ = pretty_env_logger::try_init();
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = oneshot::channel();
+
+    thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            GET / HTTP/1.1\r\n\
+            Upgrade: foobar\r\n\
+            Connection: upgrade\r\n\
+            \r\n\
+            eagerly optimistic\
+        ",
+        )
+        .expect("write 1");
+        let mut buf = [0; 256];
+        tcp.read(&mut buf).expect("read 1");
+
+        let expected = "HTTP/1.1 101 Switching Protocols\r\n";
+        assert_eq!(s(&buf[..expected.len()]), expected);
+        let _ = tx.send(());
 
         let n = tcp.read(&mut buf).expect("read 2");
         assert_eq!(s(&buf[..n]), "foo=bar");
@@ -2305,12 +3365,45 @@ fn http2_body_user_error_sends_reset_reason() {
     assert_eq!(h2_err.reason(), Some(h2::Reason::INADEQUATE_SECURITY));
 }
 
-struct Http2ReadyErrorSvc;
+struct TrailersBody {
+    data: Option<Bytes>,
+    trailers: Option<http::HeaderMap>,
+}
 
-impl tower_service::Service<Request<Recv>> for Http2ReadyErrorSvc {
-    type Response = Response<Recv>;
-    type Error = h2::Error;
-    type Future = Box<
+impl TrailersBody {
+    fn new(data: Bytes, trailers: http::HeaderMap) -> Self {
+        TrailersBody {
+            data: Some(data),
+            trailers: Some(trailers),
+        }
+    }
+}
+
+impl HttpBody for TrailersBody {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        Poll::Ready(self.data.take().map(Ok))
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(self.trailers.take()))
+    }
+}
+
+struct Http2ReadyErrorSvc;
+
+impl tower_service::Service<Request<Recv>> for Http2ReadyErrorSvc {
+    type Response = Response<Recv>;
+    type Error = h2::Error;
+    type Future = Box<
         dyn futures_core::Future<Output = Result<Self::Response, Self::Error>>
             + Send
             + Sync
@@ -2620,6 +3713,733 @@ async fn http2_keep_alive_count_server_pings() {
         .expect("timed out waiting for pings");
 }
 
+#[tokio::test]
+async fn h2c_upgrade() {
+    let _ = pretty_env_logger::try_init();
+
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.expect("accept");
+
+        Http::new()
+            .h2c_upgrade(true)
+            .serve_connection(
+                socket,
+                service_fn(|req: Request<Recv>| async move {
+                    assert_eq!(req.version(), Version::HTTP_2);
+                    assert_eq!(req.uri().authority().unwrap(), "example.local");
+                    assert!(!req.headers().contains_key("upgrade"));
+                    assert!(!req.headers().contains_key("http2-settings"));
+                    let body = Full::new(Bytes::from(req.uri().path().to_owned()));
+                    Ok::<_, hyper::Error>(Response::new(body))
+                }),
+            )
+            .await
+            .expect("serve_connection");
+    });
+
+    let mut conn = connect_async(addr).await;
+    conn.write_all(
+        b"\
+        GET /foo HTTP/1.1\r\n\
+        host: example.local\r\n\
+        connection: Upgrade, HTTP2-Settings\r\n\
+        upgrade: h2c\r\n\
+        http2-settings: AAMAAABk\r\n\
+        \r\n\
+        ",
+    )
+    .await
+    .expect("client upgrade request");
+
+    let mut buf = Vec::new();
+    let head_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        let mut chunk = [0; 1024];
+        let n = conn.read(&mut chunk).await.expect("client.read");
+        assert_ne!(n, 0, "eof before 101 response");
+        buf.extend_from_slice(&chunk[..n]);
+    };
+    let head = s(&buf[..head_end]).to_owned();
+    assert!(
+        head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"),
+        "head: {:?}",
+        head
+    );
+    assert!(has_header(&head, "upgrade: h2c"));
+    buf.drain(..head_end);
+
+    // client preface, empty settings, and a second request on stream 3
+    conn.write_all(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n")
+        .await
+        .expect("client preface");
+    conn.write_all(&[0, 0, 0, 4, 0, 0, 0, 0, 0])
+        .await
+        .expect("client settings");
+    let mut block = vec![0x82, 0x86, 0x84, 0x01, 13];
+    block.extend_from_slice(b"example.local");
+    conn.write_all(&[0, 0, block.len() as u8, 1, 0x5, 0, 0, 0, 3])
+        .await
+        .expect("client headers");
+    conn.write_all(&block).await.expect("client headers block");
+
+    let mut bodies = vec![Vec::new(), Vec::new()];
+    let mut ended = 0;
+    while ended < 2 {
+        let (kind, flags, stream_id, payload) = read_h2_frame(&mut conn, &mut buf).await;
+        let idx = match stream_id {
+            0 => continue,
+            1 => 0,
+            3 => 1,
+            other => panic!("unexpected stream {}", other),
+        };
+        match kind {
+            // HEADERS, `:status: 200` is indexed
+            1 => assert_eq!(payload[0], 0x88),
+            // DATA
+            0 => bodies[idx].extend_from_slice(&payload),
+            other => panic!("unexpected frame type {} on stream {}", other, stream_id),
+        }
+        if flags & 0x1 != 0 {
+            ended += 1;
+        }
+    }
+    assert_eq!(bodies[0], b"/foo");
+    assert_eq!(bodies[1], b"/");
+}
+
+#[tokio::test]
+async fn h2c_upgrade_ignored_with_body() {
+    let _ = pretty_env_logger::try_init();
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let t1 = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            POST / HTTP/1.1\r\n\
+            host: example.local\r\n\
+            connection: Upgrade, HTTP2-Settings\r\n\
+            upgrade: h2c\r\n\
+            http2-settings: AAMAAABk\r\n\
+            content-length: 5\r\n\
+            \r\n\
+            hello\
+            ",
+        )
+        .unwrap();
+
+        let mut buf = [0; 256];
+        tcp.read(&mut buf).unwrap();
+        let expected = "HTTP/1.1 200 OK\r\n";
+        assert_eq!(s(&buf[..expected.len()]), expected);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    Http::new()
+        .h2c_upgrade(true)
+        .serve_connection(
+            socket,
+            service_fn(|mut req: Request<Recv>| async move {
+                assert_eq!(req.version(), Version::HTTP_11);
+                let chunk = req.body_mut().data().await.expect("body")?;
+                assert_eq!(chunk, "hello");
+                Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+            }),
+        )
+        .await
+        .unwrap();
+
+    t1.join().expect("client thread");
+}
+
+#[test]
+fn h2c_upgrade_disabled_by_default() {
+    let server = serve();
+    let mut req = connect(server.addr());
+    req.write_all(
+        b"\
+        GET / HTTP/1.1\r\n\
+        host: example.local\r\n\
+        connection: Upgrade, HTTP2-Settings\r\n\
+        upgrade: h2c\r\n\
+        http2-settings: AAMAAABk\r\n\
+        \r\n\
+        ",
+    )
+    .unwrap();
+
+    let mut buf = [0; 256];
+    let n = req.read(&mut buf).unwrap();
+    let expected = "HTTP/1.1 200 OK\r\n";
+    assert_eq!(s(&buf[..expected.len()]), expected, "{:?}", s(&buf[..n]));
+}
+
+fn counting_make_service(
+    connections: Arc<std::sync::atomic::AtomicUsize>,
+) -> impl for<'a> tower_service::Service<
+    &'a TkTcpStream,
+    Response = HelloWorld,
+    Error = hyper::Error,
+    Future = future::Ready<Result<HelloWorld, hyper::Error>>,
+> + Send
+       + 'static {
+    make_service_fn(move |_: &TkTcpStream| {
+        connections.fetch_add(1, Ordering::SeqCst);
+        future::ok::<_, hyper::Error>(HelloWorld)
+    })
+}
+
+async fn get_hello(addr: SocketAddr) -> String {
+    let mut tcp = connect_async(addr).await;
+    tcp.write_all(b"GET / HTTP/1.1\r\nhost: example.domain\r\nconnection: close\r\n\r\n")
+        .await
+        .expect("write");
+    let mut buf = Vec::new();
+    tcp.read_to_end(&mut buf).await.expect("read");
+    String::from_utf8(buf).expect("utf8")
+}
+
+#[tokio::test]
+async fn server_serves_every_accepted_connection() {
+    let connections = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let server =
+        Server::bind(&([127, 0, 0, 1], 0).into()).serve(counting_make_service(connections.clone()));
+    let addr = server.local_addr();
+    tokio::spawn(server);
+
+    for _ in 0..3 {
+        let res = get_hello(addr).await;
+        assert!(res.starts_with("HTTP/1.1 200 OK\r\n"), "{:?}", res);
+        assert!(res.ends_with(HELLO), "{:?}", res);
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn server_accepts_from_stream() {
+    let (client, server_io) = tokio::io::duplex(1024);
+    let incoming = futures_util::stream::iter(vec![Ok::<_, io::Error>(server_io)]);
+
+    let make_service =
+        make_service_fn(|_: &tokio::io::DuplexStream| future::ok::<_, hyper::Error>(HelloWorld));
+    // The server completes once the stream is exhausted, while the
+    // accepted connection keeps being served on its own task.
+    Server::builder(accept::from_stream(incoming))
+        .serve(make_service)
+        .await
+        .expect("server");
+
+    let mut client = client;
+    client
+        .write_all(b"GET / HTTP/1.1\r\nconnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut buf = Vec::new();
+    client.read_to_end(&mut buf).await.unwrap();
+    let res = String::from_utf8(buf).unwrap();
+    assert!(res.starts_with("HTTP/1.1 200 OK\r\n"), "{:?}", res);
+}
+
+#[tokio::test(start_paused = true)]
+async fn server_accept_error_is_retried() {
+    let (client, server_io) = tokio::io::duplex(1024);
+    let mut incoming = vec![
+        Ok(server_io),
+        Err(io::Error::new(io::ErrorKind::Other, "too many open files")),
+    ];
+    let incoming = accept::poll_fn(move |_cx| Poll::Ready(incoming.pop()));
+
+    let make_service =
+        make_service_fn(|_: &tokio::io::DuplexStream| future::ok::<_, hyper::Error>(HelloWorld));
+    let start = tokio::time::Instant::now();
+    Server::builder(incoming)
+        .serve(make_service)
+        .await
+        .expect("server");
+    assert!(start.elapsed() >= Duration::from_secs(1));
+
+    let mut client = client;
+    client
+        .write_all(b"GET / HTTP/1.1\r\nconnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut buf = Vec::new();
+    client.read_to_end(&mut buf).await.unwrap();
+    let res = String::from_utf8(buf).unwrap();
+    assert!(res.starts_with("HTTP/1.1 200 OK\r\n"), "{:?}", res);
+}
+
+#[tokio::test]
+async fn server_try_bind_address_in_use() {
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let err = Server::try_bind(&addr).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("error creating server listener"),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn graceful_shutdown_waits_for_in_flight_requests() {
+    let (release_tx, release_rx) = oneshot::channel::<()>();
+    let release_rx = Arc::new(Mutex::new(Some(release_rx)));
+    let (received_tx, received_rx) = oneshot::channel::<()>();
+    let received_tx = Arc::new(Mutex::new(Some(received_tx)));
+
+    let make_service = make_service_fn(move |_: &TkTcpStream| {
+        let release_rx = release_rx.clone();
+        let received_tx = received_tx.clone();
+        future::ok::<_, hyper::Error>(service_fn(move |_req: Request<Recv>| {
+            let release = release_rx.lock().unwrap().take().expect("one request");
+            let _ = received_tx.lock().unwrap().take().unwrap().send(());
+            async move {
+                let _ = release.await;
+                Ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from(HELLO))))
+            }
+        }))
+    });
+
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+    let addr = server.local_addr();
+    let graceful = tokio::spawn(server.with_graceful_shutdown(async {
+        let _ = shutdown_rx.await;
+    }));
+
+    let res = tokio::spawn(async move {
+        let mut tcp = connect_async(addr).await;
+        tcp.write_all(b"GET / HTTP/1.1\r\nhost: example.domain\r\n\r\n")
+            .await
+            .expect("write");
+        let mut buf = Vec::new();
+        tcp.read_to_end(&mut buf).await.expect("read");
+        String::from_utf8(buf).expect("utf8")
+    });
+
+    received_rx.await.expect("request received");
+    shutdown_tx.send(()).unwrap();
+
+    // The listener is closed right away...
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(TkTcpStream::connect(addr).await.is_err());
+
+    // ...but the server waits for the in-flight request.
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!graceful.is_finished());
+
+    release_tx.send(()).unwrap();
+    let res = res.await.unwrap();
+    assert!(res.starts_with("HTTP/1.1 200 OK\r\n"), "{:?}", res);
+    assert!(res.ends_with(HELLO), "{:?}", res);
+
+    tokio::time::timeout(Duration::from_secs(1), graceful)
+        .await
+        .expect("graceful shutdown completes")
+        .unwrap()
+        .expect("server");
+}
+
+#[tokio::test]
+async fn graceful_shutdown_closes_idle_connections() {
+    let server =
+        Server::bind(&([127, 0, 0, 1], 0).into()).serve(counting_make_service(Default::default()));
+    let addr = server.local_addr();
+
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let graceful = tokio::spawn(server.with_graceful_shutdown(async {
+        let _ = shutdown_rx.await;
+    }));
+
+    // A keep-alive connection, idle after its first response.
+    let mut tcp = connect_async(addr).await;
+    tcp.write_all(b"GET / HTTP/1.1\r\nhost: example.domain\r\n\r\n")
+        .await
+        .expect("write");
+    let mut buf = [0; 256];
+    let n = tcp.read(&mut buf).await.expect("read");
+    assert!(s(&buf[..n]).ends_with(HELLO), "{:?}", s(&buf[..n]));
+
+    shutdown_tx.send(()).unwrap();
+    tokio::time::timeout(Duration::from_secs(1), graceful)
+        .await
+        .expect("graceful shutdown completes")
+        .unwrap()
+        .expect("server");
+
+    let n = tcp.read(&mut buf).await.expect("read eof");
+    assert_eq!(n, 0);
+}
+
+#[derive(Debug, Default)]
+struct Observed {
+    read: AtomicUsize,
+    written: AtomicUsize,
+    heads: AtomicUsize,
+    parse_errors: AtomicUsize,
+    body_received: AtomicUsize,
+    body_sent: AtomicUsize,
+    complete: AtomicUsize,
+    reused: AtomicUsize,
+    ping_rtts: AtomicUsize,
+}
+
+impl Observer for Observed {
+    fn on_read(&self, bytes: usize) {
+        self.read.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    fn on_write(&self, bytes: usize) {
+        self.written.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    fn on_head_parsed(&self) {
+        self.heads.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_parse_error(&self, _err: &hyper::Error) {
+        self.parse_errors.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_body_received(&self, bytes: usize) {
+        self.body_received.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    fn on_body_sent(&self, bytes: usize) {
+        self.body_sent.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    fn on_message_complete(&self) {
+        self.complete.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_keep_alive_reuse(&self) {
+        self.reused.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_ping_rtt(&self, _rtt: Duration) {
+        self.ping_rtts.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+async fn echo(req: Request<Recv>) -> Result<Response<Full<Bytes>>, hyper::Error> {
+    let body = hyper::body::to_bytes(req.into_body()).await?;
+    Ok(Response::new(Full::new(body)))
+}
+
+#[tokio::test]
+async fn observer_reports_http1_connection_events() {
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    const REQ1: &[u8] =
+        b"POST / HTTP/1.1\r\nhost: example.domain\r\ncontent-length: 5\r\n\r\nhello";
+    const REQ2: &[u8] = b"GET / HTTP/1.1\r\nhost: example.domain\r\nconnection: close\r\n\r\n";
+
+    let client = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(REQ1).unwrap();
+        read_until(&mut tcp, |buf| buf.ends_with(b"hello")).unwrap();
+        tcp.write_all(REQ2).unwrap();
+        let mut buf = Vec::new();
+        tcp.read_to_end(&mut buf).unwrap();
+    });
+
+    let observed = Arc::new(Observed::default());
+    let (socket, _) = listener.accept().await.unwrap();
+    Http::new()
+        .observer(observed.clone())
+        .serve_connection(socket, service_fn(echo))
+        .await
+        .unwrap();
+    client.join().unwrap();
+
+    assert_eq!(
+        observed.read.load(Ordering::SeqCst),
+        REQ1.len() + REQ2.len()
+    );
+    assert!(observed.written.load(Ordering::SeqCst) > 0);
+    assert_eq!(observed.heads.load(Ordering::SeqCst), 2);
+    assert_eq!(observed.body_received.load(Ordering::SeqCst), 5);
+    assert_eq!(observed.body_sent.load(Ordering::SeqCst), 5);
+    assert_eq!(observed.complete.load(Ordering::SeqCst), 2);
+    assert_eq!(observed.reused.load(Ordering::SeqCst), 1);
+    assert_eq!(observed.parse_errors.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn observer_reports_http1_parse_errors() {
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(b"GE T / HTTP/1.1\r\n\r\n").unwrap();
+        let mut buf = [0; 256];
+        tcp.read(&mut buf).unwrap();
+    });
+
+    let observed = Arc::new(Observed::default());
+    let (socket, _) = listener.accept().await.unwrap();
+    Http::new()
+        .observer(observed.clone())
+        .serve_connection(socket, HelloWorld)
+        .await
+        .expect_err("HTTP parse error");
+
+    assert_eq!(observed.parse_errors.load(Ordering::SeqCst), 1);
+    assert_eq!(observed.heads.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn observer_reports_http2_connection_events() {
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let observed = Arc::new(Observed::default());
+    let server_observed = observed.clone();
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        Http::new()
+            .http2_only(true)
+            .http2_keep_alive_interval(Duration::from_millis(10))
+            .observer(server_observed)
+            .serve_connection(socket, service_fn(echo))
+            .await
+    });
+
+    let conn = connect_async(addr).await;
+    let (h2, connection) = h2::client::handshake(conn).await.unwrap();
+    let connection = tokio::spawn(connection);
+    let mut h2 = h2.ready().await.unwrap();
+
+    let req = Request::post("http://example.domain/").body(()).unwrap();
+    let (response, mut send_stream) = h2.send_request(req, false).unwrap();
+    send_stream.send_data(Bytes::from("hello"), true).unwrap();
+    let res = response.await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let mut body = res.into_body();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.unwrap();
+        let _ = body.flow_control().release_capacity(chunk.len());
+    }
+
+    // Give the server some time to ping the idle connection.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    drop((h2, send_stream, body));
+    connection.await.unwrap().unwrap();
+    server.await.unwrap().unwrap();
+
+    assert!(observed.read.load(Ordering::SeqCst) > 0);
+    assert!(observed.written.load(Ordering::SeqCst) > 0);
+    assert_eq!(observed.heads.load(Ordering::SeqCst), 1);
+    assert_eq!(observed.body_received.load(Ordering::SeqCst), 5);
+    assert_eq!(observed.body_sent.load(Ordering::SeqCst), 5);
+    assert_eq!(observed.complete.load(Ordering::SeqCst), 1);
+    assert_eq!(observed.reused.load(Ordering::SeqCst), 0);
+    assert!(observed.ping_rtts.load(Ordering::SeqCst) > 0);
+}
+
+fn early_hints() -> Response<()> {
+    Response::builder()
+        .status(103)
+        .header("link", "</style.css>; rel=preload; as=style")
+        .body(())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn informational_responses_sent_before_final_response() {
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let client = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(b"GET / HTTP/1.1\r\nhost: example.domain\r\nconnection: close\r\n\r\n")
+            .unwrap();
+        let mut buf = String::new();
+        tcp.read_to_string(&mut buf).unwrap();
+        buf
+    });
+
+    let (sender_tx, sender_rx) = oneshot::channel();
+    let mut sender_tx = Some(sender_tx);
+    let (socket, _) = listener.accept().await.unwrap();
+    Http::new()
+        .serve_connection(
+            socket,
+            service_fn(move |req: Request<Recv>| {
+                let informational = req
+                    .extensions()
+                    .get::<InformationalSender>()
+                    .expect("InformationalSender extension")
+                    .clone();
+                informational.send(early_hints()).unwrap();
+                let not_informational = Response::builder().status(200).body(()).unwrap();
+                informational
+                    .send(not_informational)
+                    .expect_err("200 is not informational");
+                let _ = sender_tx.take().unwrap().send(informational);
+                future::ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from("hello"))))
+            }),
+        )
+        .await
+        .unwrap();
+
+    let res = client.join().unwrap();
+    let expected = "\
+        HTTP/1.1 103 Early Hints\r\n\
+        link: </style.css>; rel=preload; as=style\r\n\
+        \r\n\
+        HTTP/1.1 200 OK\r\n\
+    ";
+    assert!(res.starts_with(expected), "response: {:?}", res);
+    assert!(res.ends_with("hello"), "response: {:?}", res);
+
+    // Once the final response is sent, it's too late.
+    let informational = sender_rx.await.unwrap();
+    let err = informational.send(early_hints()).unwrap_err();
+    assert!(err.is_closed(), "{:?}", err);
+}
+
+#[tokio::test]
+async fn informational_responses_pending_are_bounded() {
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let client = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(b"GET / HTTP/1.1\r\nhost: example.domain\r\nconnection: close\r\n\r\n")
+            .unwrap();
+        let mut buf = String::new();
+        tcp.read_to_string(&mut buf).unwrap();
+        buf
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    Http::new()
+        .serve_connection(
+            socket,
+            service_fn(|req: Request<Recv>| {
+                let informational = req.extensions().get::<InformationalSender>().unwrap();
+                // Nothing is written until the service yields.
+                let sent = (0..100)
+                    .take_while(|_| informational.send(early_hints()).is_ok())
+                    .count();
+                assert!(sent < 100, "sent {} informational responses", sent);
+                future::ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from("hello"))))
+            }),
+        )
+        .await
+        .unwrap();
+
+    let res = client.join().unwrap();
+    assert!(
+        res.starts_with("HTTP/1.1 103 Early Hints\r\n"),
+        "response: {:?}",
+        res
+    );
+    assert!(res.ends_with("hello"), "response: {:?}", res);
+}
+
+#[tokio::test]
+async fn informational_responses_not_sent_to_http10_clients() {
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let client = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+        let mut buf = String::new();
+        tcp.read_to_string(&mut buf).unwrap();
+        buf
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    Http::new()
+        .serve_connection(
+            socket,
+            service_fn(|req: Request<Recv>| {
+                let informational = req.extensions().get::<InformationalSender>().unwrap();
+                informational.send(early_hints()).unwrap();
+                future::ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from("hello"))))
+            }),
+        )
+        .await
+        .unwrap();
+
+    let res = client.join().unwrap();
+    assert!(
+        res.starts_with("HTTP/1.0 200 OK\r\n"),
+        "response: {:?}",
+        res
+    );
+}
+
+#[tokio::test]
+async fn http2_informational_responses_unsupported() {
+    let listener = tcp_bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        Http::new()
+            .http2_only(true)
+            .serve_connection(
+                socket,
+                service_fn(|req: Request<Recv>| {
+                    let informational = req.extensions().get::<InformationalSender>().unwrap();
+                    let err = informational.send(early_hints()).unwrap_err();
+                    assert!(!err.is_closed(), "{:?}", err);
+                    future::ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+                }),
+            )
+            .await
+            .unwrap();
+    });
+
+    let conn = connect_async(addr).await;
+    let (h2, connection) = h2::client::handshake(conn).await.unwrap();
+    tokio::spawn(async move {
+        connection.await.unwrap();
+    });
+    let mut h2 = h2.ready().await.unwrap();
+
+    let req = Request::get("http://example.domain/").body(()).unwrap();
+    let (response, _) = h2.send_request(req, true).unwrap();
+    let res = response.await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+async fn read_h2_frame(conn: &mut TkTcpStream, buf: &mut Vec<u8>) -> (u8, u8, u32, Vec<u8>) {
+    loop {
+        if buf.len() >= 9 {
+            let len = (buf[0] as usize) << 16 | (buf[1] as usize) << 8 | buf[2] as usize;
+            if buf.len() >= 9 + len {
+                let kind = buf[3];
+                let flags = buf[4];
+                let stream_id = u32::from_be_bytes([buf[5], buf[6], buf[7], buf[8]]);
+                let payload = buf[9..9 + len].to_vec();
+                buf.drain(..9 + len);
+                return (kind, flags, stream_id, payload);
+            }
+        }
+        let mut chunk = [0; 1024];
+        let n = conn.read(&mut chunk).await.expect("client.read");
+        assert_ne!(n, 0, "eof before frame");
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
 // -------------------------------------------------
 // the Server that is used to run all the tests with
 // -------------------------------------------------